@@ -64,6 +64,7 @@ class Keyboard {
   constructor(descriptor) {
     this.descriptor = descriptor
     this.transformRef = null
+    this.sequence = ""
   }
 
   *deriveFallbackLayers(layer) {
@@ -107,6 +108,7 @@ class Keyboard {
     }
 
     console.log("DeadKey: transforming")
+    this.sequence = ""
     this.transformRef = this.descriptor[keyboardId].transforms[value]
     return this.transformRef
   }
@@ -120,16 +122,25 @@ class Keyboard {
       return null
     }
 
-    const t = ref[value]
-    console.log("t:", t, value)
+    // Chained dead keys are flattened into sequences of what is typed after
+    // the first dead key
+    const sequence = this.sequence + value
+    const t = ref[sequence]
+    console.log("t:", t, sequence)
 
-    // If the current transform is not valid for the list, return nothing
-    // This is what other kbds do on Chrome OS.
-    if (t == null) {
-      return null
+    if (t != null) {
+      return t
+    }
+
+    // Part of a longer sequence, keep the transform and wait for the rest
+    if (Object.keys(ref).some((x) => x.startsWith(sequence))) {
+      this.sequence = sequence
+      return ref
     }
 
-    return t
+    // If the current transform is not valid for the list, return nothing
+    // This is what other kbds do on Chrome OS.
+    return null
   }
 
   processInput(keyboardId, code, startingLayerName) {
@@ -178,11 +189,15 @@ class Keyboard {
         // If it's a string, we're at the end of the line
         if (typeof t === "string") {
           this.transformRef = null
+          this.sequence = ""
           return t
         }
 
         // Otherwise, buckle up for more transforms!
         this.transformRef = t
+        if (t == null) {
+          this.sequence = ""
+        }
         return null
       }
 
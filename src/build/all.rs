@@ -0,0 +1,259 @@
+//! Builds every configured target of a bundle from a single read of it
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail};
+use futures::future::join_all;
+
+use super::BuildStep;
+use crate::bundle::KbdgenBundle;
+
+/// A target that `build_all` can build, named like the `target` subcommands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuildTarget {
+    Windows,
+    MacOs,
+    ChromeOs,
+    Linux,
+    Keyman,
+    Android,
+    Ios,
+    Cldr,
+    Svg,
+    Docs,
+}
+
+impl BuildTarget {
+    pub const ALL: &'static [BuildTarget] = &[
+        BuildTarget::Windows,
+        BuildTarget::MacOs,
+        BuildTarget::ChromeOs,
+        BuildTarget::Linux,
+        BuildTarget::Keyman,
+        BuildTarget::Android,
+        BuildTarget::Ios,
+        BuildTarget::Cldr,
+        BuildTarget::Svg,
+        BuildTarget::Docs,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuildTarget::Windows => "windows",
+            BuildTarget::MacOs => "macos",
+            BuildTarget::ChromeOs => "chromeos",
+            BuildTarget::Linux => "linux",
+            BuildTarget::Keyman => "keyman",
+            BuildTarget::Android => "android",
+            BuildTarget::Ios => "ios",
+            BuildTarget::Cldr => "cldr",
+            BuildTarget::Svg => "svg",
+            BuildTarget::Docs => "docs",
+        }
+    }
+
+    /// Whether the bundle has what the target needs: its file in `targets`,
+    /// if it has one, and a layout with a section for it
+    pub fn is_configured(&self, bundle: &KbdgenBundle) -> bool {
+        let targets = &bundle.targets;
+        let mut layouts = bundle.layouts.values();
+
+        match self {
+            BuildTarget::Windows => {
+                targets.windows.is_some() && layouts.any(|x| x.windows.is_some())
+            }
+            BuildTarget::MacOs => targets.macos.is_some() && layouts.any(|x| x.mac_os.is_some()),
+            BuildTarget::ChromeOs => {
+                targets.chromeos.is_some() && layouts.any(|x| x.chrome_os.is_some())
+            }
+            BuildTarget::Linux => layouts.any(|x| x.linux.is_some()),
+            BuildTarget::Keyman => targets.keyman.is_some() && !bundle.layouts.is_empty(),
+            BuildTarget::Android => {
+                targets.android.is_some() && layouts.any(|x| x.android.is_some())
+            }
+            BuildTarget::Ios => targets.ios.is_some() && layouts.any(|x| x.i_os.is_some()),
+            BuildTarget::Cldr => layouts.any(|x| x.windows.is_some() || x.mac_os.is_some()),
+            BuildTarget::Svg => layouts.any(|x| {
+                x.windows.is_some()
+                    || x.mac_os.is_some()
+                    || x.chrome_os.is_some()
+                    || x.android.is_some()
+                    || x.i_os.is_some()
+            }),
+            BuildTarget::Docs => !bundle.layouts.is_empty(),
+        }
+    }
+
+    fn build_steps(&self) -> Vec<Box<dyn BuildStep>> {
+        match self {
+            BuildTarget::Windows => super::windows::build_steps(),
+            BuildTarget::MacOs => super::macos::build_steps(),
+            BuildTarget::ChromeOs => super::chromeos::build_steps(),
+            BuildTarget::Linux => super::linux::build_steps(),
+            BuildTarget::Keyman => super::keyman::build_steps(),
+            BuildTarget::Android => super::android::build_steps(),
+            BuildTarget::Ios => super::ios::build_steps(),
+            BuildTarget::Cldr => super::cldr::build_steps(),
+            BuildTarget::Svg => super::svg::build_steps(),
+            BuildTarget::Docs => super::docs::build_steps(),
+        }
+    }
+}
+
+impl std::fmt::Display for BuildTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for BuildTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BuildTarget::ALL
+            .iter()
+            .find(|target| target.name().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "`{}` is not one of {}",
+                    s,
+                    BuildTarget::ALL
+                        .iter()
+                        .map(|x| x.name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+}
+
+/// The targets of `BuildTarget::ALL` that the bundle is configured for
+pub fn configured_targets(bundle: &KbdgenBundle) -> Vec<BuildTarget> {
+    BuildTarget::ALL
+        .iter()
+        .filter(|x| x.is_configured(bundle))
+        .copied()
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct BuildResult {
+    pub target: BuildTarget,
+    pub output_path: PathBuf,
+    pub duration: Duration,
+    pub result: Result<()>,
+}
+
+async fn build_target(
+    bundle: &KbdgenBundle,
+    target: BuildTarget,
+    output_path: PathBuf,
+) -> BuildResult {
+    let start = Instant::now();
+
+    let result = async {
+        if !target.is_configured(bundle) {
+            bail!("The bundle has no configuration for {}", target);
+        }
+
+        std::fs::create_dir_all(&output_path)?;
+        for step in target.build_steps() {
+            step.build(bundle, &output_path).await?;
+        }
+
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = &result {
+        tracing::error!("Building {} failed: {:?}", target, e);
+    }
+
+    BuildResult {
+        target,
+        output_path,
+        duration: start.elapsed(),
+        result,
+    }
+}
+
+/// Runs the build steps of each target into `<output_path>/<target>`. The
+/// targets are built concurrently, and a failing target doesn't stop the
+/// others.
+pub async fn build_all(
+    bundle: &KbdgenBundle,
+    output_path: &Path,
+    targets: &[BuildTarget],
+) -> Vec<BuildResult> {
+    join_all(
+        targets
+            .iter()
+            .map(|target| build_target(bundle, *target, output_path.join(target.name()))),
+    )
+    .await
+}
+
+/// A table of the results, with the error of each failure
+pub fn summary_table(results: &[BuildResult]) -> String {
+    let width = results
+        .iter()
+        .map(|x| x.target.name().len())
+        .max()
+        .unwrap_or(0);
+
+    let mut output = String::new();
+    for result in results {
+        let outcome = match &result.result {
+            Ok(()) => format!("ok      {}", result.output_path.display()),
+            Err(e) => format!("failed  {}", e),
+        };
+
+        output.push_str(&format!(
+            "{:width$}  {:>6.1}s  {}\n",
+            result.target.name(),
+            result.duration.as_secs_f64(),
+            outcome,
+            width = width
+        ));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::init::{init_bundle, new_layout};
+    use crate::bundle::read_kbdgen_bundle;
+    use crate::import::write_layout;
+
+    #[test]
+    fn test_configured_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sje.kbdgen");
+        let bundle = init_bundle(&path).unwrap();
+        assert_eq!(configured_targets(&bundle), vec![]);
+
+        let layout = new_layout(&bundle, &"sje".parse().unwrap(), "iso-qwerty").unwrap();
+        write_layout(&bundle.path, &layout).unwrap();
+        let bundle = read_kbdgen_bundle(&path).unwrap();
+
+        assert_eq!(
+            configured_targets(&bundle),
+            vec![
+                BuildTarget::Windows,
+                BuildTarget::MacOs,
+                BuildTarget::ChromeOs,
+                BuildTarget::Linux,
+                BuildTarget::Android,
+                BuildTarget::Ios,
+                BuildTarget::Cldr,
+                BuildTarget::Svg,
+                BuildTarget::Docs,
+            ]
+        );
+        assert_eq!("macOS".parse(), Ok(BuildTarget::MacOs));
+    }
+}
@@ -15,14 +15,13 @@ use serde::Serialize;
 use url::Url;
 use xmlem::{Document, NewElement, Node, Selector};
 
-use crate::bundle::layout::Transform;
 use crate::bundle::project::LocaleProjectDescription;
 use crate::bundle::target;
 use crate::{
     build::BuildStep,
     bundle::{KbdgenBundle, layout::android::AndroidKbdLayer},
-    util::TRANSFORM_ESCAPE,
-    util::split_keys,
+    model::DeadKeys,
+    util::{decode_unicode_escapes, split_keys},
 };
 
 use super::REPOSITORY_FOLDER;
@@ -164,45 +163,13 @@ impl BuildStep for GenerateAndroid {
         // x files for lines (should be 3)
         // (pretending we're following the primary approach for start)
         for (language_tag, layout) in &bundle.layouts {
-            let mut transforms_by_dead_key: IndexMap<String, IndexMap<String, String>> =
-                IndexMap::new();
-            let mut dead_keys: Vec<&String> = Vec::new();
-            if let Some(transforms) = layout.transforms.as_ref() {
-                transforms.into_iter().for_each(|item| {
-                    let (dead_key, transform) = item;
-                    let mut transforms_by_char: IndexMap<String, String> = IndexMap::new();
-
-                    dead_keys.push(dead_key);
-                    match transform {
-                        Transform::End(character) => {
-                            tracing::error!(
-                                "Transform ended too soon for dead key {} - character {}",
-                                dead_key,
-                                character
-                            );
-                        }
-                        Transform::More(map) => {
-                            for (next_char, transform) in map {
-                                if next_char == TRANSFORM_ESCAPE {
-                                    transforms_by_char.insert(next_char.clone(), dead_key.clone());
-                                }
-
-                                match transform {
-                                    Transform::End(end_char) => {
-                                        transforms_by_char
-                                            .insert(next_char.clone(), end_char.clone());
-                                    }
-                                    Transform::More(_transform) => {
-                                        todo!("Recursion required ahead");
-                                    }
-                                }
-                            }
-                        }
-                    }
-
-                    transforms_by_dead_key.insert(dead_key.clone(), transforms_by_char);
-                });
-            }
+            let dead_key_states = layout
+                .transforms
+                .as_ref()
+                .map(DeadKeys::from_transforms)
+                .unwrap_or_default();
+            let transforms_by_dead_key = dead_key_states.sequences();
+            let dead_keys: Vec<&String> = dead_key_states.starts.keys().collect();
 
             tracing::info!("Building Android layouts for lang {}", language_tag);
             if let Some(android_target) = &layout.android {
@@ -460,6 +427,31 @@ impl BuildStep for GenerateAndroid {
     }
 }
 
+/// The width of a key in percent of the keyboard, so that the longest row of
+/// the default layer fills it
+pub fn android_key_width(tablet_600: bool, default_layer: &str) -> f64 {
+    let longest_row_count = default_layer
+        .split("\n")
+        .map(|line| split_keys(line).len())
+        .max()
+        .unwrap();
+
+    if tablet_600 {
+        90.0f64 / longest_row_count as f64
+    } else {
+        100.0f64 / longest_row_count as f64
+    }
+}
+
+/// The width of a shift key in percent of the keyboard, the special keys of
+/// a row sharing what its other keys leave
+pub fn shift_key_width(key_width: f64, keys_count: usize, special_keys_count: usize) -> f64 {
+    let normal_keys = keys_count - special_keys_count;
+    let total_width = key_width * normal_keys as f64;
+    let remaining_space = 100f64 - total_width;
+    remaining_space / special_keys_count as f64
+}
+
 fn create_and_write_rows_keys_for_layer(
     tablet_600: bool,
     layers: &IndexMap<AndroidKbdLayer, String>,
@@ -484,17 +476,7 @@ fn create_and_write_rows_keys_for_layer(
     let mut rowkeys_docs_map = IndexMap::new();
 
     let default_layer = layers.get(&AndroidKbdLayer::Default).unwrap();
-    let longest_row_count = default_layer
-        .split("\n")
-        .map(|line| split_keys(line).len())
-        .max()
-        .unwrap();
-
-    let key_width = if tablet_600 {
-        90.0f64 / longest_row_count as f64
-    } else {
-        100.0f64 / longest_row_count as f64
-    };
+    let key_width = android_key_width(tablet_600, default_layer);
 
     for (layer_key, layer) in layers {
         let selector_string;
@@ -538,7 +520,7 @@ fn create_and_write_rows_keys_for_layer(
                         &key,
                         compute_key_hint_label_index(key_index),
                         longpress,
-                        dead_keys.contains(&key),
+                        dead_keys.contains(&&decode_unicode_escapes(key)),
                         preserve_case,
                     );
                 } else {
@@ -548,7 +530,7 @@ fn create_and_write_rows_keys_for_layer(
                         key_width,
                         current_keys_count,
                         special_keys_count,
-                        dead_keys.contains(&key),
+                        dead_keys.contains(&&decode_unicode_escapes(key)),
                         preserve_case,
                     );
                 }
@@ -945,10 +927,7 @@ fn create_key_xml_element(
 
     if key == "\\s{shift}" {
         attrs.insert(qname!("latin:keyStyle"), "shiftKeyStyle".to_owned());
-        let normal_keys = keys_count - special_keys_count;
-        let total_width = key_width * normal_keys as f64;
-        let remaining_space = 100f64 - total_width;
-        let fill_left = remaining_space / special_keys_count as f64;
+        let fill_left = shift_key_width(key_width, keys_count, special_keys_count);
         tracing::debug!("Shift fill left: {:.2}%", fill_left);
         attrs.insert(qname!("latin:keyWidth"), format!("{fill_left:.2}%"));
     } else if key == "\\s{backspace}" {
@@ -32,14 +32,18 @@ impl BuildStep for DownloadDependencies {
     }
 }
 
+pub fn build_steps() -> Vec<Box<dyn BuildStep>> {
+    vec![
+        Box::new(CloneGiellaKbd),
+        Box::new(DownloadDependencies),
+        Box::new(GenerateAndroid),
+    ]
+}
+
 #[async_trait(?Send)]
 impl BuildSteps for AndroidBuild {
     fn new(bundle: KbdgenBundle, output_path: PathBuf) -> Self {
-        let steps: Vec<Box<dyn BuildStep>> = vec![
-            Box::new(CloneGiellaKbd),
-            Box::new(DownloadDependencies),
-            Box::new(GenerateAndroid),
-        ];
+        let steps = build_steps();
 
         AndroidBuild {
             bundle,
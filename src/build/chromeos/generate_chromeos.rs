@@ -12,11 +12,12 @@ use crate::build::chromeos::keymap::CHROMEOS_KEYS;
 use crate::build::chromeos::manifest::{
     ChromeOsManifest, ManifestBackground, ManifestIcons, ManifestInputComponent,
 };
-use crate::bundle::layout::{ChromeOsTarget, Layout};
+use crate::bundle::layout::ChromeOsTarget;
 use crate::{
     build::BuildStep,
     bundle::{KbdgenBundle, layout::chrome::ChromeOsKbdLayer},
-    util::split_keys,
+    model::{Keyboard, PlatformLayer},
+    util::decode_unicode_escapes,
 };
 
 const BACKGROUND_FILE_NAME: &str = "background.js";
@@ -265,68 +266,36 @@ fn generate_dead_keys(chromeos_target: &ChromeOsTarget) -> IndexMap<ChromeOsKbdL
     let mut json_dead_keys = IndexMap::new();
     if let Some(dead_keys) = &chromeos_target.dead_keys {
         for (dead_key_name, dead_key_list) in dead_keys {
-            json_dead_keys.insert(dead_key_name.clone(), dead_key_list.to_vec());
+            json_dead_keys.insert(
+                *dead_key_name,
+                dead_key_list
+                    .iter()
+                    .map(|x| decode_unicode_escapes(x))
+                    .collect(),
+            );
         }
     }
     json_dead_keys
 }
 
-fn generate_transforms(
-    bundle: &KbdgenBundle,
-    layout: &Layout,
-) -> IndexMap<String, IndexMap<String, String>> {
-    if let Some(_layout_transforms) = &layout.transforms {
-        let layout_file_path = bundle
-            .path
-            .join("layouts")
-            .join(format!("{}.yaml", layout.language_tag));
-
-        let yaml_file = std::fs::File::open(&layout_file_path).unwrap();
-        let original_layout_yaml: serde_yaml::Value = serde_yaml::from_reader(yaml_file).unwrap();
-
-        let owning_transform = *&original_layout_yaml.get("transforms").unwrap();
-
-        return serde_yaml::from_value(owning_transform.clone()).unwrap();
-    } else {
-        return IndexMap::new();
-    }
-}
-
-fn generate_layers(
-    chromeos_target: &ChromeOsTarget,
-    language_tag: LanguageTag,
-) -> IndexMap<ChromeOsKbdLayer, IndexMap<String, String>> {
-    let layers = &chromeos_target.primary.layers;
+fn generate_layers(keyboard: &Keyboard) -> IndexMap<ChromeOsKbdLayer, IndexMap<String, String>> {
     let mut json_layers = IndexMap::new();
 
-    for (layer_name, key_map) in layers {
-        let key_map: Vec<String> = split_keys(&key_map);
-
-        tracing::debug!(
-            "iso len: {}; keymap len: {}",
-            CHROMEOS_KEYS.len(),
-            key_map.len()
-        );
-        if CHROMEOS_KEYS.len() > key_map.len() {
-            panic!(
-                r#"Provided layer does not have enough keys, expected {} keys but got {}, in {}:{}:{}:{:?}: \n{:?}"#,
-                CHROMEOS_KEYS.len(),
-                key_map.len(),
-                language_tag.to_string(),
-                "Windows",
-                "Primary",
-                layer_name,
-                key_map
-            );
-        }
+    for layer in &keyboard.layers {
+        let Some(layer_name) = ChromeOsKbdLayer::from_modifiers(layer.modifiers) else {
+            continue;
+        };
 
         let mut modifiers = IndexMap::new();
-        for (cursor, (_iso_key, modifier_name)) in CHROMEOS_KEYS.iter().enumerate() {
-            modifiers.insert(modifier_name.clone(), key_map[cursor].clone());
+        for (iso_key, modifier_name) in CHROMEOS_KEYS.iter() {
+            if let Some(output) = layer.key(*iso_key).output() {
+                modifiers.insert(modifier_name.clone(), output);
+            }
         }
 
-        json_layers.insert(layer_name.clone(), modifiers);
+        json_layers.insert(layer_name, modifiers);
     }
+
     json_layers
 }
 
@@ -364,12 +333,20 @@ impl BuildStep for GenerateChromeOs {
 
                 manifest_input_components.push(input_component);
 
+                let keyboard = Keyboard::lower(
+                    bundle,
+                    language_tag,
+                    "chromeOS",
+                    &chromeos_target.primary.layers,
+                    chromeos_target.dead_keys.as_ref(),
+                )?;
+
                 descriptor.insert(
                     language_tag.clone(),
                     ChromeOsDescriptor {
                         dead_keys: generate_dead_keys(chromeos_target),
-                        transforms: generate_transforms(bundle, layout),
-                        layers: generate_layers(chromeos_target, language_tag.clone()),
+                        transforms: keyboard.dead_keys.sequences(),
+                        layers: generate_layers(&keyboard),
                     },
                 );
             }
@@ -390,3 +367,44 @@ impl BuildStep for GenerateChromeOs {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::test_layout;
+    use crate::model::Platform;
+
+    #[test]
+    fn test_generate_layers() {
+        let text = r#"
+displayNames:
+  sje: Bidumsámegiella
+chromeOS:
+  primary:
+    layers:
+      default: |
+        § 1 2 3 4 5 6 7 8 9 0 + ´
+        á š e r t y u i o p å ŋ
+        a s d f g h j k l ö ä đ
+        ž z č c v b n m , . -
+      shift: |
+        ½ \u{0} " # ¤ % & / ( ) = ? `
+        Á Š E R T Y U I O P Å Ŋ
+        A S D F G H J K L Ö Ä Đ
+        Ž Z Č C V B N M ; : _
+  deadKeys:
+    default: ['´']
+"#;
+
+        let layout = test_layout("sje", text);
+        let keyboard = Keyboard::from_layout(&layout, Platform::ChromeOs).unwrap();
+        let layers = generate_layers(&keyboard);
+
+        assert_eq!(layers[&ChromeOsKbdLayer::Default]["Digit1"], "1");
+        assert_eq!(layers[&ChromeOsKbdLayer::Default]["Equal"], "´");
+        assert_eq!(layers[&ChromeOsKbdLayer::Shift]["Digit2"], "\"");
+        // Keys without output are left out, so that the keyboard falls back
+        // to the same key on the default layer
+        assert!(!layers[&ChromeOsKbdLayer::Shift].contains_key("Digit1"));
+    }
+}
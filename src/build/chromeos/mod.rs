@@ -8,8 +8,8 @@ use self::generate_chromeos::GenerateChromeOs;
 
 use super::{BuildStep, BuildSteps};
 
-mod generate_chromeos;
-mod keymap;
+pub(crate) mod generate_chromeos;
+pub(crate) mod keymap;
 mod manifest;
 
 pub struct ChromeOsBuild {
@@ -18,10 +18,14 @@ pub struct ChromeOsBuild {
     pub steps: Vec<Box<dyn BuildStep>>,
 }
 
+pub fn build_steps() -> Vec<Box<dyn BuildStep>> {
+    vec![Box::new(GenerateChromeOs)]
+}
+
 #[async_trait(?Send)]
 impl BuildSteps for ChromeOsBuild {
     fn new(bundle: KbdgenBundle, output_path: PathBuf) -> Self {
-        let steps: Vec<Box<dyn BuildStep>> = vec![Box::new(GenerateChromeOs)];
+        let steps = build_steps();
 
         ChromeOsBuild {
             bundle,
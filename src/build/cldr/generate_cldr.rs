@@ -0,0 +1,417 @@
+use std::path::Path;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use indexmap::{IndexMap, IndexSet};
+use language_tags::LanguageTag;
+use xmlem::{Document, Element};
+
+use crate::{
+    build::BuildStep,
+    bundle::{
+        Error, KbdgenBundle,
+        layout::{Layout, macos::MacOsKbdLayer, windows::WindowsKbdLayer},
+    },
+    model::{DeadKeys, KeyAction, Keyboard, Layer, Next, PlatformLayer, StateId},
+    util::{
+        TRANSFORM_ESCAPE,
+        iso_key::{ISO_ROW_LENGTHS, layer_keys},
+    },
+};
+
+const XML_EXT: &str = "xml";
+const KEYBOARD3_XMLNS: &str = "https://schemas.unicode.org/cldr/45/keyboard3";
+const CONFORMS_TO: &str = "45";
+const FORM_ID: &str = "iso";
+
+const GAP_KEY_ID: &str = "gap";
+const SPACE_KEY_ID: &str = "space";
+
+/// A layer of a layout as LDML sees it, a modifier set and its keys
+pub struct CldrLayer {
+    pub modifiers: &'static str,
+    pub layer: Layer,
+}
+
+/// The hardware layers of a layout with the dead keys shared by them
+pub struct CldrKeyboard {
+    pub layers: Vec<CldrLayer>,
+    pub dead_keys: DeadKeys,
+}
+
+fn windows_modifiers(layer: &WindowsKbdLayer) -> Option<&'static str> {
+    Some(match layer {
+        WindowsKbdLayer::Default => "none",
+        WindowsKbdLayer::Shift => "shift",
+        WindowsKbdLayer::Caps => "caps",
+        WindowsKbdLayer::CapsAndShift => "caps shift",
+        WindowsKbdLayer::Alt => "altR",
+        WindowsKbdLayer::AltAndShift => "altR shift",
+        WindowsKbdLayer::AltAndCaps => "altR caps",
+        WindowsKbdLayer::Ctrl => "ctrl",
+    })
+}
+
+fn macos_modifiers(layer: &MacOsKbdLayer) -> Option<&'static str> {
+    Some(match layer {
+        MacOsKbdLayer::Default => "none",
+        MacOsKbdLayer::Shift => "shift",
+        MacOsKbdLayer::Caps => "caps",
+        MacOsKbdLayer::CapsAndShift => "caps shift",
+        MacOsKbdLayer::Alt => "alt",
+        MacOsKbdLayer::AltAndShift => "alt shift",
+        MacOsKbdLayer::AltAndCaps => "alt caps",
+        MacOsKbdLayer::Ctrl => "ctrl",
+        // LDML has no modifier for the command key
+        MacOsKbdLayer::Cmd
+        | MacOsKbdLayer::CmdAndShift
+        | MacOsKbdLayer::CmdAndAlt
+        | MacOsKbdLayer::CmdAndAltAndShift => return None,
+    })
+}
+
+fn to_cldr_layers<L>(keyboard: Keyboard, modifiers: fn(&L) -> Option<&'static str>) -> CldrKeyboard
+where
+    L: PlatformLayer,
+{
+    CldrKeyboard {
+        layers: keyboard
+            .layers
+            .into_iter()
+            .filter_map(|layer| {
+                Some(CldrLayer {
+                    modifiers: modifiers(&L::from_modifiers(layer.modifiers)?)?,
+                    layer,
+                })
+            })
+            .collect(),
+        dead_keys: keyboard.dead_keys,
+    }
+}
+
+/// Returns the hardware layers of a layout, preferring the Windows layers and
+/// falling back to the macOS ones
+pub fn cldr_keyboard(
+    bundle: &KbdgenBundle,
+    language_tag: &LanguageTag,
+    layout: &Layout,
+) -> Result<Option<CldrKeyboard>, Error> {
+    if let Some(target) = &layout.windows {
+        let keyboard = Keyboard::lower(
+            bundle,
+            language_tag,
+            "windows",
+            &target.primary.layers,
+            target.dead_keys.as_ref(),
+        )?;
+        return Ok(Some(to_cldr_layers(keyboard, windows_modifiers)));
+    }
+
+    if let Some(target) = &layout.mac_os {
+        let keyboard = Keyboard::lower(
+            bundle,
+            language_tag,
+            "macOS",
+            &target.primary.layers,
+            target.dead_keys.as_ref(),
+        )?;
+        return Ok(Some(to_cldr_layers(keyboard, macos_modifiers)));
+    }
+
+    Ok(None)
+}
+
+/// The marker name used for the state reached by typing `typed`
+pub fn marker_name(typed: &str) -> String {
+    let codes = typed
+        .chars()
+        .map(|c| format!("{:04x}", c as u32))
+        .collect::<Vec<_>>()
+        .join("_");
+
+    format!("dk_{}", codes)
+}
+
+fn marker(typed: &str) -> String {
+    format!("\\m{{{}}}", marker_name(typed))
+}
+
+/// Key ids are the character itself for ASCII letters and digits, and the
+/// code points (`u00e1`) for everything else
+pub fn key_id(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return key.to_string();
+    }
+
+    key.chars()
+        .map(|c| format!("u{:04x}", c as u32))
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Escapes text for `output` and `to` attributes, where `\` starts an escape
+fn escape_output(input: &str) -> String {
+    input.replace('\\', "\\u{5c}")
+}
+
+/// Escapes text for `from` attributes, which are regular expressions
+fn escape_from(input: &str) -> String {
+    input
+        .chars()
+        .map(|c| {
+            if c.is_ascii_punctuation() {
+                format!("\\u{{{:x}}}", c as u32)
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Collects the `from` and `to` pairs for a dead key state, recursing into
+/// chained dead keys
+fn collect_transforms(
+    output: &mut Vec<(String, String)>,
+    automaton: &DeadKeys,
+    id: StateId,
+    dead_keys: &IndexSet<String>,
+) {
+    let state = &automaton.states[id];
+    let from_marker = marker(&state.sequence);
+
+    output.push((
+        format!("{}{}", from_marker, escape_from(TRANSFORM_ESCAPE)),
+        escape_output(state.escape_or_fallback()),
+    ));
+
+    for (next_char, next) in &state.transitions {
+        // A dead key typed after a dead key outputs its marker, not the character
+        let mut froms = vec![format!("{}{}", from_marker, escape_from(next_char))];
+        if dead_keys.contains(next_char) {
+            froms.push(format!("{}{}", from_marker, marker(next_char)));
+        }
+
+        let to = match next {
+            Next::Output(end_char) => escape_output(end_char),
+            Next::State(next_id) => {
+                collect_transforms(output, automaton, *next_id, dead_keys);
+                marker(&automaton.states[*next_id].sequence)
+            }
+        };
+
+        for from in froms {
+            output.push((from, to.clone()));
+        }
+    }
+}
+
+fn append_keys(
+    document: &mut Document,
+    root: &Element,
+    layers: &[CldrLayer],
+) -> IndexMap<String, Vec<Vec<String>>> {
+    let keys_element = root.append_new_element(document, "keys");
+    let mut key_outputs: IndexMap<String, String> = IndexMap::new();
+    let mut layer_rows: IndexMap<String, Vec<Vec<String>>> = IndexMap::new();
+
+    for layer in layers {
+        let mut keys = layer_keys().iter();
+        let mut rows = vec![];
+
+        for row_length in ISO_ROW_LENGTHS {
+            let mut row = vec![];
+
+            for iso_key in keys.by_ref().take(row_length) {
+                let (id, output) = match layer.layer.key(*iso_key) {
+                    KeyAction::DeadKey(key) => (marker_name(key), marker(key)),
+                    key => match key.output() {
+                        Some(key) => (key_id(&key), escape_output(&key)),
+                        None => {
+                            row.push(GAP_KEY_ID.to_string());
+                            continue;
+                        }
+                    },
+                };
+
+                key_outputs.entry(id.clone()).or_insert(output);
+                row.push(id);
+            }
+
+            rows.push(row);
+        }
+
+        rows.push(vec![SPACE_KEY_ID.to_string()]);
+        layer_rows.insert(layer.modifiers.to_string(), rows);
+    }
+
+    keys_element.append_new_element(document, ("key", [("id", GAP_KEY_ID), ("gap", "true")]));
+    keys_element.append_new_element(document, ("key", [("id", SPACE_KEY_ID), ("output", " ")]));
+    for (id, output) in key_outputs {
+        keys_element.append_new_element(document, ("key", [("id", id), ("output", output)]));
+    }
+
+    layer_rows
+}
+
+pub fn generate_keyboard3(
+    bundle: &KbdgenBundle,
+    language_tag: &LanguageTag,
+    layout: &Layout,
+    keyboard: &CldrKeyboard,
+) -> Result<String> {
+    let layers = &keyboard.layers;
+    let mut document = Document::new("keyboard3");
+    let root = document.root();
+
+    root.set_attribute(&mut document, "xmlns", KEYBOARD3_XMLNS);
+    root.set_attribute(&mut document, "locale", language_tag.as_str());
+    root.set_attribute(&mut document, "conformsTo", CONFORMS_TO);
+
+    root.append_new_element(
+        &mut document,
+        (
+            "info",
+            [
+                ("name", layout.autonym()),
+                ("author", bundle.project.author.as_str()),
+            ],
+        ),
+    );
+
+    let dead_keys: IndexSet<String> = layers
+        .iter()
+        .flat_map(|x| x.layer.keys.values())
+        .filter_map(|x| match x {
+            KeyAction::DeadKey(key) => Some(key.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let displays = root.append_new_element(&mut document, "displays");
+    for dead_key in &dead_keys {
+        displays.append_new_element(
+            &mut document,
+            (
+                "display",
+                [
+                    ("output", marker(dead_key)),
+                    ("display", escape_output(dead_key)),
+                ],
+            ),
+        );
+    }
+    if let Some(key_names) = &layout.key_names {
+        displays.append_new_element(
+            &mut document,
+            (
+                "display",
+                [
+                    ("keyId", SPACE_KEY_ID),
+                    ("display", key_names.space.as_str()),
+                ],
+            ),
+        );
+    }
+
+    let layer_rows = append_keys(&mut document, &root, layers);
+
+    let layers_element = root.append_new_element(&mut document, ("layers", [("formId", FORM_ID)]));
+    for (modifiers, rows) in layer_rows {
+        let layer_element =
+            layers_element.append_new_element(&mut document, ("layer", [("modifiers", modifiers)]));
+
+        for row in rows {
+            layer_element.append_new_element(&mut document, ("row", [("keys", row.join(" "))]));
+        }
+    }
+
+    let mut cldr_transforms = vec![];
+    for (dead_key, id) in &keyboard.dead_keys.starts {
+        if dead_keys.contains(dead_key) {
+            collect_transforms(&mut cldr_transforms, &keyboard.dead_keys, *id, &dead_keys);
+        }
+    }
+
+    if !cldr_transforms.is_empty() {
+        let transform_group = root
+            .append_new_element(&mut document, ("transforms", [("type", "simple")]))
+            .append_new_element(&mut document, "transformGroup");
+
+        for (from, to) in cldr_transforms {
+            transform_group
+                .append_new_element(&mut document, ("transform", [("from", from), ("to", to)]));
+        }
+    }
+
+    Ok(document.to_string_pretty())
+}
+
+pub struct GenerateCldr;
+
+#[async_trait(?Send)]
+impl BuildStep for GenerateCldr {
+    async fn build(&self, bundle: &KbdgenBundle, output_path: &Path) -> Result<()> {
+        for (language_tag, layout) in &bundle.layouts {
+            let Some(keyboard) = cldr_keyboard(bundle, language_tag, layout)? else {
+                continue;
+            };
+
+            tracing::debug!("Generating keyboard3 xml for {}", language_tag);
+
+            std::fs::write(
+                output_path.join(format!("{}.{}", language_tag, XML_EXT)),
+                generate_keyboard3(bundle, language_tag, layout, &keyboard)?,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::test_layout;
+
+    #[test]
+    fn test_collect_transforms() {
+        let text = r#"
+displayNames:
+  sje: Bidumsámegiella
+transforms:
+  ´:
+    ' ': ´
+    e: é
+    '\': x
+    ¨:
+      u: ǘ
+  ¨:
+    u: ü
+"#;
+
+        let layout = test_layout("sje", text);
+        let automaton = DeadKeys::from_transforms(layout.transforms.as_ref().unwrap());
+        let dead_keys = IndexSet::from(["´".to_string(), "¨".to_string()]);
+
+        let mut found = vec![];
+        collect_transforms(&mut found, &automaton, automaton.starts["´"], &dead_keys);
+
+        let expected = [
+            (r"\m{dk_00b4} ", "´"),
+            (r"\m{dk_00b4}e", "é"),
+            (r"\m{dk_00b4}\u{5c}", "x"),
+            // The chained state has no escape, so what was typed is output
+            (r"\m{dk_00b4_00a8} ", "´¨"),
+            (r"\m{dk_00b4_00a8}u", "ǘ"),
+            (r"\m{dk_00b4}¨", r"\m{dk_00b4_00a8}"),
+            (r"\m{dk_00b4}\m{dk_00a8}", r"\m{dk_00b4_00a8}"),
+        ];
+        assert_eq!(
+            found,
+            expected
+                .iter()
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+}
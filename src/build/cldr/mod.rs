@@ -0,0 +1,46 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+
+use crate::bundle::KbdgenBundle;
+
+use self::generate_cldr::GenerateCldr;
+
+use super::{BuildStep, BuildSteps};
+
+pub mod generate_cldr;
+
+pub struct CldrBuild {
+    pub bundle: KbdgenBundle,
+    pub output_path: PathBuf,
+    pub steps: Vec<Box<dyn BuildStep>>,
+}
+
+pub fn build_steps() -> Vec<Box<dyn BuildStep>> {
+    vec![Box::new(GenerateCldr)]
+}
+
+#[async_trait(?Send)]
+impl BuildSteps for CldrBuild {
+    fn new(bundle: KbdgenBundle, output_path: PathBuf) -> Self {
+        let steps = build_steps();
+
+        CldrBuild {
+            bundle,
+            output_path,
+            steps,
+        }
+    }
+
+    fn steps(&self) -> &[Box<dyn BuildStep>] {
+        &self.steps
+    }
+
+    fn bundle(&self) -> &KbdgenBundle {
+        &self.bundle
+    }
+
+    fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+}
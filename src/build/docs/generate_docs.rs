@@ -0,0 +1,414 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use language_tags::LanguageTag;
+
+use crate::{
+    build::{
+        BuildStep,
+        svg::{
+            SVG_EXT, SVG_PLATFORMS, chart_file_name, generate_mobile::generate_mobile_previews,
+            generate_svg,
+        },
+    },
+    bundle::{KbdgenBundle, layout::Layout, project::LocaleProjectDescription},
+    model::{DeadKeys, Keyboard},
+    util::{TRANSFORM_ESCAPE, decode_unicode_escapes},
+};
+
+const HTML_EXT: &str = "html";
+const INDEX_FILE: &str = "index.html";
+const CHARTS_FOLDER: &str = "charts";
+const DEFAULT_LOCALE: &str = "en";
+
+/// Everything is inlined so the site works offline
+const DOCS_STYLE: &str = "
+body { font-family: sans-serif; max-width: 60em; margin: 2em auto; padding: 0 1em; color: #222; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: left; }
+th { background: #f4f4f4; }
+figure { margin: 1em 0; }
+figure img { max-width: 100%; border: 1px solid #eee; }
+.key { font-size: 1.2em; }
+";
+
+/// The targets install instructions are written for, with the name of the
+/// target in a layout file
+const TARGETS: [(&str, &str); 6] = [
+    ("windows", "Windows"),
+    ("macOS", "macOS"),
+    ("chromeOS", "ChromeOS"),
+    ("linux", "Linux"),
+    ("iOS", "iOS"),
+    ("android", "Android"),
+];
+
+pub fn escape_html(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+fn page_start(output: &mut String, language: &str, title: &str) -> Result<()> {
+    writeln!(output, "<!DOCTYPE html>")?;
+    writeln!(output, "<html lang=\"{}\">", escape_html(language))?;
+    writeln!(output, "<head>")?;
+    writeln!(output, "<meta charset=\"utf-8\">")?;
+    writeln!(
+        output,
+        "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">"
+    )?;
+    writeln!(output, "<title>{}</title>", escape_html(title))?;
+    writeln!(output, "<style>{}</style>", DOCS_STYLE)?;
+    writeln!(output, "</head>")?;
+    writeln!(output, "<body>")?;
+    Ok(())
+}
+
+fn page_end(output: &mut String) -> Result<()> {
+    writeln!(output, "</body>")?;
+    writeln!(output, "</html>")?;
+    Ok(())
+}
+
+/// The description of the project in English, or the first locale it has
+fn project_description(bundle: &KbdgenBundle) -> Option<&LocaleProjectDescription> {
+    bundle
+        .project
+        .locales
+        .get(DEFAULT_LOCALE)
+        .or_else(|| bundle.project.locales.values().next())
+}
+
+fn layout_file_name(language_tag: &LanguageTag) -> String {
+    format!("{}.{}", language_tag, HTML_EXT)
+}
+
+/// Whether any layout of the bundle has a target
+fn has_target(bundle: &KbdgenBundle, target: &str) -> bool {
+    bundle.layouts.values().any(|layout| match target {
+        "windows" => layout.windows.is_some(),
+        "macOS" => layout.mac_os.is_some(),
+        "chromeOS" => layout.chrome_os.is_some(),
+        "linux" => layout.linux.is_some(),
+        "iOS" => layout.i_os.is_some(),
+        "android" => layout.android.is_some(),
+        _ => false,
+    })
+}
+
+fn install_steps(target: &str, name: &str) -> String {
+    let name = format!("<em>{}</em>", escape_html(name));
+    match target {
+        "windows" => format!(
+            "Run the {} installer, then add the keyboard under Settings › Time &amp; language › Language &amp; region and switch to it with Windows+Space.",
+            name
+        ),
+        "macOS" => format!(
+            "Open the {} installer package, then add the keyboard under System Settings › Keyboard › Input Sources.",
+            name
+        ),
+        "chromeOS" => format!(
+            "Install the {} extension, then add the keyboard under Settings › Languages and inputs › Input methods.",
+            name
+        ),
+        "linux" => format!(
+            "Install the {} package, then add the keyboard in the keyboard settings of your desktop.",
+            name
+        ),
+        "iOS" => format!(
+            "Install the {} app, then enable the keyboard under Settings › General › Keyboard › Keyboards and allow full access if asked.",
+            name
+        ),
+        "android" => format!(
+            "Install the {} app, then enable the keyboard under Settings › System › Languages &amp; input › On-screen keyboard.",
+            name
+        ),
+        _ => String::new(),
+    }
+}
+
+pub fn generate_index(
+    bundle: &KbdgenBundle,
+    layouts: &[(&LanguageTag, &Layout)],
+) -> Result<String> {
+    let description = project_description(bundle);
+    let title = description
+        .map(|x| x.name.as_str())
+        .unwrap_or_else(|| bundle.name());
+    let mut output = String::new();
+
+    page_start(&mut output, DEFAULT_LOCALE, title)?;
+    writeln!(output, "<h1>{}</h1>", escape_html(title))?;
+    if let Some(description) = description {
+        writeln!(output, "<p>{}</p>", escape_html(&description.description))?;
+    }
+
+    writeln!(output, "<h2>Layouts</h2>")?;
+    writeln!(output, "<ul>")?;
+    for (language_tag, layout) in layouts {
+        writeln!(
+            output,
+            "<li><a href=\"{}\">{}</a> ({})</li>",
+            escape_html(&layout_file_name(language_tag)),
+            escape_html(layout.autonym()),
+            escape_html(language_tag.as_str())
+        )?;
+    }
+    writeln!(output, "</ul>")?;
+
+    writeln!(output, "<h2 id=\"install\">Installing</h2>")?;
+    for (target, target_name) in TARGETS {
+        if !has_target(bundle, target) {
+            continue;
+        }
+
+        writeln!(output, "<h3>{}</h3>", target_name)?;
+        writeln!(output, "<p>{}</p>", install_steps(target, title))?;
+    }
+
+    if bundle.project.locales.len() > 1 {
+        writeln!(output, "<h2>In other languages</h2>")?;
+        writeln!(output, "<table>")?;
+        writeln!(
+            output,
+            "<tr><th>Locale</th><th>Name</th><th>Description</th></tr>"
+        )?;
+        for (locale, description) in &bundle.project.locales {
+            writeln!(
+                output,
+                "<tr lang=\"{}\"><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape_html(locale),
+                escape_html(locale),
+                escape_html(&description.name),
+                escape_html(&description.description)
+            )?;
+        }
+        writeln!(output, "</table>")?;
+    }
+
+    writeln!(
+        output,
+        "<footer><p>{} · {}</p></footer>",
+        escape_html(&bundle.project.copyright),
+        escape_html(&bundle.project.organisation)
+    )?;
+    page_end(&mut output)?;
+
+    Ok(output)
+}
+
+/// Writes what is typed to reach a transform, e.g. `¨ u` or `´ space`
+fn typed_keys(typed: &str) -> String {
+    typed
+        .chars()
+        .map(|c| {
+            if c.to_string() == TRANSFORM_ESCAPE {
+                "space".to_string()
+            } else {
+                escape_html(&c.to_string())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn write_dead_keys(output: &mut String, layout: &Layout) -> Result<()> {
+    let Some(transforms) = &layout.transforms else {
+        return Ok(());
+    };
+
+    writeln!(output, "<h2>Dead keys</h2>")?;
+    writeln!(
+        output,
+        "<p>Type a dead key, then the keys after it, to get the result.</p>"
+    )?;
+
+    for (dead_key, sequences) in DeadKeys::from_transforms(transforms).sequences() {
+        writeln!(output, "<h3 class=\"key\">{}</h3>", escape_html(&dead_key))?;
+        writeln!(output, "<table>")?;
+        writeln!(output, "<tr><th>Then type</th><th>Result</th></tr>")?;
+        for (typed, result) in sequences {
+            writeln!(
+                output,
+                "<tr><td class=\"key\">{}</td><td class=\"key\">{}</td></tr>",
+                typed_keys(&typed),
+                escape_html(&result)
+            )?;
+        }
+        writeln!(output, "</table>")?;
+    }
+
+    Ok(())
+}
+
+fn write_longpress(output: &mut String, layout: &Layout) -> Result<()> {
+    let Some(longpress) = &layout.longpress else {
+        return Ok(());
+    };
+
+    writeln!(output, "<h2>Longpress</h2>")?;
+    writeln!(
+        output,
+        "<p>On touch keyboards, hold a key to pick one of these.</p>"
+    )?;
+    writeln!(output, "<table>")?;
+    writeln!(output, "<tr><th>Key</th><th>Longpress</th></tr>")?;
+    for (key, alternatives) in longpress {
+        let alternatives = alternatives
+            .iter()
+            .map(|x| escape_html(&decode_unicode_escapes(x)))
+            .collect::<Vec<_>>();
+        writeln!(
+            output,
+            "<tr><td class=\"key\">{}</td><td class=\"key\">{}</td></tr>",
+            escape_html(&decode_unicode_escapes(key)),
+            alternatives.join(" ")
+        )?;
+    }
+    writeln!(output, "</table>")?;
+
+    Ok(())
+}
+
+/// A page for a layout, with `charts` as captions and the files in the
+/// charts folder they are drawn in
+pub fn generate_layout_page(
+    bundle: &KbdgenBundle,
+    language_tag: &LanguageTag,
+    layout: &Layout,
+    charts: &[(String, String)],
+) -> Result<String> {
+    let title = layout.autonym();
+    let mut output = String::new();
+
+    page_start(&mut output, language_tag.as_str(), title)?;
+    writeln!(
+        output,
+        "<p><a href=\"{}\">{}</a></p>",
+        INDEX_FILE,
+        escape_html(
+            project_description(bundle)
+                .map(|x| x.name.as_str())
+                .unwrap_or_else(|| bundle.name())
+        )
+    )?;
+    writeln!(output, "<h1>{}</h1>", escape_html(title))?;
+
+    writeln!(output, "<h2>Names</h2>")?;
+    writeln!(output, "<table>")?;
+    writeln!(output, "<tr><th>Language</th><th>Name</th></tr>")?;
+    for (language, name) in &layout.display_names {
+        writeln!(
+            output,
+            "<tr><td>{}</td><td lang=\"{}\">{}</td></tr>",
+            escape_html(language.as_str()),
+            escape_html(language.as_str()),
+            escape_html(name)
+        )?;
+    }
+    writeln!(output, "</table>")?;
+
+    if !charts.is_empty() {
+        writeln!(output, "<h2>Keyboards</h2>")?;
+        for (caption, file_name) in charts {
+            writeln!(output, "<figure>")?;
+            writeln!(
+                output,
+                "<img src=\"{}/{}\" alt=\"{}\">",
+                CHARTS_FOLDER,
+                escape_html(file_name),
+                escape_html(caption)
+            )?;
+            writeln!(output, "<figcaption>{}</figcaption>", escape_html(caption))?;
+            writeln!(output, "</figure>")?;
+        }
+    }
+
+    write_dead_keys(&mut output, layout)?;
+    write_longpress(&mut output, layout)?;
+
+    writeln!(
+        output,
+        "<p>See <a href=\"{}#install\">installing</a> for how to set up the keyboard.</p>",
+        INDEX_FILE
+    )?;
+    page_end(&mut output)?;
+
+    Ok(output)
+}
+
+/// Writes the keyboard charts and touch keyboard previews of a layout,
+/// returning their captions and file names
+fn write_charts(
+    language_tag: &LanguageTag,
+    layout: &Layout,
+    charts_path: &Path,
+) -> Result<Vec<(String, String)>> {
+    let mut charts = vec![];
+
+    for platform in SVG_PLATFORMS {
+        let Some(keyboard) = Keyboard::from_layout(layout, platform) else {
+            continue;
+        };
+
+        let file_name = chart_file_name(language_tag, platform);
+        std::fs::write(
+            charts_path.join(&file_name),
+            generate_svg(layout, platform, &keyboard),
+        )?;
+        charts.push((platform.target_name().to_string(), file_name));
+    }
+
+    let prefix = format!("{}-", language_tag);
+    for (name, svg) in generate_mobile_previews(language_tag.as_str(), layout) {
+        let file_name = format!("{}.{}", name, SVG_EXT);
+        std::fs::write(charts_path.join(&file_name), svg)?;
+
+        let caption = name.strip_prefix(&prefix).unwrap_or(&name).to_string();
+        charts.push((caption, file_name));
+    }
+
+    Ok(charts)
+}
+
+pub struct GenerateDocs;
+
+#[async_trait(?Send)]
+impl BuildStep for GenerateDocs {
+    async fn build(&self, bundle: &KbdgenBundle, output_path: &Path) -> Result<()> {
+        let charts_path = output_path.join(CHARTS_FOLDER);
+        std::fs::create_dir_all(&charts_path)?;
+
+        let mut layouts = bundle.layouts.iter().collect::<Vec<_>>();
+        layouts.sort_by_key(|(language_tag, _)| language_tag.as_str());
+
+        for (language_tag, layout) in &layouts {
+            tracing::debug!("Generating docs for {}", language_tag);
+
+            let charts = write_charts(language_tag, layout, &charts_path)?;
+            std::fs::write(
+                output_path.join(layout_file_name(language_tag)),
+                generate_layout_page(bundle, language_tag, layout, &charts)?,
+            )?;
+        }
+
+        std::fs::write(
+            output_path.join(INDEX_FILE),
+            generate_index(bundle, &layouts)?,
+        )?;
+
+        Ok(())
+    }
+}
@@ -11,9 +11,10 @@ use crate::{
     build::BuildStep,
     bundle::{
         KbdgenBundle,
-        layout::{IOsPlatform, Transform, ios::IOsKbdLayer},
+        layout::{IOsPlatform, ios::IOsKbdLayer},
     },
-    util::{TRANSFORM_ESCAPE, split_keys},
+    model::DeadKeys,
+    util::split_keys,
 };
 
 const REPOSITORY: &str = "repo";
@@ -69,8 +70,8 @@ pub fn keyboard_component_from_string(input: String) -> Option<IosButton> {
 
 #[derive(Serialize, Deserialize)]
 pub struct IosButton {
-    id: String,
-    width: f32,
+    pub id: String,
+    pub width: f32,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -147,44 +148,6 @@ pub fn generate_platform(platform: &IOsPlatform) -> IndexMap<String, Vec<Vec<Ios
     layers
 }
 
-fn process_transforms(
-    transforms: &IndexMap<String, Transform>,
-) -> IndexMap<String, IndexMap<String, String>> {
-    let mut output_map: IndexMap<String, IndexMap<String, String>> = IndexMap::new();
-
-    for (dead_key, transform) in transforms {
-        let mut transforms_by_char: IndexMap<String, String> = IndexMap::new();
-        match transform {
-            Transform::End(character) => {
-                tracing::error!(
-                    "Transform ended too soon for dead key {} - character {}",
-                    dead_key,
-                    character
-                );
-            }
-            Transform::More(transforms) => {
-                for (next_char, transform) in transforms {
-                    if next_char == TRANSFORM_ESCAPE {
-                        transforms_by_char.insert(next_char.clone(), dead_key.clone());
-                    }
-                    match transform {
-                        Transform::End(end_char) => {
-                            transforms_by_char.insert(next_char.clone(), end_char.clone());
-                        }
-                        Transform::More(_transform) => {
-                            todo!("Recursion required ahead");
-                        }
-                    }
-                }
-            }
-        }
-
-        output_map.insert(dead_key.clone(), transforms_by_char);
-    }
-
-    output_map
-}
-
 pub struct GenerateIos;
 
 #[async_trait(?Send)]
@@ -240,7 +203,7 @@ impl BuildStep for GenerateIos {
                             i_pad_9in: dead_keys.clone(),
                             i_pad_12in: dead_keys.clone(),
                         },
-                        transforms: process_transforms(&transforms),
+                        transforms: DeadKeys::from_transforms(&transforms).sequences(),
                         iphone: IosPlatform {
                             layer: iphone_layers,
                         },
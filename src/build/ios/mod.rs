@@ -36,17 +36,21 @@ pub struct IosBuild {
     pub steps: Vec<Box<dyn BuildStep>>,
 }
 
+pub fn build_steps() -> Vec<Box<dyn BuildStep>> {
+    vec![
+        Box::new(CloneGiellaKbd),
+        Box::new(GenerateIos),
+        Box::new(GenerateXcode),
+        Box::new(FastlaneProvisioning),
+        Box::new(PodInstall),
+        Box::new(BuildXcarchive),
+    ]
+}
+
 #[async_trait(?Send)]
 impl BuildSteps for IosBuild {
     fn new(bundle: KbdgenBundle, output_path: PathBuf) -> Self {
-        let steps: Vec<Box<dyn BuildStep>> = vec![
-            Box::new(CloneGiellaKbd),
-            Box::new(GenerateIos),
-            Box::new(GenerateXcode),
-            Box::new(FastlaneProvisioning),
-            Box::new(PodInstall),
-            Box::new(BuildXcarchive),
-        ];
+        let steps = build_steps();
 
         IosBuild {
             bundle,
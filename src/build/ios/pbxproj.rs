@@ -1,29 +1,43 @@
 use std::{
     borrow::BorrowMut,
+    cell::RefCell,
     collections::{BTreeMap, BTreeSet},
     path::{Path, PathBuf},
     str::FromStr,
 };
 
 use indexmap::IndexMap;
+use rand::{SeedableRng, rngs::StdRng};
 use serde::{Deserialize, Serialize};
 
 #[nova::newtype(serde, display)]
 pub type ObjectId = String;
 
+thread_local! {
+    static OBJECT_ID_RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Makes the object ids created on this thread from now on reproducible
+#[cfg(test)]
+pub(crate) fn seed_object_ids(seed: u64) {
+    OBJECT_ID_RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
 impl ObjectId {
     pub fn new_random() -> Self {
         use rand::Rng;
         const CHARSET: &[u8] = b"0123456789ABCDEF";
         const LEN: usize = 24;
-        let mut rng = rand::thread_rng();
 
-        let id: String = (0..LEN)
-            .map(|_| {
-                let idx = rng.gen_range(0..CHARSET.len());
-                CHARSET[idx] as char
-            })
-            .collect();
+        let id: String = OBJECT_ID_RNG.with(|rng| {
+            let mut rng = rng.borrow_mut();
+            (0..LEN)
+                .map(|_| {
+                    let idx = rng.gen_range(0..CHARSET.len());
+                    CHARSET[idx] as char
+                })
+                .collect()
+        });
 
         ObjectId(id)
     }
@@ -977,6 +991,12 @@ pub enum Object {
 pub fn convert_pbxproj_to_json(path: &Path) -> Pbxproj {
     tracing::debug!("Getting .pbxproj as json");
 
+    // Xcode reads projects saved as JSON too, and those need no converting
+    let text = std::fs::read_to_string(path).unwrap();
+    if let Ok(pbxproj) = serde_json::from_str(&text) {
+        return pbxproj;
+    }
+
     let tempdir = tempfile::tempdir().unwrap();
     let pbxproj_path = tempdir.path().join("tmp.pbxproj");
     std::fs::copy(path, &pbxproj_path).unwrap();
@@ -0,0 +1,382 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use indexmap::IndexSet;
+use language_tags::LanguageTag;
+
+use crate::{
+    build::BuildStep,
+    bundle::{KbdgenBundle, layout::Layout},
+    model::{DeadKeys, KeyAction, Next, StateId},
+    util::TRANSFORM_ESCAPE,
+};
+
+use super::{
+    SOURCE_FOLDER, keyboard_id,
+    keymap::KEYMAN_KEYS,
+    layers::{KeymanKeyboard, KeymanLayer, keyman_keyboard},
+    package_version,
+};
+
+const KMN_EXT: &str = "kmn";
+const KEYMAN_FILE_VERSION: &str = "10.0";
+const KEYMAN_TARGETS: &str = "windows macosx linux web";
+
+/// Writes characters as `U+XXXX` sequences, which avoids any quoting issues
+pub fn kmn_chars(input: &str) -> String {
+    input
+        .chars()
+        .map(|c| format!("U+{:04X}", c as u32))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quotes a string for use in a system store. A string with both kinds of
+/// quotes has its single quotes written as `U+0027` between the quoted parts.
+pub fn kmn_string(input: &str) -> String {
+    if !input.contains('\'') {
+        return format!("'{}'", input);
+    }
+    if !input.contains('"') {
+        return format!("\"{}\"", input);
+    }
+
+    let mut parts = vec![];
+    for (index, part) in input.split('\'').enumerate() {
+        if index > 0 {
+            parts.push(kmn_chars("'"));
+        }
+        if !part.is_empty() {
+            parts.push(format!("'{}'", part));
+        }
+    }
+
+    parts.join(" ")
+}
+
+/// The deadkey name for a sequence of typed characters, e.g. `dk_00b4`
+pub fn dead_key_name(typed: &str) -> String {
+    let codes = typed
+        .chars()
+        .map(|c| format!("{:04x}", c as u32))
+        .collect::<Vec<_>>()
+        .join("_");
+
+    format!("dk_{}", codes)
+}
+
+fn key_rule(modifiers: &str, vkey: &str, output: &str) -> String {
+    if modifiers.is_empty() {
+        format!("+ [{}] > {}\n", vkey, output)
+    } else {
+        format!("+ [{} {}] > {}\n", modifiers, vkey, output)
+    }
+}
+
+fn write_layer_rules(output: &mut String, layer: &KeymanLayer) -> Result<()> {
+    let modifiers = layer.kmn_modifiers();
+
+    writeln!(output, "c {}", layer.name)?;
+    for (iso_key, vkey) in KEYMAN_KEYS.iter() {
+        let key_output = match layer.layer.key(*iso_key) {
+            KeyAction::None => continue,
+            KeyAction::Special(name) => {
+                tracing::warn!(
+                    r"Special key \s{{{}}} has no Keyman equivalent, skipping",
+                    name
+                );
+                continue;
+            }
+            KeyAction::DeadKey(key) => format!("dk({})", dead_key_name(key)),
+            KeyAction::Char(character) => kmn_chars(&character.to_string()),
+            KeyAction::Ligature(key) => kmn_chars(key),
+        };
+
+        output.push_str(&key_rule(&modifiers, vkey, &key_output));
+    }
+    writeln!(output)?;
+
+    Ok(())
+}
+
+/// Keys for which caps lock acts as shift, used when the layout has no caps
+/// layers of its own. Like on other platforms, only keys whose shifted
+/// output is the uppercase of the default output are affected.
+fn cased_keys(layers: &[KeymanLayer]) -> Vec<String> {
+    let default = layers.iter().find(|x| !x.shift && !x.alt);
+    let shift = layers.iter().find(|x| x.shift && !x.alt);
+
+    let (Some(default), Some(shift)) = (default, shift) else {
+        return vec![];
+    };
+
+    KEYMAN_KEYS
+        .iter()
+        .filter(|(iso_key, _)| {
+            let lower = default.layer.key(**iso_key).output().unwrap_or_default();
+            let upper = shift.layer.key(**iso_key).output().unwrap_or_default();
+            lower != upper && lower.to_uppercase() == upper
+        })
+        .map(|(_, vkey)| format!("[{}]", vkey))
+        .collect()
+}
+
+struct DeadKeyRules {
+    stores: String,
+    rules: String,
+}
+
+/// Writes the rules for a dead key state and the states chained from it.
+/// Single character results are collected into an `any`/`index` store pair,
+/// the rest become individual rules.
+fn write_dead_key_rules(
+    output: &mut DeadKeyRules,
+    automaton: &DeadKeys,
+    id: StateId,
+    dead_keys: &IndexSet<String>,
+) -> Result<()> {
+    let state = &automaton.states[id];
+    let name = dead_key_name(&state.sequence);
+    let mut from = String::new();
+    let mut to = String::new();
+
+    let mut ends: Vec<(&str, &str)> = vec![(TRANSFORM_ESCAPE, state.escape_or_fallback())];
+
+    for (next_char, next) in &state.transitions {
+        match next {
+            Next::Output(end_char) => ends.push((next_char, end_char)),
+            Next::State(next_id) => {
+                let next_name = dead_key_name(&automaton.states[*next_id].sequence);
+
+                writeln!(
+                    output.rules,
+                    "dk({}) {} > dk({})",
+                    name,
+                    kmn_chars(next_char),
+                    next_name
+                )?;
+                if dead_keys.contains(next_char) {
+                    writeln!(
+                        output.rules,
+                        "dk({}) dk({}) > dk({})",
+                        name,
+                        dead_key_name(next_char),
+                        next_name
+                    )?;
+                }
+
+                write_dead_key_rules(output, automaton, *next_id, dead_keys)?;
+            }
+        }
+    }
+
+    for (next_char, end_char) in ends {
+        if dead_keys.contains(next_char) {
+            writeln!(
+                output.rules,
+                "dk({}) dk({}) > {}",
+                name,
+                dead_key_name(next_char),
+                kmn_chars(end_char)
+            )?;
+        }
+
+        if next_char.chars().count() == 1 && end_char.chars().count() == 1 {
+            from.push_str(next_char);
+            to.push_str(end_char);
+        } else {
+            writeln!(
+                output.rules,
+                "dk({}) {} > {}",
+                name,
+                kmn_chars(next_char),
+                kmn_chars(end_char)
+            )?;
+        }
+    }
+
+    if !from.is_empty() {
+        let codes = name.trim_start_matches("dk_");
+        writeln!(output.stores, "store(dkf_{}) {}", codes, kmn_chars(&from))?;
+        writeln!(output.stores, "store(dkt_{}) {}", codes, kmn_chars(&to))?;
+        writeln!(
+            output.rules,
+            "dk({}) any(dkf_{}) > index(dkt_{}, 2)",
+            name, codes, codes
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn generate_kmn(
+    bundle: &KbdgenBundle,
+    language_tag: &LanguageTag,
+    layout: &Layout,
+    keyboard: &KeymanKeyboard,
+) -> Result<String> {
+    let layers = &keyboard.layers;
+    let id = keyboard_id(language_tag);
+    let mut output = String::new();
+
+    writeln!(output, "c Generated by kbdgen")?;
+    writeln!(output)?;
+    writeln!(output, "store(&VERSION) '{}'", KEYMAN_FILE_VERSION)?;
+    writeln!(output, "store(&NAME) {}", kmn_string(layout.autonym()))?;
+    writeln!(
+        output,
+        "store(&COPYRIGHT) {}",
+        kmn_string(&bundle.project.copyright)
+    )?;
+    writeln!(
+        output,
+        "store(&KEYBOARDVERSION) {}",
+        kmn_string(&package_version(bundle))
+    )?;
+    writeln!(output, "store(&TARGETS) '{}'", KEYMAN_TARGETS)?;
+    writeln!(output, "store(&VISUALKEYBOARD) '{}.kvks'", id)?;
+
+    if layers.iter().all(|x| x.caps.is_none()) {
+        let cased_keys = cased_keys(layers);
+        if !cased_keys.is_empty() {
+            writeln!(output, "store(&CasedKeys) {}", cased_keys.join(" "))?;
+        }
+    }
+
+    writeln!(output)?;
+    writeln!(output, "begin Unicode > use(main)")?;
+    writeln!(output)?;
+    writeln!(output, "group(main) using keys")?;
+    writeln!(output)?;
+
+    for layer in layers {
+        write_layer_rules(&mut output, layer)?;
+    }
+
+    output.push_str(&key_rule("", "K_SPACE", &kmn_chars(" ")));
+
+    let dead_keys: IndexSet<String> = layers
+        .iter()
+        .flat_map(|x| x.layer.keys.values())
+        .filter_map(|x| match x {
+            KeyAction::DeadKey(key) => Some(key.clone()),
+            _ => None,
+        })
+        .collect();
+
+    if dead_keys.is_empty() || keyboard.dead_keys.starts.is_empty() {
+        return Ok(output);
+    }
+
+    let mut dead_key_rules = DeadKeyRules {
+        stores: String::new(),
+        rules: String::new(),
+    };
+
+    for (dead_key, id) in &keyboard.dead_keys.starts {
+        if !dead_keys.contains(dead_key) {
+            continue;
+        }
+
+        write_dead_key_rules(&mut dead_key_rules, &keyboard.dead_keys, *id, &dead_keys)?;
+    }
+
+    writeln!(output)?;
+    writeln!(output, "match > use(deadkeys)")?;
+    writeln!(output)?;
+    writeln!(output, "group(deadkeys)")?;
+    writeln!(output)?;
+    output.push_str(&dead_key_rules.stores);
+    writeln!(output)?;
+    output.push_str(&dead_key_rules.rules);
+
+    Ok(output)
+}
+
+pub struct GenerateKmn;
+
+#[async_trait(?Send)]
+impl BuildStep for GenerateKmn {
+    async fn build(&self, bundle: &KbdgenBundle, output_path: &Path) -> Result<()> {
+        let source_path = output_path.join(SOURCE_FOLDER);
+        std::fs::create_dir_all(&source_path)?;
+
+        for (language_tag, layout) in &bundle.layouts {
+            let Some(keyboard) = keyman_keyboard(bundle, language_tag, layout)? else {
+                continue;
+            };
+
+            tracing::debug!("Generating kmn for {}", language_tag);
+
+            std::fs::write(
+                source_path.join(format!("{}.{}", keyboard_id(language_tag), KMN_EXT)),
+                generate_kmn(bundle, language_tag, layout, &keyboard)?,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::test_layout;
+
+    #[test]
+    fn test_kmn_string() {
+        assert_eq!(kmn_string("Divvun"), "'Divvun'");
+        assert_eq!(kmn_string("Divvun's"), "\"Divvun's\"");
+        assert_eq!(
+            kmn_string("\"Divvun's\" 'keyboard'"),
+            "'\"Divvun' U+0027 's\" ' U+0027 'keyboard' U+0027"
+        );
+    }
+
+    #[test]
+    fn test_write_dead_key_rules() {
+        let text = r#"
+displayNames:
+  sje: Bidumsámegiella
+transforms:
+  ´:
+    ' ': ´
+    e: é
+    '`': ´`
+    ¨:
+      u: ǘ
+"#;
+
+        let layout = test_layout("sje", text);
+        let automaton = DeadKeys::from_transforms(layout.transforms.as_ref().unwrap());
+        let dead_keys = IndexSet::from(["´".to_string(), "¨".to_string(), "`".to_string()]);
+
+        let mut rules = DeadKeyRules {
+            stores: String::new(),
+            rules: String::new(),
+        };
+        write_dead_key_rules(&mut rules, &automaton, automaton.starts["´"], &dead_keys).unwrap();
+
+        assert_eq!(
+            rules.stores,
+            "store(dkf_00b4_00a8) U+0075\n\
+            store(dkt_00b4_00a8) U+01D8\n\
+            store(dkf_00b4) U+0020 U+0065\n\
+            store(dkt_00b4) U+00B4 U+00E9\n"
+        );
+        // The chained state has no escape, so what was typed is output, and
+        // results of more than one character get a rule of their own
+        assert_eq!(
+            rules.rules,
+            "dk(dk_00b4) U+00A8 > dk(dk_00b4_00a8)\n\
+            dk(dk_00b4) dk(dk_00a8) > dk(dk_00b4_00a8)\n\
+            dk(dk_00b4_00a8) U+0020 > U+00B4 U+00A8\n\
+            dk(dk_00b4_00a8) any(dkf_00b4_00a8) > index(dkt_00b4_00a8, 2)\n\
+            dk(dk_00b4) dk(dk_0060) > U+00B4 U+0060\n\
+            dk(dk_00b4) U+0060 > U+00B4 U+0060\n\
+            dk(dk_00b4) any(dkf_00b4) > index(dkt_00b4, 2)\n"
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use language_tags::LanguageTag;
+use xmlem::Document;
+
+use crate::{build::BuildStep, bundle::KbdgenBundle};
+
+use super::{
+    SOURCE_FOLDER, keyboard_id,
+    keymap::KEYMAN_KEYS,
+    layers::{KeymanLayer, keyman_keyboard},
+};
+
+const KVKS_EXT: &str = "kvks";
+const KVKS_VERSION: &str = "10.0";
+const KVKS_FONT: &str = "Arial";
+const KVKS_FONT_SIZE: &str = "-12";
+
+/// Creates the on-screen keyboard for a layout. Caps lock layers cannot be
+/// shown by Keyman's on-screen keyboard and are left out.
+pub fn generate_kvks(language_tag: &LanguageTag, layers: &[KeymanLayer]) -> String {
+    let mut document = Document::new("visualkeyboard");
+    let root = document.root();
+
+    let header = root.append_new_element(&mut document, "header");
+    header
+        .append_new_element(&mut document, "version")
+        .set_text(&mut document, KVKS_VERSION);
+    header
+        .append_new_element(&mut document, "kbdname")
+        .set_text(&mut document, &keyboard_id(language_tag));
+
+    let flags = header.append_new_element(&mut document, "flags");
+    flags.append_new_element(&mut document, "key102");
+    if layers.iter().any(|x| x.alt) {
+        flags.append_new_element(&mut document, "usealtgr");
+    }
+
+    let encoding = root.append_new_element(
+        &mut document,
+        (
+            "encoding",
+            [
+                ("name", "unicode"),
+                ("fontname", KVKS_FONT),
+                ("fontsize", KVKS_FONT_SIZE),
+            ],
+        ),
+    );
+
+    for layer in layers {
+        let Some(shift) = layer.kvks_shift() else {
+            continue;
+        };
+
+        let layer_element =
+            encoding.append_new_element(&mut document, ("layer", [("shift", shift)]));
+
+        for (iso_key, vkey) in KEYMAN_KEYS.iter() {
+            let Some(key) = layer.layer.key(*iso_key).output() else {
+                continue;
+            };
+
+            layer_element
+                .append_new_element(&mut document, ("key", [("vkey", vkey.as_str())]))
+                .set_text(&mut document, &key);
+        }
+    }
+
+    document.to_string_pretty()
+}
+
+pub struct GenerateKvks;
+
+#[async_trait(?Send)]
+impl BuildStep for GenerateKvks {
+    async fn build(&self, bundle: &KbdgenBundle, output_path: &Path) -> Result<()> {
+        let source_path = output_path.join(SOURCE_FOLDER);
+        std::fs::create_dir_all(&source_path)?;
+
+        for (language_tag, layout) in &bundle.layouts {
+            let Some(keyboard) = keyman_keyboard(bundle, language_tag, layout)? else {
+                continue;
+            };
+
+            tracing::debug!("Generating kvks for {}", language_tag);
+
+            std::fs::write(
+                source_path.join(format!("{}.{}", keyboard_id(language_tag), KVKS_EXT)),
+                generate_kvks(language_tag, &keyboard.layers),
+            )?;
+        }
+
+        Ok(())
+    }
+}
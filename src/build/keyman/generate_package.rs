@@ -0,0 +1,147 @@
+use std::path::Path;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use xmlem::{Document, Element};
+
+use crate::{build::BuildStep, bundle::KbdgenBundle};
+
+use super::{
+    BUILD_FOLDER, SOURCE_FOLDER, keyboard_id, keyman_id, layers::has_keyman_layers, package_version,
+};
+
+const KPS_EXT: &str = "kps";
+const KPJ_EXT: &str = "kpj";
+const KPS_FILE_VERSION: &str = "7.0";
+const KPJ_VERSION: &str = "2.0";
+const DEFAULT_LOCALE: &str = "en";
+
+fn append_text_element(element: &Element, document: &mut Document, name: &str, text: &str) {
+    element
+        .append_new_element(document, name)
+        .set_text(document, text);
+}
+
+fn append_file(files: &Element, document: &mut Document, name: &str, description: &str) {
+    let file = files.append_new_element(document, "File");
+    let extension = Path::new(name)
+        .extension()
+        .map(|x| format!(".{}", x.to_string_lossy()))
+        .unwrap_or_default();
+
+    append_text_element(&file, document, "Name", name);
+    append_text_element(&file, document, "Description", description);
+    append_text_element(&file, document, "CopyLocation", "0");
+    append_text_element(&file, document, "FileType", &extension);
+}
+
+/// Creates a package containing every keyboard in the bundle
+pub fn generate_kps(bundle: &KbdgenBundle) -> String {
+    let version = package_version(bundle);
+    let locale = bundle.project.locales.get(DEFAULT_LOCALE);
+    let package_name = locale
+        .map(|x| x.name.clone())
+        .unwrap_or_else(|| bundle.name().to_string());
+
+    let mut document = Document::new("Package");
+    let root = document.root();
+
+    let system = root.append_new_element(&mut document, "System");
+    append_text_element(&system, &mut document, "FileVersion", KPS_FILE_VERSION);
+
+    let info = root.append_new_element(&mut document, "Info");
+    info.append_new_element(&mut document, ("Name", [("URL", "")]))
+        .set_text(&mut document, &package_name);
+    info.append_new_element(&mut document, ("Copyright", [("URL", "")]))
+        .set_text(&mut document, &bundle.project.copyright);
+    info.append_new_element(
+        &mut document,
+        (
+            "Author",
+            [("URL", format!("mailto:{}", bundle.project.email))],
+        ),
+    )
+    .set_text(&mut document, &bundle.project.author);
+    info.append_new_element(&mut document, ("Version", [("URL", "")]))
+        .set_text(&mut document, &version);
+    if let Some(locale) = locale {
+        append_text_element(&info, &mut document, "Description", &locale.description);
+    }
+
+    let files = root.append_new_element(&mut document, "Files");
+    let keyboards = root.append_new_element(&mut document, "Keyboards");
+
+    let mut language_tags = bundle.layouts.keys().collect::<Vec<_>>();
+    language_tags.sort_by_key(|x| x.as_str());
+
+    for language_tag in language_tags {
+        let layout = &bundle.layouts[language_tag];
+
+        if !has_keyman_layers(layout) {
+            continue;
+        }
+
+        let id = keyboard_id(language_tag);
+        let autonym = layout.autonym();
+
+        append_file(
+            &files,
+            &mut document,
+            &format!("../{}/{}.kmx", BUILD_FOLDER, id),
+            &format!("Keyboard {}", autonym),
+        );
+        append_file(
+            &files,
+            &mut document,
+            &format!("../{}/{}.kvk", BUILD_FOLDER, id),
+            &format!("On-screen keyboard {}", autonym),
+        );
+
+        let keyboard = keyboards.append_new_element(&mut document, "Keyboard");
+        append_text_element(&keyboard, &mut document, "Name", autonym);
+        append_text_element(&keyboard, &mut document, "ID", &id);
+        append_text_element(&keyboard, &mut document, "Version", &version);
+
+        let languages = keyboard.append_new_element(&mut document, "Languages");
+        languages
+            .append_new_element(&mut document, ("Language", [("ID", language_tag.as_str())]))
+            .set_text(&mut document, autonym);
+    }
+
+    document.to_string_pretty()
+}
+
+/// Creates a version 2.0 project, which picks up its files from `source`
+/// and writes the compiled keyboards to `build`
+pub fn generate_kpj() -> String {
+    let mut document = Document::new("KeymanDeveloperProject");
+    let options = document.root().append_new_element(&mut document, "Options");
+
+    append_text_element(&options, &mut document, "Version", KPJ_VERSION);
+
+    document.to_string_pretty()
+}
+
+pub struct GeneratePackage;
+
+#[async_trait(?Send)]
+impl BuildStep for GeneratePackage {
+    async fn build(&self, bundle: &KbdgenBundle, output_path: &Path) -> Result<()> {
+        let source_path = output_path.join(SOURCE_FOLDER);
+        let package_id = keyman_id(bundle.name());
+
+        std::fs::create_dir_all(&source_path)?;
+        std::fs::create_dir_all(output_path.join(BUILD_FOLDER))?;
+
+        std::fs::write(
+            source_path.join(format!("{}.{}", package_id, KPS_EXT)),
+            generate_kps(bundle),
+        )?;
+        std::fs::write(
+            output_path.join(format!("{}.{}", package_id, KPJ_EXT)),
+            generate_kpj(),
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+
+use crate::util::iso_key::IsoKey;
+
+pub static KEYMAN_KEYS: Lazy<IndexMap<IsoKey, String>> = Lazy::new(|| {
+    let mut map = IndexMap::new();
+
+    {
+        let arr = [
+            (IsoKey::E00, "K_BKQUOTE"),
+            (IsoKey::E01, "K_1"),
+            (IsoKey::E02, "K_2"),
+            (IsoKey::E03, "K_3"),
+            (IsoKey::E04, "K_4"),
+            (IsoKey::E05, "K_5"),
+            (IsoKey::E06, "K_6"),
+            (IsoKey::E07, "K_7"),
+            (IsoKey::E08, "K_8"),
+            (IsoKey::E09, "K_9"),
+            (IsoKey::E10, "K_0"),
+            (IsoKey::E11, "K_HYPHEN"),
+            (IsoKey::E12, "K_EQUAL"),
+            (IsoKey::D01, "K_Q"),
+            (IsoKey::D02, "K_W"),
+            (IsoKey::D03, "K_E"),
+            (IsoKey::D04, "K_R"),
+            (IsoKey::D05, "K_T"),
+            (IsoKey::D06, "K_Y"),
+            (IsoKey::D07, "K_U"),
+            (IsoKey::D08, "K_I"),
+            (IsoKey::D09, "K_O"),
+            (IsoKey::D10, "K_P"),
+            (IsoKey::D11, "K_LBRKT"),
+            (IsoKey::D12, "K_RBRKT"),
+            (IsoKey::C01, "K_A"),
+            (IsoKey::C02, "K_S"),
+            (IsoKey::C03, "K_D"),
+            (IsoKey::C04, "K_F"),
+            (IsoKey::C05, "K_G"),
+            (IsoKey::C06, "K_H"),
+            (IsoKey::C07, "K_J"),
+            (IsoKey::C08, "K_K"),
+            (IsoKey::C09, "K_L"),
+            (IsoKey::C10, "K_COLON"),
+            (IsoKey::C11, "K_QUOTE"),
+            (IsoKey::C12, "K_BKSLASH"),
+            (IsoKey::B00, "K_oE2"),
+            (IsoKey::B01, "K_Z"),
+            (IsoKey::B02, "K_X"),
+            (IsoKey::B03, "K_C"),
+            (IsoKey::B04, "K_V"),
+            (IsoKey::B05, "K_B"),
+            (IsoKey::B06, "K_N"),
+            (IsoKey::B07, "K_M"),
+            (IsoKey::B08, "K_COMMA"),
+            (IsoKey::B09, "K_PERIOD"),
+            (IsoKey::B10, "K_SLASH"),
+        ];
+
+        for (key, value) in arr {
+            map.insert(key, value.to_string());
+        }
+    }
+
+    map
+});
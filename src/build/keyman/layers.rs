@@ -0,0 +1,145 @@
+use language_tags::LanguageTag;
+
+use crate::{
+    bundle::{
+        Error, KbdgenBundle,
+        layout::{Layout, macos::MacOsKbdLayer, windows::WindowsKbdLayer},
+    },
+    model::{DeadKeys, Keyboard, Layer, PlatformLayer},
+};
+
+/// A positional layer along with the modifier state that selects it.
+///
+/// `caps` is `None` when the layer should match regardless of the caps lock
+/// state, which is the case unless the layout defines a caps counterpart.
+pub struct KeymanLayer {
+    pub name: String,
+    pub shift: bool,
+    pub alt: bool,
+    pub caps: Option<bool>,
+    pub layer: Layer,
+}
+
+impl KeymanLayer {
+    /// Modifiers as written inside a `.kmn` key rule, e.g. `NCAPS SHIFT RALT`
+    pub fn kmn_modifiers(&self) -> String {
+        let mut modifiers = vec![];
+
+        match self.caps {
+            Some(true) => modifiers.push("CAPS"),
+            Some(false) => modifiers.push("NCAPS"),
+            None => {}
+        }
+        if self.shift {
+            modifiers.push("SHIFT");
+        }
+        if self.alt {
+            modifiers.push("RALT");
+        }
+
+        modifiers.join(" ")
+    }
+
+    /// The `shift` attribute of a `.kvks` layer, or `None` when the on-screen
+    /// keyboard has no equivalent for this layer
+    pub fn kvks_shift(&self) -> Option<&'static str> {
+        if self.caps == Some(true) {
+            return None;
+        }
+
+        Some(match (self.shift, self.alt) {
+            (false, false) => "",
+            (true, false) => "S",
+            (false, true) => "RA",
+            (true, true) => "SRA",
+        })
+    }
+}
+
+/// The desktop layers of a layout with the dead keys shared by them
+pub struct KeymanKeyboard {
+    pub layers: Vec<KeymanLayer>,
+    pub dead_keys: DeadKeys,
+}
+
+fn to_keyman_layers<L>(keyboard: Keyboard) -> KeymanKeyboard
+where
+    L: PlatformLayer + std::fmt::Debug,
+{
+    let mut keyman_layers: Vec<KeymanLayer> = keyboard
+        .layers
+        .into_iter()
+        .filter_map(|layer| {
+            let modifiers = layer.modifiers;
+
+            // Keyman would swallow shortcuts such as Ctrl+C if these were mapped
+            if modifiers.ctrl || modifiers.cmd {
+                return None;
+            }
+
+            Some(KeymanLayer {
+                name: format!("{:?}", L::from_modifiers(modifiers)?),
+                shift: modifiers.shift,
+                alt: modifiers.alt,
+                caps: modifiers.caps.then_some(true),
+                layer,
+            })
+        })
+        .collect();
+
+    // A layer only needs to exclude caps lock if a caps layer exists for the
+    // same modifiers, otherwise it should keep matching with caps lock on
+    let caps_layers: Vec<(bool, bool)> = keyman_layers
+        .iter()
+        .filter(|x| x.caps == Some(true))
+        .map(|x| (x.shift, x.alt))
+        .collect();
+
+    for layer in keyman_layers.iter_mut() {
+        if layer.caps.is_none() && caps_layers.contains(&(layer.shift, layer.alt)) {
+            layer.caps = Some(false);
+        }
+    }
+
+    KeymanKeyboard {
+        layers: keyman_layers,
+        dead_keys: keyboard.dead_keys,
+    }
+}
+
+/// Whether a layout has desktop layers Keyman can use
+pub fn has_keyman_layers(layout: &Layout) -> bool {
+    layout.windows.is_some() || layout.mac_os.is_some()
+}
+
+/// Returns the desktop layers of a layout, preferring the Windows layers and
+/// falling back to the macOS ones
+pub fn keyman_keyboard(
+    bundle: &KbdgenBundle,
+    language_tag: &LanguageTag,
+    layout: &Layout,
+) -> Result<Option<KeymanKeyboard>, Error> {
+    if let Some(target) = &layout.windows {
+        let keyboard = Keyboard::lower(
+            bundle,
+            language_tag,
+            "windows",
+            &target.primary.layers,
+            target.dead_keys.as_ref(),
+        )?;
+        return Ok(Some(to_keyman_layers::<WindowsKbdLayer>(keyboard)));
+    }
+
+    if let Some(target) = &layout.mac_os {
+        let keyboard = Keyboard::lower(
+            bundle,
+            language_tag,
+            "macOS",
+            &target.primary.layers,
+            target.dead_keys.as_ref(),
+        )?;
+        return Ok(Some(to_keyman_layers::<MacOsKbdLayer>(keyboard)));
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use language_tags::LanguageTag;
+
+use crate::bundle::KbdgenBundle;
+
+use self::{
+    generate_kmn::GenerateKmn, generate_kvks::GenerateKvks, generate_package::GeneratePackage,
+};
+
+use super::{BuildStep, BuildSteps};
+
+pub mod generate_kmn;
+pub mod generate_kvks;
+pub mod generate_package;
+mod keymap;
+mod layers;
+
+const SOURCE_FOLDER: &str = "source";
+const BUILD_FOLDER: &str = "build";
+const DEFAULT_VERSION: &str = "1.0";
+
+/// Keyman identifiers may only contain lowercase letters, digits and `_`
+pub fn keyman_id(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+pub fn keyboard_id(language_tag: &LanguageTag) -> String {
+    keyman_id(language_tag.as_str())
+}
+
+pub fn package_version(bundle: &KbdgenBundle) -> String {
+    bundle
+        .targets
+        .keyman
+        .as_ref()
+        .map(|x| x.version.clone())
+        .unwrap_or_else(|| DEFAULT_VERSION.to_string())
+}
+
+pub struct KeymanBuild {
+    pub bundle: KbdgenBundle,
+    pub output_path: PathBuf,
+    pub steps: Vec<Box<dyn BuildStep>>,
+}
+
+pub fn build_steps() -> Vec<Box<dyn BuildStep>> {
+    vec![
+        Box::new(GenerateKmn),
+        Box::new(GenerateKvks),
+        Box::new(GeneratePackage),
+    ]
+}
+
+#[async_trait(?Send)]
+impl BuildSteps for KeymanBuild {
+    fn new(bundle: KbdgenBundle, output_path: PathBuf) -> Self {
+        let steps = build_steps();
+
+        KeymanBuild {
+            bundle,
+            output_path,
+            steps,
+        }
+    }
+
+    fn steps(&self) -> &[Box<dyn BuildStep>] {
+        &self.steps
+    }
+
+    fn bundle(&self) -> &KbdgenBundle {
+        &self.bundle
+    }
+
+    fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+}
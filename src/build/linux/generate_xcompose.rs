@@ -0,0 +1,203 @@
+use std::path::Path;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use indexmap::IndexSet;
+
+use crate::{
+    build::BuildStep,
+    bundle::{KbdgenBundle, layout::LinuxTarget},
+    model::{DeadKeys, Next, StateId},
+    util::decode_unicode_escapes,
+};
+
+use super::keysym::{dead_keysym_for, keysym_for_char};
+
+const COMPOSE_FOLDER: &str = "compose";
+const XCOMPOSE_EXT: &str = "XCompose";
+
+fn linux_dead_keys(target: &LinuxTarget) -> IndexSet<String> {
+    target
+        .dead_keys
+        .iter()
+        .flat_map(|x| x.values())
+        .flatten()
+        .map(|x| decode_unicode_escapes(x))
+        .collect()
+}
+
+/// Returns the keysym produced by a key in a compose sequence, which is its
+/// `dead_*` keysym if it is a dead key on this layout.
+fn sequence_keysym(key: &str, dead_keys: &IndexSet<String>) -> Option<String> {
+    if dead_keys.contains(key)
+        && let Some(dead_keysym) = dead_keysym_for(key)
+    {
+        return Some(dead_keysym.to_string());
+    }
+
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(character), None) => Some(keysym_for_char(character)),
+        _ => None,
+    }
+}
+
+fn escape_compose_string(input: &str) -> String {
+    input.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn write_compose_line(output: &mut String, sequence: &[String], result: &str) {
+    let keys = sequence
+        .iter()
+        .map(|x| format!("<{}>", x))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    output.push_str(&format!(
+        "{} : \"{}\"\n",
+        keys,
+        escape_compose_string(result)
+    ));
+}
+
+/// Recursively writes every sequence reaching out of a dead key state,
+/// prefixed by the keysyms in `sequence`. The characters typed to reach the
+/// state are output when it has no escape entry.
+fn write_state(
+    output: &mut String,
+    automaton: &DeadKeys,
+    id: StateId,
+    sequence: &mut Vec<String>,
+    dead_keys: &IndexSet<String>,
+) {
+    let state = &automaton.states[id];
+
+    sequence.push(keysym_for_char(' '));
+    write_compose_line(output, sequence, state.escape_or_fallback());
+    sequence.pop();
+
+    for (next_char, next) in &state.transitions {
+        let Some(keysym) = sequence_keysym(next_char, dead_keys) else {
+            tracing::warn!(
+                "Cannot compose '{}' after '{}' as it is not a single key, skipping",
+                next_char,
+                state.sequence
+            );
+            continue;
+        };
+
+        sequence.push(keysym);
+        match next {
+            Next::Output(end_char) => write_compose_line(output, sequence, end_char),
+            Next::State(id) => write_state(output, automaton, *id, sequence, dead_keys),
+        }
+        sequence.pop();
+    }
+}
+
+pub fn generate_xcompose(automaton: &DeadKeys, target: &LinuxTarget) -> String {
+    let dead_keys = linux_dead_keys(target);
+    let mut output = String::from("# Generated by kbdgen\n\ninclude \"%L\"\n\n");
+
+    for (dead_key, id) in &automaton.starts {
+        if !dead_keys.contains(dead_key) {
+            tracing::debug!("'{}' is not a dead key on Linux, skipping", dead_key);
+            continue;
+        }
+
+        let Some(dead_keysym) = dead_keysym_for(dead_key) else {
+            tracing::warn!(
+                "No xkb dead keysym exists for '{}', its transforms will not be generated",
+                dead_key
+            );
+            continue;
+        };
+
+        let mut sequence = vec![dead_keysym.to_string()];
+        write_state(&mut output, automaton, *id, &mut sequence, &dead_keys);
+    }
+
+    output
+}
+
+pub struct GenerateXCompose;
+
+#[async_trait(?Send)]
+impl BuildStep for GenerateXCompose {
+    async fn build(&self, bundle: &KbdgenBundle, output_path: &Path) -> Result<()> {
+        let compose_path = output_path.join(COMPOSE_FOLDER);
+
+        for (language_tag, layout) in &bundle.layouts {
+            let (Some(linux_target), Some(transforms)) = (&layout.linux, &layout.transforms) else {
+                continue;
+            };
+
+            tracing::debug!("Generating XCompose for {}", language_tag);
+
+            std::fs::create_dir_all(&compose_path)?;
+            std::fs::write(
+                compose_path.join(format!("{}.{}", language_tag, XCOMPOSE_EXT)),
+                generate_xcompose(&DeadKeys::from_transforms(transforms), linux_target),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::test_layout;
+
+    #[test]
+    fn test_generate_xcompose() {
+        let text = r#"
+displayNames:
+  sje: Bidumsámegiella
+linux:
+  primary:
+    layers:
+      default: |
+        § 1 2 3 4 5 6 7 8 9 0 + ´
+        á š e r t y u i o p å ŋ
+        a s d f g h j k l ö ä đ
+        ž z č c v b n m , . -
+      shift: |
+        ½ ! " # ¨ % & / ( ) = ? `
+        Á Š E R T Y U I O P Å Ŋ
+        A S D F G H J K L Ö Ä Đ
+        Ž Z Č C V B N M ; : _
+  deadKeys:
+    default: ['´']
+    shift: ['¨']
+transforms:
+  ´:
+    ' ': ´
+    e: é
+    ij: x
+    ¨:
+      u: ǘ
+  ¨:
+    '"': ‟
+"#;
+
+        let layout = test_layout("sje", text);
+        let automaton = DeadKeys::from_transforms(layout.transforms.as_ref().unwrap());
+        let xcompose = generate_xcompose(&automaton, layout.linux.as_ref().unwrap());
+
+        // States without an escape output what was typed to reach them, and
+        // `ij` is left out as it is not a single key
+        assert_eq!(
+            xcompose,
+            "# Generated by kbdgen\n\n\
+            include \"%L\"\n\n\
+            <dead_acute> <space> : \"´\"\n\
+            <dead_acute> <e> : \"é\"\n\
+            <dead_acute> <dead_diaeresis> <space> : \"´¨\"\n\
+            <dead_acute> <dead_diaeresis> <u> : \"ǘ\"\n\
+            <dead_diaeresis> <space> : \"¨\"\n\
+            <dead_diaeresis> <quotedbl> : \"‟\"\n"
+        );
+    }
+}
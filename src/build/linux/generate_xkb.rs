@@ -0,0 +1,351 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use indexmap::IndexMap;
+use language_tags::LanguageTag;
+use xmlem::Document;
+
+use crate::{
+    build::BuildStep,
+    bundle::{
+        KbdgenBundle,
+        layout::{Layout, LinuxTarget, linux::LinuxKbdLayer},
+    },
+    model::{KeyAction, Keyboard, PlatformLayer},
+    util::iso639::iso639_3,
+};
+
+use super::{
+    keymap::XKB_KEYS,
+    keysym::{NO_SYMBOL, dead_keysym_for, keysym_for_char},
+};
+
+const SYMBOLS_FOLDER: &str = "symbols";
+const RULES_FOLDER: &str = "rules";
+const DEFAULT_XKB_VARIANT: &str = "basic";
+
+/// The order in which layers are written into each key's level list.
+const XKB_LEVELS: [LinuxKbdLayer; 4] = [
+    LinuxKbdLayer::Default,
+    LinuxKbdLayer::Shift,
+    LinuxKbdLayer::Alt,
+    LinuxKbdLayer::AltAndShift,
+];
+
+pub struct XkbVariant {
+    pub name: String,
+    pub description: String,
+    pub language: String,
+    /// The ISO 639-3 code of the language, which `evdev.xml` requires
+    pub iso639_id: String,
+    pub symbols: String,
+}
+
+pub fn xkb_layout_name(language_tag: &LanguageTag, target: &LinuxTarget) -> String {
+    target
+        .config
+        .as_ref()
+        .and_then(|x| x.xkb_layout.clone())
+        .unwrap_or_else(|| language_tag.to_string().to_lowercase().replace('-', "_"))
+}
+
+pub fn xkb_variant_name(target: &LinuxTarget) -> String {
+    target
+        .config
+        .as_ref()
+        .and_then(|x| x.xkb_variant.clone())
+        .unwrap_or_else(|| DEFAULT_XKB_VARIANT.to_string())
+}
+
+/// Returns the keysym for a key of a layer
+pub fn keysym_for_key(key: &KeyAction) -> String {
+    let key = match key {
+        KeyAction::None => return NO_SYMBOL.to_string(),
+        KeyAction::DeadKey(key) => {
+            if let Some(dead_keysym) = dead_keysym_for(key) {
+                return dead_keysym.to_string();
+            }
+
+            tracing::warn!(
+                "No xkb dead keysym exists for '{}', emitting it as a plain key",
+                key
+            );
+            key.clone()
+        }
+        KeyAction::Char(character) => return keysym_for_char(*character),
+        KeyAction::Ligature(key) => key.clone(),
+        KeyAction::Special(name) => format!(r"\s{{{}}}", name),
+    };
+
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(character), None) => keysym_for_char(character),
+        _ => {
+            tracing::warn!("xkb cannot output '{}' from a single key, skipping", key);
+            NO_SYMBOL.to_string()
+        }
+    }
+}
+
+fn generate_levels(keyboard: &Keyboard) -> IndexMap<String, Vec<String>> {
+    let mut keys: IndexMap<String, Vec<String>> = XKB_KEYS
+        .values()
+        .map(|name| (name.clone(), Vec::new()))
+        .collect();
+
+    for layer in XKB_LEVELS {
+        let Some(layer) = keyboard.layer(layer.modifiers()) else {
+            for levels in keys.values_mut() {
+                levels.push(NO_SYMBOL.to_string());
+            }
+            continue;
+        };
+
+        for (iso_key, key_name) in XKB_KEYS.iter() {
+            keys[key_name].push(keysym_for_key(layer.key(*iso_key)));
+        }
+    }
+
+    // Trailing NoSymbol levels only make the output harder to read
+    for levels in keys.values_mut() {
+        while levels.len() > 1 && levels.last().map(|x| x == NO_SYMBOL).unwrap_or(false) {
+            levels.pop();
+        }
+    }
+
+    keys
+}
+
+pub fn generate_symbols(
+    layout: &Layout,
+    target: &LinuxTarget,
+    keyboard: &Keyboard,
+    is_default: bool,
+) -> Result<String> {
+    let levels = generate_levels(keyboard);
+    let mut output = String::new();
+
+    if is_default {
+        output.push_str("default ");
+    }
+    writeln!(output, "partial alphanumeric_keys")?;
+    writeln!(output, "xkb_symbols \"{}\" {{", xkb_variant_name(target))?;
+    writeln!(output, "    name[Group1] = \"{}\";", layout.autonym())?;
+    writeln!(output)?;
+
+    for (key_name, key_levels) in levels {
+        writeln!(
+            output,
+            "    key <{}> {{ [ {} ] }};",
+            key_name,
+            key_levels.join(", ")
+        )?;
+    }
+
+    writeln!(output)?;
+    writeln!(output, "    include \"level3(ralt_switch)\"")?;
+    writeln!(output, "}};")?;
+
+    Ok(output)
+}
+
+/// Creates the `layoutList` fragment to be merged into `evdev.xml`
+pub fn generate_evdev_xml(xkb_layouts: &IndexMap<String, Vec<XkbVariant>>) -> String {
+    let mut document = Document::new("layoutList");
+    let root = document.root();
+
+    for (layout_name, variants) in xkb_layouts {
+        let Some((first, rest)) = variants.split_first() else {
+            continue;
+        };
+
+        let layout_element = root.append_new_element(&mut document, "layout");
+        let config_item = layout_element.append_new_element(&mut document, "configItem");
+        config_item
+            .append_new_element(&mut document, "name")
+            .set_text(&mut document, layout_name);
+        config_item
+            .append_new_element(&mut document, "shortDescription")
+            .set_text(&mut document, &first.language);
+        config_item
+            .append_new_element(&mut document, "description")
+            .set_text(&mut document, &first.description);
+        config_item
+            .append_new_element(&mut document, "languageList")
+            .append_new_element(&mut document, "iso639Id")
+            .set_text(&mut document, &first.iso639_id);
+
+        if rest.is_empty() {
+            continue;
+        }
+
+        let variant_list = layout_element.append_new_element(&mut document, "variantList");
+        for variant in rest {
+            let config_item = variant_list
+                .append_new_element(&mut document, "variant")
+                .append_new_element(&mut document, "configItem");
+            config_item
+                .append_new_element(&mut document, "name")
+                .set_text(&mut document, &variant.name);
+            config_item
+                .append_new_element(&mut document, "description")
+                .set_text(&mut document, &variant.description);
+            config_item
+                .append_new_element(&mut document, "languageList")
+                .append_new_element(&mut document, "iso639Id")
+                .set_text(&mut document, &variant.iso639_id);
+        }
+    }
+
+    document.to_string_pretty()
+}
+
+/// Creates the `! layout` and `! variant` sections to be merged into `evdev.lst`
+pub fn generate_evdev_lst(xkb_layouts: &IndexMap<String, Vec<XkbVariant>>) -> String {
+    let mut layouts = String::from("! layout\n");
+    let mut variants = String::from("! variant\n");
+
+    for (layout_name, xkb_variants) in xkb_layouts {
+        let Some((first, rest)) = xkb_variants.split_first() else {
+            continue;
+        };
+
+        layouts.push_str(&format!("  {:<15} {}\n", layout_name, first.description));
+
+        for variant in rest {
+            variants.push_str(&format!(
+                "  {:<15} {}: {}\n",
+                variant.name, layout_name, variant.description
+            ));
+        }
+    }
+
+    format!("{}\n{}", layouts, variants)
+}
+
+pub struct GenerateXkb;
+
+#[async_trait(?Send)]
+impl BuildStep for GenerateXkb {
+    async fn build(&self, bundle: &KbdgenBundle, output_path: &Path) -> Result<()> {
+        let symbols_path = output_path.join(SYMBOLS_FOLDER);
+        let rules_path = output_path.join(RULES_FOLDER);
+
+        std::fs::create_dir_all(&symbols_path)?;
+        std::fs::create_dir_all(&rules_path)?;
+
+        // Several layouts may share an xkb layout name, in which case they
+        // become variants within the same symbols file
+        let mut xkb_layouts: IndexMap<String, Vec<XkbVariant>> = IndexMap::new();
+
+        for (language_tag, layout) in &bundle.layouts {
+            if let Some(linux_target) = &layout.linux {
+                tracing::debug!("Generating xkb symbols for {}", language_tag);
+
+                let keyboard = Keyboard::lower(
+                    bundle,
+                    language_tag,
+                    "linux",
+                    &linux_target.primary.layers,
+                    linux_target.dead_keys.as_ref(),
+                )?;
+
+                let language = language_tag.primary_language();
+                let iso639_id = iso639_3(language)
+                    .with_context(|| format!("No ISO 639-3 code is known for `{}`", language))?;
+
+                let layout_name = xkb_layout_name(language_tag, linux_target);
+                let variants = xkb_layouts.entry(layout_name).or_default();
+
+                variants.push(XkbVariant {
+                    name: xkb_variant_name(linux_target),
+                    description: layout.autonym().to_string(),
+                    language: language.to_string(),
+                    iso639_id,
+                    symbols: generate_symbols(
+                        layout,
+                        linux_target,
+                        &keyboard,
+                        variants.is_empty(),
+                    )?,
+                });
+            }
+        }
+
+        for (layout_name, variants) in &xkb_layouts {
+            let symbols = variants
+                .iter()
+                .map(|x| x.symbols.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            std::fs::write(
+                symbols_path.join(layout_name),
+                format!("// Generated by kbdgen\n\n{}", symbols),
+            )?;
+        }
+
+        std::fs::write(
+            rules_path.join("evdev.xml"),
+            generate_evdev_xml(&xkb_layouts),
+        )?;
+        std::fs::write(
+            rules_path.join("evdev.lst"),
+            generate_evdev_lst(&xkb_layouts),
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::test_layout;
+    use crate::model::Platform;
+
+    #[test]
+    fn test_generate_symbols() {
+        let text = r#"
+displayNames:
+  sje: Bidumsámegiella
+linux:
+  primary:
+    layers:
+      default: |
+        § 1 2 3 4 5 6 7 8 9 0 + ´
+        á š e r t y u i o p å ŋ
+        a s d f g h j k l ö ä đ
+        ž z č c v b n m , . -
+      alt+shift: |
+        \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} ŋ
+        ij \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0}
+        \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0}
+        \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0}
+  deadKeys:
+    default: ['´']
+    alt+shift: ['ŋ']
+"#;
+
+        let layout = test_layout("sje", text);
+        let keyboard = Keyboard::from_layout(&layout, Platform::Linux).unwrap();
+        let symbols =
+            generate_symbols(&layout, layout.linux.as_ref().unwrap(), &keyboard, true).unwrap();
+        let lines = symbols.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "default partial alphanumeric_keys");
+        assert_eq!(lines[1], "xkb_symbols \"basic\" {");
+        assert_eq!(lines[2], "    name[Group1] = \"Bidumsámegiella\";");
+        assert!(lines.contains(&"    key <AE01> { [ 1 ] };"));
+        assert!(lines.contains(&"    key <AB08> { [ comma ] };"));
+        // Dead keys get their dead keysym, or the plain keysym without one
+        assert!(lines.contains(&"    key <AE12> { [ dead_acute, NoSymbol, NoSymbol, U014B ] };"));
+        assert!(lines.contains(&"    key <AD01> { [ U00E1 ] };"));
+        // Keys of more than one character can't be typed with xkb
+        let ad01 = lines.iter().find(|x| x.contains("<AD01>")).unwrap();
+        assert!(!ad01.contains("ij") && !ad01.contains(','));
+        assert!(lines.contains(&"    include \"level3(ralt_switch)\""));
+    }
+}
@@ -0,0 +1,67 @@
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+
+use crate::util::iso_key::IsoKey;
+
+pub static XKB_KEYS: Lazy<IndexMap<IsoKey, String>> = Lazy::new(|| {
+    let mut map = IndexMap::new();
+
+    {
+        let arr = [
+            (IsoKey::E00, "TLDE"),
+            (IsoKey::E01, "AE01"),
+            (IsoKey::E02, "AE02"),
+            (IsoKey::E03, "AE03"),
+            (IsoKey::E04, "AE04"),
+            (IsoKey::E05, "AE05"),
+            (IsoKey::E06, "AE06"),
+            (IsoKey::E07, "AE07"),
+            (IsoKey::E08, "AE08"),
+            (IsoKey::E09, "AE09"),
+            (IsoKey::E10, "AE10"),
+            (IsoKey::E11, "AE11"),
+            (IsoKey::E12, "AE12"),
+            (IsoKey::D01, "AD01"),
+            (IsoKey::D02, "AD02"),
+            (IsoKey::D03, "AD03"),
+            (IsoKey::D04, "AD04"),
+            (IsoKey::D05, "AD05"),
+            (IsoKey::D06, "AD06"),
+            (IsoKey::D07, "AD07"),
+            (IsoKey::D08, "AD08"),
+            (IsoKey::D09, "AD09"),
+            (IsoKey::D10, "AD10"),
+            (IsoKey::D11, "AD11"),
+            (IsoKey::D12, "AD12"),
+            (IsoKey::C01, "AC01"),
+            (IsoKey::C02, "AC02"),
+            (IsoKey::C03, "AC03"),
+            (IsoKey::C04, "AC04"),
+            (IsoKey::C05, "AC05"),
+            (IsoKey::C06, "AC06"),
+            (IsoKey::C07, "AC07"),
+            (IsoKey::C08, "AC08"),
+            (IsoKey::C09, "AC09"),
+            (IsoKey::C10, "AC10"),
+            (IsoKey::C11, "AC11"),
+            (IsoKey::C12, "BKSL"),
+            (IsoKey::B00, "LSGT"),
+            (IsoKey::B01, "AB01"),
+            (IsoKey::B02, "AB02"),
+            (IsoKey::B03, "AB03"),
+            (IsoKey::B04, "AB04"),
+            (IsoKey::B05, "AB05"),
+            (IsoKey::B06, "AB06"),
+            (IsoKey::B07, "AB07"),
+            (IsoKey::B08, "AB08"),
+            (IsoKey::B09, "AB09"),
+            (IsoKey::B10, "AB10"),
+        ];
+
+        for (key, value) in arr {
+            map.insert(key, value.to_string());
+        }
+    }
+
+    map
+});
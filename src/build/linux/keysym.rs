@@ -0,0 +1,394 @@
+// https://gitlab.freedesktop.org/xorg/proto/xorgproto/-/blob/master/include/X11/keysymdef.h
+
+pub const NO_SYMBOL: &str = "NoSymbol";
+
+/// Printable ASCII characters that xkb expects to be spelled by name.
+pub const ASCII_KEYSYMS: &[(char, &str)] = &[
+    (' ', "space"),
+    ('!', "exclam"),
+    ('"', "quotedbl"),
+    ('#', "numbersign"),
+    ('$', "dollar"),
+    ('%', "percent"),
+    ('&', "ampersand"),
+    ('\'', "apostrophe"),
+    ('(', "parenleft"),
+    (')', "parenright"),
+    ('*', "asterisk"),
+    ('+', "plus"),
+    (',', "comma"),
+    ('-', "minus"),
+    ('.', "period"),
+    ('/', "slash"),
+    (':', "colon"),
+    (';', "semicolon"),
+    ('<', "less"),
+    ('=', "equal"),
+    ('>', "greater"),
+    ('?', "question"),
+    ('@', "at"),
+    ('[', "bracketleft"),
+    ('\\', "backslash"),
+    (']', "bracketright"),
+    ('^', "asciicircum"),
+    ('_', "underscore"),
+    ('`', "grave"),
+    ('{', "braceleft"),
+    ('|', "bar"),
+    ('}', "braceright"),
+    ('~', "asciitilde"),
+];
+
+/// Spacing and combining diacritics that have a dedicated `dead_*` keysym.
+pub const DEAD_KEYSYMS: &[(char, &str)] = &[
+    ('`', "dead_grave"),
+    ('\u{300}', "dead_grave"),
+    ('´', "dead_acute"),
+    ('\u{301}', "dead_acute"),
+    ('^', "dead_circumflex"),
+    ('ˆ', "dead_circumflex"),
+    ('\u{302}', "dead_circumflex"),
+    ('~', "dead_tilde"),
+    ('˜', "dead_tilde"),
+    ('\u{303}', "dead_tilde"),
+    ('¯', "dead_macron"),
+    ('\u{304}', "dead_macron"),
+    ('˘', "dead_breve"),
+    ('\u{306}', "dead_breve"),
+    ('˙', "dead_abovedot"),
+    ('\u{307}', "dead_abovedot"),
+    ('¨', "dead_diaeresis"),
+    ('\u{308}', "dead_diaeresis"),
+    ('\u{309}', "dead_hook"),
+    ('˚', "dead_abovering"),
+    ('\u{30A}', "dead_abovering"),
+    ('˝', "dead_doubleacute"),
+    ('\u{30B}', "dead_doubleacute"),
+    ('ˇ', "dead_caron"),
+    ('\u{30C}', "dead_caron"),
+    ('\u{31B}', "dead_horn"),
+    ('\u{323}', "dead_belowdot"),
+    ('¸', "dead_cedilla"),
+    ('\u{327}', "dead_cedilla"),
+    ('˛', "dead_ogonek"),
+    ('\u{328}', "dead_ogonek"),
+    ('\u{335}', "dead_stroke"),
+    ('ͺ', "dead_iota"),
+];
+
+/// Named keysyms for characters outside of ASCII, as found in existing
+/// symbols and Compose files. Only needed when reading them.
+pub const NAMED_KEYSYMS: &[(char, &str)] = &[
+    ('\u{a0}', "nobreakspace"),
+    ('¡', "exclamdown"),
+    ('¢', "cent"),
+    ('£', "sterling"),
+    ('¤', "currency"),
+    ('¥', "yen"),
+    ('¦', "brokenbar"),
+    ('§', "section"),
+    ('¨', "diaeresis"),
+    ('©', "copyright"),
+    ('ª', "ordfeminine"),
+    ('«', "guillemotleft"),
+    ('¬', "notsign"),
+    ('\u{ad}', "hyphen"),
+    ('®', "registered"),
+    ('¯', "macron"),
+    ('°', "degree"),
+    ('±', "plusminus"),
+    ('²', "twosuperior"),
+    ('³', "threesuperior"),
+    ('´', "acute"),
+    ('µ', "mu"),
+    ('¶', "paragraph"),
+    ('·', "periodcentered"),
+    ('¸', "cedilla"),
+    ('¹', "onesuperior"),
+    ('º', "masculine"),
+    ('»', "guillemotright"),
+    ('¼', "onequarter"),
+    ('½', "onehalf"),
+    ('¾', "threequarters"),
+    ('¿', "questiondown"),
+    ('À', "Agrave"),
+    ('Á', "Aacute"),
+    ('Â', "Acircumflex"),
+    ('Ã', "Atilde"),
+    ('Ä', "Adiaeresis"),
+    ('Å', "Aring"),
+    ('Æ', "AE"),
+    ('Ç', "Ccedilla"),
+    ('È', "Egrave"),
+    ('É', "Eacute"),
+    ('Ê', "Ecircumflex"),
+    ('Ë', "Ediaeresis"),
+    ('Ì', "Igrave"),
+    ('Í', "Iacute"),
+    ('Î', "Icircumflex"),
+    ('Ï', "Idiaeresis"),
+    ('Ð', "ETH"),
+    ('Ñ', "Ntilde"),
+    ('Ò', "Ograve"),
+    ('Ó', "Oacute"),
+    ('Ô', "Ocircumflex"),
+    ('Õ', "Otilde"),
+    ('Ö', "Odiaeresis"),
+    ('×', "multiply"),
+    ('Ø', "Oslash"),
+    ('Ù', "Ugrave"),
+    ('Ú', "Uacute"),
+    ('Û', "Ucircumflex"),
+    ('Ü', "Udiaeresis"),
+    ('Ý', "Yacute"),
+    ('Þ', "THORN"),
+    ('ß', "ssharp"),
+    ('à', "agrave"),
+    ('á', "aacute"),
+    ('â', "acircumflex"),
+    ('ã', "atilde"),
+    ('ä', "adiaeresis"),
+    ('å', "aring"),
+    ('æ', "ae"),
+    ('ç', "ccedilla"),
+    ('è', "egrave"),
+    ('é', "eacute"),
+    ('ê', "ecircumflex"),
+    ('ë', "ediaeresis"),
+    ('ì', "igrave"),
+    ('í', "iacute"),
+    ('î', "icircumflex"),
+    ('ï', "idiaeresis"),
+    ('ð', "eth"),
+    ('ñ', "ntilde"),
+    ('ò', "ograve"),
+    ('ó', "oacute"),
+    ('ô', "ocircumflex"),
+    ('õ', "otilde"),
+    ('ö', "odiaeresis"),
+    ('÷', "division"),
+    ('ø', "oslash"),
+    ('ù', "ugrave"),
+    ('ú', "uacute"),
+    ('û', "ucircumflex"),
+    ('ü', "udiaeresis"),
+    ('ý', "yacute"),
+    ('þ', "thorn"),
+    ('ÿ', "ydiaeresis"),
+    ('«', "guillemetleft"),
+    ('»', "guillemetright"),
+    ('º', "ordmasculine"),
+    ('Ð', "Eth"),
+    ('Þ', "Thorn"),
+    ('Ø', "Ooblique"),
+    ('ø', "ooblique"),
+    ('Ą', "Aogonek"),
+    ('˘', "breve"),
+    ('Ł', "Lstroke"),
+    ('Ľ', "Lcaron"),
+    ('Ś', "Sacute"),
+    ('Š', "Scaron"),
+    ('Ş', "Scedilla"),
+    ('Ť', "Tcaron"),
+    ('Ź', "Zacute"),
+    ('Ž', "Zcaron"),
+    ('Ż', "Zabovedot"),
+    ('ą', "aogonek"),
+    ('˛', "ogonek"),
+    ('ł', "lstroke"),
+    ('ľ', "lcaron"),
+    ('ś', "sacute"),
+    ('ˇ', "caron"),
+    ('š', "scaron"),
+    ('ş', "scedilla"),
+    ('ť', "tcaron"),
+    ('ź', "zacute"),
+    ('˝', "doubleacute"),
+    ('ž', "zcaron"),
+    ('ż', "zabovedot"),
+    ('Ŕ', "Racute"),
+    ('Ă', "Abreve"),
+    ('Ĺ', "Lacute"),
+    ('Ć', "Cacute"),
+    ('Č', "Ccaron"),
+    ('Ę', "Eogonek"),
+    ('Ě', "Ecaron"),
+    ('Ď', "Dcaron"),
+    ('Đ', "Dstroke"),
+    ('Ń', "Nacute"),
+    ('Ň', "Ncaron"),
+    ('Ő', "Odoubleacute"),
+    ('Ř', "Rcaron"),
+    ('Ů', "Uring"),
+    ('Ű', "Udoubleacute"),
+    ('Ţ', "Tcedilla"),
+    ('ŕ', "racute"),
+    ('ă', "abreve"),
+    ('ĺ', "lacute"),
+    ('ć', "cacute"),
+    ('č', "ccaron"),
+    ('ę', "eogonek"),
+    ('ě', "ecaron"),
+    ('ď', "dcaron"),
+    ('đ', "dstroke"),
+    ('ń', "nacute"),
+    ('ň', "ncaron"),
+    ('ő', "odoubleacute"),
+    ('ř', "rcaron"),
+    ('ů', "uring"),
+    ('ű', "udoubleacute"),
+    ('ţ', "tcedilla"),
+    ('˙', "abovedot"),
+    ('Ħ', "Hstroke"),
+    ('Ĥ', "Hcircumflex"),
+    ('İ', "Iabovedot"),
+    ('Ğ', "Gbreve"),
+    ('Ĵ', "Jcircumflex"),
+    ('ħ', "hstroke"),
+    ('ĥ', "hcircumflex"),
+    ('ı', "idotless"),
+    ('ğ', "gbreve"),
+    ('ĵ', "jcircumflex"),
+    ('Ċ', "Cabovedot"),
+    ('Ĉ', "Ccircumflex"),
+    ('Ġ', "Gabovedot"),
+    ('Ĝ', "Gcircumflex"),
+    ('Ŭ', "Ubreve"),
+    ('Ŝ', "Scircumflex"),
+    ('ċ', "cabovedot"),
+    ('ĉ', "ccircumflex"),
+    ('ġ', "gabovedot"),
+    ('ĝ', "gcircumflex"),
+    ('ŭ', "ubreve"),
+    ('ŝ', "scircumflex"),
+    ('ĸ', "kra"),
+    ('Ŗ', "Rcedilla"),
+    ('Ĩ', "Itilde"),
+    ('Ļ', "Lcedilla"),
+    ('Ē', "Emacron"),
+    ('Ģ', "Gcedilla"),
+    ('Ŧ', "Tslash"),
+    ('ŗ', "rcedilla"),
+    ('ĩ', "itilde"),
+    ('ļ', "lcedilla"),
+    ('ē', "emacron"),
+    ('ģ', "gcedilla"),
+    ('ŧ', "tslash"),
+    ('Ŋ', "ENG"),
+    ('ŋ', "eng"),
+    ('Ā', "Amacron"),
+    ('Į', "Iogonek"),
+    ('Ė', "Eabovedot"),
+    ('Ī', "Imacron"),
+    ('Ņ', "Ncedilla"),
+    ('Ō', "Omacron"),
+    ('Ķ', "Kcedilla"),
+    ('Ų', "Uogonek"),
+    ('Ũ', "Utilde"),
+    ('Ū', "Umacron"),
+    ('ā', "amacron"),
+    ('į', "iogonek"),
+    ('ė', "eabovedot"),
+    ('ī', "imacron"),
+    ('ņ', "ncedilla"),
+    ('ō', "omacron"),
+    ('ķ', "kcedilla"),
+    ('ų', "uogonek"),
+    ('ũ', "utilde"),
+    ('ū', "umacron"),
+    ('Ŵ', "Wcircumflex"),
+    ('ŵ', "wcircumflex"),
+    ('Ŷ', "Ycircumflex"),
+    ('ŷ', "ycircumflex"),
+    ('Œ', "OE"),
+    ('œ', "oe"),
+    ('Ÿ', "Ydiaeresis"),
+    ('Ə', "SCHWA"),
+    ('ə', "schwa"),
+    ('Ʒ', "EZH"),
+    ('ʒ', "ezh"),
+    ('€', "EuroSign"),
+    ('–', "endash"),
+    ('—', "emdash"),
+    ('…', "ellipsis"),
+    ('‘', "leftsinglequotemark"),
+    ('’', "rightsinglequotemark"),
+    ('‚', "singlelowquotemark"),
+    ('“', "leftdoublequotemark"),
+    ('”', "rightdoublequotemark"),
+    ('„', "doublelowquotemark"),
+    ('†', "dagger"),
+    ('‡', "doubledagger"),
+    ('‰', "permille"),
+    ('™', "trademark"),
+];
+
+/// Returns the keysym for a single character.
+///
+/// ASCII letters and digits are their own keysym, the remaining printable
+/// ASCII characters use their xkb names and everything else is written as
+/// a Unicode keysym (`U00E1`), which xkb and XCompose both accept.
+pub fn keysym_for_char(character: char) -> String {
+    if character.is_ascii_alphanumeric() {
+        return character.to_string();
+    }
+
+    if let Some((_, name)) = ASCII_KEYSYMS.iter().find(|(c, _)| *c == character) {
+        return name.to_string();
+    }
+
+    format!("U{:04X}", character as u32)
+}
+
+/// Returns the `dead_*` keysym for a dead key, if xkb has one.
+pub fn dead_keysym_for(key: &str) -> Option<&'static str> {
+    let mut chars = key.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(character), None) => DEAD_KEYSYMS
+            .iter()
+            .find(|(c, _)| *c == character)
+            .map(|(_, name)| *name),
+        _ => None,
+    }
+}
+
+/// Returns the character for a keysym, the reverse of [`keysym_for_char`].
+/// Dead keysyms and keysyms that do not produce a character give `None`.
+pub fn char_for_keysym(keysym: &str) -> Option<char> {
+    let mut chars = keysym.chars();
+    if let (Some(character), None) = (chars.next(), chars.next()) {
+        return Some(character);
+    }
+
+    if let Some((character, _)) = ASCII_KEYSYMS
+        .iter()
+        .chain(NAMED_KEYSYMS.iter())
+        .find(|(_, name)| *name == keysym)
+    {
+        return Some(*character);
+    }
+
+    if let Some(hex) = keysym.strip_prefix('U')
+        && hex.len() >= 4
+    {
+        return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+    }
+
+    // Keysyms may also be given by value, where Latin-1 is its own value and
+    // other characters are offset by 0x01000000
+    let value = u32::from_str_radix(keysym.strip_prefix("0x")?, 16).ok()?;
+    match value {
+        0x20..=0x7e | 0xa0..=0xff => char::from_u32(value),
+        0x0100_0020..=0x0110_ffff => char::from_u32(value - 0x0100_0000),
+        _ => None,
+    }
+}
+
+/// Returns the spacing character kbdgen uses for a `dead_*` keysym.
+pub fn char_for_dead_keysym(keysym: &str) -> Option<char> {
+    DEAD_KEYSYMS
+        .iter()
+        .find(|(_, name)| *name == keysym)
+        .map(|(character, _)| *character)
+}
@@ -0,0 +1,49 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+
+use crate::bundle::KbdgenBundle;
+
+use self::{generate_xcompose::GenerateXCompose, generate_xkb::GenerateXkb};
+
+use super::{BuildStep, BuildSteps};
+
+pub mod generate_xcompose;
+pub mod generate_xkb;
+pub(crate) mod keymap;
+pub mod keysym;
+
+pub struct LinuxBuild {
+    pub bundle: KbdgenBundle,
+    pub output_path: PathBuf,
+    pub steps: Vec<Box<dyn BuildStep>>,
+}
+
+pub fn build_steps() -> Vec<Box<dyn BuildStep>> {
+    vec![Box::new(GenerateXkb), Box::new(GenerateXCompose)]
+}
+
+#[async_trait(?Send)]
+impl BuildSteps for LinuxBuild {
+    fn new(bundle: KbdgenBundle, output_path: PathBuf) -> Self {
+        let steps = build_steps();
+
+        LinuxBuild {
+            bundle,
+            output_path,
+            steps,
+        }
+    }
+
+    fn steps(&self) -> &[Box<dyn BuildStep>] {
+        &self.steps
+    }
+
+    fn bundle(&self) -> &KbdgenBundle {
+        &self.bundle
+    }
+
+    fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+}
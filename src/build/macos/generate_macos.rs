@@ -10,9 +10,9 @@ use xmlem::{Document, Element, Selector};
 
 use crate::build::macos::keymap::{MACOS_HARDCODED, MACOS_KEYS};
 use crate::build::macos::layers::layer_attributes;
-use crate::bundle::layout::Transform;
 use crate::bundle::layout::macos::MacOsKbdLayer;
-use crate::util::{TRANSFORM_ESCAPE, decode_unicode_escapes, split_keys};
+use crate::model::{DeadKeys, KeyAction, Keyboard, Layer, Next, PlatformLayer, StateId};
+use crate::util::decode_unicode_escapes;
 use crate::{build::BuildStep, bundle::KbdgenBundle};
 
 use super::macos_bundle::MacOsBundle;
@@ -44,10 +44,13 @@ pub struct DeadKeyAction {
     id: ActionId,
     code: usize,
     states: Vec<DeadKeyOutput>,
+    /// States in which the key leads on to a chained dead key
+    next_states: Vec<DeadKeyNext>,
 }
 
 #[derive(Debug, Clone)]
 pub struct DeadKeyNext {
+    state: DeadKeyId,
     next: DeadKeyId,
 }
 
@@ -95,7 +98,7 @@ pub struct GenerateMacOs;
 
 fn generate_key_layout_files(
     bundle: &KbdgenBundle,
-) -> IndexMap<LanguageTag, (Document, &IndexMap<LanguageTag, String>)> {
+) -> Result<IndexMap<LanguageTag, (Document, &IndexMap<LanguageTag, String>)>> {
     let mut key_layouts = IndexMap::new();
 
     // One .keylayout file in Resources folder per language with MacOS primary platform
@@ -125,47 +128,45 @@ fn generate_key_layout_files(
 
             add_layer_tags(&layers, &mut document, &key_map_set);
 
-            initialize_key_transition_map(&language_tag, &layers, &mut layered_key_transition_map);
+            let keyboard = Keyboard::lower(
+                bundle,
+                language_tag,
+                "macOS",
+                layers,
+                mac_os_target.dead_keys.as_ref(),
+            )?;
+
+            initialize_key_transition_map(&keyboard, &mut layered_key_transition_map);
 
             let mut id_manager = TransformIdManager::new();
 
-            if let Some(transforms) = &layout.transforms {
-                if let Some(target_dead_keys) = &mac_os_target.dead_keys {
-                    process_transforms(
-                        &layers,
-                        transforms,
-                        target_dead_keys,
-                        &mut dead_keys,
-                        &mut layered_key_transition_map,
-                        &mut id_manager,
-                    );
-                } else {
-                    tracing::warn!(
-                        r#"No dead keys in {}:{}:{}"#,
-                        language_tag.to_string(),
-                        "MacOS",
-                        "Primary",
-                    );
-                }
-            } else {
+            if keyboard.dead_keys.starts.is_empty() {
                 tracing::warn!(
                     r#"No transforms in {}:{}:{}"#,
                     language_tag.to_string(),
                     "MacOS",
                     "Primary",
                 );
-            }
-
-            if let Some(_transforms) = &layout.transforms {
-                if let Some(target_dead_keys) = &mac_os_target.dead_keys {
-                    create_dead_key_actions(
-                        &layers,
-                        &mut layered_key_transition_map,
-                        &target_dead_keys,
-                        &mut dead_keys,
-                        &mut id_manager,
-                    );
-                }
+            } else if mac_os_target.dead_keys.is_none() {
+                tracing::warn!(
+                    r#"No dead keys in {}:{}:{}"#,
+                    language_tag.to_string(),
+                    "MacOS",
+                    "Primary",
+                );
+            } else {
+                process_transforms(
+                    &keyboard,
+                    &mut dead_keys,
+                    &mut layered_key_transition_map,
+                    &mut id_manager,
+                );
+                create_dead_key_actions(
+                    &keyboard,
+                    &mut layered_key_transition_map,
+                    &dead_keys,
+                    &mut id_manager,
+                );
             }
 
             let decimal = layout.decimal.as_deref().unwrap_or(".");
@@ -184,13 +185,13 @@ fn generate_key_layout_files(
         }
     }
 
-    key_layouts
+    Ok(key_layouts)
 }
 
 #[async_trait(?Send)]
 impl BuildStep for GenerateMacOs {
     async fn build(&self, bundle: &KbdgenBundle, output_path: &Path) -> Result<()> {
-        let key_layouts = generate_key_layout_files(&bundle);
+        let key_layouts = generate_key_layout_files(&bundle)?;
 
         let mut key_layout_macos_bundle =
             MacOsBundle::new(output_path.to_path_buf(), bundle.name(), &bundle).unwrap();
@@ -241,42 +242,39 @@ fn add_layer_tags(
     }
 }
 
+/// The text typed by each key of a layer, in the order of `MACOS_KEYS`.
+/// Keys without any output are left out of the key map.
+fn layer_outputs(layer: &Layer) -> Vec<Option<String>> {
+    MACOS_KEYS
+        .keys()
+        .map(|iso_key| match layer.key(*iso_key) {
+            KeyAction::Special(name) => {
+                tracing::warn!(
+                    r"Special key \s{{{}}} has no macOS equivalent, skipping",
+                    name
+                );
+                None
+            }
+            key => key.output(),
+        })
+        .collect()
+}
+
 fn initialize_key_transition_map(
-    language_tag: &LanguageTag,
-    layers: &IndexMap<MacOsKbdLayer, String>,
+    keyboard: &Keyboard,
     layered_key_transition_map: &mut IndexMap<MacOsKbdLayer, IndexMap<String, Vec<KeyTransition>>>,
 ) {
-    for (_layer_index, (layer, key_map)) in layers.iter().enumerate() {
-        let mut cursor = 0;
-
-        layered_key_transition_map.insert(*layer, IndexMap::new());
-
-        let key_transition_map = layered_key_transition_map
-            .get_mut(layer)
-            .expect("getting back the value that was just inserted");
+    for layer in &keyboard.layers {
+        let Some(macos_layer) = MacOsKbdLayer::from_modifiers(layer.modifiers) else {
+            continue;
+        };
 
-        let key_map: Vec<String> = split_keys(&key_map);
+        let key_transition_map = layered_key_transition_map.entry(macos_layer).or_default();
 
-        for (_iso_key, key_code) in MACOS_KEYS.iter() {
-            tracing::debug!(
-                "iso len: {}; keymap len: {}",
-                MACOS_KEYS.len(),
-                key_map.len()
-            );
-            if MACOS_KEYS.len() > key_map.len() {
-                panic!(
-                    r#"Provided layer does not have enough keys, expected {} keys but`` got {}, in {}:{}:{}:{:?}: \n{:?}"#,
-                    MACOS_KEYS.len(),
-                    key_map.len(),
-                    language_tag.to_string(),
-                    "MacOS",
-                    "Primary",
-                    layer,
-                    key_map
-                );
-            }
-
-            let key = key_map[cursor].clone();
+        for (key_code, key) in MACOS_KEYS.values().zip(layer_outputs(layer)) {
+            let Some(key) = key else {
+                continue;
+            };
 
             let key_transition = KeyTransition::Output(KeyOutput {
                 code: *key_code,
@@ -285,110 +283,115 @@ fn initialize_key_transition_map(
 
             // Add to existing Vec or create new Vec if key doesn't exist
             key_transition_map
-                .entry(key.clone())
+                .entry(key)
                 .or_insert_with(Vec::new)
                 .push(key_transition);
-
-            cursor += 1;
         }
     }
 }
 
 fn process_transforms(
-    layers: &IndexMap<MacOsKbdLayer, String>,
-    transforms: &IndexMap<String, Transform>,
-    target_dead_keys: &IndexMap<MacOsKbdLayer, Vec<String>>,
+    keyboard: &Keyboard,
     dead_keys: &mut IndexMap<String, DeadKeyOutput>,
     layered_key_transition_map: &mut IndexMap<MacOsKbdLayer, IndexMap<String, Vec<KeyTransition>>>,
     id_manager: &mut TransformIdManager,
 ) {
-    for (_layer_index, (layer, key_map)) in layers.iter().enumerate() {
-        let mut cursor = 0;
+    for layer in &keyboard.layers {
+        let has_dead_keys = layer
+            .keys
+            .values()
+            .any(|key| matches!(key, KeyAction::DeadKey(_)));
+
+        let Some(macos_layer) = MacOsKbdLayer::from_modifiers(layer.modifiers) else {
+            continue;
+        };
 
-        let layer_dead_keys = target_dead_keys.get(layer);
+        if !has_dead_keys {
+            continue;
+        }
 
-        if let Some(_layer_dead_keys) = layer_dead_keys {
-            let key_transition_map = layered_key_transition_map
-                .get_mut(layer)
-                .expect("this map should be prefilled by now");
+        let key_transition_map = layered_key_transition_map
+            .get_mut(&macos_layer)
+            .expect("this map should be prefilled by now");
 
-            for (_iso_key, _code) in MACOS_KEYS.iter() {
-                let key_map: Vec<String> = split_keys(&key_map);
+        let key_map: Vec<String> = layer.keys.values().filter_map(KeyAction::output).collect();
 
-                for (dead_key, value) in transforms {
-                    //if !layer_dead_keys.contains(dead_key) {
-                    //    continue;
-                    //}
+        for id in keyboard.dead_keys.starts.values() {
+            process_dead_key_state(
+                &keyboard.dead_keys,
+                *id,
+                &key_map,
+                dead_keys,
+                key_transition_map,
+                id_manager,
+            );
+        }
+    }
+}
 
-                    match value {
-                        Transform::End(_character) => {
-                            tracing::error!("Transform ended too soon for dead key {}", dead_key);
-                        }
-                        Transform::More(map) => {
-                            let escape_transform = map.get(TRANSFORM_ESCAPE).expect(&format!(
-                                "The escape transform `{}` not found for dead key `{}`",
-                                TRANSFORM_ESCAPE, &dead_key
-                            ));
-
-                            match escape_transform {
-                                Transform::End(end_char) => {
-                                    if !dead_keys.contains_key(dead_key) {
-                                        let id = id_manager.next_dead_key();
-
-                                        dead_keys.insert(
-                                            dead_key.clone(),
-                                            DeadKeyOutput {
-                                                id,
-                                                output: end_char.clone(),
-                                            },
-                                        );
-                                    }
-                                }
-                                Transform::More(_transform) => {
-                                    panic!(
-                                        "The escape transform should be a string, not another transform"
-                                    );
-                                }
-                            };
-
-                            let dead_key_transform = dead_keys[dead_key].clone();
-                            let id = dead_key_transform.id.clone();
-
-                            for (next_char, transform) in map {
-                                match transform {
-                                    Transform::End(end_char) => {
-                                        if next_char == TRANSFORM_ESCAPE {
-                                            continue;
-                                        }
-
-                                        if next_char.to_string() == key_map[cursor] {
-                                            let key_transform = DeadKeyOutput {
-                                                id: id.clone(),
-                                                output: end_char.to_string(),
-                                            };
-
-                                            update_key_transition_map_with_transform(
-                                                key_transition_map,
-                                                next_char,
-                                                key_transform,
-                                                id_manager,
-                                            );
-
-                                            break;
-                                        }
-                                    }
-                                    Transform::More(_transform) => {
-                                        todo!("Recursion required ahead");
-                                    }
-                                };
-                            }
-                        }
+/// Adds the transitions for a dead key state, and the states of any dead
+/// keys chained after it
+fn process_dead_key_state(
+    automaton: &DeadKeys,
+    state_id: StateId,
+    key_map: &[String],
+    dead_keys: &mut IndexMap<String, DeadKeyOutput>,
+    key_transition_map: &mut IndexMap<String, Vec<KeyTransition>>,
+    id_manager: &mut TransformIdManager,
+) {
+    let state = &automaton.states[state_id];
+    let escape = state.escape_or_fallback().to_string();
+
+    if !dead_keys.contains_key(&state.sequence) {
+        let id = id_manager.next_dead_key();
+
+        dead_keys.insert(state.sequence.clone(), DeadKeyOutput { id, output: escape });
+    }
+
+    let id = dead_keys[&state.sequence].id.clone();
+
+    for (next_char, next) in &state.transitions {
+        match next {
+            Next::Output(end_char) => {
+                if key_map.contains(next_char) {
+                    let key_transform = DeadKeyOutput {
+                        id: id.clone(),
+                        output: end_char.to_string(),
                     };
+
+                    update_key_transition_map_with_transform(
+                        key_transition_map,
+                        next_char,
+                        key_transform,
+                        id_manager,
+                    );
                 }
+            }
+            Next::State(next_id) => {
+                process_dead_key_state(
+                    automaton,
+                    *next_id,
+                    key_map,
+                    dead_keys,
+                    key_transition_map,
+                    id_manager,
+                );
+
+                if key_map.contains(next_char) {
+                    let next = DeadKeyNext {
+                        state: id.clone(),
+                        next: dead_keys[&automaton.states[*next_id].sequence].id.clone(),
+                    };
 
-                cursor += 1;
+                    update_key_transition_map_with_next(
+                        key_transition_map,
+                        next_char,
+                        next,
+                        id_manager,
+                    );
+                }
             }
-        }
+        };
     }
 }
 
@@ -416,6 +419,7 @@ fn update_key_transition_map_with_transform(
                         id: id_manager.next_action(),
                         code,
                         states: vec![none_state, transform.clone()],
+                        next_states: vec![],
                     };
 
                     *transition = KeyTransition::Action(action);
@@ -431,7 +435,9 @@ fn update_key_transition_map_with_transform(
                     }
                 }
                 KeyTransition::Next(_) => {
-                    panic!("Next states shouldn't exist yet!?!??!!??!?!");
+                    unreachable!(
+                        "Dead keys get their Next transitions after all transforms are added"
+                    );
                 }
             }
         }
@@ -442,58 +448,102 @@ fn update_key_transition_map_with_transform(
     }
 }
 
+fn update_key_transition_map_with_next(
+    key_transition_map: &mut IndexMap<String, Vec<KeyTransition>>,
+    key: &str,
+    next: DeadKeyNext,
+    id_manager: &mut TransformIdManager,
+) {
+    let transitions = key_transition_map.get_mut(key).expect(
+        "The key_transition_map must already have Output entries for all keys by this point.",
+    );
+
+    for transition in transitions.iter_mut() {
+        match transition {
+            KeyTransition::Output(output) => {
+                let none_state = DeadKeyOutput {
+                    id: "none".to_string(),
+                    output: output.output.clone(),
+                };
+
+                *transition = KeyTransition::Action(DeadKeyAction {
+                    id: id_manager.next_action(),
+                    code: output.code,
+                    states: vec![none_state],
+                    next_states: vec![next.clone()],
+                });
+            }
+            KeyTransition::Action(action) => {
+                if !action
+                    .next_states
+                    .iter()
+                    .any(|x| x.state == next.state && x.next == next.next)
+                {
+                    action.next_states.push(next.clone());
+                }
+            }
+            KeyTransition::Next(_) => {
+                unreachable!("Dead keys get their Next transitions after all transforms are added");
+            }
+        }
+    }
+}
+
 fn create_dead_key_actions(
-    layers: &IndexMap<MacOsKbdLayer, String>,
+    keyboard: &Keyboard,
     layered_key_transition_map: &mut IndexMap<MacOsKbdLayer, IndexMap<String, Vec<KeyTransition>>>,
-    target_dead_keys: &IndexMap<MacOsKbdLayer, Vec<String>>,
     dead_keys: &IndexMap<String, DeadKeyOutput>,
     id_manager: &mut TransformIdManager,
 ) {
-    for (_layer_index, (layer, key_map)) in layers.iter().enumerate() {
-        let mut cursor = 0;
+    for layer in &keyboard.layers {
+        let Some(macos_layer) = MacOsKbdLayer::from_modifiers(layer.modifiers) else {
+            continue;
+        };
 
-        let layer_dead_keys = target_dead_keys.get(layer);
+        let key_transition_map = layered_key_transition_map
+            .get_mut(&macos_layer)
+            .expect("this map should be prefilled by now");
 
-        if let Some(layer_dead_keys) = layer_dead_keys {
-            let key_transition_map = layered_key_transition_map
-                .get_mut(layer)
-                .expect("this map should be prefilled by now");
+        for (iso_key, key_code) in MACOS_KEYS.iter() {
+            let KeyAction::DeadKey(dead_key) = layer.key(*iso_key) else {
+                continue;
+            };
 
-            for (_iso_key, key_code) in MACOS_KEYS.iter() {
-                let key_map: Vec<String> = split_keys(&key_map);
+            tracing::debug!("layer {:?} dead key: {}", macos_layer, dead_key);
 
-                tracing::debug!(
-                    "layer dead keys: {:?}, key: {}",
-                    layer_dead_keys,
-                    &key_map[cursor]
+            let Some(dead_key_in_list) = dead_keys.get(dead_key) else {
+                tracing::warn!(
+                    "Dead key `{}` has no transforms, so it only types itself",
+                    dead_key
                 );
+                continue;
+            };
 
-                if layer_dead_keys.contains(&key_map[cursor]) {
-                    if let Some(dead_key_in_list) = dead_keys.get(&key_map[cursor]) {
-                        let none_state = DeadKeyNext {
-                            next: dead_key_in_list.id.clone(),
-                        };
-
-                        let action = DeadKeyNextAction {
-                            id: id_manager.next_action(),
-                            code: key_code.clone(),
-                            states: vec![none_state],
-                        };
-
-                        if let Some(transitions) = key_transition_map.get_mut(&key_map[cursor]) {
-                            for transition in transitions.iter_mut() {
-                                *transition = KeyTransition::Next(action.clone());
+            let none_state = DeadKeyNext {
+                state: "none".to_string(),
+                next: dead_key_in_list.id.clone(),
+            };
+
+            let action = DeadKeyNextAction {
+                id: id_manager.next_action(),
+                code: *key_code,
+                states: vec![none_state],
+            };
+
+            if let Some(transitions) = key_transition_map.get_mut(dead_key) {
+                for transition in transitions.iter_mut() {
+                    match transition {
+                        // Keep what the dead key does in other dead key states,
+                        // only typing it on its own starts its state
+                        KeyTransition::Action(existing) => {
+                            existing.states.retain(|state| state.id != "none");
+                            if !existing.next_states.iter().any(|x| x.state == "none") {
+                                existing.next_states.insert(0, action.states[0].clone());
                             }
                         }
-                    } else {
-                        panic!(
-                            "dead key `{}` in target list but not the transforms.",
-                            &key_map[cursor]
-                        );
+                        _ => *transition = KeyTransition::Next(action.clone()),
                     }
                 }
-
-                cursor += 1;
             }
         }
     }
@@ -548,6 +598,10 @@ fn write_key_transition_map(
                         for state in &dead_key_action.states {
                             append_dead_key_output_element(&action, document, &state);
                         }
+
+                        for state in &dead_key_action.next_states {
+                            append_dead_key_next_element(&action, document, &state);
+                        }
                     }
                     KeyTransition::Next(next_action) => {
                         xml_key_map.append_new_element(
@@ -633,7 +687,7 @@ fn append_dead_key_next_element(element: &Element, document: &mut Document, key:
         document,
         (
             "when",
-            [("state", "none".to_string()), ("next", key.next.clone())],
+            [("state", key.state.clone()), ("next", key.next.clone())],
         ),
     );
 }
@@ -656,11 +710,25 @@ mod tests {
     use super::*;
     use crate::bundle::KbdgenBundle;
     use crate::bundle::layout::macos::MacOsKbdLayer;
-    use crate::bundle::layout::{Layout, MacOsPrimaryPlatform, MacOsTarget};
+    use crate::bundle::layout::{Layout, MacOsPrimaryPlatform, MacOsTarget, Transform};
     use indexmap::IndexMap;
     use language_tags::LanguageTag;
     use std::str::FromStr;
 
+    fn lower(bundle: &KbdgenBundle) -> Keyboard {
+        let layout = bundle.layouts.values().next().unwrap();
+        let mac_os_target = layout.mac_os.as_ref().unwrap();
+
+        Keyboard::lower(
+            bundle,
+            &layout.language_tag,
+            "macOS",
+            &mac_os_target.primary.layers,
+            mac_os_target.dead_keys.as_ref(),
+        )
+        .unwrap()
+    }
+
     // Helper function to create a minimal test bundle
     fn create_test_bundle() -> KbdgenBundle {
         let mut layouts = IndexMap::new();
@@ -687,6 +755,7 @@ mod tests {
             mac_os: Some(mac_os_target),
             i_os: None,
             android: None,
+            linux: None,
             longpress: None,
             transforms: None,
             key_names: None,
@@ -735,6 +804,7 @@ mod tests {
             mac_os: Some(mac_os_target),
             i_os: None,
             android: None,
+            linux: None,
             longpress: None,
             transforms: Some(transforms),
             key_names: None,
@@ -771,6 +841,7 @@ mod tests {
             mac_os: Some(mac_os_target),
             i_os: None,
             android: None,
+            linux: None,
             longpress: None,
             transforms: None,
             key_names: None,
@@ -809,12 +880,10 @@ mod tests {
     #[test]
     fn test_initialize_key_transition_map_basic() {
         let bundle = create_test_bundle();
-        let layout = bundle.layouts.values().next().unwrap();
-        let layers = &layout.mac_os.as_ref().unwrap().primary.layers;
-        let language_tag = &layout.language_tag;
+        let keyboard = lower(&bundle);
 
         let mut layered_key_transition_map = IndexMap::new();
-        initialize_key_transition_map(language_tag, layers, &mut layered_key_transition_map);
+        initialize_key_transition_map(&keyboard, &mut layered_key_transition_map);
 
         // Verify that the map was populated
         assert!(!layered_key_transition_map.is_empty());
@@ -844,12 +913,10 @@ mod tests {
     #[test]
     fn test_initialize_key_transition_map_with_transforms() {
         let bundle = create_test_bundle_with_transforms();
-        let layout = bundle.layouts.values().next().unwrap();
-        let layers = &layout.mac_os.as_ref().unwrap().primary.layers;
-        let language_tag = &layout.language_tag;
+        let keyboard = lower(&bundle);
 
         let mut layered_key_transition_map = IndexMap::new();
-        initialize_key_transition_map(language_tag, layers, &mut layered_key_transition_map);
+        initialize_key_transition_map(&keyboard, &mut layered_key_transition_map);
 
         // Should initialize normally even with transforms present
         let base_layer_map = layered_key_transition_map
@@ -861,19 +928,20 @@ mod tests {
     #[test]
     fn test_duplicate_keys_issue() {
         let bundle = create_test_bundle_with_duplicate_keys();
-        let layout = bundle.layouts.values().next().unwrap();
-        let layers = &layout.mac_os.as_ref().unwrap().primary.layers;
-        let language_tag = &layout.language_tag;
+        let keyboard = lower(&bundle);
 
         let mut layered_key_transition_map = IndexMap::new();
-        initialize_key_transition_map(language_tag, layers, &mut layered_key_transition_map);
+        initialize_key_transition_map(&keyboard, &mut layered_key_transition_map);
 
         let base_layer_map = layered_key_transition_map
             .get(&MacOsKbdLayer::Default)
             .unwrap();
 
         // Count how many 'a' keys should exist in the layer
-        let layer_keys: Vec<String> = split_keys(&layers.get(&MacOsKbdLayer::Default).unwrap());
+        let layout = bundle.layouts.values().next().unwrap();
+        let layers = &layout.mac_os.as_ref().unwrap().primary.layers;
+        let layer_keys: Vec<String> =
+            crate::util::split_keys(layers.get(&MacOsKbdLayer::Default).unwrap());
         let a_count = layer_keys.iter().filter(|&k| k == "a").count();
         assert_eq!(a_count, 2); // We have 2 'a' keys
 
@@ -897,24 +965,18 @@ mod tests {
     #[test]
     fn test_process_transforms_with_dead_keys() {
         let bundle = create_test_bundle_with_transforms();
-        let layout = bundle.layouts.values().next().unwrap();
-        let layers = &layout.mac_os.as_ref().unwrap().primary.layers;
-        let language_tag = &layout.language_tag;
-        let transforms = layout.transforms.as_ref().unwrap();
-        let target_dead_keys = layout.mac_os.as_ref().unwrap().dead_keys.as_ref().unwrap();
+        let keyboard = lower(&bundle);
 
         let mut layered_key_transition_map = IndexMap::new();
         let mut dead_keys = IndexMap::new();
         let mut id_manager = TransformIdManager::new();
 
         // Initialize first
-        initialize_key_transition_map(language_tag, layers, &mut layered_key_transition_map);
+        initialize_key_transition_map(&keyboard, &mut layered_key_transition_map);
 
         // Process transforms
         process_transforms(
-            layers,
-            transforms,
-            target_dead_keys,
+            &keyboard,
             &mut dead_keys,
             &mut layered_key_transition_map,
             &mut id_manager,
@@ -946,6 +1008,64 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_process_transforms_with_chained_dead_keys() {
+        let mut bundle = create_test_bundle_with_transforms();
+        let layout = bundle.layouts.values_mut().next().unwrap();
+
+        // ' then ` then a gives ǎ, and ` on its own is a dead key too
+        let mac_os_target = layout.mac_os.as_mut().unwrap();
+        mac_os_target.dead_keys.as_mut().unwrap()[&MacOsKbdLayer::Default].push("`".to_string());
+
+        let mut grave_map = IndexMap::new();
+        grave_map.insert(" ".to_string(), Transform::End("`".to_string()));
+        grave_map.insert("a".to_string(), Transform::End("à".to_string()));
+
+        let mut chained_map = IndexMap::new();
+        chained_map.insert(" ".to_string(), Transform::End("ˇ".to_string()));
+        chained_map.insert("a".to_string(), Transform::End("ǎ".to_string()));
+
+        let transforms = layout.transforms.as_mut().unwrap();
+        transforms.insert("`".to_string(), Transform::More(grave_map));
+        if let Transform::More(acute_map) = &mut transforms["'"] {
+            acute_map.insert("`".to_string(), Transform::More(chained_map));
+        }
+
+        let key_layouts = generate_key_layout_files(&bundle).unwrap();
+        let (document, _) = key_layouts.values().next().unwrap();
+        let xml = document.to_string();
+
+        // The chained state is terminated by the escape of its map
+        assert!(xml.contains(r#"<when state="dead_key001" output="ˇ""#));
+        // ` starts its own state, and moves on from the state of '
+        assert!(xml.contains(r#"<when state="none" next="dead_key002""#));
+        assert!(xml.contains(r#"<when state="dead_key000" next="dead_key001""#));
+        assert!(xml.contains(r#"<when state="dead_key001" output="ǎ""#));
+    }
+
+    #[test]
+    fn test_missing_escape_and_transforms() {
+        let mut bundle = create_test_bundle_with_transforms();
+        let layout = bundle.layouts.values_mut().next().unwrap();
+
+        // ` is a dead key without transforms, and ' then ` has no escape
+        let mac_os_target = layout.mac_os.as_mut().unwrap();
+        mac_os_target.dead_keys.as_mut().unwrap()[&MacOsKbdLayer::Default].push("`".to_string());
+
+        let chained_map = IndexMap::from([("a".to_string(), Transform::End("ǎ".to_string()))]);
+        if let Transform::More(acute_map) = &mut layout.transforms.as_mut().unwrap()["'"] {
+            acute_map.insert("`".to_string(), Transform::More(chained_map));
+        }
+
+        let key_layouts = generate_key_layout_files(&bundle).unwrap();
+        let (document, _) = key_layouts.values().next().unwrap();
+        let xml = document.to_string();
+
+        // The chained state is terminated by its own sequence
+        assert!(xml.contains(r#"<when state="dead_key001" output="'`""#));
+        assert!(xml.contains(r#"<when state="dead_key001" output="ǎ""#));
+    }
+
     #[test]
     fn test_update_key_transition_map_with_transform() {
         let mut key_transition_map = IndexMap::new();
@@ -1024,10 +1144,7 @@ mod tests {
     #[test]
     fn test_create_dead_key_actions() {
         let bundle = create_test_bundle_with_transforms();
-        let layout = bundle.layouts.values().next().unwrap();
-        let layers = &layout.mac_os.as_ref().unwrap().primary.layers;
-        let language_tag = &layout.language_tag;
-        let target_dead_keys = layout.mac_os.as_ref().unwrap().dead_keys.as_ref().unwrap();
+        let keyboard = lower(&bundle);
 
         let mut layered_key_transition_map = IndexMap::new();
         let mut dead_keys = IndexMap::new();
@@ -1042,12 +1159,11 @@ mod tests {
             },
         );
 
-        initialize_key_transition_map(language_tag, layers, &mut layered_key_transition_map);
+        initialize_key_transition_map(&keyboard, &mut layered_key_transition_map);
 
         create_dead_key_actions(
-            layers,
+            &keyboard,
             &mut layered_key_transition_map,
-            target_dead_keys,
             &dead_keys,
             &mut id_manager,
         );
@@ -1074,7 +1190,7 @@ mod tests {
     #[test]
     fn test_generate_key_layout_files() {
         let bundle = create_test_bundle();
-        let key_layouts = generate_key_layout_files(&bundle);
+        let key_layouts = generate_key_layout_files(&bundle).unwrap();
 
         assert_eq!(key_layouts.len(), 1);
 
@@ -1092,7 +1208,7 @@ mod tests {
     #[test]
     fn test_generate_key_layout_files_with_transforms() {
         let bundle = create_test_bundle_with_transforms();
-        let key_layouts = generate_key_layout_files(&bundle);
+        let key_layouts = generate_key_layout_files(&bundle).unwrap();
 
         assert_eq!(key_layouts.len(), 1);
 
@@ -1137,7 +1253,7 @@ mod tests {
         }
 
         // Generate the key layout files
-        let key_layouts = generate_key_layout_files(&bundle);
+        let key_layouts = generate_key_layout_files(&bundle).unwrap();
         let (document, _) = key_layouts.values().next().unwrap();
 
         // Convert the document to XML string
@@ -1211,6 +1327,7 @@ mod tests {
                     output: "á".to_string(),
                 },
             ],
+            next_states: vec![],
         };
 
         assert_eq!(action.id, "action001");
@@ -1,5 +1,5 @@
 mod generate_macos;
-mod keymap;
+pub(crate) mod keymap;
 mod layers;
 mod macos_bundle;
 mod package_macos;
@@ -20,18 +20,24 @@ pub struct MacOsBuild {
     pub steps: Vec<Box<dyn BuildStep>>,
 }
 
+pub fn build_steps() -> Vec<Box<dyn BuildStep>> {
+    #[allow(unused_mut)]
+    let mut steps: Vec<Box<dyn BuildStep>> = vec![Box::new(GenerateMacOs)];
+    #[cfg(target_os = "macos")]
+    steps.push(Box::new(GenerateInstaller));
+    #[cfg(not(target_os = "macos"))]
+    {
+        tracing::warn!("Skipping Installer step");
+        tracing::warn!("pkgutil and friends are only available on macOS");
+    }
+
+    steps
+}
+
 #[async_trait(?Send)]
 impl BuildSteps for MacOsBuild {
     fn new(bundle: KbdgenBundle, output_path: PathBuf) -> Self {
-        #[allow(unused_mut)]
-        let mut steps: Vec<Box<dyn BuildStep>> = vec![Box::new(GenerateMacOs)];
-        #[cfg(target_os = "macos")]
-        steps.push(Box::new(GenerateInstaller));
-        #[cfg(not(target_os = "macos"))]
-        {
-            tracing::warn!("Skipping Installer step");
-            tracing::warn!("pkgutil and friends are only available on macOS");
-        }
+        let steps = build_steps();
 
         MacOsBuild {
             bundle,
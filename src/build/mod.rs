@@ -5,11 +5,18 @@ use async_trait::async_trait;
 use crate::bundle::KbdgenBundle;
 use anyhow::Result;
 
+pub mod all;
 pub mod android;
 pub mod chromeos;
+pub mod cldr;
+pub mod docs;
 pub mod ios;
+pub mod keyman;
+pub mod linux;
 pub mod macos;
 pub mod pahkat;
+#[cfg(test)]
+mod snapshots;
 #[allow(dead_code)]
 pub mod svg;
 pub mod windows;
@@ -0,0 +1,567 @@
+//! Runs the generation steps of each target on the bundle in
+//! `tests/fixtures` and compares what they write with the files in
+//! `tests/snapshots`. Run with `UPDATE_SNAPSHOTS=1` to accept changes.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::BuildStep;
+use super::android::generate_android::GenerateAndroid;
+use super::chromeos::generate_chromeos::GenerateChromeOs;
+use super::cldr::generate_cldr::GenerateCldr;
+use super::docs::generate_docs::GenerateDocs;
+use super::ios::{generate_ios::GenerateIos, generate_xcode::GenerateXcode, pbxproj};
+use super::keyman::{
+    generate_kmn::GenerateKmn, generate_kvks::GenerateKvks, generate_package::GeneratePackage,
+};
+use super::linux::{generate_xcompose::GenerateXCompose, generate_xkb::GenerateXkb};
+use super::macos::GenerateMacOs;
+use super::windows::{
+    generate_installer::GenerateWindowsInstaller, generate_kbd_source::GenerateKbdSource,
+    generate_klc::GenerateKlc,
+};
+use crate::bundle::read_kbdgen_bundle;
+use crate::util::diff_lines;
+
+const FIXTURE_BUNDLE: &str = "tests/fixtures/sje.kbdgen";
+const SNAPSHOTS_FOLDER: &str = "tests/snapshots";
+const OUTPUT_PLACEHOLDER: &str = "$OUTPUT";
+
+/// Xcode object ids, which kbdgen makes up for every object it adds
+static OBJECT_ID_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b[0-9A-F]{24}\b").unwrap());
+static TIMESTAMP_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:?\d{2})?").unwrap()
+});
+
+/// The parts of the keyboard app's repository that the Android step edits
+const ANDROID_METHOD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<input-method xmlns:android="http://schemas.android.com/apk/res/android">
+  <subtype android:label="@string/subtype_generic" />
+</input-method>
+"#;
+const ANDROID_SPELLCHECKER: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<spell-checker xmlns:android="http://schemas.android.com/apk/res/android">
+  <subtype android:label="@string/subtype_generic" />
+</spell-checker>
+"#;
+const ANDROID_APP_NAME: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<resources>
+  <string name="english_ime_name">Divvun Keyboards</string>
+</resources>
+"#;
+const ANDROID_STRINGS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<resources>
+</resources>
+"#;
+
+/// The parts of the keyboard app's Xcode project that the iOS steps edit,
+/// with the project saved as JSON so that it can be read without `plutil`
+const XCODE_PROJECT: &str = r#"{
+  "archiveVersion": "1",
+  "classes": {},
+  "objectVersion": "56",
+  "objects": {
+    "0000000000000000000000A1": {
+      "isa": "PBXProject",
+      "attributes": { "LastUpgradeCheck": "1500", "ORGANIZATIONNAME": "Divvun" },
+      "buildConfigurationList": "0000000000000000000000A2",
+      "compatibilityVersion": "Xcode 14.0",
+      "developmentRegion": "en",
+      "hasScannedForEncodings": "0",
+      "knownRegions": ["Base", "en"],
+      "mainGroup": "0000000000000000000000B1",
+      "productRefGroup": "0000000000000000000000B2",
+      "projectDirPath": "",
+      "projectRoot": "",
+      "targets": ["0000000000000000000000D1", "0000000000000000000000D2"]
+    },
+    "0000000000000000000000A2": {
+      "isa": "XCConfigurationList",
+      "buildConfigurations": ["0000000000000000000000A3"],
+      "defaultConfigurationIsVisible": "0",
+      "defaultConfigurationName": "Release"
+    },
+    "0000000000000000000000A3": {
+      "isa": "XCBuildConfiguration",
+      "buildSettings": { "SDKROOT": "iphoneos" },
+      "name": "Release"
+    },
+    "0000000000000000000000B1": {
+      "isa": "PBXGroup",
+      "children": ["0000000000000000000000B2", "0000000000000000000000B3", "0000000000000000000000B5"],
+      "sourceTree": "<group>"
+    },
+    "0000000000000000000000B2": {
+      "isa": "PBXGroup",
+      "children": ["0000000000000000000000C1", "0000000000000000000000C2"],
+      "name": "Products",
+      "sourceTree": "<group>"
+    },
+    "0000000000000000000000B3": {
+      "isa": "PBXGroup",
+      "children": ["0000000000000000000000B4"],
+      "path": "HostingApp",
+      "sourceTree": "<group>"
+    },
+    "0000000000000000000000B4": {
+      "isa": "PBXGroup",
+      "children": [],
+      "name": "Supporting Files",
+      "sourceTree": "<group>"
+    },
+    "0000000000000000000000B5": {
+      "isa": "PBXGroup",
+      "children": [],
+      "path": "Keyboard",
+      "sourceTree": "<group>"
+    },
+    "0000000000000000000000C1": {
+      "isa": "PBXFileReference",
+      "explicitFileType": "wrapper.application",
+      "includeInIndex": "0",
+      "path": "HostingApp.app",
+      "sourceTree": "BUILT_PRODUCTS_DIR"
+    },
+    "0000000000000000000000C2": {
+      "isa": "PBXFileReference",
+      "explicitFileType": "wrapper.app-extension",
+      "includeInIndex": "0",
+      "path": "Keyboard.appex",
+      "sourceTree": "BUILT_PRODUCTS_DIR"
+    },
+    "0000000000000000000000C3": {
+      "isa": "PBXBuildFile",
+      "fileRef": "0000000000000000000000C2",
+      "settings": { "ATTRIBUTES": ["RemoveHeadersOnCopy"] }
+    },
+    "0000000000000000000000D1": {
+      "isa": "PBXNativeTarget",
+      "buildConfigurationList": "0000000000000000000000E1",
+      "buildPhases": ["0000000000000000000000F1", "0000000000000000000000F2"],
+      "buildRules": [],
+      "dependencies": ["0000000000000000000000F3"],
+      "name": "HostingApp",
+      "productName": "HostingApp",
+      "productReference": "0000000000000000000000C1",
+      "productType": "com.apple.product-type.application"
+    },
+    "0000000000000000000000D2": {
+      "isa": "PBXNativeTarget",
+      "buildConfigurationList": "0000000000000000000000E3",
+      "buildPhases": [],
+      "buildRules": [],
+      "dependencies": [],
+      "name": "Keyboard",
+      "productName": "Keyboard",
+      "productReference": "0000000000000000000000C2",
+      "productType": "com.apple.product-type.app-extension"
+    },
+    "0000000000000000000000E1": {
+      "isa": "XCConfigurationList",
+      "buildConfigurations": ["0000000000000000000000E2"],
+      "defaultConfigurationIsVisible": "0",
+      "defaultConfigurationName": "Release"
+    },
+    "0000000000000000000000E2": {
+      "isa": "XCBuildConfiguration",
+      "buildSettings": { "INFOPLIST_FILE": "HostingApp/Info.plist" },
+      "name": "Release"
+    },
+    "0000000000000000000000E3": {
+      "isa": "XCConfigurationList",
+      "buildConfigurations": ["0000000000000000000000E4"],
+      "defaultConfigurationIsVisible": "0",
+      "defaultConfigurationName": "Release"
+    },
+    "0000000000000000000000E4": {
+      "isa": "XCBuildConfiguration",
+      "buildSettings": { "INFOPLIST_FILE": "Keyboard/Info.plist" },
+      "name": "Release"
+    },
+    "0000000000000000000000F1": {
+      "isa": "PBXResourcesBuildPhase",
+      "buildActionMask": "2147483647",
+      "files": [],
+      "runOnlyForDeploymentPostprocessing": "0"
+    },
+    "0000000000000000000000F2": {
+      "isa": "PBXCopyFilesBuildPhase",
+      "buildActionMask": "2147483647",
+      "dstPath": "",
+      "dstSubfolderSpec": "13",
+      "files": ["0000000000000000000000C3"],
+      "name": "Embed App Extensions",
+      "runOnlyForDeploymentPostprocessing": "0"
+    },
+    "0000000000000000000000F3": {
+      "isa": "PBXTargetDependency",
+      "target": "0000000000000000000000D2",
+      "targetProxy": "0000000000000000000000F4"
+    },
+    "0000000000000000000000F4": {
+      "isa": "PBXContainerItemProxy",
+      "containerPortal": "0000000000000000000000A1",
+      "proxyType": "1",
+      "remoteGlobalIDString": "0000000000000000000000D2",
+      "remoteInfo": "Keyboard"
+    }
+  },
+  "rootObject": "0000000000000000000000A1"
+}
+"#;
+const XCODE_KEYBOARD_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>DivvunContactEmail</key><string>feedback@divvun.no</string>
+  <key>CFBundleDevelopmentRegion</key><string>en</string>
+  <key>CFBundleDisplayName</key><string>Keyboard</string>
+  <key>CFBundleExecutable</key><string>$(EXECUTABLE_NAME)</string>
+  <key>CFBundleIdentifier</key><string>$(PRODUCT_BUNDLE_IDENTIFIER)</string>
+  <key>CFBundleInfoDictionaryVersion</key><string>6.0</string>
+  <key>CFBundleName</key><string>$(PRODUCT_NAME)</string>
+  <key>CFBundlePackageType</key><string>XPC!</string>
+  <key>CFBundleShortVersionString</key><string>0.0.0</string>
+  <key>CFBundleSignature</key><string>????</string>
+  <key>CFBundleVersion</key><string>0</string>
+  <key>DivvunKeyboardIndex</key><integer>0</integer>
+  <key>ITSAppUsesNonExemptEncryption</key><false/>
+  <key>LSApplicationQueriesSchemes</key><array><string>keyboard</string></array>
+  <key>NSExtension</key>
+  <dict>
+    <key>NSExtensionAttributes</key>
+    <dict>
+      <key>IsASCIICapable</key><false/>
+      <key>PrefersRightToLeft</key><false/>
+      <key>PrimaryLanguage</key><string>en</string>
+      <key>RequestsOpenAccess</key><true/>
+    </dict>
+    <key>NSExtensionPointIdentifier</key><string>com.apple.keyboard-service</string>
+    <key>NSExtensionPrincipalClass</key><string>$(PRODUCT_MODULE_NAME).KeyboardViewController</string>
+  </dict>
+</dict>
+</plist>
+"#;
+const XCODE_HOSTING_APP_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>CFBundleDevelopmentRegion</key><string>en</string>
+  <key>CFBundleDisplayName</key><string>Keyboards</string>
+  <key>CFBundleExecutable</key><string>$(EXECUTABLE_NAME)</string>
+  <key>CFBundleIdentifier</key><string>$(PRODUCT_BUNDLE_IDENTIFIER)</string>
+  <key>CFBundleInfoDictionaryVersion</key><string>6.0</string>
+  <key>CFBundleName</key><string>$(PRODUCT_NAME)</string>
+  <key>CFBundlePackageType</key><string>APPL</string>
+  <key>CFBundleShortVersionString</key><string>0.0.0</string>
+  <key>CFBundleSignature</key><string>????</string>
+  <key>CFBundleURLTypes</key>
+  <array><dict><key>CFBundleURLSchemes</key><array><string>keyboard</string></array></dict></array>
+  <key>CFBundleVersion</key><string>0</string>
+  <key>ITSAppUsesNonExemptEncryption</key><false/>
+  <key>LSApplicationQueriesSchemes</key><array><string>keyboard</string></array>
+  <key>LSRequiresIPhoneOS</key><true/>
+  <key>UIBackgroundModes</key><array/>
+  <key>UILaunchStoryboardName</key><string>LaunchScreen</string>
+  <key>UIRequiredDeviceCapabilities</key><array><string>armv7</string></array>
+  <key>UISupportedInterfaceOrientations</key><array><string>UIInterfaceOrientationPortrait</string></array>
+  <key>UIUserInterfaceStyle</key><string>Light</string>
+</dict>
+</plist>
+"#;
+const XCODE_ENTITLEMENTS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>com.apple.security.application-groups</key><array/>
+</dict>
+</plist>
+"#;
+const XCODE_SETTINGS_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>StringsTable</key><string>Root</string>
+  <key>PreferenceSpecifiers</key><array/>
+  <key>ApplicationGroupContainerIdentifier</key><string>group</string>
+</dict>
+</plist>
+"#;
+/// No icon sizes, so that no icons are rendered with `convert`
+const XCODE_APP_ICONS: &str = r#"{ "images": [], "info": { "author": "xcode", "version": 1 } }
+"#;
+
+struct Case {
+    name: &'static str,
+    steps: Vec<Box<dyn BuildStep>>,
+    /// Folders the steps expect to exist, such as parts of a cloned repository
+    folders: &'static [&'static str],
+    /// Files the steps expect to exist and edit, with their contents
+    files: &'static [(&'static str, &'static str)],
+    /// The files written by the steps that are compared, relative to the output
+    outputs: &'static [&'static str],
+}
+
+fn cases() -> Vec<Case> {
+    vec![
+        Case {
+            name: "windows",
+            steps: vec![
+                Box::new(GenerateKlc {}),
+                Box::new(GenerateKbdSource {}),
+                Box::new(GenerateWindowsInstaller),
+            ],
+            folders: &[],
+            files: &[],
+            outputs: &[
+                "kbdsje.klc",
+                "kbdsje/kbdsje.c",
+                "kbdsje/kbdsje.h",
+                "kbdsje/kbdsje.def",
+                "kbdsje/kbdsje.rc",
+                "install.iss",
+            ],
+        },
+        Case {
+            name: "chromeos",
+            steps: vec![Box::new(GenerateChromeOs)],
+            folders: &[],
+            files: &[],
+            outputs: &[
+                "background.js",
+                "manifest.json",
+                "_locales/en/messages.json",
+                "_locales/sje/messages.json",
+            ],
+        },
+        Case {
+            name: "linux",
+            steps: vec![Box::new(GenerateXkb), Box::new(GenerateXCompose)],
+            folders: &[],
+            files: &[],
+            outputs: &[
+                "symbols/sje",
+                "rules/evdev.xml",
+                "rules/evdev.lst",
+                "compose/sje.XCompose",
+            ],
+        },
+        Case {
+            name: "keyman",
+            steps: vec![
+                Box::new(GenerateKmn),
+                Box::new(GenerateKvks),
+                Box::new(GeneratePackage),
+            ],
+            folders: &[],
+            files: &[],
+            outputs: &["source/sje.kmn", "source/sje.kvks", "source/sje.kps"],
+        },
+        Case {
+            name: "cldr",
+            steps: vec![Box::new(GenerateCldr)],
+            folders: &[],
+            files: &[],
+            outputs: &["sje.xml"],
+        },
+        Case {
+            name: "macos",
+            steps: vec![Box::new(GenerateMacOs)],
+            folders: &[],
+            files: &[],
+            outputs: &[
+                "no.divvun.keyboards.sje.keyboardlayout.sje.bundle/Contents/Info.plist",
+                "no.divvun.keyboards.sje.keyboardlayout.sje.bundle/Contents/Resources/sje.keylayout",
+                "no.divvun.keyboards.sje.keyboardlayout.sje.bundle/Contents/Resources/en.lproj/InfoPlist.strings",
+                "no.divvun.keyboards.sje.keyboardlayout.sje.bundle/Contents/Resources/sje.lproj/InfoPlist.strings",
+            ],
+        },
+        Case {
+            name: "android",
+            steps: vec![Box::new(GenerateAndroid)],
+            folders: &[],
+            files: &[
+                ("repo/app/src/main/res/xml/method.xml", ANDROID_METHOD),
+                (
+                    "repo/app/src/main/res/xml/spellchecker.xml",
+                    ANDROID_SPELLCHECKER,
+                ),
+                (
+                    "repo/app/src/main/res/values/strings-appname.xml",
+                    ANDROID_APP_NAME,
+                ),
+                ("repo/app/src/main/res/values/strings.xml", ANDROID_STRINGS),
+            ],
+            outputs: &[
+                "repo/app/src/main/assets/layouts/sje.json",
+                "repo/app/src/main/res/values/strings.xml",
+                "repo/app/src/main/res/xml/method.xml",
+                "repo/app/src/main/res/xml/spellchecker.xml",
+                "repo/app/src/main/res/xml/keyboard_layout_set_pitexsami.xml",
+                "repo/app/src/main/res/xml/kbd_pitexsami.xml",
+                "repo/app/src/main/res/xml/rows_pitexsami.xml",
+                "repo/app/src/main/res/xml/rowkeys_pitexsami1.xml",
+                "repo/app/src/main/res/xml/rowkeys_pitexsami2.xml",
+                "repo/app/src/main/res/xml/rowkeys_pitexsami3.xml",
+                "repo/app/src/main/res/xml-sw600dp/rows_pitexsami.xml",
+                "repo/app/src/main/res/xml-sw600dp/rowkeys_pitexsami1.xml",
+                "repo/app/src/main/res/xml-sw600dp/rowkeys_pitexsami2.xml",
+                "repo/app/src/main/res/xml-sw600dp/rowkeys_pitexsami3.xml",
+            ],
+        },
+        Case {
+            name: "ios",
+            steps: vec![Box::new(GenerateIos), Box::new(GenerateXcode)],
+            folders: &["repo/Keyboard/Models"],
+            files: &[
+                (
+                    "repo/GiellaKeyboard.xcodeproj/project.pbxproj",
+                    XCODE_PROJECT,
+                ),
+                ("repo/Keyboard/Info.plist", XCODE_KEYBOARD_PLIST),
+                ("repo/Keyboard/Keyboard.entitlements", XCODE_ENTITLEMENTS),
+                ("repo/HostingApp/Info.plist", XCODE_HOSTING_APP_PLIST),
+                (
+                    "repo/HostingApp/HostingApp.entitlements",
+                    XCODE_ENTITLEMENTS,
+                ),
+                (
+                    "repo/HostingApp/Settings.bundle/Root.plist",
+                    XCODE_SETTINGS_PLIST,
+                ),
+                (
+                    "repo/HostingApp/Images.xcassets/AppIcon.appiconset/Contents.json",
+                    XCODE_APP_ICONS,
+                ),
+            ],
+            outputs: &[
+                "repo/Keyboard/Models/KeyboardDefinitions.json",
+                "repo/GiellaKeyboard.xcodeproj/project.pbxproj",
+                "repo/Keyboard/sje/Info.plist",
+                "repo/HostingApp/Info.plist",
+            ],
+        },
+        Case {
+            name: "docs",
+            steps: vec![Box::new(GenerateDocs)],
+            folders: &[],
+            files: &[],
+            outputs: &["index.html", "sje.html"],
+        },
+    ]
+}
+
+/// Replaces each distinct match with a placeholder numbered by where it
+/// first appears, so that outputs stay comparable while still showing which
+/// matches are the same
+fn number_matches(text: &str, re: &Regex, placeholder: &str) -> String {
+    let mut numbers = HashMap::new();
+    re.replace_all(text, |captures: &regex::Captures| {
+        let next = numbers.len() + 1;
+        let number = *numbers.entry(captures[0].to_string()).or_insert(next);
+        format!("{placeholder}_{number}")
+    })
+    .to_string()
+}
+
+/// Makes an output comparable between runs and machines: UTF-16 files are
+/// decoded, line endings unified, the output path replaced with a
+/// placeholder, and Xcode object ids and timestamps numbered
+fn normalise(bytes: &[u8], output_path: &Path) -> String {
+    let text = match bytes {
+        [0xff, 0xfe, rest @ ..] => String::from_utf16_lossy(
+            &rest
+                .chunks_exact(2)
+                .map(|x| u16::from_le_bytes([x[0], x[1]]))
+                .collect::<Vec<_>>(),
+        ),
+        [0xef, 0xbb, 0xbf, rest @ ..] => String::from_utf8_lossy(rest).to_string(),
+        _ => String::from_utf8_lossy(bytes).to_string(),
+    };
+
+    let text = text
+        .replace("\r\n", "\n")
+        .replace(&output_path.display().to_string(), OUTPUT_PLACEHOLDER);
+    let text = number_matches(&text, &OBJECT_ID_RE, "OBJECTID");
+    number_matches(&text, &TIMESTAMP_RE, "TIMESTAMP")
+}
+
+fn root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+async fn check_case(case: Case, update: bool) -> Vec<String> {
+    let bundle = read_kbdgen_bundle(&root().join(FIXTURE_BUNDLE)).unwrap();
+    let output = tempfile::tempdir().unwrap();
+    let output_path = output.path();
+    // The Xcode step makes up ids for the objects it adds, and the project
+    // lists objects ordered by id
+    pbxproj::seed_object_ids(0);
+
+    for folder in case.folders {
+        std::fs::create_dir_all(output_path.join(folder)).unwrap();
+    }
+    for (file, contents) in case.files {
+        let path = output_path.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+    for step in &case.steps {
+        step.build(&bundle, output_path).await.unwrap();
+    }
+
+    let mut failures = vec![];
+    for file in case.outputs {
+        let snapshot_path = root().join(SNAPSHOTS_FOLDER).join(case.name).join(file);
+        let found = match std::fs::read(output_path.join(file)) {
+            Ok(bytes) => normalise(&bytes, output_path),
+            Err(e) => {
+                failures.push(format!("{}/{}: not written: {}", case.name, file, e));
+                continue;
+            }
+        };
+
+        if update {
+            std::fs::create_dir_all(snapshot_path.parent().unwrap()).unwrap();
+            std::fs::write(&snapshot_path, found).unwrap();
+            continue;
+        }
+
+        match std::fs::read_to_string(&snapshot_path) {
+            Ok(expected) if expected == found => {}
+            Ok(expected) => failures.push(format!(
+                "{}/{} differs from its snapshot:\n{}",
+                case.name,
+                file,
+                diff_lines(&expected, &found)
+            )),
+            Err(_) => failures.push(format!(
+                "{}/{} has no snapshot at {}",
+                case.name,
+                file,
+                snapshot_path.display()
+            )),
+        }
+    }
+
+    failures
+}
+
+#[tokio::test]
+async fn test_snapshots() {
+    let update = std::env::var("UPDATE_SNAPSHOTS").is_ok_and(|x| x == "1");
+
+    let mut failures = vec![];
+    for case in cases() {
+        failures.extend(check_case(case, update).await);
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{}\n\nIf the changes are intended, run the tests with UPDATE_SNAPSHOTS=1",
+        failures.join("\n\n")
+    );
+}
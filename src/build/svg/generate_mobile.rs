@@ -0,0 +1,456 @@
+use std::path::Path;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use indexmap::IndexMap;
+use xmlem::{Document, Element};
+
+use crate::{
+    build::{
+        BuildStep,
+        android::generate_android::{android_key_width, shift_key_width},
+        ios::generate_ios::keyboard_component_from_string,
+    },
+    bundle::{
+        KbdgenBundle,
+        layout::{Layout, android::AndroidKbdLayer},
+        source::yaml_key,
+    },
+    util::{decode_unicode_escapes, split_keys},
+};
+
+use super::SVG_EXT;
+
+const KEYBOARD_WIDTH: f64 = 640.0;
+const KEY_HEIGHT: f64 = 56.0;
+const KEY_GAP: f64 = 4.0;
+const MARGIN: f64 = 8.0;
+const LONGPRESS_WIDTH: f64 = 240.0;
+const LONGPRESS_LINE_HEIGHT: f64 = 22.0;
+
+const PREVIEW_STYLE: &str = "
+    .key rect { fill: white; stroke: #999; stroke-width: 1; }
+    .key-special rect { fill: #ddd; }
+    .key text { text-anchor: middle; font-size: 20px; }
+    .key .key-dead { fill: green; font-weight: bold; }
+    .key .key-hint { font-size: 10px; fill: #777; }
+    .longpress { font-size: 14px; }
+";
+
+/// A key of a previewed row, with its width in pixels
+struct PreviewKey {
+    label: String,
+    width: f64,
+    special: bool,
+    dead: bool,
+    longpress: Option<Vec<String>>,
+}
+
+/// What a `\s{..}` key is drawn as
+fn special_label(name: &str) -> &str {
+    match name {
+        "shift" => "⇧",
+        "backspace" => "⌫",
+        "return" | "enter" => "⏎",
+        "space" | "spacebar" => "␣",
+        "keyboard" => "⌨",
+        name => name,
+    }
+}
+
+fn special_name(key: &str) -> Option<&str> {
+    key.strip_prefix(r"\s{").and_then(|x| x.strip_suffix('}'))
+}
+
+/// Lays out the rows of an Android layer like the rowkeys files do: the
+/// first row and ordinary keys have the same width, shift keys share what is
+/// left of their row and backspace fills the rest of it
+fn android_rows(
+    layer: &str,
+    key_width: f64,
+    longpress: Option<&IndexMap<String, Vec<String>>>,
+    dead_keys: &[&String],
+) -> Vec<Vec<PreviewKey>> {
+    let mut rows = vec![];
+
+    for (line_index, line) in layer.lines().filter(|x| !x.trim().is_empty()).enumerate() {
+        let keys = split_keys(line);
+        let special_keys_count = keys.iter().filter(|x| x.starts_with("\\s")).count();
+
+        let widths = keys
+            .iter()
+            .map(|key| match key.as_str() {
+                _ if line_index == 0 => Some(key_width),
+                "\\s{shift}" => Some(shift_key_width(key_width, keys.len(), special_keys_count)),
+                "\\s{backspace}" => None,
+                _ => Some(key_width),
+            })
+            .collect::<Vec<_>>();
+        let fill_right = 100.0 - widths.iter().flatten().sum::<f64>();
+
+        let row = keys
+            .iter()
+            .zip(widths)
+            .map(|(key, width)| {
+                let width = width.unwrap_or(if fill_right > 0.0 {
+                    fill_right
+                } else {
+                    key_width
+                });
+                let special = special_name(key);
+
+                PreviewKey {
+                    label: match special {
+                        Some(name) => special_label(name).to_string(),
+                        None => decode_unicode_escapes(key),
+                    },
+                    width: width * KEYBOARD_WIDTH / 100.0,
+                    special: special.is_some(),
+                    dead: dead_keys.contains(&key),
+                    longpress: longpress.and_then(|x| x.get(key)).cloned(),
+                }
+            })
+            .collect();
+
+        rows.push(row);
+    }
+
+    rows
+}
+
+/// Lays out the rows of an iOS layer, where `\s{name:1.5}` keys are wider by
+/// their given factor and the widest row fills the keyboard
+fn ios_rows(
+    layer: &str,
+    longpress: Option<&IndexMap<String, Vec<String>>>,
+    dead_keys: &[String],
+) -> Vec<Vec<PreviewKey>> {
+    let rows = layer
+        .trim()
+        .split('\n')
+        .map(|line| {
+            split_keys(line)
+                .into_iter()
+                .map(|key| match keyboard_component_from_string(key.clone()) {
+                    Some(button) => {
+                        let label = match button.id.strip_prefix('_') {
+                            Some(name) => special_label(name).to_string(),
+                            None => button.id.clone(),
+                        };
+                        (key, label, button.width as f64, true)
+                    }
+                    None => {
+                        let label = decode_unicode_escapes(&key);
+                        (key, label, 1.0, false)
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let widest_row = rows
+        .iter()
+        .map(|row| row.iter().map(|(_, _, width, _)| width).sum::<f64>())
+        .fold(1.0, f64::max);
+    let unit = KEYBOARD_WIDTH / widest_row;
+
+    rows.into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|(key, label, width, special)| PreviewKey {
+                    dead: !special && dead_keys.contains(&label),
+                    longpress: longpress.and_then(|x| x.get(&key)).cloned(),
+                    label,
+                    width: width * unit,
+                    special,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn append_key(document: &mut Document, row: &Element, key: &PreviewKey, x: f64) {
+    let class = if key.special {
+        "key key-special"
+    } else {
+        "key"
+    };
+    let group = row.append_new_element(
+        document,
+        (
+            "g",
+            [
+                ("class", class.to_string()),
+                ("transform", format!("translate({:.2}, 0)", x)),
+            ],
+        ),
+    );
+
+    group.append_new_element(
+        document,
+        (
+            "rect",
+            [
+                ("x", format!("{}", KEY_GAP / 2.0)),
+                ("y", format!("{}", KEY_GAP / 2.0)),
+                ("width", format!("{:.2}", key.width - KEY_GAP)),
+                ("height", format!("{}", KEY_HEIGHT - KEY_GAP)),
+                ("rx", "4".to_string()),
+            ],
+        ),
+    );
+
+    let class = if key.dead { "key-dead" } else { "key-label" };
+    group
+        .append_new_element(
+            document,
+            (
+                "text",
+                [
+                    ("class", class.to_string()),
+                    ("x", format!("{:.2}", key.width / 2.0)),
+                    ("y", format!("{}", KEY_HEIGHT / 2.0)),
+                    ("dy", ".35em".to_string()),
+                ],
+            ),
+        )
+        .set_text(document, &key.label);
+
+    if let Some(first) = key.longpress.as_ref().and_then(|x| x.first()) {
+        group
+            .append_new_element(
+                document,
+                (
+                    "text",
+                    [
+                        ("class", "key-hint".to_string()),
+                        ("x", format!("{:.2}", key.width - 10.0)),
+                        ("y", "16".to_string()),
+                    ],
+                ),
+            )
+            .set_text(document, &decode_unicode_escapes(first));
+    }
+}
+
+/// Draws the rows of a touch keyboard layer, with the longpress keys of
+/// each key listed beside the keyboard
+fn generate_preview(title: &str, rows: &[Vec<PreviewKey>]) -> String {
+    let longpress = rows
+        .iter()
+        .flatten()
+        .filter_map(|key| Some((&key.label, key.longpress.as_ref()?)))
+        .collect::<Vec<_>>();
+
+    let keyboard_height = rows.len() as f64 * KEY_HEIGHT;
+    let longpress_height = longpress.len() as f64 * LONGPRESS_LINE_HEIGHT;
+    let width = KEYBOARD_WIDTH
+        + 2.0 * MARGIN
+        + if longpress.is_empty() {
+            0.0
+        } else {
+            LONGPRESS_WIDTH
+        };
+    let height = keyboard_height.max(longpress_height) + 2.0 * MARGIN;
+
+    let mut document = Document::new("svg");
+    let root = document.root();
+    root.set_attribute(&mut document, "xmlns", "http://www.w3.org/2000/svg");
+    root.set_attribute(&mut document, "width", &format!("{}", width));
+    root.set_attribute(&mut document, "height", &format!("{}", height));
+    root.set_attribute(&mut document, "font-family", "sans-serif");
+
+    root.append_new_element(&mut document, "title")
+        .set_text(&mut document, title);
+    root.append_new_element(&mut document, "style")
+        .set_text(&mut document, PREVIEW_STYLE);
+
+    for (index, row) in rows.iter().enumerate() {
+        let row_width: f64 = row.iter().map(|x| x.width).sum();
+        let row_element = root.append_new_element(
+            &mut document,
+            (
+                "g",
+                [(
+                    "transform",
+                    format!(
+                        "translate({:.2}, {})",
+                        MARGIN + (KEYBOARD_WIDTH - row_width).max(0.0) / 2.0,
+                        MARGIN + index as f64 * KEY_HEIGHT
+                    ),
+                )],
+            ),
+        );
+
+        let mut x = 0.0;
+        for key in row {
+            append_key(&mut document, &row_element, key, x);
+            x += key.width;
+        }
+    }
+
+    for (index, (label, keys)) in longpress.into_iter().enumerate() {
+        let keys = keys
+            .iter()
+            .map(|x| decode_unicode_escapes(x))
+            .collect::<Vec<_>>();
+
+        root.append_new_element(
+            &mut document,
+            (
+                "text",
+                [
+                    ("class", "longpress".to_string()),
+                    ("x", format!("{}", 2.0 * MARGIN + KEYBOARD_WIDTH)),
+                    (
+                        "y",
+                        format!("{}", MARGIN + (index as f64 + 0.75) * LONGPRESS_LINE_HEIGHT),
+                    ),
+                ],
+            ),
+        )
+        .set_text(&mut document, &format!("{}: {}", label, keys.join(" ")));
+    }
+
+    document.to_string_pretty()
+}
+
+/// Previews of every layer of the Android and iOS targets of a layout, by
+/// file name
+pub fn generate_mobile_previews(language_tag: &str, layout: &Layout) -> IndexMap<String, String> {
+    let mut previews = IndexMap::new();
+    let longpress = layout.longpress.as_ref();
+
+    if let Some(target) = &layout.android {
+        let dead_keys = layout
+            .transforms
+            .iter()
+            .flat_map(|x| x.keys())
+            .collect::<Vec<_>>();
+
+        for (platform_name, platform, tablet_600) in [
+            ("primary", &target.primary, false),
+            ("tablet-600", &target.tablet_600, true),
+        ] {
+            let Some(default_layer) = platform.layers.get(&AndroidKbdLayer::Default) else {
+                continue;
+            };
+            let key_width = android_key_width(tablet_600, default_layer);
+
+            for (layer_name, layer) in &platform.layers {
+                let name = format!(
+                    "{}-android-{}-{}",
+                    language_tag,
+                    platform_name,
+                    yaml_key(layer_name)
+                );
+                let rows = android_rows(layer, key_width, longpress, &dead_keys);
+                previews.insert(name.clone(), generate_preview(&name, &rows));
+            }
+        }
+    }
+
+    if let Some(target) = &layout.i_os {
+        for (platform_name, platform) in [
+            ("primary", &target.primary),
+            ("iPad-9in", &target.i_pad_9in),
+            ("iPad-12in", &target.i_pad_12in),
+        ] {
+            let Some(platform) = platform else {
+                continue;
+            };
+
+            for (layer_name, layer) in &platform.layers {
+                let dead_keys = target
+                    .dead_keys
+                    .as_ref()
+                    .and_then(|x| x.get(layer_name))
+                    .map(|x| {
+                        x.iter()
+                            .map(|x| decode_unicode_escapes(x))
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+
+                let name = format!(
+                    "{}-iOS-{}-{}",
+                    language_tag,
+                    platform_name,
+                    yaml_key(layer_name)
+                );
+                let rows = ios_rows(layer, longpress, &dead_keys);
+                previews.insert(name.clone(), generate_preview(&name, &rows));
+            }
+        }
+    }
+
+    previews
+}
+
+pub struct GenerateMobilePreviews;
+
+#[async_trait(?Send)]
+impl BuildStep for GenerateMobilePreviews {
+    async fn build(&self, bundle: &KbdgenBundle, output_path: &Path) -> Result<()> {
+        for (language_tag, layout) in &bundle.layouts {
+            for (name, svg) in generate_mobile_previews(language_tag.as_str(), layout) {
+                tracing::debug!("Generating preview {}", name);
+
+                std::fs::write(output_path.join(format!("{}.{}", name, SVG_EXT)), svg)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn widths(rows: &[Vec<PreviewKey>]) -> Vec<Vec<f64>> {
+        rows.iter()
+            .map(|row| {
+                row.iter()
+                    .map(|x| (x.width * 100.0).round() / 100.0)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_android_rows() {
+        // Shift and backspace share the 30% the other keys of their row leave
+        let layer =
+            "q w e r t y u i o p\na s d f g h j k l\n\\s{shift} z x c v b n m \\s{backspace}\n";
+        let rows = android_rows(layer, android_key_width(false, layer), None, &[]);
+
+        assert_eq!(
+            widths(&rows),
+            vec![
+                vec![64.0; 10],
+                vec![64.0; 9],
+                vec![96.0, 64.0, 64.0, 64.0, 64.0, 64.0, 64.0, 64.0, 96.0],
+            ]
+        );
+        assert_eq!(rows[2][0].label, "⇧");
+    }
+
+    #[test]
+    fn test_ios_rows() {
+        let longpress = IndexMap::from([("a".to_string(), vec!["á".to_string()])]);
+        let rows = ios_rows(
+            "a b c d\n\\s{shift:2} ´ \\s{backspace}",
+            Some(&longpress),
+            &["´".to_string()],
+        );
+
+        assert_eq!(
+            widths(&rows),
+            vec![vec![160.0; 4], vec![320.0, 160.0, 160.0]]
+        );
+        assert_eq!(rows[0][0].longpress, Some(vec!["á".to_string()]));
+        assert!(rows[1][1].dead);
+        assert!(rows[1][2].special);
+    }
+}
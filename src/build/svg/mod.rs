@@ -0,0 +1,272 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use language_tags::LanguageTag;
+use xmlem::{Document, Element, Selector};
+
+use crate::bundle::{KbdgenBundle, layout::Layout};
+use crate::model::{KeyAction, Keyboard, Modifiers, Platform};
+use crate::util::iso_key::IsoKey;
+
+use self::generate_mobile::GenerateMobilePreviews;
+
+use super::{BuildStep, BuildSteps};
+
+pub mod generate_mobile;
+
+pub const SVG_EXT: &str = "svg";
+static KEYBOARD_SVG: &str = include_str!("../../../resources/template-iso-keyboard.svg");
+
+/// The platforms a keyboard chart is drawn for
+pub const SVG_PLATFORMS: [Platform; 3] = [Platform::Windows, Platform::MacOs, Platform::ChromeOs];
+
+const SHIFT: Modifiers = Modifiers {
+    shift: true,
+    ..Modifiers::NONE
+};
+const ALT: Modifiers = Modifiers {
+    alt: true,
+    ..Modifiers::NONE
+};
+const ALT_SHIFT: Modifiers = Modifiers {
+    alt: true,
+    shift: true,
+    ..Modifiers::NONE
+};
+
+/// Where the output of a layer is drawn on a key cap
+struct Label {
+    modifiers: Modifiers,
+    x: &'static str,
+    y: &'static str,
+}
+
+/// The layers drawn on each key cap, by the key group of the template they
+/// are styled with. Unshifted outputs are at the bottom, alt outputs on the
+/// right.
+const KEY_GROUPS: [(&str, [Label; 2]); 2] = [
+    (
+        "key-group key-group-1",
+        [
+            Label {
+                modifiers: SHIFT,
+                x: "20",
+                y: "26",
+            },
+            Label {
+                modifiers: Modifiers::NONE,
+                x: "20",
+                y: "48",
+            },
+        ],
+    ),
+    (
+        "key-group key-group-2",
+        [
+            Label {
+                modifiers: ALT_SHIFT,
+                x: "46",
+                y: "26",
+            },
+            Label {
+                modifiers: ALT,
+                x: "46",
+                y: "48",
+            },
+        ],
+    ),
+];
+
+pub struct SvgBuild {
+    pub bundle: KbdgenBundle,
+    pub output_path: PathBuf,
+    pub steps: Vec<Box<dyn BuildStep>>,
+}
+
+pub fn build_steps() -> Vec<Box<dyn BuildStep>> {
+    vec![Box::new(GenerateSvg), Box::new(GenerateMobilePreviews)]
+}
+
+#[async_trait(?Send)]
+impl BuildSteps for SvgBuild {
+    fn new(bundle: KbdgenBundle, output_path: PathBuf) -> Self {
+        let steps = build_steps();
+
+        Self {
+            bundle,
+            output_path,
+            steps,
+        }
+    }
+
+    fn steps(&self) -> &[Box<dyn BuildStep>] {
+        &self.steps
+    }
+
+    fn bundle(&self) -> &KbdgenBundle {
+        &self.bundle
+    }
+
+    fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+}
+
+/// The key caps of the template that type something, by their ISO key
+fn template_keys(document: &Document) -> Vec<(IsoKey, Element)> {
+    let selector = Selector::new("g").expect("valid selector");
+
+    document
+        .root()
+        .query_selector_all(document, &selector)
+        .into_iter()
+        .filter_map(|element| {
+            let classes = element.attribute(document, "class")?;
+            let classes = classes.split_whitespace().collect::<Vec<_>>();
+            if !classes.contains(&"key") || classes.contains(&"key-function") {
+                return None;
+            }
+
+            let iso_key = classes.iter().find_map(|x| IsoKey::from_str(x).ok())?;
+            Some((iso_key, element))
+        })
+        .collect()
+}
+
+/// Draws the default, shift and alt outputs of a keyboard on the ISO
+/// keyboard template, with dead keys highlighted
+pub fn generate_svg(layout: &Layout, platform: Platform, keyboard: &Keyboard) -> String {
+    let mut document = Document::from_str(KEYBOARD_SVG).expect("valid svg template");
+    let root = document.root();
+
+    for (iso_key, element) in template_keys(&document) {
+        for (class, labels) in &KEY_GROUPS {
+            let labels = labels
+                .iter()
+                .filter_map(|label| {
+                    let key = keyboard.layer(label.modifiers)?.key(iso_key);
+                    let class = match key {
+                        KeyAction::DeadKey(_) => "key-label key-dead",
+                        _ => "key-label",
+                    };
+                    Some((key.output()?, class, label.x, label.y))
+                })
+                .collect::<Vec<_>>();
+
+            if labels.is_empty() {
+                continue;
+            }
+
+            let group = element.append_new_element(&mut document, ("g", [("class", *class)]));
+            for (output, class, x, y) in labels {
+                group
+                    .append_new_element(
+                        &mut document,
+                        ("text", [("class", class), ("x", x), ("y", y)]),
+                    )
+                    .set_text(&mut document, &output);
+            }
+        }
+    }
+
+    root.append_new_element(&mut document, "title").set_text(
+        &mut document,
+        &format!("{} ({})", layout.autonym(), platform),
+    );
+
+    document.to_string_pretty()
+}
+
+/// The file a keyboard chart is written to, e.g. `sme-windows.svg`
+pub fn chart_file_name(language_tag: &LanguageTag, platform: Platform) -> String {
+    format!("{}-{}.{}", language_tag, platform.target_name(), SVG_EXT)
+}
+
+pub struct GenerateSvg;
+
+#[async_trait(?Send)]
+impl BuildStep for GenerateSvg {
+    async fn build(&self, bundle: &KbdgenBundle, output_path: &Path) -> Result<()> {
+        for (language_tag, layout) in &bundle.layouts {
+            for platform in SVG_PLATFORMS {
+                let Some(keyboard) = Keyboard::from_layout(layout, platform) else {
+                    continue;
+                };
+
+                tracing::debug!("Generating {} svg for {}", platform, language_tag);
+
+                std::fs::write(
+                    output_path.join(chart_file_name(language_tag, platform)),
+                    generate_svg(layout, platform, &keyboard),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::test_layout;
+
+    #[test]
+    fn test_generate_svg() {
+        let text = r#"
+displayNames:
+  sje: Bidumsámegiella
+windows:
+  primary:
+    layers:
+      default: |
+        § 1 2 3 4 5 6 7 8 9 0 + ´
+        á š e r t y u i o p å ŋ
+        a s d f g h j k l ö ä đ
+        ž z č c v b n m , . -
+      alt: |
+        \u{0} \u{0} @ £ $ € \u{0} { [ ] } \ \u{0}
+        \u{0} \u{0} € \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0}
+        \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} ø æ \u{0}
+        \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0}
+  deadKeys:
+    default: ['´']
+"#;
+
+        let layout = test_layout("sje", text);
+        let keyboard = Keyboard::from_layout(&layout, Platform::Windows).unwrap();
+
+        let svg = generate_svg(&layout, Platform::Windows, &keyboard);
+        let document = Document::from_str(&svg).unwrap();
+        let labels = |iso_key| {
+            let (_, element) = template_keys(&document)
+                .into_iter()
+                .find(|(x, _)| *x == iso_key)
+                .unwrap();
+            element
+                .query_selector_all(&document, &Selector::new("text").unwrap())
+                .into_iter()
+                .filter_map(|x| {
+                    let class = x.attribute(&document, "class")?;
+                    class
+                        .starts_with("key-label")
+                        .then(|| (class.to_string(), x.text_content(&document)))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            labels(IsoKey::E12),
+            vec![("key-label key-dead".to_string(), "´".to_string())]
+        );
+        assert_eq!(
+            labels(IsoKey::C10),
+            vec![
+                ("key-label".to_string(), "ö".to_string()),
+                ("key-label".to_string(), "ø".to_string())
+            ]
+        );
+    }
+}
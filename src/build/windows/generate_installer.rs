@@ -0,0 +1,320 @@
+//! An Inno Setup script installing the keyboard DLLs built for each
+//! architecture and registering their layouts with Windows.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use language_tags::LanguageTag;
+use tracing::warn;
+
+use crate::{
+    build::BuildStep,
+    bundle::{KbdgenBundle, target::Windows},
+};
+
+use super::{
+    generate_kbd_source::file_version, generate_klc::generate_metadata, klc::file::KlcFileMetadata,
+};
+
+pub const INSTALLER_FILE: &str = "install.iss";
+const KEYBOARD_LAYOUTS_KEY: &str = r"SYSTEM\CurrentControlSet\Control\Keyboard Layouts";
+
+/// Checks run before installing: a layout may not take the registry key or
+/// the Layout Id of another keyboard. Its own key from an earlier version
+/// names the same DLL, so upgrades pass.
+const CODE_SECTION: &str = r"[Code]
+const
+  KeyboardLayouts = 'SYSTEM\CurrentControlSet\Control\Keyboard Layouts';
+
+function IsTaken(Klid, LayoutFile, LayoutId: String): Boolean;
+var
+  Names: TArrayOfString;
+  Value: String;
+  I: Integer;
+begin
+  Result := False;
+  if RegQueryStringValue(HKLM, KeyboardLayouts + '\' + Klid, 'Layout File', Value) then
+    Result := CompareText(Value, LayoutFile) <> 0;
+
+  if RegGetSubkeyNames(HKLM, KeyboardLayouts, Names) then
+    for I := 0 to GetArrayLength(Names) - 1 do
+      if (CompareText(Names[I], Klid) <> 0) and
+        RegQueryStringValue(HKLM, KeyboardLayouts + '\' + Names[I], 'Layout Id', Value) and
+        (CompareText(Value, LayoutId) = 0) then
+        Result := True;
+end;
+
+";
+
+/// The folders of the DLLs of each architecture, and where they are installed
+const FILES: &[(&str, &str, &str)] = &[
+    ("x86", "{sys}", "IsX86"),
+    ("x64", "{sys}", "IsX64"),
+    ("arm64", "{sys}", "IsARM64"),
+    // 32-bit programs on 64-bit Windows load their keyboards from SysWOW64
+    ("x86", "{syswow64}", "IsX64 or IsARM64"),
+];
+
+pub struct GenerateWindowsInstaller;
+
+#[async_trait(?Send)]
+impl BuildStep for GenerateWindowsInstaller {
+    async fn build(&self, bundle: &KbdgenBundle, output_path: &Path) -> Result<()> {
+        let Some(target) = &bundle.targets.windows else {
+            warn!("Skipping the installer, as the bundle has no targets/windows.yaml");
+            return Ok(());
+        };
+
+        let mut layouts = bundle
+            .layouts
+            .iter()
+            .filter_map(|(language_tag, layout)| {
+                let windows = layout.windows.as_ref()?;
+                Some((
+                    language_tag,
+                    generate_metadata(bundle, language_tag, layout, windows),
+                ))
+            })
+            .collect::<Vec<_>>();
+        // Ids are assigned in this order when they collide
+        layouts.sort_by_key(|(language_tag, _)| language_tag.to_string());
+
+        std::fs::write(
+            output_path.join(INSTALLER_FILE),
+            installer_script(bundle, target, &layouts),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// 32-bit FNV-1a, which unlike `std`'s hasher is the same in every build
+fn stable_hash(value: &str) -> u32 {
+    value.bytes().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+/// The registry key name of each layout and its `Layout Id`. Both are derived
+/// from the installer's uuid and the language tag, so they stay the same
+/// between releases and upgrades replace the layout instead of adding one.
+/// When two layouts of the bundle hash to the same id, the later one takes
+/// the next free one.
+///
+/// The Layout Ids of the layouts shipped with Windows are below 0x100, and
+/// those of other installed keyboards are checked by the installer itself.
+fn layout_ids(target: &Windows, layouts: &[(&LanguageTag, u32)]) -> Vec<(String, String)> {
+    let mut ids: Vec<(String, String)> = vec![];
+
+    for (language_tag, locale_id) in layouts {
+        let hash = stable_hash(&format!("{}:{}", target.uuid.to_lowercase(), language_tag));
+
+        let klid = (0..0x1000)
+            .map(|x| format!("a{:03x}{:04x}", (hash + x) & 0xfff, locale_id & 0xffff))
+            .find(|klid| !ids.iter().any(|x| x.0 == *klid))
+            .expect("A locale has room for 4096 layouts");
+        let layout_id = (0..0xf00)
+            .map(|x| format!("{:04x}", 0x100 + ((hash >> 12) + x) % 0xf00))
+            .find(|layout_id| !ids.iter().any(|x| x.1 == *layout_id))
+            .expect("There is room for 3840 layouts");
+
+        ids.push((klid, layout_id));
+    }
+
+    ids
+}
+
+/// Escapes the braces Inno would read as the start of a constant
+fn iss_value(value: &str) -> String {
+    value.replace('{', "{{")
+}
+
+/// Quotes a parameter value, in which quotes are doubled
+fn iss_string(value: &str) -> String {
+    format!("\"{}\"", iss_value(value).replace('"', "\"\""))
+}
+
+/// Quotes a Pascal string for the [Code] section, in which quotes are doubled
+fn pascal_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn file_name(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn installer_script(
+    bundle: &KbdgenBundle,
+    target: &Windows,
+    layouts: &[(&LanguageTag, KlcFileMetadata)],
+) -> String {
+    let version = file_version(&target.version)
+        .map(|x| x.to_string())
+        .join(".");
+
+    let mut output = String::from("; Generated by kbdgen\n\n[Setup]\n");
+    let _ = write!(
+        output,
+        "AppId={{{{{uuid}}}\n\
+         AppName={name}\n\
+         AppVersion={version}\n\
+         AppPublisher={publisher}\n\
+         AppPublisherURL={url}\n\
+         AppCopyright={copyright}\n\
+         VersionInfoVersion={numeric_version}\n\
+         DefaultDirName={{autopf}}\\{name}\n\
+         DisableDirPage=yes\n\
+         DisableProgramGroupPage=yes\n\
+         OutputBaseFilename={output_name}-{output_version}\n\
+         ArchitecturesAllowed=x86 x64 arm64\n\
+         ArchitecturesInstallIn64BitMode=x64 arm64\n\
+         PrivilegesRequired=admin\n\
+         AlwaysRestart=yes\n\n",
+        uuid = target.uuid.to_uppercase(),
+        name = iss_value(&target.app_name),
+        version = iss_value(&target.version),
+        publisher = iss_value(&bundle.project.organisation),
+        url = iss_value(&target.url),
+        copyright = iss_value(&bundle.project.copyright),
+        numeric_version = version,
+        output_name = file_name(&target.app_name),
+        output_version = file_name(&format!("{}-{}", target.version, target.build)),
+    );
+
+    output.push_str("[Files]\n");
+    for (_, metadata) in layouts {
+        for (arch, dest_dir, check) in FILES {
+            let _ = writeln!(
+                output,
+                "Source: \"{arch}\\{name}.dll\"; DestDir: \"{dest_dir}\"; Check: {check}; \
+                 Flags: ignoreversion restartreplace uninsrestartdelete",
+                name = metadata.keyboard_name,
+            );
+        }
+    }
+
+    let ids = layout_ids(
+        target,
+        &layouts
+            .iter()
+            .map(|(language_tag, metadata)| (*language_tag, metadata.locale_id))
+            .collect::<Vec<_>>(),
+    );
+
+    // Only the LCID, in the low word of the key name, ties a layout to its
+    // language here. The locale name matters for locales without an LCID of
+    // their own, which share the transient LCID 0x2000. Windows gives those
+    // a transient LCID per user when the language is added to the user's
+    // language list, so they can't be registered for the whole machine, and
+    // users add the keyboard to the language in Settings instead.
+    output.push_str("\n[Registry]\n");
+    for ((_, metadata), (klid, layout_id)) in layouts.iter().zip(&ids) {
+        let key = format!("{}\\{}", KEYBOARD_LAYOUTS_KEY, klid);
+
+        let _ = writeln!(
+            output,
+            "; {}, {} (LCID {:04x})",
+            metadata.description, metadata.locale_name, metadata.locale_id
+        );
+        let _ = writeln!(
+            output,
+            "Root: HKLM; Subkey: {}; Flags: uninsdeletekey",
+            iss_string(&key)
+        );
+
+        for (name, value) in [
+            ("Layout File", format!("{}.dll", metadata.keyboard_name)),
+            ("Layout Text", metadata.description.clone()),
+            (
+                "Layout Display Name",
+                format!(
+                    "@%SystemRoot%\\system32\\{}.dll,-1000",
+                    metadata.keyboard_name
+                ),
+            ),
+            ("Layout Id", layout_id.clone()),
+        ] {
+            let _ = writeln!(
+                output,
+                "Root: HKLM; Subkey: {}; ValueType: string; ValueName: {}; ValueData: {}",
+                iss_string(&key),
+                iss_string(name),
+                iss_string(&value)
+            );
+        }
+        output.push('\n');
+    }
+
+    output.push_str(CODE_SECTION);
+    output.push_str(
+        "function InitializeSetup(): Boolean;\n\
+         begin\n\
+         \x20 Result := True;\n",
+    );
+    for ((_, metadata), (klid, layout_id)) in layouts.iter().zip(&ids) {
+        let _ = write!(
+            output,
+            "  if Result and IsTaken({}, {}, {}) then begin\n\
+             \x20   MsgBox({}, mbError, MB_OK);\n\
+             \x20   Result := False;\n\
+             \x20 end;\n",
+            pascal_string(klid),
+            pascal_string(&format!("{}.dll", metadata.keyboard_name)),
+            pascal_string(layout_id),
+            pascal_string(&format!(
+                "The keyboard layout {} would replace another keyboard on this computer.",
+                metadata.description
+            )),
+        );
+    }
+    output.push_str("end;\n");
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_ids() {
+        let target = Windows {
+            app_name: "Keyboards".to_owned(),
+            version: "1.0.0".to_owned(),
+            url: String::new(),
+            uuid: "3b4c3a5e-8c7d-4f1a-9e2b-6d5f0a1c2b3d".to_owned(),
+            build: "1".to_owned(),
+        };
+        let sje: LanguageTag = "sje".parse().unwrap();
+        let se: LanguageTag = "se".parse().unwrap();
+
+        let ids = layout_ids(&target, &[(&sje, 0x2000), (&se, 0x2000)]);
+        let (klid, layout_id) = &ids[0];
+        assert_eq!((klid.len(), layout_id.len()), (8, 4));
+        assert!(klid.starts_with('a') && klid.ends_with("2000"));
+        assert_ne!(ids[1].0, *klid);
+        assert_eq!(layout_ids(&target, &[(&sje, 0x2000)])[0], ids[0]);
+
+        // Layouts hashing to the same ids take the next free ones
+        let ids = layout_ids(&target, &[(&sje, 0x2000), (&sje, 0x2000)]);
+        assert_eq!(ids[0], (klid.clone(), layout_id.clone()));
+        let hash = u32::from_str_radix(&klid[1..4], 16).unwrap();
+        assert_eq!(ids[1].0, format!("a{:03x}2000", (hash + 1) & 0xfff));
+        let id = u32::from_str_radix(layout_id, 16).unwrap();
+        assert_eq!(
+            ids[1].1,
+            format!("{:04x}", 0x100 + (id - 0x100 + 1) % 0xf00)
+        );
+    }
+}
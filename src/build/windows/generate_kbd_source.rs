@@ -0,0 +1,868 @@
+//! The C sources of a keyboard layout DLL, as `kbdutool` writes them from a
+//! .klc file. They build with any compiler targeting Windows, given `kbd.h`.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use tracing::debug;
+
+use crate::{build::BuildStep, bundle::KbdgenBundle};
+
+use super::{
+    generate_klc::{klc_file, lower_keyboard},
+    klc::{
+        dead_key::DeadKeyTable,
+        file::{KEY_NAMES, KEY_NAMES_EXT, KlcFile},
+        key::KlcKey,
+        layout::KlcLayoutRow,
+    },
+    layer_set::SG_CAP,
+};
+
+const DEFAULT_VERSION: [u16; 4] = [1, 0, 0, 0];
+
+/// Keys sent with an `E0` prefix, with whether they are extended keys
+const E0_KEYS: &[(u8, bool, &str)] = &[
+    (0x10, true, "Speedracer: Previous Track"),
+    (0x19, true, "Speedracer: Next Track"),
+    (0x1d, true, "RControl"),
+    (0x20, true, "Speedracer: Volume Mute"),
+    (0x21, true, "Speedracer: Launch App 2"),
+    (0x22, true, "Speedracer: Media Play/Pause"),
+    (0x24, true, "Speedracer: Media Stop"),
+    (0x2e, true, "Speedracer: Volume Down"),
+    (0x30, true, "Speedracer: Volume Up"),
+    (0x32, true, "Speedracer: Browser Home"),
+    (0x35, true, "Numpad Divide"),
+    (0x37, true, "Snapshot"),
+    (0x38, true, "RMenu"),
+    (0x47, true, "Home"),
+    (0x48, true, "Up"),
+    (0x49, true, "Prior"),
+    (0x4b, true, "Left"),
+    (0x4d, true, "Right"),
+    (0x4f, true, "End"),
+    (0x50, true, "Down"),
+    (0x51, true, "Next"),
+    (0x52, true, "Insert"),
+    (0x53, true, "Delete"),
+    (0x5b, true, "Left Win"),
+    (0x5c, true, "Right Win"),
+    (0x5d, true, "Application"),
+    (0x5f, true, "Speedracer: Sleep"),
+    (0x65, true, "Speedracer: Browser Search"),
+    (0x66, true, "Speedracer: Browser Favorites"),
+    (0x67, true, "Speedracer: Browser Refresh"),
+    (0x68, true, "Speedracer: Browser Stop"),
+    (0x69, true, "Speedracer: Browser Forward"),
+    (0x6a, true, "Speedracer: Browser Back"),
+    (0x6b, true, "Speedracer: Launch App 1"),
+    (0x6c, true, "Speedracer: Launch Mail"),
+    (0x6d, true, "Speedracer: Launch Media Selector"),
+    (0x1c, false, "Numpad Enter"),
+    (0x46, false, "Break (Ctrl + Pause)"),
+];
+
+/// The keys every layout types the same, with their characters by shift state
+const FIXED_KEYS_2: &[(&str, [&str; 2])] = &[
+    ("VK_TAB", ["'\\t'", "'\\t'"]),
+    ("VK_ADD", ["'+'", "'+'"]),
+    ("VK_DIVIDE", ["'/'", "'/'"]),
+    ("VK_MULTIPLY", ["'*'", "'*'"]),
+    ("VK_SUBTRACT", ["'-'", "'-'"]),
+];
+
+const FIXED_KEYS_3: &[(&str, [&str; 3])] = &[
+    ("VK_BACK", ["'\\b'", "'\\b'", "0x007f"]),
+    ("VK_ESCAPE", ["0x001b", "0x001b", "0x001b"]),
+    ("VK_RETURN", ["'\\r'", "'\\r'", "'\\n'"]),
+    ("VK_CANCEL", ["0x0003", "0x0003", "0x0003"]),
+];
+
+/// The columns of the layout rows, as keys pressed
+/// Wide enough for `CAPLOK | CAPLOKALTGR`
+const ATTRIBUTES_WIDTH: usize = 20;
+
+const SHIFT_STATES: [&str; 5] = ["", "Shift", "Ctrl", "Ctl+Alt", "S+Ctl+Alt"];
+
+pub struct GenerateKbdSource {}
+
+#[async_trait(?Send)]
+impl BuildStep for GenerateKbdSource {
+    async fn build(&self, bundle: &KbdgenBundle, output_path: &Path) -> Result<()> {
+        let version = bundle
+            .targets
+            .windows
+            .as_ref()
+            .map(|x| file_version(&x.version))
+            .unwrap_or(DEFAULT_VERSION);
+
+        for (language_tag, layout) in &bundle.layouts {
+            if let Some(windows_target) = &layout.windows {
+                debug!("Generating keyboard DLL sources for {}", language_tag);
+                let keyboard = lower_keyboard(bundle, language_tag, windows_target)?;
+                let klc_file = klc_file(
+                    bundle,
+                    language_tag,
+                    layout,
+                    windows_target,
+                    &keyboard,
+                    // Reported by GenerateKlc
+                    &mut vec![],
+                );
+
+                let name = &klc_file.metadata.keyboard_name;
+                let source_path = output_path.join(name);
+                std::fs::create_dir_all(&source_path)?;
+
+                std::fs::write(source_path.join(format!("{name}.h")), header(&klc_file))?;
+                std::fs::write(source_path.join(format!("{name}.c")), source(&klc_file)?)?;
+                std::fs::write(source_path.join(format!("{name}.def")), def(&klc_file))?;
+                std::fs::write(
+                    source_path.join(format!("{name}.rc")),
+                    resources(&klc_file, version),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `1.2.3` becomes `[1, 2, 3, 0]`, anything unparseable the default version
+pub(crate) fn file_version(version: &str) -> [u16; 4] {
+    let parts = version
+        .split('.')
+        .map(|x| x.parse::<u16>())
+        .collect::<Result<Vec<_>, _>>();
+
+    match parts {
+        Ok(parts) if !parts.is_empty() && parts.len() <= 4 => {
+            let mut output = [0; 4];
+            output[..parts.len()].copy_from_slice(&parts);
+            output
+        }
+        _ => DEFAULT_VERSION,
+    }
+}
+
+fn module_header(title: &str, klc_file: &KlcFile) -> String {
+    format!(
+        "/****************************** Module Header ******************************\\\n\
+         * Module Name: {}\n\
+         *\n\
+         * {}\n\
+         *\n\
+         * {}\n\
+         *\n\
+         * Generated by kbdgen\n\
+         \\***************************************************************************/\n\n",
+        title, klc_file.metadata.description, klc_file.metadata.copyright
+    )
+}
+
+fn section(title: &str) -> String {
+    format!(
+        "/***************************************************************************\\\n\
+         * {}\n\
+         \\***************************************************************************/\n\n",
+        title
+    )
+}
+
+/// `Q` is `'Q'` in C, the others have a `VK_` constant
+fn c_virtual_key(virtual_key: &str) -> String {
+    if virtual_key.chars().count() == 1 {
+        format!("'{}'", virtual_key)
+    } else {
+        format!("VK_{}", virtual_key)
+    }
+}
+
+fn c_character(utf16: u16) -> String {
+    match char::from_u32(utf16 as u32) {
+        Some(c) if (c.is_ascii_graphic() || c == ' ') && c != '\'' && c != '\\' => {
+            format!("'{}'", c)
+        }
+        _ => format!("0x{:04x}", utf16),
+    }
+}
+
+/// Like `c_character`, with character literals wide
+fn c_wide_character(utf16: u16) -> String {
+    let character = c_character(utf16);
+    if character.starts_with('\'') {
+        format!("L{}", character)
+    } else {
+        character
+    }
+}
+
+fn key_utf16(c: char) -> u16 {
+    // .klc rows only hold characters of the Basic Multilingual Plane
+    c.encode_utf16(&mut [0; 2])[0]
+}
+
+fn key_cell(key: &KlcKey) -> String {
+    match key {
+        KlcKey::Character(c) => c_character(key_utf16(*c)),
+        KlcKey::DeadKey(_) => "WCH_DEAD".to_owned(),
+        KlcKey::Ligature => "WCH_LGTR".to_owned(),
+        KlcKey::None | KlcKey::Skip => "WCH_NONE".to_owned(),
+    }
+}
+
+fn caps_attributes(caps_mode: &str) -> String {
+    if caps_mode == SG_CAP {
+        return "SGCAPS".to_owned();
+    }
+
+    let caps_mode = caps_mode.parse::<u8>().unwrap_or(0);
+    let mut attributes = vec![];
+    if caps_mode & 1 != 0 {
+        attributes.push("CAPLOK");
+    }
+    if caps_mode & 4 != 0 {
+        attributes.push("CAPLOKALTGR");
+    }
+
+    if attributes.is_empty() {
+        "0".to_owned()
+    } else {
+        attributes.join(" | ")
+    }
+}
+
+fn table_row(virtual_key: &str, attributes: &str, cells: &[String]) -> String {
+    let mut row = format!(
+        "  {{{:<13},{:<width$}",
+        virtual_key,
+        attributes,
+        width = ATTRIBUTES_WIDTH
+    );
+    for cell in cells {
+        row.push_str(&format!(",{:<9}", cell));
+    }
+    row.push_str("},\n");
+    row
+}
+
+/// The zeroed row ending a table
+fn table_end(width: usize) -> String {
+    table_row("0", "0", &vec!["0".to_owned(); width]).replace("},", "}")
+}
+
+fn row_keys(row: &KlcLayoutRow) -> [&KlcKey; 5] {
+    [
+        &row.default_key,
+        &row.shift_key,
+        &row.ctrl_key,
+        &row.alt_key,
+        &row.alt_and_shift_key,
+    ]
+}
+
+/// The rows of `aVkToWch5`. Rows of keys with dead keys are followed by one
+/// holding the dead key characters, and SGCap rows by one holding what the
+/// key types with caps lock, both with the virtual key `0xff`. Windows looks
+/// for either in the row right after the key, so an SGCap key can't have
+/// dead keys.
+fn layout_rows(rows: &[KlcLayoutRow]) -> Result<String> {
+    let mut output = String::new();
+    let mut sg_cap_key = None;
+
+    for row in rows {
+        let keys = row_keys(row);
+
+        let (virtual_key, attributes) = if row.scancode == "-1" {
+            ("0xff".to_owned(), "0".to_owned())
+        } else {
+            (
+                c_virtual_key(&row.virtual_key),
+                caps_attributes(&row.caps_mode),
+            )
+        };
+
+        let cells = keys.iter().map(|x| key_cell(x)).collect::<Vec<_>>();
+        output.push_str(&table_row(&virtual_key, &attributes, &cells));
+
+        if keys.iter().any(|x| matches!(x, KlcKey::DeadKey(_))) {
+            let virtual_key = match (row.scancode.as_str(), sg_cap_key) {
+                ("-1", Some(virtual_key)) => Some(virtual_key),
+                _ if row.caps_mode == SG_CAP => Some(row.virtual_key.as_str()),
+                _ => None,
+            };
+            if let Some(virtual_key) = virtual_key {
+                bail!(
+                    "The key {} has dead keys and an SGCap row, which Windows can't combine",
+                    virtual_key
+                );
+            }
+
+            let cells = keys
+                .iter()
+                .map(|x| match x {
+                    KlcKey::DeadKey(c) => c_character(key_utf16(*c)),
+                    _ => "WCH_NONE".to_owned(),
+                })
+                .collect::<Vec<_>>();
+            output.push_str(&table_row("0xff", "0", &cells));
+        }
+
+        sg_cap_key = (row.caps_mode == SG_CAP).then_some(row.virtual_key.as_str());
+    }
+
+    Ok(output)
+}
+
+fn header(klc_file: &KlcFile) -> String {
+    let mut output = module_header(&format!("{}.h", klc_file.metadata.keyboard_name), klc_file);
+
+    output.push_str("#define KBD_TYPE 4\n\n#include \"kbd.h\"\n\n");
+    output.push_str(&section(
+        "The virtual keys of the scancodes, redefined from the US keyboard of kbd.h",
+    ));
+
+    for row in &klc_file.layout.rows {
+        let Ok(scancode) = u8::from_str_radix(&row.scancode, 16) else {
+            continue;
+        };
+
+        // The numpad keys keep their definitions, which depend on num lock
+        if row.virtual_key == "DECIMAL" {
+            continue;
+        }
+
+        let virtual_key = if row.virtual_key.chars().count() == 1 {
+            format!("'{}'", row.virtual_key)
+        } else {
+            format!("_EQ({})", row.virtual_key)
+        };
+        let _ = writeln!(
+            output,
+            "#undef  T{:02X}\n#define T{:02X} {}",
+            scancode, scancode, virtual_key
+        );
+    }
+
+    output
+}
+
+fn source(klc_file: &KlcFile) -> Result<String> {
+    let name = &klc_file.metadata.keyboard_name;
+    let mut output = module_header(&format!("{}.c", name), klc_file);
+
+    let _ = write!(
+        output,
+        "#include <windows.h>\n\
+         #include \"kbd.h\"\n\
+         #include \"{}.h\"\n\n\
+         #if defined(_M_IA64)\n\
+         #pragma section(\".data\")\n\
+         #define ALLOC_SECTION_LDATA __declspec(allocate(\".data\"))\n\
+         #else\n\
+         #pragma data_seg(\".data\")\n\
+         #define ALLOC_SECTION_LDATA\n\
+         #endif\n\n",
+        name
+    );
+
+    output.push_str(&scancode_tables());
+    output.push_str(&modifiers());
+    output.push_str(&character_tables(klc_file)?);
+    output.push_str(&key_names());
+
+    let dead_key_tables = &klc_file.dead_keys.tables;
+    output.push_str(&dead_key_names(dead_key_tables));
+    output.push_str(&dead_keys(dead_key_tables));
+
+    let ligature_width = klc_file.ligature.rows.iter().map(|x| x.utf16s.len()).max();
+    if let Some(width) = ligature_width {
+        output.push_str(&ligatures(klc_file, width));
+    }
+
+    output.push_str(&kbd_tables(ligature_width, !dead_key_tables.is_empty()));
+
+    Ok(output)
+}
+
+fn scancode_tables() -> String {
+    let mut output = section("ausVK[] - Virtual Scan Code to Virtual Key conversion table");
+
+    output.push_str("static ALLOC_SECTION_LDATA USHORT ausVK[] = {\n");
+    let scancodes = (0x00..=0x7e)
+        .map(|x| format!("T{:02X}", x))
+        .collect::<Vec<_>>();
+    let lines = scancodes
+        .chunks(8)
+        .map(|x| format!("    {}", x.join(", ")))
+        .collect::<Vec<_>>();
+    output.push_str(&lines.join(",\n"));
+    output.push_str("\n};\n\n");
+
+    output.push_str("static ALLOC_SECTION_LDATA VSC_VK aE0VscToVk[] = {\n");
+    for (scancode, extended, comment) in E0_KEYS {
+        let value = if *extended {
+            format!("X{:02X} | KBDEXT", scancode)
+        } else {
+            format!("X{:02X}", scancode)
+        };
+        let _ = writeln!(
+            output,
+            "        {{ 0x{:02X}, {:<22}}},  // {}",
+            scancode, value, comment
+        );
+    }
+    let _ = writeln!(output, "        {{ 0   , {:<22}}}\n}};\n", "0");
+
+    output.push_str(
+        "static ALLOC_SECTION_LDATA VSC_VK aE1VscToVk[] = {\n\
+         \x20       { 0x1D, Y1D                   },  // Pause\n\
+         \x20       { 0   ,   0                   }\n\
+         };\n\n",
+    );
+
+    output
+}
+
+fn modifiers() -> String {
+    let mut output = section("aVkToBits[] - map Virtual Keys to Modifier Bits");
+    output.push_str(
+        "static ALLOC_SECTION_LDATA VK_TO_BIT aVkToBits[] = {\n\
+         \x20   { VK_SHIFT    ,   KBDSHIFT     },\n\
+         \x20   { VK_CONTROL  ,   KBDCTRL      },\n\
+         \x20   { VK_MENU     ,   KBDALT       },\n\
+         \x20   { 0           ,   0            }\n\
+         };\n\n",
+    );
+
+    output.push_str(&section(
+        "CharModifiers - map character modifier bits to modification number",
+    ));
+    output.push_str(
+        "static ALLOC_SECTION_LDATA MODIFIERS CharModifiers = {\n\
+         \x20   &aVkToBits[0],\n\
+         \x20   7,\n\
+         \x20   {\n\
+         \x20   //  Modification# //  Keys Pressed\n\
+         \x20   //  ============= // =============\n\
+         \x20       0,            //\n\
+         \x20       1,            // Shift\n\
+         \x20       2,            // Control\n\
+         \x20       SHFT_INVALID, // Shift + Control\n\
+         \x20       SHFT_INVALID, // Menu\n\
+         \x20       SHFT_INVALID, // Shift + Menu\n\
+         \x20       3,            // Control + Menu\n\
+         \x20       4             // Shift + Control + Menu\n\
+         \x20   }\n\
+         };\n\n",
+    );
+
+    output
+}
+
+fn character_tables(klc_file: &KlcFile) -> Result<String> {
+    let mut output = section("aVkToWch<n>[] - Virtual Key to WCHAR translation for n shift states");
+
+    output.push_str("static ALLOC_SECTION_LDATA VK_TO_WCHARS5 aVkToWch5[] = {\n");
+    let indent = " ".repeat(ATTRIBUTES_WIDTH + 14);
+    let mut columns = format!("//{}", indent);
+    for shift_state in SHIFT_STATES {
+        let _ = write!(columns, "|{:^9}", shift_state);
+    }
+    let _ = writeln!(output, "{}|", columns);
+    let _ = writeln!(output, "//{}{}|", indent, "|=========".repeat(5));
+    output.push_str(&layout_rows(&klc_file.layout.rows)?);
+    output.push_str(&table_end(5));
+    output.push_str("};\n\n");
+
+    output.push_str("static ALLOC_SECTION_LDATA VK_TO_WCHARS3 aVkToWch3[] = {\n");
+    for (virtual_key, cells) in FIXED_KEYS_3 {
+        output.push_str(&table_row(virtual_key, "0", &cells.map(str::to_owned)));
+    }
+    output.push_str(&table_end(3));
+    output.push_str("};\n\n");
+
+    output.push_str("static ALLOC_SECTION_LDATA VK_TO_WCHARS2 aVkToWch2[] = {\n");
+    for (virtual_key, cells) in FIXED_KEYS_2 {
+        output.push_str(&table_row(virtual_key, "0", &cells.map(str::to_owned)));
+    }
+    output.push_str(&table_end(2));
+    output.push_str("};\n\n");
+
+    output.push_str("static ALLOC_SECTION_LDATA VK_TO_WCHARS1 aVkToWch1[] = {\n");
+    for digit in 0..10 {
+        output.push_str(&table_row(
+            &format!("VK_NUMPAD{}", digit),
+            "0",
+            &[format!("'{}'", digit)],
+        ));
+    }
+    output.push_str(&table_end(1));
+    output.push_str("};\n\n");
+
+    output.push_str(
+        "static ALLOC_SECTION_LDATA VK_TO_WCHAR_TABLE aVkToWcharTable[] = {\n\
+         \x20   {  (PVK_TO_WCHARS1)aVkToWch5, 5, sizeof(aVkToWch5[0]) },\n\
+         \x20   {  (PVK_TO_WCHARS1)aVkToWch3, 3, sizeof(aVkToWch3[0]) },\n\
+         \x20   {  (PVK_TO_WCHARS1)aVkToWch2, 2, sizeof(aVkToWch2[0]) },\n\
+         \x20   {  (PVK_TO_WCHARS1)aVkToWch1, 1, sizeof(aVkToWch1[0]) },  // Numpad must be last\n\
+         \x20   {                       NULL, 0, 0                    },\n\
+         };\n\n",
+    );
+
+    Ok(output)
+}
+
+fn key_names() -> String {
+    let mut output = section("aKeyNames[], aKeyNamesExt[] - Virtual Scancode to Key Name tables");
+
+    for (table, names) in [("aKeyNames", KEY_NAMES), ("aKeyNamesExt", KEY_NAMES_EXT)] {
+        let _ = writeln!(
+            output,
+            "static ALLOC_SECTION_LDATA VSC_LPWSTR {}[] = {{",
+            table
+        );
+        for (scancode, name) in names {
+            let _ = writeln!(output, "    0x{:02x},    L\"{}\",", scancode, name);
+        }
+        output.push_str("    0   ,    NULL\n};\n\n");
+    }
+
+    output
+}
+
+/// Windows names a dead key by a string starting with its character. Bundles
+/// don't name them, so the character names itself.
+fn dead_key_names(tables: &[DeadKeyTable]) -> String {
+    if tables.is_empty() {
+        return String::new();
+    }
+
+    let mut output =
+        String::from("static ALLOC_SECTION_LDATA DEADKEY_LPWSTR aKeyNamesDead[] = {\n");
+    for table in tables {
+        let utf16 = key_utf16(table.dead_key);
+        let _ = writeln!(output, "    L\"\\x{:04x}\"\tL\"\\x{:04x}\",", utf16, utf16);
+    }
+    output.push_str("    NULL\n};\n\n");
+
+    output
+}
+
+fn dead_keys(tables: &[DeadKeyTable]) -> String {
+    if tables.is_empty() {
+        return String::new();
+    }
+
+    let mut output = String::from("static ALLOC_SECTION_LDATA DEADKEY aDeadKey[] = {\n");
+    for table in tables {
+        let dead_key = key_utf16(table.dead_key);
+        for transform in &table.transforms {
+            let flags = if transform.chained {
+                "DKF_DEAD"
+            } else {
+                "0x0000"
+            };
+            let _ = writeln!(
+                output,
+                "    DEADTRANS( {:<7}, 0x{:04x} , 0x{:04x} , {}),",
+                c_wide_character(transform.from),
+                dead_key,
+                transform.to,
+                flags
+            );
+        }
+        output.push('\n');
+    }
+    output.push_str("    0, 0\n};\n\n");
+
+    output
+}
+
+fn ligatures(klc_file: &KlcFile, width: usize) -> String {
+    let mut output = format!(
+        "static ALLOC_SECTION_LDATA LIGATURE{} aLigature[] = {{\n",
+        width
+    );
+
+    for row in &klc_file.ligature.rows {
+        let mut cells = row
+            .utf16s
+            .iter()
+            .map(|x| c_character(*x))
+            .collect::<Vec<_>>();
+        cells.resize(width, "WCH_NONE".to_owned());
+        output.push_str(&table_row(
+            &c_virtual_key(&row.virtual_key),
+            &row.shift_state,
+            &cells,
+        ));
+    }
+    output.push_str(&table_end(width));
+    output.push_str("};\n\n");
+
+    output
+}
+
+fn kbd_tables(ligature_width: Option<usize>, has_dead_keys: bool) -> String {
+    let (dead_keys, dead_key_names) = if has_dead_keys {
+        ("aDeadKey", "aKeyNamesDead")
+    } else {
+        ("NULL", "NULL")
+    };
+
+    let ligatures = match ligature_width {
+        Some(width) => format!(
+            "{},\n    sizeof(aLigature[0]),\n    (PLIGATURE1)aLigature",
+            width
+        ),
+        None => "0,\n    0,\n    NULL".to_owned(),
+    };
+
+    format!(
+        "static ALLOC_SECTION_LDATA KBDTABLES KbdTables = {{\n\
+         \x20   /*\n\
+         \x20    * Modifier keys\n\
+         \x20    */\n\
+         \x20   &CharModifiers,\n\n\
+         \x20   /*\n\
+         \x20    * Characters tables\n\
+         \x20    */\n\
+         \x20   aVkToWcharTable,\n\n\
+         \x20   /*\n\
+         \x20    * Diacritics\n\
+         \x20    */\n\
+         \x20   {},\n\n\
+         \x20   /*\n\
+         \x20    * Names of Keys\n\
+         \x20    */\n\
+         \x20   aKeyNames,\n\
+         \x20   aKeyNamesExt,\n\
+         \x20   {},\n\n\
+         \x20   /*\n\
+         \x20    * Scan codes to Virtual Keys\n\
+         \x20    */\n\
+         \x20   ausVK,\n\
+         \x20   sizeof(ausVK) / sizeof(ausVK[0]),\n\
+         \x20   aE0VscToVk,\n\
+         \x20   aE1VscToVk,\n\n\
+         \x20   /*\n\
+         \x20    * Locale-specific special processing\n\
+         \x20    */\n\
+         \x20   MAKELONG(KLLF_ALTGR, KBD_VERSION),\n\n\
+         \x20   /*\n\
+         \x20    * Ligatures\n\
+         \x20    */\n\
+         \x20   {},\n\n\
+         \x20   /*\n\
+         \x20    * Type and subtype, unused\n\
+         \x20    */\n\
+         \x20   0,\n\
+         \x20   0\n\
+         }};\n\n\
+         PKBDTABLES KbdLayerDescriptor(VOID)\n\
+         {{\n\
+         \x20   return &KbdTables;\n\
+         }}\n",
+        dead_keys, dead_key_names, ligatures
+    )
+}
+
+fn def(klc_file: &KlcFile) -> String {
+    format!(
+        "LIBRARY {}\n\nEXPORTS\n    KbdLayerDescriptor @1\n",
+        klc_file.metadata.keyboard_name.to_uppercase()
+    )
+}
+
+/// Quotes are doubled in resource script strings
+fn rc_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+fn resources(klc_file: &KlcFile, version: [u16; 4]) -> String {
+    let metadata = &klc_file.metadata;
+    let commas = version.map(|x| x.to_string()).join(",");
+    let dotted = version.map(|x| x.to_string()).join(".");
+    let language = metadata.locale_id & 0x3ff;
+    let sublanguage = (metadata.locale_id >> 10) & 0x3f;
+
+    let mut output = String::from("#pragma code_page(65001)\n\n#include \"winver.h\"\n\n");
+
+    let _ = write!(
+        output,
+        "1 VERSIONINFO\n\
+         \x20FILEVERSION       {commas}\n\
+         \x20PRODUCTVERSION    {commas}\n\
+         \x20FILEFLAGSMASK 0x3fL\n\
+         \x20FILEFLAGS 0x0L\n\
+         \x20FILEOS 0x40004L\n\
+         \x20FILETYPE VFT_DLL\n\
+         \x20FILESUBTYPE VFT2_DRV_KEYBOARD\n\
+         BEGIN\n\
+         \x20   BLOCK \"StringFileInfo\"\n\
+         \x20   BEGIN\n\
+         \x20       BLOCK \"000004B0\"\n\
+         \x20       BEGIN\n\
+         \x20           VALUE \"CompanyName\",      {company}\n\
+         \x20           VALUE \"FileDescription\",  {description}\n\
+         \x20           VALUE \"FileVersion\",      \"{dotted}\"\n\
+         \x20           VALUE \"InternalName\",     \"{name}\"\n\
+         \x20           VALUE \"LegalCopyright\",   {copyright}\n\
+         \x20           VALUE \"OriginalFilename\", \"{name}.dll\"\n\
+         \x20           VALUE \"ProductName\",      {description}\n\
+         \x20           VALUE \"ProductVersion\",   \"{dotted}\"\n\
+         \x20       END\n\
+         \x20   END\n\
+         \x20   BLOCK \"VarFileInfo\"\n\
+         \x20   BEGIN\n\
+         \x20       VALUE \"Translation\", 0x0000, 0x04B0\n\
+         \x20   END\n\
+         END\n\n",
+        company = rc_string(&metadata.company),
+        description = rc_string(&metadata.description),
+        copyright = rc_string(&metadata.copyright),
+        name = metadata.keyboard_name,
+    );
+
+    // The layout's display name, its language's name and its locale id
+    for (id, value) in [
+        (1000, rc_string(&metadata.description)),
+        (1100, rc_string(&metadata.autonym)),
+        (1200, format!("\"{:08x}\"", metadata.locale_id)),
+    ] {
+        let _ = write!(
+            output,
+            "STRINGTABLE DISCARDABLE\n\
+             LANGUAGE 0x{:x}, 0x{:x}\n\
+             BEGIN\n\
+             \x20   {}    {}\n\
+             END\n\n",
+            language, sublanguage, id, value
+        );
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(scancode: &str, virtual_key: &str, caps_mode: &str, keys: [KlcKey; 5]) -> KlcLayoutRow {
+        let [default_key, shift_key, ctrl_key, alt_key, alt_and_shift_key] = keys;
+        KlcLayoutRow {
+            scancode: scancode.to_owned(),
+            virtual_key: virtual_key.to_owned(),
+            caps_mode: caps_mode.to_owned(),
+            default_key,
+            shift_key,
+            ctrl_key,
+            alt_key,
+            alt_and_shift_key,
+        }
+    }
+
+    #[test]
+    fn test_layout_rows() {
+        let rows = [
+            row(
+                "1a",
+                "OEM_4",
+                SG_CAP,
+                [
+                    KlcKey::Character('å'),
+                    KlcKey::Character('Å'),
+                    KlcKey::None,
+                    KlcKey::Ligature,
+                    KlcKey::None,
+                ],
+            ),
+            row(
+                "-1",
+                "-1",
+                "0",
+                [
+                    KlcKey::Character('ŋ'),
+                    KlcKey::Character('Ŋ'),
+                    KlcKey::Skip,
+                    KlcKey::Skip,
+                    KlcKey::Skip,
+                ],
+            ),
+            row(
+                "0d",
+                "OEM_PLUS",
+                "5",
+                [
+                    KlcKey::DeadKey('´'),
+                    KlcKey::Character('\''),
+                    KlcKey::None,
+                    KlcKey::None,
+                    KlcKey::None,
+                ],
+            ),
+        ];
+
+        let lines = layout_rows(&rows)
+            .unwrap()
+            .lines()
+            .map(|x| x.split_whitespace().collect::<String>())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                "{VK_OEM_4,SGCAPS,0x00e5,0x00c5,WCH_NONE,WCH_LGTR,WCH_NONE},",
+                "{0xff,0,0x014b,0x014a,WCH_NONE,WCH_NONE,WCH_NONE},",
+                "{VK_OEM_PLUS,CAPLOK|CAPLOKALTGR,WCH_DEAD,0x0027,WCH_NONE,WCH_NONE,WCH_NONE},",
+                "{0xff,0,0x00b4,WCH_NONE,WCH_NONE,WCH_NONE,WCH_NONE},",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sg_cap_dead_keys() {
+        let mut rows = [
+            row(
+                "0d",
+                "OEM_PLUS",
+                SG_CAP,
+                [
+                    KlcKey::DeadKey('´'),
+                    KlcKey::Character('\''),
+                    KlcKey::None,
+                    KlcKey::None,
+                    KlcKey::None,
+                ],
+            ),
+            row(
+                "-1",
+                "-1",
+                "0",
+                [
+                    KlcKey::DeadKey('`'),
+                    KlcKey::Character('\''),
+                    KlcKey::Skip,
+                    KlcKey::Skip,
+                    KlcKey::Skip,
+                ],
+            ),
+        ];
+
+        // Dead keys on either the SGCap row or its caps lock row
+        assert!(layout_rows(&rows[..1]).is_err());
+        rows[0].default_key = KlcKey::Character('+');
+        assert!(layout_rows(&rows).is_err());
+        assert!(layout_rows(&rows[..1]).is_ok());
+    }
+
+    #[test]
+    fn test_file_version() {
+        assert_eq!(file_version("1.2.3"), [1, 2, 3, 0]);
+        assert_eq!(file_version("1.0.0-beta"), DEFAULT_VERSION);
+    }
+}
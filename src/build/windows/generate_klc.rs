@@ -1,10 +1,10 @@
 use std::path::Path;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use codecs::utf16::Utf16Ext;
 use language_tags::LanguageTag;
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
 use crate::{
     build::BuildStep,
@@ -12,7 +12,7 @@ use crate::{
         DEFAULT_DECIMAL, KbdgenBundle,
         layout::{Layout, WindowsTarget},
     },
-    util::split_keys,
+    model::Keyboard,
 };
 
 use super::{
@@ -24,6 +24,7 @@ use super::{
         keymap::MSKLC_KEYS,
         layout::{KlcLayer, KlcLayout, KlcLayoutRow},
         ligature::{KlcLigature, KlcLigatureRow},
+        parse,
     },
     layer_set::{SG_CAP, WindowsLayerSet, WindowsLayerSetKey, populate_layer_set},
 };
@@ -39,145 +40,32 @@ impl BuildStep for GenerateKlc {
             trace!("Checking if we need a klc file for {}", language_tag);
             if let Some(windows_target) = &layout.windows {
                 debug!("Generating klc for {}", language_tag);
-                let metadata = generate_metadata(bundle, language_tag, layout, windows_target);
-
-                let layers = &windows_target.primary.layers;
-
-                let mut klc_layout_rows = Vec::new();
-                let mut klc_ligature_rows = Vec::new();
-                let mut dead_key_characters = Vec::new();
-
-                let mut cursor = 0;
-                for (_iso_key, klc_key) in MSKLC_KEYS.iter() {
-                    let mut layer_set = WindowsLayerSet::default();
-
-                    // Layer set to determine caps_mode, special escapes, dead keys and null keys
-                    for (layer, key_map) in layers {
-                        let key_map: Vec<String> = split_keys(&key_map);
-
-                        tracing::debug!(
-                            "iso len: {}; keymap len: {}",
-                            MSKLC_KEYS.len(),
-                            key_map.len()
-                        );
-                        if MSKLC_KEYS.len() > key_map.len() {
-                            panic!(
-                                r#"Provided layer does not have enough keys, expected {} keys but got {}, in {}:{}:{}:{:?}: \n{:?}"#,
-                                MSKLC_KEYS.len(),
-                                key_map.len(),
-                                language_tag.to_string(),
-                                "Windows",
-                                "Primary",
-                                layer,
-                                key_map
-                            );
-                        }
-
-                        populate_layer_set(
-                            &mut layer_set,
-                            layer,
-                            key_map,
-                            cursor,
-                            windows_target.dead_keys.as_ref(),
-                        );
-                    }
-
-                    let caps_mode = layer_set.caps_mode();
-
-                    klc_layout_rows.push(KlcLayoutRow {
-                        scancode: klc_key.scancode.clone(),
-                        virtual_key: klc_key.virtual_key.clone(),
-                        caps_mode: caps_mode.clone(), // Generate extra key for SGCap here?
-                        default_key: convert_to_klc_key(
-                            layer_set.default,
-                            &klc_key.virtual_key,
-                            KlcLayer::Default,
-                            &mut klc_ligature_rows,
-                            &mut dead_key_characters,
-                        ),
-                        shift_key: convert_to_klc_key(
-                            layer_set.shift,
-                            &klc_key.virtual_key,
-                            KlcLayer::Shift,
-                            &mut klc_ligature_rows,
-                            &mut dead_key_characters,
-                        ),
-                        ctrl_key: convert_to_klc_key(
-                            layer_set.ctrl,
-                            &klc_key.virtual_key,
-                            KlcLayer::Ctrl,
-                            &mut klc_ligature_rows,
-                            &mut dead_key_characters,
-                        ),
-                        alt_key: convert_to_klc_key(
-                            layer_set.alt,
-                            &klc_key.virtual_key,
-                            KlcLayer::Alt,
-                            &mut klc_ligature_rows,
-                            &mut dead_key_characters,
-                        ),
-                        alt_and_shift_key: convert_to_klc_key(
-                            layer_set.alt_and_shift,
-                            &klc_key.virtual_key,
-                            KlcLayer::AltAndShift,
-                            &mut klc_ligature_rows,
-                            &mut dead_key_characters,
-                        ),
-                    });
-
-                    if caps_mode == SG_CAP {
-                        let caps_key = convert_to_klc_key(
-                            layer_set.caps,
-                            &klc_key.virtual_key,
-                            KlcLayer::Default,
-                            &mut klc_ligature_rows,
-                            &mut dead_key_characters,
-                        );
-
-                        let caps_shift_key = convert_to_klc_key(
-                            layer_set.caps_and_shift,
-                            &klc_key.virtual_key,
-                            KlcLayer::Shift,
-                            &mut klc_ligature_rows,
-                            &mut dead_key_characters,
-                        );
-
-                        klc_layout_rows.push(KlcLayoutRow {
-                            scancode: "-1".to_owned(),
-                            virtual_key: "-1".to_owned(),
-                            caps_mode: "0".to_owned(),
-                            default_key: caps_key,
-                            shift_key: caps_shift_key,
-                            ctrl_key: KlcKey::Skip,
-                            alt_key: KlcKey::Skip,
-                            alt_and_shift_key: KlcKey::Skip,
-                        });
-                    }
-
-                    cursor += 1;
+                let keyboard = lower_keyboard(bundle, language_tag, windows_target)?;
+
+                let mut lost = vec![];
+                let klc_file = klc_file(
+                    bundle,
+                    language_tag,
+                    layout,
+                    windows_target,
+                    &keyboard,
+                    &mut lost,
+                );
+                if !lost.is_empty() {
+                    warn!(
+                        "Layout {} has outputs Windows can't represent:\n- {}",
+                        language_tag,
+                        lost.join("\n- ")
+                    );
                 }
 
-                klc_layout_rows.push(space_layout_row());
-                klc_layout_rows.push(decimal_layout_row(&layout.decimal));
-
-                let klc_file = KlcFile {
-                    metadata,
-                    layout: KlcLayout {
-                        rows: klc_layout_rows,
-                    },
-                    ligature: KlcLigature {
-                        rows: klc_ligature_rows,
-                    },
-                    dead_keys: KlcDeadKeys {
-                        characters: dead_key_characters,
-                        transforms: &layout.transforms,
-                    },
-                };
-
                 let klc_bytes = klc_file.to_string().encode_utf16_le_bom();
                 let klc_path =
                     output_path.join(format!("{}.{}", klc_file.metadata.keyboard_name, KLC_EXT));
-                std::fs::write(klc_path, klc_bytes).unwrap();
+                std::fs::write(&klc_path, &klc_bytes).unwrap();
+
+                parse::parse(&klc_bytes)
+                    .with_context(|| format!("Verifying '{}'", klc_path.display()))?;
             }
         }
 
@@ -185,7 +73,143 @@ impl BuildStep for GenerateKlc {
     }
 }
 
-fn generate_metadata(
+pub(crate) fn lower_keyboard(
+    bundle: &KbdgenBundle,
+    language_tag: &LanguageTag,
+    target: &WindowsTarget,
+) -> Result<Keyboard> {
+    Ok(Keyboard::lower(
+        bundle,
+        language_tag,
+        "windows",
+        &target.primary.layers,
+        target.dead_keys.as_ref(),
+    )?)
+}
+
+/// Collects the rows of the .klc file of a layout. The C sources are
+/// generated from the same rows, so both describe the same keyboard. Outputs
+/// that can only be written with a fallback are described in `lost`.
+pub(crate) fn klc_file(
+    bundle: &KbdgenBundle,
+    language_tag: &LanguageTag,
+    layout: &Layout,
+    target: &WindowsTarget,
+    keyboard: &Keyboard,
+    lost: &mut Vec<String>,
+) -> KlcFile {
+    let metadata = generate_metadata(bundle, language_tag, layout, target);
+
+    let mut klc_layout_rows = Vec::new();
+    let mut klc_ligature_rows = Vec::new();
+    let mut dead_key_characters = Vec::new();
+
+    for (iso_key, klc_key) in MSKLC_KEYS.iter() {
+        let mut layer_set = WindowsLayerSet::default();
+
+        // Layer set to determine caps_mode, special escapes, dead keys and null keys
+        for layer in &keyboard.layers {
+            populate_layer_set(&mut layer_set, layer.modifiers, layer.key(*iso_key));
+        }
+
+        let caps_mode = layer_set.caps_mode();
+
+        klc_layout_rows.push(KlcLayoutRow {
+            scancode: klc_key.scancode.clone(),
+            virtual_key: klc_key.virtual_key.clone(),
+            caps_mode: caps_mode.clone(), // Generate extra key for SGCap here?
+            default_key: convert_to_klc_key(
+                layer_set.default,
+                &klc_key.virtual_key,
+                KlcLayer::Default,
+                &mut klc_ligature_rows,
+                &mut dead_key_characters,
+                lost,
+            ),
+            shift_key: convert_to_klc_key(
+                layer_set.shift,
+                &klc_key.virtual_key,
+                KlcLayer::Shift,
+                &mut klc_ligature_rows,
+                &mut dead_key_characters,
+                lost,
+            ),
+            ctrl_key: convert_to_klc_key(
+                layer_set.ctrl,
+                &klc_key.virtual_key,
+                KlcLayer::Ctrl,
+                &mut klc_ligature_rows,
+                &mut dead_key_characters,
+                lost,
+            ),
+            alt_key: convert_to_klc_key(
+                layer_set.alt,
+                &klc_key.virtual_key,
+                KlcLayer::Alt,
+                &mut klc_ligature_rows,
+                &mut dead_key_characters,
+                lost,
+            ),
+            alt_and_shift_key: convert_to_klc_key(
+                layer_set.alt_and_shift,
+                &klc_key.virtual_key,
+                KlcLayer::AltAndShift,
+                &mut klc_ligature_rows,
+                &mut dead_key_characters,
+                lost,
+            ),
+        });
+
+        if caps_mode == SG_CAP {
+            let caps_key = convert_to_klc_key(
+                layer_set.caps,
+                &klc_key.virtual_key,
+                KlcLayer::Default,
+                &mut klc_ligature_rows,
+                &mut dead_key_characters,
+                lost,
+            );
+
+            let caps_shift_key = convert_to_klc_key(
+                layer_set.caps_and_shift,
+                &klc_key.virtual_key,
+                KlcLayer::Shift,
+                &mut klc_ligature_rows,
+                &mut dead_key_characters,
+                lost,
+            );
+
+            klc_layout_rows.push(KlcLayoutRow {
+                scancode: "-1".to_owned(),
+                virtual_key: "-1".to_owned(),
+                caps_mode: "0".to_owned(),
+                default_key: caps_key,
+                shift_key: caps_shift_key,
+                ctrl_key: KlcKey::Skip,
+                alt_key: KlcKey::Skip,
+                alt_and_shift_key: KlcKey::Skip,
+            });
+        }
+    }
+
+    klc_layout_rows.push(space_layout_row());
+    klc_layout_rows.push(decimal_layout_row(&layout.decimal));
+
+    let dead_keys = KlcDeadKeys::new(&dead_key_characters, &keyboard.dead_keys, lost);
+
+    KlcFile {
+        metadata,
+        layout: KlcLayout {
+            rows: klc_layout_rows,
+        },
+        ligature: KlcLigature {
+            rows: klc_ligature_rows,
+        },
+        dead_keys,
+    }
+}
+
+pub(crate) fn generate_metadata(
     bundle: &KbdgenBundle,
     language_tag: &LanguageTag,
     layout: &Layout,
@@ -253,6 +277,7 @@ fn convert_to_klc_key(
     klc_layer: KlcLayer,
     klc_ligature_rows: &mut Vec<KlcLigatureRow>,
     dead_key_characters: &mut Vec<char>,
+    lost: &mut Vec<String>,
 ) -> KlcKey {
     match key {
         Some(key) => {
@@ -269,6 +294,16 @@ fn convert_to_klc_key(
                     KlcKey::Character(character)
                 }
             } else {
+                // Dead keys must be a single UTF-16 unit, so the others only
+                // type their own characters. Characters outside the BMP are
+                // written as ligatures of their surrogate pairs.
+                if key.dead_key {
+                    lost.push(format!(
+                        "dead key {:?} on {}: typed as a ligature, without its transforms",
+                        key.string, virtual_key
+                    ));
+                }
+
                 let ligature_row = KlcLigatureRow {
                     virtual_key: virtual_key.to_owned(),
                     shift_state: (klc_layer as u8).to_string(),
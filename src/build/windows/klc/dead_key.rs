@@ -1,96 +1,335 @@
 use std::fmt::Display;
 
-use indexmap::IndexMap;
+use crate::{
+    model::{DeadKeyState, DeadKeys, Next, StateId},
+    util::TRANSFORM_ESCAPE,
+};
 
-use crate::{bundle::layout::Transform, util::TRANSFORM_ESCAPE};
+#[derive(Debug, PartialEq)]
+pub struct KlcDeadKeys {
+    pub tables: Vec<DeadKeyTable>,
+}
+
+/// The first character of the Private Use Area, where ids for chained dead
+/// keys are taken from when the escape output of their state can't be used
+const PRIVATE_USE_START: u32 = 0xE000;
 
-use super::key::validate_for_klc;
+/// A `DEADKEY` table: what typing each character after the dead key gives
+#[derive(Debug, PartialEq)]
+pub struct DeadKeyTable {
+    pub dead_key: char,
+    pub transforms: Vec<DeadKeyTransform>,
+}
 
-pub struct KlcDeadKeys<'a> {
-    pub characters: Vec<char>,
-    pub transforms: &'a Option<IndexMap<String, Transform>>,
+#[derive(Debug, PartialEq)]
+pub struct DeadKeyTransform {
+    pub from: u16,
+    pub to: u16,
+    /// Whether `to` is another dead key rather than an output
+    pub chained: bool,
 }
 
-impl Display for KlcDeadKeys<'_> {
+impl Display for KlcDeadKeys {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.characters.is_empty() {
-            return Ok(());
+        for table in &self.tables {
+            f.write_fmt(format_args!("DEADKEY {:04x}\n\n", table.dead_key as u32))?;
+
+            for transform in &table.transforms {
+                let chained = if transform.chained { "@" } else { "" };
+                f.write_fmt(format_args!(
+                    "{:04x}\t{:04x}{}\n",
+                    transform.from, transform.to, chained
+                ))?;
+            }
+
+            f.write_str("\n")?;
         }
 
-        if let Some(transforms) = self.transforms {
-            for dead_key in &self.characters {
-                f.write_fmt(format_args!("DEADKEY {:04x}\n\n", *dead_key as u32))?;
+        Ok(())
+    }
+}
 
-                if let Some(transform) = transforms.get(&dead_key.to_string()) {
-                    match transform {
-                        Transform::End(_character) => {
-                            tracing::error!("Transform ended too soon for dead key {}", dead_key);
-                        }
-                        Transform::More(map) => {
-                            for (next_char, transform) in map {
-                                if next_char == TRANSFORM_ESCAPE {
-                                    continue;
-                                }
-
-                                match transform {
-                                    Transform::End(end_char) => {
-                                        write_transform(next_char, end_char, f)?;
-                                    }
-                                    Transform::More(_transform) => {
-                                        todo!("Recursion required ahead");
-                                    }
-                                }
-                            }
-
-                            let escape_transform = map.get(TRANSFORM_ESCAPE).expect(&format!(
-                                "The escape transform `{}` not found for dead key `{}`",
-                                TRANSFORM_ESCAPE, &dead_key
-                            ));
-
-                            match escape_transform {
-                                Transform::End(end_char) => {
-                                    write_transform(TRANSFORM_ESCAPE, end_char, f)?;
-                                }
-                                Transform::More(_transform) => {
-                                    panic!(
-                                        "The escape transform should be a string, not another transform"
-                                    );
-                                }
-                            };
+impl KlcDeadKeys {
+    /// The tables of the dead keys typed by `characters`, followed by those of
+    /// the dead keys chained from them. Transforms that aren't a single
+    /// UTF-16 unit on both sides are left out and described in `lost`.
+    pub fn new(characters: &[char], dead_keys: &DeadKeys, lost: &mut Vec<String>) -> Self {
+        if characters.is_empty() {
+            return KlcDeadKeys { tables: vec![] };
+        }
+
+        if dead_keys.starts.is_empty() {
+            tracing::error!("Dead Keys present but no transforms");
+            return KlcDeadKeys { tables: vec![] };
+        }
+
+        // Chained dead keys get a DEADKEY table of their own, added to the end
+        // of this list as they are found
+        let mut tables: Vec<(char, StateId)> = vec![];
+        for dead_key in characters {
+            if tables.iter().any(|x| x.0 == *dead_key) {
+                continue;
+            }
+
+            match dead_keys.starts.get(&dead_key.to_string()) {
+                Some(id) => tables.push((*dead_key, *id)),
+                None => tracing::error!("No transforms for dead key {}", dead_key),
+            }
+        }
+
+        let mut output = vec![];
+        let mut index = 0;
+        while index < tables.len() {
+            let (dead_key, id) = tables[index];
+            let state = &dead_keys.states[id];
+            let mut transforms = vec![];
+
+            for (next_char, next) in &state.transitions {
+                let Some(from) = single_unit(next_char) else {
+                    lost.push(lost_transform(dead_key, state, next_char, next, dead_keys));
+                    continue;
+                };
+
+                match next {
+                    Next::Output(end_char) => match single_unit(end_char) {
+                        Some(to) => transforms.push(DeadKeyTransform {
+                            from,
+                            to,
+                            chained: false,
+                        }),
+                        None => {
+                            lost.push(lost_transform(dead_key, state, next_char, next, dead_keys))
                         }
-                    };
-                } else {
-                    tracing::error!("No transforms for dead key {}", dead_key);
+                    },
+                    Next::State(next_id) => {
+                        let chained =
+                            chained_dead_key(characters, dead_keys, *next_id, next_char, &tables);
+                        tables.push((chained, *next_id));
+                        transforms.push(DeadKeyTransform {
+                            from,
+                            to: chained as u16,
+                            chained: true,
+                        });
+                    }
                 }
+            }
+
+            let escape = state.escape_or_fallback();
 
-                f.write_str("\n")?;
+            // Windows types the table's character and the key when no
+            // transform matches, which only gives the escape output when
+            // the character is that output
+            if single_unit(escape) != Some(dead_key as u16) {
+                lost.push(format!(
+                    "{:?} then a key without a transform gives {:?} and the key, rather than {:?} and the key",
+                    state.sequence, dead_key, escape
+                ));
             }
 
-            Ok(())
-        } else {
-            tracing::error!("Dead Keys present but no transforms");
+            // Typing space always gives a single unit: the escape output, or
+            // else its first character that has one
+            let to = single_unit(escape).unwrap_or_else(|| {
+                let to = escape
+                    .chars()
+                    .find_map(|c| single_unit(&c.to_string()))
+                    .unwrap_or(dead_key as u16);
+                lost.push(format!(
+                    "{:?} then {:?} gives {:?}: gives {:?} instead",
+                    state.sequence,
+                    TRANSFORM_ESCAPE,
+                    escape,
+                    String::from_utf16_lossy(&[to])
+                ));
+                to
+            });
+            transforms.push(DeadKeyTransform {
+                from: TRANSFORM_ESCAPE.encode_utf16().next().unwrap(),
+                to,
+                chained: false,
+            });
 
-            Ok(())
+            output.push(DeadKeyTable {
+                dead_key,
+                transforms,
+            });
+            index += 1;
         }
+
+        KlcDeadKeys { tables: output }
     }
 }
 
-fn write_transform(from: &str, to: &str, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    validate_for_klc(from);
-    validate_for_klc(to);
+/// The combining marks of spacing accents, which can identify a chained
+/// dead key when the accents themselves are taken
+const COMBINING_ACCENTS: &[(char, char)] = &[
+    ('`', '\u{300}'),
+    ('´', '\u{301}'),
+    ('^', '\u{302}'),
+    ('~', '\u{303}'),
+    ('¯', '\u{304}'),
+    ('˘', '\u{306}'),
+    ('˙', '\u{307}'),
+    ('¨', '\u{308}'),
+    ('˚', '\u{30a}'),
+    ('˝', '\u{30b}'),
+    ('ˇ', '\u{30c}'),
+    ('¸', '\u{327}'),
+    ('˛', '\u{328}'),
+];
 
-    let from_16s = from.encode_utf16().collect::<Vec<_>>();
-    let to_16s = to.encode_utf16().collect::<Vec<_>>();
+/// Picks the character identifying the state after a chained dead key,
+/// reached by typing `key`. Windows types it when no key of the state
+/// matches, so the escape output is used when it is a single character not
+/// already taken by another dead key. Otherwise the first free one of `key`,
+/// the characters of the escape and their combining marks is used, and the
+/// Private Use Area only when all of those are taken.
+fn chained_dead_key(
+    characters: &[char],
+    dead_keys: &DeadKeys,
+    id: StateId,
+    key: &str,
+    tables: &[(char, StateId)],
+) -> char {
+    let is_taken = |c: &char| characters.contains(c) || tables.iter().any(|x| x.0 == *c);
+    let state = &dead_keys.states[id];
+    let escape = state.escape_or_fallback();
 
-    if from_16s.len() > 1 || to_16s.len() > 1 {
-        tracing::error!(
-            "Key or value of transform too long: {} {}, skipping",
-            from,
-            to
-        );
+    let visible = key.chars().chain(escape.chars()).collect::<Vec<_>>();
+    let combining = visible.iter().filter_map(|c| {
+        COMBINING_ACCENTS
+            .iter()
+            .find(|(accent, _)| accent == c)
+            .map(|(_, mark)| *mark)
+    });
+
+    single_unit(escape)
+        .and_then(|x| char::from_u32(x as u32))
+        .into_iter()
+        .chain(visible.iter().copied())
+        .chain(combining)
+        .find(|c| *c <= '\u{FFFF}' && !is_taken(c))
+        .or_else(|| {
+            (PRIVATE_USE_START..)
+                .filter_map(char::from_u32)
+                .find(|c| !is_taken(c))
+        })
+        .expect("The Private Use Area has room for every dead key")
+}
+
+/// The UTF-16 unit of a string that is exactly one unit long
+fn single_unit(value: &str) -> Option<u16> {
+    let mut units = value.encode_utf16();
+    units.next().filter(|_| units.next().is_none())
+}
+
+fn lost_transform(
+    dead_key: char,
+    state: &DeadKeyState,
+    from: &str,
+    next: &Next,
+    dead_keys: &DeadKeys,
+) -> String {
+    let to = match next {
+        Next::Output(output) => format!("{:?}", output),
+        Next::State(id) => format!("dead key {:?}", dead_keys.states[*id].sequence),
+    };
+
+    format!(
+        "{:?} then {:?} gives {}: left out, so {:?} and the key are typed instead",
+        state.sequence, from, to, dead_key
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use super::*;
+    use crate::bundle::layout::Transform;
 
-        return Ok(());
+    #[test]
+    fn test_fallbacks() {
+        let end = |x: &str| Transform::End(x.to_string());
+
+        let acute = IndexMap::from([
+            (" ".to_string(), end("´")),
+            ("a".to_string(), end("á")),
+            ("ŋ".to_string(), end("ŋ\u{301}")),
+            ("𐌰".to_string(), end("x")),
+            ("x".to_string(), end("𐌰")),
+        ]);
+        let transforms = IndexMap::from([("´".to_string(), Transform::More(acute))]);
+        let dead_keys = DeadKeys::from_transforms(&transforms);
+
+        let mut lost = vec![];
+        let klc = KlcDeadKeys::new(&['´'], &dead_keys, &mut lost);
+
+        assert_eq!(
+            klc.tables[0].transforms,
+            vec![
+                DeadKeyTransform {
+                    from: 'a' as u16,
+                    to: 'á' as u16,
+                    chained: false,
+                },
+                DeadKeyTransform {
+                    from: ' ' as u16,
+                    to: '´' as u16,
+                    chained: false,
+                },
+            ]
+        );
+        assert_eq!(lost.len(), 3);
+        assert!(lost[2].starts_with(r#""´" then "x" gives "𐌰""#));
     }
 
-    f.write_fmt(format_args!("{:04x}\t{:04x}\n", from_16s[0], to_16s[0]))
+    #[test]
+    fn test_chained_escape() {
+        let end = |x: &str| Transform::End(x.to_string());
+
+        let chained = IndexMap::from([(" ".to_string(), end("´¨")), ("u".to_string(), end("ǘ"))]);
+        let acute = IndexMap::from([
+            (" ".to_string(), end("´")),
+            ("¨".to_string(), Transform::More(chained)),
+        ]);
+        let diaeresis = IndexMap::from([(" ".to_string(), end("¨")), ("u".to_string(), end("ü"))]);
+        let transforms = IndexMap::from([
+            ("´".to_string(), Transform::More(acute)),
+            ("¨".to_string(), Transform::More(diaeresis)),
+        ]);
+        let dead_keys = DeadKeys::from_transforms(&transforms);
+
+        let mut lost = vec![];
+        let klc = KlcDeadKeys::new(&['´', '¨'], &dead_keys, &mut lost);
+
+        // ´ and ¨ are dead keys of their own, so the chained state is named
+        // by the combining diaeresis
+        let table = &klc.tables[2];
+        assert_eq!(table.dead_key, '\u{308}');
+        assert_eq!(
+            klc.tables[0].transforms[0],
+            DeadKeyTransform {
+                from: '¨' as u16,
+                to: 0x308,
+                chained: true,
+            }
+        );
+        assert_eq!(
+            table.transforms,
+            vec![
+                DeadKeyTransform {
+                    from: 'u' as u16,
+                    to: 'ǘ' as u16,
+                    chained: false,
+                },
+                DeadKeyTransform {
+                    from: ' ' as u16,
+                    to: '´' as u16,
+                    chained: false,
+                },
+            ]
+        );
+        assert_eq!(lost.len(), 2);
+        assert!(lost[1].starts_with(r#""´¨" then " " gives "´¨": gives "´" instead"#));
+    }
 }
@@ -4,13 +4,15 @@ use super::{dead_key::KlcDeadKeys, layout::KlcLayout, ligature::KlcLigature};
 
 pub const KLC_EXT: &str = "klc";
 
-pub struct KlcFile<'a> {
+#[derive(Debug, PartialEq)]
+pub struct KlcFile {
     pub metadata: KlcFileMetadata,
     pub layout: KlcLayout,
     pub ligature: KlcLigature,
-    pub dead_keys: KlcDeadKeys<'a>,
+    pub dead_keys: KlcDeadKeys,
 }
 
+#[derive(Debug, PartialEq)]
 pub struct KlcFileMetadata {
     pub keyboard_name: String,
     pub description: String,
@@ -21,7 +23,7 @@ pub struct KlcFileMetadata {
     pub locale_name: String,
 }
 
-impl Display for KlcFile<'_> {
+impl Display for KlcFile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
             "KBD\t{}\t\"{}\"\n\n",
@@ -52,7 +54,10 @@ impl Display for KlcFile<'_> {
 
         f.write_str(&self.dead_keys.to_string())?;
 
-        f.write_str(FOOTER_CONTENT)?;
+        f.write_str("\nKEYNAME\n\n")?;
+        write_key_names(KEY_NAMES, f)?;
+        f.write_str("\nKEYNAME_EXT\n\n")?;
+        write_key_names(KEY_NAMES_EXT, f)?;
 
         f.write_str("\nDESCRIPTIONS\n\n")?;
         f.write_fmt(format_args!(
@@ -72,83 +77,95 @@ impl Display for KlcFile<'_> {
     }
 }
 
-const FOOTER_CONTENT: &str = r#"
-KEYNAME
-
-01	Esc
-0e	Backspace
-0f	Tab
-1c	Enter
-1d	Ctrl
-2a	Shift
-36	"Right Shift"
-37	"Num *"
-38	Alt
-39	Space
-3a	"Caps Lock"
-3b	F1
-3c	F2
-3d	F3
-3e	F4
-3f	F5
-40	F6
-41	F7
-42	F8
-43	F9
-44	F10
-45	Pause
-46	"Scroll Lock"
-47	"Num 7"
-48	"Num 8"
-49	"Num 9"
-4a	"Num -"
-4b	"Num 4"
-4c	"Num 5"
-4d	"Num 6"
-4e	"Num +"
-4f	"Num 1"
-50	"Num 2"
-51	"Num 3"
-52	"Num 0"
-53	"Num Del"
-54	"Sys Req"
-57	F11
-58	F12
-7c	F13
-7d	F14
-7e	F15
-7f	F16
-80	F17
-81	F18
-82	F19
-83	F20
-84	F21
-85	F22
-86	F23
-87	F24
-
-KEYNAME_EXT
-
-1c	"Num Enter"
-1d	"Right Ctrl"
-35	"Num /"
-37	"Prnt Scrn"
-38	"Right Alt"
-45	"Num Lock"
-46	Break
-47	Home
-48	Up
-49	"Page Up"
-4b	Left
-4d	Right
-4f	End
-50	Down
-51	"Page Down"
-52	Insert
-53	Delete
-54	<00>
-56	Help
-5b	"Left Windows"
-5c	"Right Windows"
-5d	Application
-"#;
+fn write_key_names(names: &[(u8, &str)], f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    for (scancode, name) in names {
+        if name.contains(' ') {
+            f.write_fmt(format_args!("{:02x}\t\"{}\"\n", scancode, name))?;
+        } else {
+            f.write_fmt(format_args!("{:02x}\t{}\n", scancode, name))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Names of the keys shown by Windows, by scancode
+pub const KEY_NAMES: &[(u8, &str)] = &[
+    (0x01, "Esc"),
+    (0x0e, "Backspace"),
+    (0x0f, "Tab"),
+    (0x1c, "Enter"),
+    (0x1d, "Ctrl"),
+    (0x2a, "Shift"),
+    (0x36, "Right Shift"),
+    (0x37, "Num *"),
+    (0x38, "Alt"),
+    (0x39, "Space"),
+    (0x3a, "Caps Lock"),
+    (0x3b, "F1"),
+    (0x3c, "F2"),
+    (0x3d, "F3"),
+    (0x3e, "F4"),
+    (0x3f, "F5"),
+    (0x40, "F6"),
+    (0x41, "F7"),
+    (0x42, "F8"),
+    (0x43, "F9"),
+    (0x44, "F10"),
+    (0x45, "Pause"),
+    (0x46, "Scroll Lock"),
+    (0x47, "Num 7"),
+    (0x48, "Num 8"),
+    (0x49, "Num 9"),
+    (0x4a, "Num -"),
+    (0x4b, "Num 4"),
+    (0x4c, "Num 5"),
+    (0x4d, "Num 6"),
+    (0x4e, "Num +"),
+    (0x4f, "Num 1"),
+    (0x50, "Num 2"),
+    (0x51, "Num 3"),
+    (0x52, "Num 0"),
+    (0x53, "Num Del"),
+    (0x54, "Sys Req"),
+    (0x57, "F11"),
+    (0x58, "F12"),
+    (0x7c, "F13"),
+    (0x7d, "F14"),
+    (0x7e, "F15"),
+    (0x7f, "F16"),
+    (0x80, "F17"),
+    (0x81, "F18"),
+    (0x82, "F19"),
+    (0x83, "F20"),
+    (0x84, "F21"),
+    (0x85, "F22"),
+    (0x86, "F23"),
+    (0x87, "F24"),
+];
+
+/// Names of the keys sent with an `E0` prefix, by scancode
+pub const KEY_NAMES_EXT: &[(u8, &str)] = &[
+    (0x1c, "Num Enter"),
+    (0x1d, "Right Ctrl"),
+    (0x35, "Num /"),
+    (0x37, "Prnt Scrn"),
+    (0x38, "Right Alt"),
+    (0x45, "Num Lock"),
+    (0x46, "Break"),
+    (0x47, "Home"),
+    (0x48, "Up"),
+    (0x49, "Page Up"),
+    (0x4b, "Left"),
+    (0x4d, "Right"),
+    (0x4f, "End"),
+    (0x50, "Down"),
+    (0x51, "Page Down"),
+    (0x52, "Insert"),
+    (0x53, "Delete"),
+    (0x54, "<00>"),
+    (0x56, "Help"),
+    (0x5b, "Left Windows"),
+    (0x5c, "Right Windows"),
+    (0x5d, "Application"),
+];
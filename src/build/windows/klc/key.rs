@@ -1,7 +1,6 @@
 use std::fmt::{Display, Write};
 
-const EXCEEDS_BMP: char = '\u{FFFF}';
-
+#[derive(Debug, PartialEq)]
 pub enum KlcKey {
     Character(char),
     DeadKey(char),
@@ -34,11 +33,3 @@ pub fn display_klc_character(character: char, f: &mut std::fmt::Formatter<'_>) -
         f.write_fmt(format_args!("{:04x}", character as u32))
     }
 }
-
-pub fn validate_for_klc(key: &str) {
-    key.chars().for_each(|character| {
-        if character > EXCEEDS_BMP {
-            panic!("Unrepresentable key detected! {}", key);
-        }
-    });
-}
@@ -2,6 +2,7 @@ use std::fmt::Display;
 
 use super::key::KlcKey;
 
+#[derive(Debug, PartialEq)]
 pub struct KlcLayout {
     pub rows: Vec<KlcLayoutRow>,
 }
@@ -43,6 +44,7 @@ pub enum KlcLayer {
 // Hardcoded columns. .klc can support a different number
 // of columns or column assignments than this
 // but we don't support that.
+#[derive(Debug, PartialEq)]
 pub struct KlcLayoutRow {
     pub scancode: String,
     pub virtual_key: String,
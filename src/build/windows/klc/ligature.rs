@@ -1,5 +1,6 @@
 use std::fmt::Display;
 
+#[derive(Debug, PartialEq)]
 pub struct KlcLigature {
     pub rows: Vec<KlcLigatureRow>,
 }
@@ -29,6 +30,7 @@ impl Display for KlcLigature {
     }
 }
 
+#[derive(Debug, PartialEq)]
 pub struct KlcLigatureRow {
     pub virtual_key: String,
     pub shift_state: String,
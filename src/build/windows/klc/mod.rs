@@ -4,3 +4,4 @@ pub mod key;
 pub mod keymap;
 pub mod layout;
 pub mod ligature;
+pub mod parse;
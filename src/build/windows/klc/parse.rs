@@ -0,0 +1,539 @@
+//! Reads .klc files back into a `KlcFile`, and checks them for the mistakes
+//! MSKLC refuses to build, so they are found without a Windows machine.
+
+use std::collections::HashSet;
+
+use anyhow::{Context, Result, bail};
+
+use super::{
+    dead_key::{DeadKeyTable, DeadKeyTransform, KlcDeadKeys},
+    file::{KlcFile, KlcFileMetadata},
+    key::KlcKey,
+    layout::{KlcLayout, KlcLayoutRow},
+    ligature::{KlcLigature, KlcLigatureRow},
+};
+
+/// The shift states of the columns kbdgen writes, the only ones `KlcFile` has
+const SHIFT_STATES: [u8; 5] = [0, 1, 2, 6, 7];
+const KEY_COLUMNS: usize = SHIFT_STATES.len();
+/// The most UTF-16 code units MSKLC allows in a ligature
+pub const MAX_LIGATURE_LENGTH: usize = 4;
+
+enum Section {
+    None,
+    ShiftState,
+    Layout,
+    Ligature,
+    DeadKey,
+    Descriptions,
+    LanguageNames,
+    Skip,
+}
+
+/// Cuts a line at `//`, unless it is inside a double-quoted string such as a
+/// URL in `COPYRIGHT`
+fn strip_comment(line: &str) -> &str {
+    let mut in_quotes = false;
+
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '/' if !in_quotes && line[index + 1..].starts_with('/') => {
+                return line[..index].trim();
+            }
+            _ => {}
+        }
+    }
+
+    line.trim()
+}
+
+fn quoted(line: &str) -> Option<String> {
+    let start = line.find('"')?;
+    let end = line.rfind('"')?;
+
+    (end > start).then(|| line[start + 1..end].to_string())
+}
+
+/// Characters are written either as themselves or as 4 hexadecimal digits
+pub fn parse_character(cell: &str) -> Result<char> {
+    let mut chars = cell.chars();
+
+    if let (Some(character), None) = (chars.next(), chars.next()) {
+        return Ok(character);
+    }
+
+    let code =
+        u32::from_str_radix(cell, 16).with_context(|| format!("Invalid character '{}'", cell))?;
+    char::from_u32(code).with_context(|| format!("Invalid character '{}'", cell))
+}
+
+/// UTF-16 units are written like characters, but may be half of a surrogate
+/// pair in ligatures
+fn parse_utf16(cell: &str) -> Result<u16> {
+    let mut chars = cell.chars();
+
+    let code = match (chars.next(), chars.next()) {
+        (Some(character), None) => character as u32,
+        _ => u32::from_str_radix(cell, 16)
+            .with_context(|| format!("Invalid character '{}'", cell))?,
+    };
+    u16::try_from(code)
+        .with_context(|| format!("'{}' is outside the Basic Multilingual Plane", cell))
+}
+
+fn parse_key(cell: &str) -> Result<KlcKey> {
+    Ok(match cell {
+        "-1" => KlcKey::None,
+        "%%" => KlcKey::Ligature,
+        _ if cell.len() > 1 && cell.ends_with('@') => {
+            KlcKey::DeadKey(parse_character(cell.trim_end_matches('@'))?)
+        }
+        _ => KlcKey::Character(parse_character(cell)?),
+    })
+}
+
+/// Parses the bytes of a .klc file, which MSKLC only reads as UTF-16LE with a
+/// byte order mark
+pub fn parse(bytes: &[u8]) -> Result<KlcFile> {
+    let Some(rest) = bytes.strip_prefix(&[0xff, 0xfe]) else {
+        bail!("A .klc file must be UTF-16LE with a byte order mark");
+    };
+
+    let units = rest
+        .chunks_exact(2)
+        .map(|x| u16::from_le_bytes([x[0], x[1]]))
+        .collect::<Vec<_>>();
+    let text = String::from_utf16(&units).context("Invalid UTF-16")?;
+
+    parse_text(&text)
+}
+
+/// Parses a .klc file and checks it for what MSKLC would reject
+pub fn parse_text(text: &str) -> Result<KlcFile> {
+    let (file, shift_states, sections) = read_text(text)?;
+
+    if shift_states != SHIFT_STATES {
+        bail!(
+            "Only the shift states {:?} are supported, found {:?}",
+            SHIFT_STATES,
+            shift_states
+        );
+    }
+
+    let mut problems = ["LAYOUT", "KEYNAME", "KEYNAME_EXT"]
+        .iter()
+        .filter(|x| !sections.contains(**x))
+        .map(|x| format!("No {} section", x))
+        .collect::<Vec<_>>();
+    problems.extend(check(&file));
+
+    if !problems.is_empty() {
+        bail!("MSKLC would reject the file:\n- {}", problems.join("\n- "));
+    }
+
+    Ok(file)
+}
+
+/// Parses a .klc file without the checks of `parse_text`, for files made by
+/// other tools or by hand. Columns of shift states kbdgen doesn't write are
+/// left out, along with their ligatures, and missing ones are `KlcKey::Skip`.
+pub fn parse_lenient(text: &str) -> Result<KlcFile> {
+    let (file, shift_states, _) = read_text(text)?;
+
+    if shift_states.is_empty() {
+        bail!("No SHIFTSTATE section found");
+    }
+
+    Ok(file)
+}
+
+/// The file, the shift states of its columns and the sections it has
+fn read_text(text: &str) -> Result<(KlcFile, Vec<u8>, HashSet<&str>)> {
+    let mut metadata = KlcFileMetadata {
+        keyboard_name: String::new(),
+        description: String::new(),
+        copyright: String::new(),
+        company: String::new(),
+        autonym: String::new(),
+        locale_id: 0,
+        locale_name: String::new(),
+    };
+    let mut shift_states = vec![];
+    let mut rows = vec![];
+    let mut ligature_rows = vec![];
+    let mut tables: Vec<DeadKeyTable> = vec![];
+    let mut sections = HashSet::new();
+    let mut section = Section::None;
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let line = strip_comment(raw_line);
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+
+        let Some(first) = tokens.first() else {
+            continue;
+        };
+
+        let error = || format!("Invalid line {}: '{}'", index + 1, raw_line.trim());
+
+        match *first {
+            "KBD" => {
+                metadata.keyboard_name = tokens.get(1).with_context(error)?.to_string();
+                metadata.description = quoted(line).unwrap_or_default();
+                section = Section::None;
+            }
+            "COPYRIGHT" => {
+                metadata.copyright = quoted(line).unwrap_or_default();
+                section = Section::None;
+            }
+            "COMPANY" => {
+                metadata.company = quoted(line).unwrap_or_default();
+                section = Section::None;
+            }
+            "LOCALENAME" => {
+                metadata.locale_name = quoted(line).unwrap_or_default();
+                section = Section::None;
+            }
+            "LOCALEID" => {
+                let locale_id = quoted(line).with_context(error)?;
+                metadata.locale_id = u32::from_str_radix(&locale_id, 16).with_context(error)?;
+                section = Section::None;
+            }
+            "VERSION" => section = Section::None,
+            "SHIFTSTATE" => section = Section::ShiftState,
+            "LAYOUT" => {
+                sections.insert(*first);
+                section = Section::Layout;
+            }
+            "LIGATURE" => section = Section::Ligature,
+            "DEADKEY" => {
+                tables.push(DeadKeyTable {
+                    dead_key: parse_character(tokens.get(1).with_context(error)?)
+                        .with_context(error)?,
+                    transforms: vec![],
+                });
+                section = Section::DeadKey;
+            }
+            "DESCRIPTIONS" => section = Section::Descriptions,
+            "LANGUAGENAMES" => section = Section::LanguageNames,
+            "ATTRIBUTES" | "KEYNAME" | "KEYNAME_EXT" | "KEYNAME_DEAD" => {
+                sections.insert(*first);
+                section = Section::Skip;
+            }
+            "ENDKBD" => break,
+            _ => match section {
+                Section::ShiftState => {
+                    shift_states.push(first.parse::<u8>().with_context(error)?);
+                }
+                Section::Layout => {
+                    if tokens.len() < 3 || tokens.len() - 3 > shift_states.len() {
+                        bail!(error());
+                    }
+
+                    let mut keys = (0..KEY_COLUMNS).map(|_| KlcKey::Skip).collect::<Vec<_>>();
+                    for (cell, shift_state) in tokens[3..].iter().zip(&shift_states) {
+                        let key = parse_key(cell).with_context(error)?;
+                        if let Some(column) = key_column(*shift_state) {
+                            keys[column] = key;
+                        }
+                    }
+                    let [default_key, shift_key, ctrl_key, alt_key, alt_and_shift_key] =
+                        <[KlcKey; KEY_COLUMNS]>::try_from(keys)
+                            .ok()
+                            .with_context(error)?;
+
+                    rows.push(KlcLayoutRow {
+                        scancode: tokens[0].to_string(),
+                        virtual_key: tokens[1].to_string(),
+                        caps_mode: tokens[2].to_string(),
+                        default_key,
+                        shift_key,
+                        ctrl_key,
+                        alt_key,
+                        alt_and_shift_key,
+                    });
+                }
+                Section::Ligature => {
+                    if tokens.len() < 3 {
+                        bail!(error());
+                    }
+
+                    let file_column = tokens[1].parse::<usize>().with_context(error)?;
+                    let Some(column) = shift_states.get(file_column).and_then(|x| key_column(*x))
+                    else {
+                        continue;
+                    };
+
+                    ligature_rows.push(KlcLigatureRow {
+                        virtual_key: tokens[0].to_string(),
+                        shift_state: column.to_string(),
+                        utf16s: tokens[2..]
+                            .iter()
+                            .map(|x| parse_utf16(x))
+                            .collect::<Result<Vec<_>>>()
+                            .with_context(error)?,
+                    });
+                }
+                Section::DeadKey => {
+                    if tokens.len() != 2 {
+                        bail!(error());
+                    }
+
+                    let chained = tokens[1].len() > 1 && tokens[1].ends_with('@');
+                    let transform = DeadKeyTransform {
+                        from: parse_utf16(tokens[0]).with_context(error)?,
+                        to: parse_utf16(tokens[1].trim_end_matches('@')).with_context(error)?,
+                        chained,
+                    };
+                    tables
+                        .last_mut()
+                        .with_context(error)?
+                        .transforms
+                        .push(transform);
+                }
+                Section::Descriptions | Section::LanguageNames => {
+                    let text = line[first.len()..].trim().to_string();
+                    let field = match section {
+                        Section::Descriptions => &mut metadata.description,
+                        _ => &mut metadata.autonym,
+                    };
+
+                    if field.is_empty() {
+                        *field = text;
+                    }
+                }
+                Section::None | Section::Skip => {}
+            },
+        }
+    }
+
+    if metadata.keyboard_name.is_empty() {
+        bail!("No KBD line found");
+    }
+
+    let file = KlcFile {
+        metadata,
+        layout: KlcLayout { rows },
+        ligature: KlcLigature {
+            rows: ligature_rows,
+        },
+        dead_keys: KlcDeadKeys { tables },
+    };
+
+    Ok((file, shift_states, sections))
+}
+
+/// The column of `KlcLayoutRow` a shift state is kept in
+fn key_column(shift_state: u8) -> Option<usize> {
+    SHIFT_STATES.iter().position(|x| *x == shift_state)
+}
+
+/// The keys of a row, in the order of `SHIFT_STATES`
+pub fn layout_keys(row: &KlcLayoutRow) -> [&KlcKey; KEY_COLUMNS] {
+    [
+        &row.default_key,
+        &row.shift_key,
+        &row.ctrl_key,
+        &row.alt_key,
+        &row.alt_and_shift_key,
+    ]
+}
+
+/// The problems of the file that MSKLC checks for before building it
+pub fn check(file: &KlcFile) -> Vec<String> {
+    let mut problems = vec![];
+
+    // The rows of SGCap keys with caps lock have no virtual key of their own
+    let rows = file
+        .layout
+        .rows
+        .iter()
+        .filter(|x| x.virtual_key != "-1")
+        .collect::<Vec<_>>();
+
+    let mut virtual_keys = HashSet::new();
+    for row in &rows {
+        if !virtual_keys.insert(&row.virtual_key) {
+            problems.push(format!("The virtual key {} is used twice", row.virtual_key));
+        }
+    }
+
+    let defined = file
+        .dead_keys
+        .tables
+        .iter()
+        .map(|x| x.dead_key)
+        .collect::<HashSet<_>>();
+    let chained = file.dead_keys.tables.iter().flat_map(|table| {
+        table
+            .transforms
+            .iter()
+            .filter(|x| x.chained)
+            .filter_map(|x| char::from_u32(x.to as u32))
+    });
+    let referenced = file
+        .layout
+        .rows
+        .iter()
+        .flat_map(layout_keys)
+        .filter_map(|key| match key {
+            KlcKey::DeadKey(c) => Some(*c),
+            _ => None,
+        })
+        .chain(chained)
+        .collect::<Vec<_>>();
+    let mut reported = HashSet::new();
+    for dead_key in referenced {
+        if !defined.contains(&dead_key) && reported.insert(dead_key) {
+            problems.push(format!(
+                "The dead key {:04x} has no DEADKEY table",
+                dead_key as u32
+            ));
+        }
+    }
+
+    for row in &rows {
+        for (column, key) in layout_keys(row).iter().enumerate() {
+            let has_ligature =
+                file.ligature.rows.iter().any(|x| {
+                    x.virtual_key == row.virtual_key && x.shift_state == column.to_string()
+                });
+
+            if matches!(key, KlcKey::Ligature) && !has_ligature {
+                problems.push(format!(
+                    "The ligature of {} in column {} has no LIGATURE row",
+                    row.virtual_key, column
+                ));
+            }
+        }
+    }
+
+    for row in &file.ligature.rows {
+        if row.utf16s.len() > MAX_LIGATURE_LENGTH {
+            problems.push(format!(
+                "The ligature of {} in column {} has {} characters, more than {}",
+                row.virtual_key,
+                row.shift_state,
+                row.utf16s.len(),
+                MAX_LIGATURE_LENGTH
+            ));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use codecs::utf16::Utf16Ext;
+
+    use super::*;
+    use crate::build::windows::generate_klc::{klc_file, lower_keyboard};
+    use crate::bundle::read_kbdgen_bundle;
+
+    const FIXTURE_BUNDLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sje.kbdgen");
+
+    #[test]
+    fn test_round_trip() {
+        let bundle = read_kbdgen_bundle(std::path::Path::new(FIXTURE_BUNDLE)).unwrap();
+
+        for (language_tag, layout) in &bundle.layouts {
+            let Some(target) = &layout.windows else {
+                continue;
+            };
+
+            let keyboard = lower_keyboard(&bundle, language_tag, target).unwrap();
+            let generated = klc_file(
+                &bundle,
+                language_tag,
+                layout,
+                target,
+                &keyboard,
+                &mut vec![],
+            );
+            let bytes = generated.to_string().encode_utf16_le_bom();
+
+            let parsed = parse(&bytes).unwrap();
+            assert_eq!(parsed, generated);
+            assert_eq!(parsed.to_string(), generated.to_string());
+        }
+    }
+
+    #[test]
+    fn test_sg_caps_and_ligatures() {
+        let text = "KBD\tkbdtest\t\"Test\"\n\
+            LOCALEID\t\"00002000\"\n\
+            SHIFTSTATE\n\
+            0\n1\n2\n6\n7\n\
+            LAYOUT\n\
+            1a\tOEM_4\tSGCap\t00e5\t00c5\t-1\t%%\t%%\n\
+            -1\t-1\t0\t014b\t014a\n\
+            LIGATURE\n\
+            OEM_4\t3\t0069\t0308\n\
+            OEM_4\t4\td800\tdf30\n\
+            KEYNAME\n\
+            01\tEsc\n\
+            KEYNAME_EXT\n\
+            1c\t\"Num Enter\"\n\
+            ENDKBD\n";
+
+        let file = parse_text(text).unwrap();
+        assert_eq!(file.metadata.locale_id, 0x2000);
+        assert_eq!(file.layout.rows[1].default_key, KlcKey::Character('ŋ'));
+        assert_eq!(file.layout.rows[1].ctrl_key, KlcKey::Skip);
+        assert_eq!(file.ligature.rows[0].utf16s, vec![0x69, 0x308]);
+        assert_eq!(String::from_utf16_lossy(&file.ligature.rows[1].utf16s), "𐌰");
+
+        assert_eq!(parse_text(&file.to_string()).unwrap(), file);
+    }
+
+    #[test]
+    fn test_comments() {
+        let text = "KBD\tkbdtest\t\"Test\" // The name\n\
+            COPYRIGHT\t\"(c) Divvun, https://divvun.no\"\t// Not part of it\n\
+            COMPANY\t\"https://divvun.no\"\n\
+            SHIFTSTATE\n\
+            0\n1\n2\n6\n7\n\
+            LAYOUT\n\
+            10\tQ\t1\tq\tQ\t-1\t-1\t-1 // q\n\
+            KEYNAME\n\
+            01\tEsc\n\
+            KEYNAME_EXT\n\
+            1c\t\"Num Enter\"\n\
+            ENDKBD\n";
+
+        let file = parse_text(text).unwrap();
+        assert_eq!(file.metadata.description, "Test");
+        assert_eq!(file.metadata.copyright, "(c) Divvun, https://divvun.no");
+        assert_eq!(file.metadata.company, "https://divvun.no");
+        assert_eq!(file.layout.rows[0].default_key, KlcKey::Character('q'));
+    }
+
+    #[test]
+    fn test_problems() {
+        let text = "KBD\tkbdtest\t\"Test\"\n\
+            SHIFTSTATE\n\
+            0\n1\n2\n6\n7\n\
+            LAYOUT\n\
+            10\tQ\t1\tq\tQ\t-1\t%%\t-1\n\
+            11\tQ\t1\t00b4@\tW\t-1\t-1\t-1\n\
+            LIGATURE\n\
+            Q\t4\t0061\t0062\t0063\t0064\t0065\n\
+            KEYNAME\n\
+            01\tEsc\n\
+            ENDKBD\n";
+
+        let error = parse_text(text).unwrap_err().to_string();
+        for problem in [
+            "No KEYNAME_EXT section",
+            "The virtual key Q is used twice",
+            "The dead key 00b4 has no DEADKEY table",
+            "The ligature of Q in column 3 has no LIGATURE row",
+            "The ligature of Q in column 4 has 5 characters, more than 4",
+        ] {
+            assert!(error.contains(problem), "{} not in {}", problem, error);
+        }
+
+        assert!(parse(text.as_bytes()).is_err());
+    }
+}
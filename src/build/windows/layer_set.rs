@@ -1,8 +1,7 @@
-use indexmap::IndexMap;
-
-use crate::{bundle::layout::windows::WindowsKbdLayer, util::decode_unicode_escapes};
-
-use super::klc::key::validate_for_klc;
+use crate::{
+    bundle::layout::windows::WindowsKbdLayer,
+    model::{KeyAction, Modifiers, PlatformLayer},
+};
 
 pub const SG_CAP: &str = "SGCap";
 
@@ -56,75 +55,47 @@ impl WindowsLayerSet {
     }
 }
 
-pub fn populate_layer_set(
-    layer_set: &mut WindowsLayerSet,
-    layer: &WindowsKbdLayer,
-    key_map: Vec<String>,
-    cursor: usize,
-    dead_keys: Option<&IndexMap<WindowsKbdLayer, Vec<String>>>,
-) {
+pub fn populate_layer_set(layer_set: &mut WindowsLayerSet, modifiers: Modifiers, key: &KeyAction) {
+    let Some(layer) = WindowsKbdLayer::from_modifiers(modifiers) else {
+        return;
+    };
+
+    let key = process_key(key);
     match layer {
-        WindowsKbdLayer::Default => {
-            layer_set.default = process_key(&layer, &key_map[cursor], dead_keys);
-        }
-        WindowsKbdLayer::Shift => {
-            layer_set.shift = process_key(&layer, &key_map[cursor], dead_keys);
-        }
-        WindowsKbdLayer::Caps => {
-            layer_set.caps = process_key(&layer, &key_map[cursor], dead_keys);
-        }
-        WindowsKbdLayer::CapsAndShift => {
-            layer_set.caps_and_shift = process_key(&layer, &key_map[cursor], dead_keys);
-        }
-        WindowsKbdLayer::Alt => {
-            layer_set.alt = process_key(&layer, &key_map[cursor], dead_keys);
-        }
-        WindowsKbdLayer::AltAndShift => {
-            layer_set.alt_and_shift = process_key(&layer, &key_map[cursor], dead_keys);
-        }
-        WindowsKbdLayer::AltAndCaps => {
-            layer_set.alt_and_caps = process_key(&layer, &key_map[cursor], dead_keys);
-        }
-        WindowsKbdLayer::Ctrl => {
-            layer_set.ctrl = process_key(&layer, &key_map[cursor], dead_keys);
-        }
+        WindowsKbdLayer::Default => layer_set.default = key,
+        WindowsKbdLayer::Shift => layer_set.shift = key,
+        WindowsKbdLayer::Caps => layer_set.caps = key,
+        WindowsKbdLayer::CapsAndShift => layer_set.caps_and_shift = key,
+        WindowsKbdLayer::Alt => layer_set.alt = key,
+        WindowsKbdLayer::AltAndShift => layer_set.alt_and_shift = key,
+        WindowsKbdLayer::AltAndCaps => layer_set.alt_and_caps = key,
+        WindowsKbdLayer::Ctrl => layer_set.ctrl = key,
     };
 }
 
-fn process_key(
-    layer_key: &WindowsKbdLayer,
-    key: &str,
-    dead_keys: Option<&IndexMap<WindowsKbdLayer, Vec<String>>>,
-) -> Option<WindowsLayerSetKey> {
-    if key == r"\u{0}" {
-        return None;
-    }
-
-    validate_for_klc(key);
-
-    let key = decode_unicode_escapes(key);
+fn process_key(key: &KeyAction) -> Option<WindowsLayerSetKey> {
+    let (key, dead_key) = match key {
+        KeyAction::Char(character) => (character.to_string(), false),
+        KeyAction::Ligature(text) => (text.clone(), false),
+        KeyAction::DeadKey(text) => (text.clone(), true),
+        KeyAction::Special(name) => {
+            tracing::error!(
+                r"Special key \s{{{}}} can't be written to a .klc file",
+                name
+            );
+            return None;
+        }
+        KeyAction::None => return None,
+    };
 
     let utf16s = key.encode_utf16().collect::<Vec<_>>();
-    if utf16s.len() == 0 || utf16s[0] == 0 {
-        tracing::error!("Empty key: {:?}", key);
-        return None;
-    } else if utf16s.len() > 4 {
+    if utf16s.len() > 4 {
         tracing::error!("Input key too long: {:?}", key);
         return None;
     }
 
-    let mut dead_key: bool = false;
-
-    if let Some(dead_keys) = dead_keys {
-        if let Some(layer_dead_keys) = dead_keys.get(layer_key) {
-            if layer_dead_keys.contains(&key.to_string()) {
-                dead_key = true;
-            }
-        }
-    }
-
     Some(WindowsLayerSetKey {
-        string: key.to_owned(),
+        string: key,
         dead_key,
     })
 }
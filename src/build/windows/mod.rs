@@ -6,12 +6,16 @@ use crate::bundle::KbdgenBundle;
 
 use super::{BuildStep, BuildSteps};
 
+use generate_installer::GenerateWindowsInstaller;
+use generate_kbd_source::GenerateKbdSource;
 use generate_klc::GenerateKlc;
 
 #[cfg(target_os = "windows")]
 mod build_klc;
-mod generate_klc;
-mod klc;
+pub(crate) mod generate_installer;
+pub(crate) mod generate_kbd_source;
+pub(crate) mod generate_klc;
+pub(crate) mod klc;
 mod layer_set;
 
 pub struct WindowsBuild {
@@ -20,18 +24,26 @@ pub struct WindowsBuild {
     pub steps: Vec<Box<dyn BuildStep>>,
 }
 
+pub fn build_steps() -> Vec<Box<dyn BuildStep>> {
+    let mut steps: Vec<Box<dyn BuildStep>> = vec![];
+    steps.push(Box::new(GenerateKlc {}));
+    steps.push(Box::new(GenerateKbdSource {}));
+    steps.push(Box::new(GenerateWindowsInstaller));
+    #[cfg(target_os = "windows")]
+    steps.push(Box::new(build_klc::BuildKlc {}));
+    #[cfg(not(target_os = "windows"))]
+    {
+        tracing::warn!("Skipping BuildKlc step");
+        tracing::warn!(".klc .dlls require MSKLC to build, which is only available on Windows");
+    }
+
+    steps
+}
+
 #[async_trait(?Send)]
 impl BuildSteps for WindowsBuild {
     fn new(bundle: KbdgenBundle, output_path: PathBuf) -> Self {
-        let mut steps: Vec<Box<dyn BuildStep>> = vec![];
-        steps.push(Box::new(GenerateKlc {}));
-        #[cfg(target_os = "windows")]
-        steps.push(Box::new(build_klc::BuildKlc {}));
-        #[cfg(not(target_os = "windows"))]
-        {
-            tracing::warn!("Skipping BuildKlc step");
-            tracing::warn!(".klc .dlls require MSKLC to build, which is only available on Windows");
-        }
+        let steps = build_steps();
 
         Self {
             bundle,
@@ -0,0 +1,508 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use indexmap::IndexMap;
+use language_tags::LanguageTag;
+
+use super::layout::{
+    AndroidPlatform, AndroidTarget, ChromeOsPrimaryPlatform, ChromeOsTarget, IOsConfig,
+    IOsPlatform, IOsTarget, Layout, LinuxPrimaryPlatform, LinuxTarget, MacOsPrimaryPlatform,
+    MacOsTarget, WindowsPrimaryPlatform, WindowsTarget, android::AndroidKbdLayer,
+    chrome::ChromeOsKbdLayer, ios::IOsKbdLayer, linux::LinuxKbdLayer, macos::MacOsKbdLayer,
+    windows::WindowsKbdLayer,
+};
+use super::{KbdgenBundle, read_kbdgen_bundle};
+use super::{LAYOUTS_FOLDER, PROJECT_FILENAME, RESOURCES_FOLDER, TARGETS_FOLDER, YAML_EXT};
+use crate::import::layer_string;
+use crate::model::{KeyAction, Keyboard, Modifiers, Platform};
+use crate::util::encode_unicode_escapes;
+use crate::util::iso_key::{IsoKey, layer_keys};
+
+const BUNDLE_EXT: &str = "kbdgen";
+
+/// Built-in layouts that `new_layout` can start from
+pub const BASES: &[&str] = &["us", "iso-qwerty"];
+
+const US_DEFAULT: &str = r"
+` 1 2 3 4 5 6 7 8 9 0 - =
+q w e r t y u i o p [ ]
+a s d f g h j k l ; ' \
+\ z x c v b n m , . /
+";
+
+const US_SHIFT: &str = r#"
+~ ! @ # $ % ^ & * ( ) _ +
+Q W E R T Y U I O P { }
+A S D F G H J K L : " |
+| Z X C V B N M < > ?
+"#;
+
+/// Only the letters and digits of a QWERTY keyboard, leaving the other keys
+/// for the language to fill in
+const ISO_QWERTY_DEFAULT: &str = r"
+\u{0} 1 2 3 4 5 6 7 8 9 0 \u{0} \u{0}
+q w e r t y u i o p \u{0} \u{0}
+a s d f g h j k l \u{0} \u{0} \u{0}
+\u{0} z x c v b n m \u{0} \u{0} \u{0}
+";
+
+const ISO_QWERTY_SHIFT: &str = r"
+\u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0}
+Q W E R T Y U I O P \u{0} \u{0}
+A S D F G H J K L \u{0} \u{0} \u{0}
+\u{0} Z X C V B N M \u{0} \u{0} \u{0}
+";
+
+const WINDOWS_TARGET: &str = "\
+appName: {name}
+version: 0.1.0
+url: https://example.com
+uuid: {uuid}
+build: '1'
+";
+
+const MACOS_TARGET: &str = "\
+codeSignId: ''
+packageId: com.example.keyboards.{id}
+bundleName: {name}
+version: 0.1.0
+build: '1'
+";
+
+const CHROMEOS_TARGET: &str = "\
+appId: ''
+version: 0.1.0
+build: '1'
+";
+
+const ANDROID_TARGET: &str = "\
+packageId: com.example.keyboards.{id}
+version: 0.1.0
+build: 1
+";
+
+const IOS_TARGET: &str = "\
+packageId: com.example.keyboards.{id}
+bundleName: {name}
+version: 0.1.0
+build: 1
+";
+
+/// The default and shift keys of a layout, E00 to B10
+struct BaseKeys {
+    default: Vec<Option<String>>,
+    shift: Vec<Option<String>>,
+}
+
+fn parse_base(default: &str, shift: &str) -> BaseKeys {
+    let keys = |text: &str| {
+        text.split_whitespace()
+            .map(|key| KeyAction::parse(key, &[]).output())
+            .collect()
+    };
+
+    BaseKeys {
+        default: keys(default),
+        shift: keys(shift),
+    }
+}
+
+fn builtin_keys(name: &str) -> Option<BaseKeys> {
+    match name {
+        "us" => Some(parse_base(US_DEFAULT, US_SHIFT)),
+        "iso-qwerty" => Some(parse_base(ISO_QWERTY_DEFAULT, ISO_QWERTY_SHIFT)),
+        _ => None,
+    }
+}
+
+/// The keys of the first desktop target of a layout
+fn layout_keys(layout: &Layout) -> Option<BaseKeys> {
+    let keyboard = Platform::ALL
+        .iter()
+        .find_map(|platform| Keyboard::from_layout(layout, *platform))?;
+    let keys = |modifiers| {
+        let layer = keyboard.layer(modifiers);
+        layer_keys()
+            .iter()
+            .map(|iso_key| layer.and_then(|x| x.key(*iso_key).output()))
+            .collect::<Vec<_>>()
+    };
+
+    Some(BaseKeys {
+        default: keys(Modifiers::NONE),
+        shift: keys(Modifiers {
+            shift: true,
+            ..Modifiers::NONE
+        }),
+    })
+}
+
+/// The letters of each row of the base, for the rows of touch keyboards
+fn letter_rows(keys: &BaseKeys) -> [(Vec<String>, Vec<String>); 3] {
+    let row = |first: IsoKey, last: IsoKey| {
+        let mut default = vec![];
+        let mut shift = vec![];
+
+        for index in first as usize..=last as usize {
+            let Some(key) = keys.default.get(index).cloned().flatten() else {
+                continue;
+            };
+            if !key.chars().all(char::is_alphabetic) {
+                continue;
+            }
+
+            let shifted = keys.shift.get(index).cloned().flatten();
+            shift.push(encode_unicode_escapes(
+                &shifted.unwrap_or_else(|| key.to_uppercase()),
+            ));
+            default.push(encode_unicode_escapes(&key));
+        }
+
+        (default, shift)
+    };
+
+    [
+        row(IsoKey::D01, IsoKey::D12),
+        row(IsoKey::C01, IsoKey::C12),
+        row(IsoKey::B00, IsoKey::B10),
+    ]
+}
+
+fn mobile_layer(rows: [String; 3]) -> String {
+    let mut output = rows.join("\n");
+    output.push('\n');
+    output
+}
+
+fn android_platform(rows: &[(Vec<String>, Vec<String>); 3]) -> AndroidPlatform {
+    let layer = |keys: [&Vec<String>; 3]| {
+        mobile_layer([
+            keys[0].join(" "),
+            keys[1].join(" "),
+            format!(r"\s{{shift}} {} \s{{backspace}}", keys[2].join(" ")),
+        ])
+    };
+
+    AndroidPlatform {
+        layers: IndexMap::from([
+            (
+                AndroidKbdLayer::Default,
+                layer([&rows[0].0, &rows[1].0, &rows[2].0]),
+            ),
+            (
+                AndroidKbdLayer::Shift,
+                layer([&rows[0].1, &rows[1].1, &rows[2].1]),
+            ),
+        ]),
+    }
+}
+
+fn iphone_platform(rows: &[(Vec<String>, Vec<String>); 3]) -> IOsPlatform {
+    let layer = |keys: [&Vec<String>; 3]| {
+        mobile_layer([
+            keys[0].join(" "),
+            keys[1].join(" "),
+            format!(
+                r"\s{{shift:1.25}} \s{{spacer:0.25}} {} \s{{spacer:0.25}} \s{{backspace:1.25}}",
+                keys[2].join(" ")
+            ),
+        ])
+    };
+
+    IOsPlatform {
+        layers: IndexMap::from([
+            (
+                IOsKbdLayer::Default,
+                layer([&rows[0].0, &rows[1].0, &rows[2].0]),
+            ),
+            (
+                IOsKbdLayer::Shift,
+                layer([&rows[0].1, &rows[1].1, &rows[2].1]),
+            ),
+        ]),
+    }
+}
+
+fn ipad_platform(rows: &[(Vec<String>, Vec<String>); 3]) -> IOsPlatform {
+    let layer = |keys: [&Vec<String>; 3], punctuation: &str| {
+        mobile_layer([
+            format!(r"{} \s{{backspace:1.25}}", keys[0].join(" ")),
+            format!(r"\s{{spacer:0.5}} {} \s{{return:1.75}}", keys[1].join(" ")),
+            format!(
+                r"\s{{shift}} {} {} \s{{shift}}",
+                keys[2].join(" "),
+                punctuation
+            ),
+        ])
+    };
+
+    IOsPlatform {
+        layers: IndexMap::from([
+            (
+                IOsKbdLayer::Default,
+                layer([&rows[0].0, &rows[1].0, &rows[2].0], ", ."),
+            ),
+            (
+                IOsKbdLayer::Shift,
+                layer([&rows[0].1, &rows[1].1, &rows[2].1], "! ?"),
+            ),
+        ]),
+    }
+}
+
+/// A layout with every target filled in from the keys of a base
+fn template_layout(language_tag: &LanguageTag, keys: &BaseKeys) -> Layout {
+    let default = layer_string(&keys.default);
+    let shift = layer_string(&keys.shift);
+    let rows = letter_rows(keys);
+
+    let autonym_tag: LanguageTag = language_tag.primary_language().parse().unwrap();
+
+    Layout {
+        language_tag: language_tag.clone(),
+        display_names: IndexMap::from([(autonym_tag, language_tag.to_string())]),
+        decimal: None,
+        windows: Some(WindowsTarget {
+            config: None,
+            primary: WindowsPrimaryPlatform {
+                layers: IndexMap::from([
+                    (WindowsKbdLayer::Default, default.clone()),
+                    (WindowsKbdLayer::Shift, shift.clone()),
+                ]),
+            },
+            dead_keys: None,
+        }),
+        chrome_os: Some(ChromeOsTarget {
+            config: None,
+            primary: ChromeOsPrimaryPlatform {
+                layers: IndexMap::from([
+                    (ChromeOsKbdLayer::Default, default.clone()),
+                    (ChromeOsKbdLayer::Shift, shift.clone()),
+                ]),
+            },
+            dead_keys: None,
+        }),
+        mac_os: Some(MacOsTarget {
+            primary: MacOsPrimaryPlatform {
+                layers: IndexMap::from([
+                    (MacOsKbdLayer::Default, default.clone()),
+                    (MacOsKbdLayer::Shift, shift.clone()),
+                ]),
+            },
+            dead_keys: None,
+            space: IndexMap::new(),
+        }),
+        i_os: Some(IOsTarget {
+            config: IOsConfig::default(),
+            primary: Some(iphone_platform(&rows)),
+            i_pad_9in: Some(ipad_platform(&rows)),
+            i_pad_12in: Some(ipad_platform(&rows)),
+            dead_keys: None,
+        }),
+        android: Some(AndroidTarget {
+            config: None,
+            primary: android_platform(&rows),
+            tablet_600: android_platform(&rows),
+        }),
+        linux: Some(LinuxTarget {
+            config: None,
+            primary: LinuxPrimaryPlatform {
+                layers: IndexMap::from([
+                    (LinuxKbdLayer::Default, default),
+                    (LinuxKbdLayer::Shift, shift),
+                ]),
+            },
+            dead_keys: None,
+        }),
+        longpress: None,
+        transforms: None,
+        key_names: None,
+    }
+}
+
+/// Copies a layout under a new language tag, adding the targets it lacks
+fn copy_layout(language_tag: &LanguageTag, layout: &Layout) -> Result<Layout> {
+    let mut value = serde_yaml::to_value(layout)?;
+    if let Some(mapping) = value.as_mapping_mut() {
+        mapping.insert("languageTag".into(), language_tag.to_string().into());
+    }
+    let mut copy: Layout = serde_yaml::from_value(value)?;
+
+    let Some(keys) = layout_keys(layout) else {
+        bail!(
+            "{} has no windows, macOS, chromeOS or linux target to start from",
+            layout.language_tag
+        );
+    };
+    let template = template_layout(language_tag, &keys);
+
+    copy.display_names = template.display_names;
+    copy.windows = copy.windows.or(template.windows);
+    copy.chrome_os = copy.chrome_os.or(template.chrome_os);
+    copy.mac_os = copy.mac_os.or(template.mac_os);
+    copy.i_os = copy.i_os.or(template.i_os);
+    copy.android = copy.android.or(template.android);
+    copy.linux = copy.linux.or(template.linux);
+
+    Ok(copy)
+}
+
+/// Creates a layout for `language_tag` from `from`, which is either a layout
+/// of the bundle or one of the built-in `BASES`
+pub fn new_layout(bundle: &KbdgenBundle, language_tag: &LanguageTag, from: &str) -> Result<Layout> {
+    if bundle.layouts.contains_key(language_tag) {
+        bail!("The bundle already has a layout for {}", language_tag);
+    }
+
+    let existing = from
+        .parse::<LanguageTag>()
+        .ok()
+        .and_then(|tag| bundle.layouts.get(&tag));
+
+    if let Some(layout) = existing {
+        return copy_layout(language_tag, layout);
+    }
+
+    match builtin_keys(from) {
+        Some(keys) => Ok(template_layout(language_tag, &keys)),
+        None => bail!(
+            "'{}' is neither a layout of the bundle nor one of {}",
+            from,
+            BASES.join(", ")
+        ),
+    }
+}
+
+fn random_uuid() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = bytes
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn project_template(name: &str) -> String {
+    format!(
+        "\
+locales:
+  en:
+    name: {name} keyboards
+    description: Keyboard layouts for {name}
+author: ''
+copyright: ''
+email: ''
+organisation: ''
+"
+    )
+}
+
+/// Creates an empty bundle with a project file, templates for each target
+/// and the folders that `read_kbdgen_bundle` expects
+pub fn init_bundle(path: &Path) -> Result<KbdgenBundle> {
+    if path.extension().is_none_or(|x| x != BUNDLE_EXT) {
+        bail!(
+            "Bundle names end in .{}, e.g. sme.{}",
+            BUNDLE_EXT,
+            BUNDLE_EXT
+        );
+    }
+    if path.exists() {
+        bail!("'{}' already exists", path.display());
+    }
+
+    let name = path
+        .file_stem()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let id = name.to_lowercase().replace(['-', ' '], "_");
+
+    fs::create_dir_all(path.join(LAYOUTS_FOLDER))?;
+    fs::create_dir_all(path.join(RESOURCES_FOLDER))?;
+    fs::create_dir_all(path.join(TARGETS_FOLDER))?;
+
+    fs::write(path.join(PROJECT_FILENAME), project_template(&name))?;
+
+    let targets = [
+        ("windows", WINDOWS_TARGET),
+        ("macos", MACOS_TARGET),
+        ("chromeos", CHROMEOS_TARGET),
+        ("android", ANDROID_TARGET),
+        ("ios", IOS_TARGET),
+    ];
+    for (target, template) in targets {
+        let text = template
+            .replace("{name}", &name)
+            .replace("{id}", &id)
+            .replace("{uuid}", &random_uuid());
+        fs::write(
+            path.join(TARGETS_FOLDER)
+                .join(format!("{}.{}", target, YAML_EXT)),
+            text,
+        )?;
+    }
+
+    Ok(read_kbdgen_bundle(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::chromeos::keymap::CHROMEOS_KEYS;
+    use crate::build::macos::keymap::MACOS_KEYS;
+    use crate::build::windows::klc::keymap::MSKLC_KEYS;
+    use crate::bundle::validate::{Severity, validate};
+    use crate::util::split_keys;
+
+    #[test]
+    fn test_new_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sje.kbdgen");
+        let bundle = init_bundle(&path).unwrap();
+
+        let language_tag: LanguageTag = "sje".parse().unwrap();
+        let layout = new_layout(&bundle, &language_tag, "us").unwrap();
+
+        let windows = &layout.windows.as_ref().unwrap().primary.layers;
+        let mac_os = &layout.mac_os.as_ref().unwrap().primary.layers;
+        let chrome_os = &layout.chrome_os.as_ref().unwrap().primary.layers;
+        assert_eq!(
+            split_keys(&windows[&WindowsKbdLayer::Shift]).len(),
+            MSKLC_KEYS.len()
+        );
+        assert_eq!(
+            split_keys(&mac_os[&MacOsKbdLayer::Default]).len(),
+            MACOS_KEYS.len()
+        );
+        assert_eq!(
+            split_keys(&chrome_os[&ChromeOsKbdLayer::Default]).len(),
+            CHROMEOS_KEYS.len()
+        );
+        assert_eq!(
+            layout.android.as_ref().unwrap().primary.layers[&AndroidKbdLayer::Shift],
+            "Q W E R T Y U I O P\nA S D F G H J K L\n\\s{shift} Z X C V B N M \\s{backspace}\n"
+        );
+
+        crate::import::write_layout(&bundle.path, &layout).unwrap();
+        let bundle = read_kbdgen_bundle(&path).unwrap();
+        assert!(
+            validate(&bundle)
+                .iter()
+                .all(|x| x.severity != Severity::Error)
+        );
+
+        let copy = new_layout(&bundle, &"sma".parse().unwrap(), "sje").unwrap();
+        assert_eq!(copy.windows.unwrap().primary.layers, *windows);
+    }
+}
@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LinuxKbdLayer {
+    Default,
+    Shift,
+    Alt,
+    #[serde(rename = "alt+shift")]
+    AltAndShift,
+}
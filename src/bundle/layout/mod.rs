@@ -1,11 +1,12 @@
 use indexmap::IndexMap;
 use language_tags::LanguageTag;
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_yaml::Value;
 
 use android::AndroidKbdLayer;
 use chrome::ChromeOsKbdLayer;
 use ios::IOsKbdLayer;
+use linux::LinuxKbdLayer;
 use macos::MacOsKbdLayer;
 use windows::WindowsKbdLayer;
 
@@ -14,10 +15,12 @@ use crate::util::split_keys;
 pub mod android;
 pub mod chrome;
 pub mod ios;
+pub mod linux;
 pub mod macos;
 pub mod windows;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
 pub enum Transform {
     End(String),
     More(IndexMap<String, Transform>),
@@ -26,27 +29,43 @@ pub enum Transform {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Layout {
+    #[serde(skip_serializing)]
     pub language_tag: LanguageTag,
 
     pub display_names: IndexMap<LanguageTag, String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub decimal: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub windows: Option<WindowsTarget>,
     #[serde(rename = "chromeOS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub chrome_os: Option<ChromeOsTarget>,
     #[serde(rename = "macOS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub mac_os: Option<MacOsTarget>,
     #[serde(rename = "iOS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub i_os: Option<IOsTarget>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub android: Option<AndroidTarget>,
-
-    #[serde(default, deserialize_with = "from_mapped_sequence")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linux: Option<LinuxTarget>,
+
+    #[serde(
+        default,
+        deserialize_with = "from_mapped_sequence",
+        serialize_with = "to_mapped_sequence"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub longpress: Option<IndexMap<String, Vec<String>>>,
 
     #[serde(default, deserialize_with = "from_nested_sequence")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub transforms: Option<IndexMap<String, Transform>>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub key_names: Option<KeyNames>,
 }
 
@@ -63,8 +82,10 @@ impl Layout {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WindowsTarget {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub config: Option<WindowsConfig>,
     pub primary: WindowsPrimaryPlatform,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dead_keys: Option<IndexMap<WindowsKbdLayer, Vec<String>>>,
 }
 
@@ -77,8 +98,10 @@ pub struct WindowsPrimaryPlatform {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ChromeOsTarget {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub config: Option<ChromeConfig>,
     pub primary: ChromeOsPrimaryPlatform,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dead_keys: Option<IndexMap<ChromeOsKbdLayer, Vec<String>>>,
 }
 
@@ -92,8 +115,9 @@ pub struct ChromeOsPrimaryPlatform {
 #[serde(rename_all = "camelCase")]
 pub struct MacOsTarget {
     pub primary: MacOsPrimaryPlatform,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dead_keys: Option<IndexMap<MacOsKbdLayer, Vec<String>>>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
     pub space: IndexMap<MacOsKbdLayer, String>,
 }
 
@@ -107,12 +131,16 @@ pub struct MacOsPrimaryPlatform {
 pub struct IOsTarget {
     #[serde(default)]
     pub config: IOsConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub primary: Option<IOsPlatform>,
     #[serde(rename = "iPad-9in")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub i_pad_9in: Option<IOsPlatform>,
     #[serde(rename = "iPad-12in")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub i_pad_12in: Option<IOsPlatform>,
     #[serde(rename = "deadKeys")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dead_keys: Option<IndexMap<IOsKbdLayer, Vec<String>>>,
 }
 
@@ -123,6 +151,7 @@ pub struct IOsPlatform {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AndroidTarget {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub config: Option<AndroidConfig>,
     pub primary: AndroidPlatform,
     #[serde(rename = "tablet-600")]
@@ -134,6 +163,22 @@ pub struct AndroidPlatform {
     pub layers: IndexMap<AndroidKbdLayer, String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinuxTarget {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config: Option<LinuxConfig>,
+    pub primary: LinuxPrimaryPlatform,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dead_keys: Option<IndexMap<LinuxKbdLayer, Vec<String>>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinuxPrimaryPlatform {
+    pub layers: IndexMap<LinuxKbdLayer, String>,
+}
+
 fn from_mapped_sequence<'de, D>(
     deserializer: D,
 ) -> Result<Option<IndexMap<String, Vec<String>>>, D::Error>
@@ -149,6 +194,19 @@ where
     ))
 }
 
+fn to_mapped_sequence<S>(
+    value: &Option<IndexMap<String, Vec<String>>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value {
+        Some(map) => serializer.collect_map(map.iter().map(|(key, value)| (key, value.join(" ")))),
+        None => serializer.serialize_none(),
+    }
+}
+
 fn from_nested_sequence<'de, D>(
     deserializer: D,
 ) -> Result<Option<IndexMap<String, Transform>>, D::Error>
@@ -198,27 +256,44 @@ pub struct KeyNames {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WindowsConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub locale: Option<LanguageTag>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ChromeConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub locale: Option<LanguageTag>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xkb_layout: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinuxConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub xkb_layout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xkb_variant: Option<String>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IOsConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub speller_package_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub speller_path: Option<String>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AndroidConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub speller_package_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub speller_path: Option<String>,
 }
@@ -0,0 +1,639 @@
+use std::fs;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use indexmap::IndexMap;
+use language_tags::LanguageTag;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_yaml::{Mapping, Value};
+
+use super::layout::{
+    AndroidConfig, AndroidPlatform, AndroidTarget, ChromeConfig, ChromeOsPrimaryPlatform,
+    ChromeOsTarget, IOsConfig, IOsPlatform, IOsTarget, KeyNames, Layout, MacOsPrimaryPlatform,
+    MacOsTarget, Transform, WindowsConfig, WindowsPrimaryPlatform, WindowsTarget,
+    android::AndroidKbdLayer,
+};
+use super::project::Project;
+use super::target;
+use super::{LAYOUTS_FOLDER, PROJECT_FILENAME, TARGETS_FOLDER, YAML_EXT};
+use crate::util::{diff_lines, split_keys};
+
+/// Target files that kbdgen 2 named after its mode names
+const TARGET_RENAMES: &[(&str, &str)] =
+    &[("win", "windows"), ("mac", "macos"), ("chrome", "chromeos")];
+
+const SHIFT_KEY: &str = r"\s{shift}";
+const BACKSPACE_KEY: &str = r"\s{backspace}";
+
+/// A file in the bundle that is created, rewritten or removed by a migration
+#[derive(Debug)]
+pub struct Change {
+    pub path: PathBuf,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl Change {
+    pub fn diff(&self) -> String {
+        let name = |text: &Option<String>| match text {
+            Some(_) => self.path.display().to_string(),
+            None => "/dev/null".to_string(),
+        };
+
+        format!(
+            "--- {}\n+++ {}\n{}",
+            name(&self.old),
+            name(&self.new),
+            diff_lines(
+                self.old.as_deref().unwrap_or_default(),
+                self.new.as_deref().unwrap_or_default()
+            )
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct V2Layout {
+    display_names: IndexMap<LanguageTag, String>,
+    #[serde(default)]
+    decimal: Option<String>,
+    modes: IndexMap<String, IndexMap<String, String>>,
+    #[serde(default)]
+    dead_keys: IndexMap<String, IndexMap<String, Vec<String>>>,
+    #[serde(default)]
+    space: IndexMap<String, IndexMap<String, String>>,
+    #[serde(default)]
+    longpress: Option<IndexMap<String, String>>,
+    #[serde(default)]
+    transforms: Option<IndexMap<String, Transform>>,
+    #[serde(default)]
+    strings: Option<Value>,
+    #[serde(default)]
+    targets: IndexMap<String, Value>,
+    #[serde(flatten)]
+    rest: IndexMap<String, Value>,
+}
+
+/// Works out the changes needed to bring a kbdgen 2 bundle to the current
+/// format, without touching the bundle
+pub fn migrate_bundle(bundle_path: &Path) -> Result<Vec<Change>> {
+    let mut changes = vec![];
+
+    let project_path = bundle_path.join(PROJECT_FILENAME);
+    let text = fs::read_to_string(&project_path)
+        .with_context(|| format!("Reading '{}'", project_path.display()))?;
+    if let Some(new) = migrate_yaml::<Project>(&project_path, &text, &[], &[])? {
+        changes.push(Change {
+            path: project_path,
+            old: Some(text),
+            new: Some(new),
+        });
+    }
+
+    for path in yaml_files(&bundle_path.join(LAYOUTS_FOLDER))? {
+        let text =
+            fs::read_to_string(&path).with_context(|| format!("Reading '{}'", path.display()))?;
+        let tag = path
+            .file_stem()
+            .map(|x| x.to_string_lossy())
+            .unwrap_or_default();
+        let tag: LanguageTag = tag
+            .parse()
+            .with_context(|| format!("'{}' is not a valid language tag", tag))?;
+
+        let layout = migrate_layout(tag, &text)
+            .with_context(|| format!("Migrating '{}'", path.display()))?;
+        if let Some(layout) = layout {
+            changes.push(Change {
+                path,
+                old: Some(text),
+                new: Some(serde_yaml::to_string(&layout)?),
+            });
+        }
+    }
+
+    let targets_path = bundle_path.join(TARGETS_FOLDER);
+    for path in yaml_files(&targets_path)? {
+        let stem = path
+            .file_stem()
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let name = TARGET_RENAMES
+            .iter()
+            .find(|(old, _)| *old == stem)
+            .map_or(stem.as_str(), |(_, new)| *new);
+
+        let text =
+            fs::read_to_string(&path).with_context(|| format!("Reading '{}'", path.display()))?;
+        let new = match name {
+            "windows" => migrate_yaml::<target::Windows>(&path, &text, &["version", "build"], &[])?,
+            "macos" => migrate_yaml::<target::MacOS>(&path, &text, &["version", "build"], &[])?,
+            "chromeos" => {
+                migrate_yaml::<target::ChromeOS>(&path, &text, &["version", "build"], &[])?
+            }
+            "keyman" => migrate_yaml::<target::Keyman>(&path, &text, &["version"], &[])?,
+            "ios" => migrate_yaml::<target::iOS>(&path, &text, &["version"], &["build"])?,
+            "android" => migrate_yaml::<target::Android>(&path, &text, &["version"], &["build"])?,
+            name => {
+                tracing::warn!("Skipping target file {name}.yaml, it is not used by kbdgen 3");
+                continue;
+            }
+        };
+
+        let new_path = targets_path.join(format!("{}.{}", name, YAML_EXT));
+        if new_path == path {
+            if let Some(new) = new {
+                changes.push(Change {
+                    path,
+                    old: Some(text),
+                    new: Some(new),
+                });
+            }
+            continue;
+        }
+
+        if new_path.exists() {
+            bail!(
+                "Cannot rename '{}' to '{}' as it already exists",
+                path.display(),
+                new_path.display()
+            );
+        }
+
+        changes.push(Change {
+            path: new_path,
+            old: None,
+            new: Some(new.unwrap_or_else(|| text.clone())),
+        });
+        changes.push(Change {
+            path,
+            old: Some(text),
+            new: None,
+        });
+    }
+
+    Ok(changes)
+}
+
+/// Copies the bundle to a `.bak` folder next to it, then writes the changes
+/// to the bundle, returning the path of the backup
+pub fn apply_migration(bundle_path: &Path, changes: &[Change]) -> Result<PathBuf> {
+    let bundle_path = dunce::canonicalize(bundle_path)
+        .with_context(|| format!("Reading '{}'", bundle_path.display()))?;
+    let file_name = bundle_path
+        .file_name()
+        .context("Bundle path has no file name")?
+        .to_string_lossy();
+    let backup_path = bundle_path.with_file_name(format!("{}.bak", file_name));
+
+    if backup_path.exists() {
+        bail!(
+            "Backup '{}' already exists, remove it first to migrate again",
+            backup_path.display()
+        );
+    }
+
+    dircpy::copy_dir(&bundle_path, &backup_path)
+        .with_context(|| format!("Backing up to '{}'", backup_path.display()))?;
+
+    for change in changes {
+        match &change.new {
+            Some(new) => fs::write(&change.path, new),
+            None => fs::remove_file(&change.path),
+        }
+        .with_context(|| format!("Writing to '{}'", change.path.display()))?;
+    }
+
+    Ok(backup_path)
+}
+
+fn yaml_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut paths = fs::read_dir(path)
+        .with_context(|| format!("Reading '{}'", path.display()))?
+        .filter_map(Result::ok)
+        .map(|file| file.path())
+        .filter(|path| path.is_file())
+        .filter(|path| path.extension().is_some_and(|ext| ext == YAML_EXT))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Round trips a YAML file through its current serde type, coercing the
+/// given scalar fields first, and returns the new text if anything changed
+fn migrate_yaml<T>(
+    path: &Path,
+    text: &str,
+    string_fields: &[&str],
+    number_fields: &[&str],
+) -> Result<Option<String>>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let original: Value =
+        serde_yaml::from_str(text).with_context(|| format!("Parsing '{}'", path.display()))?;
+
+    let mut value = original.clone();
+    if let Some(mapping) = value.as_mapping_mut() {
+        coerce_fields(mapping, string_fields, number_fields);
+    }
+
+    let parsed: T = serde_yaml::from_value(value)
+        .with_context(|| format!("'{}' cannot be migrated", path.display()))?;
+    let mut migrated = serde_yaml::to_value(&parsed)?;
+    if let Some(mapping) = migrated.as_mapping_mut() {
+        mapping.retain(|_, value| !value.is_null());
+    }
+
+    if migrated == original {
+        return Ok(None);
+    }
+
+    if let (Some(original), Some(migrated)) = (original.as_mapping(), migrated.as_mapping()) {
+        for key in original.keys().filter(|key| !migrated.contains_key(*key)) {
+            tracing::warn!(
+                "Dropping {} from {}, it is not used by kbdgen 3",
+                serde_yaml::to_string(key)?.trim(),
+                path.display()
+            );
+        }
+    }
+
+    Ok(Some(serde_yaml::to_string(&migrated)?))
+}
+
+fn coerce_fields(mapping: &mut Mapping, string_fields: &[&str], number_fields: &[&str]) {
+    for field in string_fields {
+        if let Some(Value::Number(number)) = mapping.get(*field) {
+            let value = Value::String(number.to_string());
+            mapping.insert(Value::from(*field), value);
+        }
+    }
+
+    for field in number_fields {
+        let number = mapping
+            .get(*field)
+            .and_then(Value::as_str)
+            .and_then(|x| x.trim().parse::<u64>().ok());
+        if let Some(number) = number {
+            mapping.insert(Value::from(*field), Value::Number(number.into()));
+        }
+    }
+}
+
+/// Converts a kbdgen 2 layout, which keeps its layers under `modes`, into a
+/// current layout. Returns `None` if the layout is already current.
+fn migrate_layout(language_tag: LanguageTag, text: &str) -> Result<Option<Layout>> {
+    let value: Value = serde_yaml::from_str(text)?;
+    if value.get("modes").is_none() {
+        return Ok(None);
+    }
+
+    let V2Layout {
+        display_names,
+        decimal,
+        mut modes,
+        mut dead_keys,
+        mut space,
+        longpress,
+        transforms,
+        strings,
+        targets,
+        rest,
+    } = serde_yaml::from_value(value)?;
+
+    let windows = modes.shift_remove("win").map(|layers| WindowsTarget {
+        config: target_config::<WindowsConfig>(&targets, "win"),
+        primary: WindowsPrimaryPlatform {
+            layers: convert_layers("win", layers),
+        },
+        dead_keys: convert_dead_keys(&mut dead_keys, "win"),
+    });
+
+    let mac_os = modes.shift_remove("mac").map(|layers| MacOsTarget {
+        primary: MacOsPrimaryPlatform {
+            layers: convert_layers("mac", layers),
+        },
+        dead_keys: convert_dead_keys(&mut dead_keys, "mac"),
+        space: space
+            .shift_remove("mac")
+            .map(|space| convert_layers("mac", space))
+            .unwrap_or_default(),
+    });
+
+    let chrome_os = modes.shift_remove("chrome").map(|layers| ChromeOsTarget {
+        config: target_config::<ChromeConfig>(&targets, "chrome"),
+        primary: ChromeOsPrimaryPlatform {
+            layers: convert_layers("chrome", layers),
+        },
+        dead_keys: convert_dead_keys(&mut dead_keys, "chrome"),
+    });
+
+    let i_pad_9in = modes.shift_remove("ipad-9in");
+    let i_pad_12in = modes.shift_remove("ipad-12in");
+    let i_os = modes.shift_remove("ios").map(|layers| {
+        let layers = layers
+            .into_iter()
+            .map(|(name, layer)| (name, normalize_layer(&layer)))
+            .collect();
+
+        IOsTarget {
+            config: target_config::<IOsConfig>(&targets, "ios").unwrap_or_default(),
+            primary: Some(IOsPlatform {
+                layers: convert_layers("ios", layers),
+            }),
+            i_pad_9in: i_pad_9in.map(|layers| IOsPlatform {
+                layers: convert_layers("ipad-9in", layers),
+            }),
+            i_pad_12in: i_pad_12in.map(|layers| IOsPlatform {
+                layers: convert_layers("ipad-12in", layers),
+            }),
+            dead_keys: convert_dead_keys(&mut dead_keys, "ios"),
+        }
+    });
+
+    let android = modes.shift_remove("android").map(|layers| {
+        let actions = targets
+            .get("android")
+            .and_then(|x| x.get("styles"))
+            .and_then(|x| x.get("phone"))
+            .and_then(|x| x.get("actions"));
+        let action_row = |action: &str| {
+            actions
+                .and_then(|x| x.get(action))
+                .and_then(|x| x.get(0))
+                .and_then(Value::as_u64)
+                .map(|row| row as usize)
+        };
+        let (shift_row, backspace_row) = (action_row("shift"), action_row("backspace"));
+
+        let mut layers: IndexMap<AndroidKbdLayer, String> = convert_layers("android", layers);
+        for layer in layers.values_mut() {
+            *layer = add_android_actions(layer, shift_row, backspace_row);
+        }
+
+        tracing::warn!("kbdgen 2 has no tablet layout for android, using the phone layout");
+        AndroidTarget {
+            config: target_config::<AndroidConfig>(&targets, "android"),
+            primary: AndroidPlatform {
+                layers: layers.clone(),
+            },
+            tablet_600: AndroidPlatform { layers },
+        }
+    });
+
+    for mode in modes.keys() {
+        tracing::warn!("Dropping mode '{mode}', it has no equivalent in kbdgen 3");
+    }
+    for mode in dead_keys.keys() {
+        tracing::warn!("Dropping dead keys of mode '{mode}', it has no equivalent in kbdgen 3");
+    }
+    for mode in space.keys() {
+        tracing::warn!("Dropping space of mode '{mode}', it has no equivalent in kbdgen 3");
+    }
+    for key in rest.keys() {
+        tracing::warn!("Dropping '{key}', it has no equivalent in kbdgen 3");
+    }
+
+    let key_names = strings.and_then(|strings| {
+        serde_yaml::from_value::<KeyNames>(strings)
+            .map_err(|e| tracing::warn!("Dropping strings, they cannot be used as keyNames: {e}"))
+            .ok()
+    });
+
+    Ok(Some(Layout {
+        language_tag,
+        display_names,
+        decimal,
+        windows,
+        chrome_os,
+        mac_os,
+        i_os,
+        android,
+        linux: None,
+        longpress: longpress.map(|longpress| {
+            longpress
+                .into_iter()
+                .map(|(key, value)| (key, split_keys(&value)))
+                .collect()
+        }),
+        transforms,
+        key_names,
+    }))
+}
+
+/// Renames kbdgen 2 layers to their current layer type, dropping those that
+/// no longer exist
+fn convert_layers<K, V>(mode: &str, layers: IndexMap<String, V>) -> IndexMap<K, V>
+where
+    K: DeserializeOwned + Eq + Hash,
+{
+    layers
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let name = match name.as_str() {
+                "caps+alt" => "alt+caps".to_string(),
+                _ => name,
+            };
+
+            match serde_yaml::from_value(Value::String(name.clone())) {
+                Ok(layer) => Some((layer, value)),
+                Err(_) => {
+                    tracing::warn!(
+                        "Dropping layer '{name}' of mode '{mode}', it has no equivalent in kbdgen 3"
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn convert_dead_keys<K>(
+    dead_keys: &mut IndexMap<String, IndexMap<String, Vec<String>>>,
+    mode: &str,
+) -> Option<IndexMap<K, Vec<String>>>
+where
+    K: DeserializeOwned + Eq + Hash,
+{
+    dead_keys
+        .shift_remove(mode)
+        .map(|layers| convert_layers(mode, layers))
+        .filter(|layers| !layers.is_empty())
+}
+
+fn target_config<T: DeserializeOwned>(targets: &IndexMap<String, Value>, mode: &str) -> Option<T> {
+    let config = targets.get(mode)?;
+
+    serde_yaml::from_value(config.clone())
+        .map_err(|e| tracing::warn!("Dropping targets.{mode}, it cannot be used as config: {e}"))
+        .ok()
+}
+
+fn normalize_layer(layer: &str) -> String {
+    layer
+        .lines()
+        .map(|row| split_keys(row).join(" "))
+        .filter(|row| !row.is_empty())
+        .map(|row| format!("{}\n", row))
+        .collect()
+}
+
+/// Adds the shift and backspace keys that kbdgen 2 placed on the android
+/// layout implicitly, on the given one-based rows or else the last row
+fn add_android_actions(
+    layer: &str,
+    shift_row: Option<usize>,
+    backspace_row: Option<usize>,
+) -> String {
+    let mut rows = layer
+        .lines()
+        .map(split_keys)
+        .filter(|row| !row.is_empty())
+        .collect::<Vec<_>>();
+
+    if rows.is_empty() || rows.iter().flatten().any(|key| key == SHIFT_KEY) {
+        return normalize_layer(layer);
+    }
+
+    let row_index = |row: Option<usize>| {
+        row.filter(|row| (1..=rows.len()).contains(row))
+            .map_or(rows.len() - 1, |row| row - 1)
+    };
+    let (shift_index, backspace_index) = (row_index(shift_row), row_index(backspace_row));
+
+    rows[shift_index].insert(0, SHIFT_KEY.to_string());
+    rows[backspace_index].push(BACKSPACE_KEY.to_string());
+
+    rows.into_iter()
+        .map(|row| format!("{}\n", row.join(" ")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::layout::{ios::IOsKbdLayer, macos::MacOsKbdLayer, windows::WindowsKbdLayer};
+
+    #[test]
+    fn test_v2_layout_to_layout() {
+        let text = r#"
+displayNames:
+  se: Davvisámegiella
+modes:
+  win:
+    default: |
+      § 1 2 3 4 5 6 7 8 9 0 + ´
+      á š e r t y u i o p å ŋ
+      a s d f g h j k l ö ä đ
+      ž z č c v b n m , . -
+    caps+alt: |
+      \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0}
+      q w € \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0}
+      \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0}
+      \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0}
+  mac:
+    default: |
+      § 1 2 3 4 5 6 7 8 9 0 + ´
+      á š e r t y u i o p å ŋ
+      a s d f g h j k l ö ä đ
+      ž z č c v b n m , . -
+  android:
+    default: |
+      á š e r t y u i o p ŋ
+      a s d f g h j k l đ ŧ
+      ž z č c v b n m
+    shift: |
+      Á Š E R T Y U I O P Ŋ
+      A S D F G H J K L Đ Ŧ
+      Ž Z Č C V B N M
+  ios:
+    default: |
+      á   š e r t y u i o p ŋ
+      a s d f g h j k l đ ŧ
+  ipad-9in:
+    default: q w e
+deadKeys:
+  win:
+    default: ["´"]
+  mac:
+    default: ["´"]
+space:
+  mac:
+    caps+alt: " "
+longpress:
+  a: á à
+transforms:
+  ´:
+    a: á
+    " ": ´
+strings:
+  space: space
+  return: return
+targets:
+  win:
+    locale: se-Latn-NO
+    id: SE
+  android:
+    spellerPath: se.bhfst
+"#;
+
+        let layout = migrate_layout("se".parse().unwrap(), text)
+            .unwrap()
+            .expect("v2 layout");
+
+        let windows = layout.windows.as_ref().unwrap();
+        assert!(
+            windows
+                .primary
+                .layers
+                .contains_key(&WindowsKbdLayer::AltAndCaps)
+        );
+        assert_eq!(windows.config.as_ref().unwrap().id.as_deref(), Some("SE"));
+        assert_eq!(
+            windows.dead_keys.as_ref().unwrap()[&WindowsKbdLayer::Default],
+            vec!["´"]
+        );
+
+        let mac_os = layout.mac_os.as_ref().unwrap();
+        assert_eq!(mac_os.space[&MacOsKbdLayer::AltAndCaps], "\u{a0}");
+
+        let android = layout.android.as_ref().unwrap();
+        assert_eq!(
+            android.primary.layers[&AndroidKbdLayer::Shift]
+                .lines()
+                .last(),
+            Some(r"\s{shift} Ž Z Č C V B N M \s{backspace}")
+        );
+        assert_eq!(
+            android.config.as_ref().unwrap().speller_path.as_deref(),
+            Some("se.bhfst")
+        );
+
+        let i_os = layout.i_os.as_ref().unwrap();
+        assert_eq!(
+            i_os.primary.as_ref().unwrap().layers[&IOsKbdLayer::Default],
+            "á š e r t y u i o p ŋ\na s d f g h j k l đ ŧ\n"
+        );
+        assert!(i_os.i_pad_9in.is_some());
+
+        assert_eq!(layout.longpress.as_ref().unwrap()["a"], vec!["á", "à"]);
+        assert_eq!(layout.key_names.as_ref().unwrap().space, "space");
+
+        assert!(
+            migrate_layout(
+                "se".parse().unwrap(),
+                &serde_yaml::to_string(&layout).unwrap()
+            )
+            .unwrap()
+            .is_none()
+        );
+    }
+}
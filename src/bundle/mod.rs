@@ -13,12 +13,17 @@ use serde_yaml::Value;
 use target::Targets;
 
 use self::resources::Resources;
+use self::source::{SourceFile, key_name};
 
 pub(crate) mod fetch;
+pub mod init;
 pub mod layout;
+pub mod migrate;
 pub(crate) mod project;
 pub(crate) mod resources;
+pub mod source;
 pub(crate) mod target;
+pub mod validate;
 
 pub use fetch::fetch;
 
@@ -37,6 +42,7 @@ pub struct KbdgenBundle {
     pub path: PathBuf,
     pub project: Project,
     pub layouts: HashMap<LanguageTag, Layout>,
+    pub sources: HashMap<LanguageTag, SourceFile>,
     pub targets: Targets,
     pub resources: Resources,
 }
@@ -50,6 +56,27 @@ impl KbdgenBundle {
             .expect("Must be valid utf-8")
     }
 
+    /// Builds an error about a key of a layer, pointing at where it is or
+    /// would be in the layout file
+    pub fn key_error(
+        &self,
+        language_tag: &LanguageTag,
+        path: &[&str],
+        index: usize,
+        message: String,
+    ) -> Error {
+        let snippet = self
+            .sources
+            .get(language_tag)
+            .and_then(|source| {
+                let span = source.key_span(path, index)?;
+                Some(source.snippet(span, &format!("expected {} here", key_name(index))))
+            })
+            .unwrap_or_default();
+
+        Error::InvalidKey { message, snippet }
+    }
+
     #[cfg(test)]
     pub fn new_test(name: String, layouts: IndexMap<LanguageTag, Layout>) -> Self {
         KbdgenBundle {
@@ -63,12 +90,28 @@ impl KbdgenBundle {
                 dependencies: IndexMap::new(),
             },
             layouts: layouts.into_iter().collect(),
+            sources: HashMap::new(),
             targets: target::Targets::default(),
             resources: resources::Resources::default(),
         }
     }
 }
 
+/// Reads a layout from YAML the way `read_layouts` does, with the language
+/// tag that would come from its file name
+#[cfg(test)]
+pub fn test_layout(language_tag: &str, yaml_text: &str) -> Layout {
+    let mut yaml: Value = serde_yaml::from_str(yaml_text).unwrap();
+    yaml.as_mapping_mut()
+        .expect("top level yaml type must be a mapping")
+        .insert(
+            Value::String("languageTag".to_owned()),
+            Value::String(language_tag.to_owned()),
+        );
+
+    serde_yaml::from_value(yaml).unwrap()
+}
+
 pub fn read_kbdgen_bundle(path: &Path) -> Result<KbdgenBundle, Error> {
     let canonical_bundle_path: PathBuf =
         canonicalize(path).map_err(|e| Error::Io(path.to_path_buf(), e))?;
@@ -78,14 +121,19 @@ pub fn read_kbdgen_bundle(path: &Path) -> Result<KbdgenBundle, Error> {
     let project_text = fs::read_to_string(canonical_bundle_path.join(PROJECT_FILENAME))
         .map_err(|e| Error::Io(path.to_path_buf(), e))?;
     let deserializer = serde_yaml::Deserializer::from_str(&project_text);
-    let project: Project = serde_path_to_error::deserialize(deserializer)
-        .map_err(|e| Error::Yaml(path.to_path_buf(), e))?;
+    let project: Project = serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        yaml_error(
+            &canonical_bundle_path.join(PROJECT_FILENAME),
+            &project_text,
+            e,
+        )
+    })?;
 
     let layouts_path = canonical_bundle_path.join(LAYOUTS_FOLDER);
     let targets_path = canonical_bundle_path.join(TARGETS_FOLDER);
     let resources_path = canonical_bundle_path.join(RESOURCES_FOLDER);
 
-    let layouts = read_layouts(&layouts_path)?;
+    let (layouts, sources) = read_layouts(&layouts_path)?;
     let targets = read_targets(&targets_path)?;
     let resources = read_resources(&resources_path)?;
 
@@ -93,14 +141,20 @@ pub fn read_kbdgen_bundle(path: &Path) -> Result<KbdgenBundle, Error> {
         path: canonical_bundle_path,
         project,
         layouts,
+        sources,
         targets,
         resources,
     })
 }
 
-fn read_layouts(path: &Path) -> Result<HashMap<LanguageTag, Layout>, Error> {
+type Layouts = (
+    HashMap<LanguageTag, Layout>,
+    HashMap<LanguageTag, SourceFile>,
+);
+
+fn read_layouts(path: &Path) -> Result<Layouts, Error> {
     tracing::debug!("Reading layouts");
-    read_dir(path)
+    let layouts = read_dir(path)
         .map_err(|e| Error::Io(path.to_path_buf(), e))?
         .filter_map(Result::ok)
         .map(|file| file.path())
@@ -124,7 +178,7 @@ fn read_layouts(path: &Path) -> Result<HashMap<LanguageTag, Layout>, Error> {
                 fs::read_to_string(&path).map_err(|e| Error::Io(path.to_path_buf(), e))?;
             let deserializer = serde_yaml::Deserializer::from_str(&yaml_text);
             let mut yaml: Value = serde_path_to_error::deserialize(deserializer)
-                .map_err(|e| Error::Yaml(path.to_path_buf(), e))?;
+                .map_err(|e| yaml_error(&path, &yaml_text, e))?;
             yaml.as_mapping_mut()
                 .expect("top level yaml type must be a mapping")
                 .insert(
@@ -133,7 +187,7 @@ fn read_layouts(path: &Path) -> Result<HashMap<LanguageTag, Layout>, Error> {
                 );
 
             let mut layout: Layout = serde_path_to_error::deserialize(yaml)
-                .map_err(|e| Error::Yaml(path.to_path_buf(), e))?;
+                .map_err(|e| yaml_error(&path, &yaml_text, e))?;
 
             let _autonym = match layout
                 .display_names
@@ -158,9 +212,72 @@ fn read_layouts(path: &Path) -> Result<HashMap<LanguageTag, Layout>, Error> {
                 }
             };
 
-            Ok((tag, layout))
+            let source = SourceFile::parse(path, yaml_text);
+
+            Ok((tag, layout, source))
         })
-        .collect()
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let mut sources = HashMap::new();
+    let layouts = layouts
+        .into_iter()
+        .map(|(tag, layout, source)| {
+            sources.insert(tag.clone(), source);
+            (tag, layout)
+        })
+        .collect();
+
+    Ok((layouts, sources))
+}
+
+/// Wraps a YAML error with a snippet of where it happened in the file
+fn yaml_error(
+    path: &Path,
+    text: &str,
+    error: serde_path_to_error::Error<serde_yaml::Error>,
+) -> Error {
+    let source = SourceFile::parse(path.to_path_buf(), text.to_string());
+
+    let segments = error
+        .path()
+        .iter()
+        .filter_map(|segment| match segment {
+            serde_path_to_error::Segment::Map { key } => Some(key.clone()),
+            serde_path_to_error::Segment::Enum { variant } => Some(variant.clone()),
+            serde_path_to_error::Segment::Seq { .. } | serde_path_to_error::Segment::Unknown => {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    let segments = segments.iter().map(String::as_str).collect::<Vec<_>>();
+
+    // Misspelled keys are reported at their parent mapping, point at the key
+    // itself when it can be found
+    let message = error.inner().to_string();
+    let unknown_key = ["unknown variant `", "unknown field `"]
+        .iter()
+        .find_map(|prefix| message.strip_prefix(prefix))
+        .and_then(|rest| rest.split_once('`'))
+        .map(|(key, _)| key);
+    let unknown_key_span = unknown_key.and_then(|key| {
+        let path = [segments.as_slice(), &[key]].concat();
+        source.exact_span(&path)
+    });
+
+    let span = match (unknown_key_span, error.inner().location()) {
+        (Some(span), _) => Some(span),
+        (None, Some(location)) => Some(source::Span {
+            line: location.line(),
+            column: location.column(),
+            length: 1,
+        }),
+        (None, None) => source.span(&segments),
+    };
+    let snippet = span
+        .map(|span| source.snippet(span, &message))
+        .unwrap_or_default();
+
+    Error::Yaml(path.to_path_buf(), error, snippet)
 }
 
 fn load_yaml<T>(path: &Path) -> Result<T, Error>
@@ -175,7 +292,7 @@ where
     };
 
     let deserializer = serde_yaml::Deserializer::from_str(&s);
-    serde_path_to_error::deserialize(deserializer).map_err(|e| Error::Yaml(path.to_path_buf(), e))
+    serde_path_to_error::deserialize(deserializer).map_err(|e| yaml_error(path, &s, e))
 }
 
 fn load_yaml_with_env<T>(path: &Path, env_vars: HashMap<&str, &str>) -> Result<T, Error>
@@ -190,8 +307,8 @@ where
     };
 
     let deserializer = serde_yaml::Deserializer::from_str(&s);
-    let mut raw: serde_yaml::Value = serde_path_to_error::deserialize(deserializer)
-        .map_err(|e| Error::Yaml(path.to_path_buf(), e))?;
+    let mut raw: serde_yaml::Value =
+        serde_path_to_error::deserialize(deserializer).map_err(|e| yaml_error(path, &s, e))?;
 
     {
         let root = raw.as_mapping_mut().unwrap();
@@ -211,7 +328,7 @@ where
 
     match serde_path_to_error::deserialize(raw) {
         Ok(v) => Ok(v),
-        Err(e) => Err(Error::Yaml(path.to_path_buf(), e)),
+        Err(e) => Err(yaml_error(path, &s, e)),
     }
 }
 
@@ -241,6 +358,9 @@ fn read_resources(path: &Path) -> Result<Resources, Error> {
             "ios" => {
                 resources.ios = resources::IOS::load(&path).ok();
             }
+            "exemplars" => {
+                resources.exemplars = resources::Exemplars::load(&path).ok();
+            }
             name => {
                 tracing::warn!("Saw resource folder with name {name} but did not parse");
                 continue;
@@ -294,6 +414,7 @@ fn read_targets(path: &Path) -> Result<Targets, Error> {
                 targets.macos = load_yaml(&path)?;
             }
             "chromeos" => targets.chromeos = load_yaml(&path)?,
+            "keyman" => targets.keyman = load_yaml(&path)?,
             "android" => {
                 targets.android = load_yaml_with_env(
                     &path,
@@ -323,10 +444,11 @@ pub enum Error {
     #[error("IO error for path: {0}")]
     Io(PathBuf, #[source] std::io::Error),
 
-    #[error("Error parsing YAML for path: {0} at {}", .1.path())]
+    #[error("Error parsing YAML for path: {0} at {}{2}", .1.path())]
     Yaml(
         PathBuf,
         #[source] serde_path_to_error::Error<serde_yaml::Error>,
+        String,
     ),
 
     #[error(".yaml files must have a stem, failed to parse: `{}`", path.display())]
@@ -337,4 +459,7 @@ pub enum Error {
 
     #[error("Missing mandatory display name for language tag: `{}`", tag)]
     MissingMandatoryDisplayName { tag: String },
+
+    #[error("{message}{snippet}")]
+    InvalidKey { message: String, snippet: String },
 }
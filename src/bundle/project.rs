@@ -8,7 +8,7 @@ pub struct Project {
     pub copyright: String,
     pub email: String,
     pub organisation: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
     pub dependencies: IndexMap<String, Dependency>,
 }
 
@@ -16,7 +16,7 @@ pub struct Project {
 pub struct Dependency {
     pub url: String,
     pub layouts: Vec<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub branch: Option<String>,
 }
 
@@ -9,6 +9,7 @@ pub struct Resources {
     pub(crate) chromeos: Option<ChromeOS>,
     pub(crate) android: Option<Android>,
     pub(crate) ios: Option<IOS>,
+    pub(crate) exemplars: Option<Exemplars>,
 }
 
 #[derive(Debug, Default)]
@@ -120,3 +121,31 @@ impl Android {
         Ok(Self { icon })
     }
 }
+
+/// Exemplar character lists, one `<tag>.txt` file per layout
+#[derive(Debug, Default)]
+pub(crate) struct Exemplars {
+    pub(crate) files: IndexMap<LanguageTag, PathBuf>,
+}
+
+impl Exemplars {
+    pub fn load(path: &Path) -> Result<Self, std::io::Error> {
+        let files = std::fs::read_dir(path)?
+            .filter_map(Result::ok)
+            .map(|x| x.path())
+            .filter(|x| x.extension().is_some_and(|x| x == "txt"))
+            .filter_map(|x| {
+                let stem = x.file_stem()?.to_string_lossy();
+                match stem.parse::<LanguageTag>() {
+                    Ok(lang_tag) => Some((lang_tag, x)),
+                    Err(_) => {
+                        tracing::warn!("Exemplar file {} is not named after a language tag", stem);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Ok(Self { files })
+    }
+}
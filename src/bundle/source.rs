@@ -0,0 +1,410 @@
+use std::path::PathBuf;
+
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use crate::util::iso_key::IsoKey;
+
+/// A position in a YAML file, counted in characters from 1 like editors do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+/// The text of a bundle YAML file along with where each mapping entry is, so
+/// that problems can be pointed at by their YAML path.
+///
+/// This only understands the block style YAML that bundles are written in,
+/// values in flow style are located by their key.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    text: String,
+    entries: IndexMap<Vec<String>, Entry>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    key: Span,
+    /// Each line of the value, from a block scalar, a sequence or a plain
+    /// scalar that continues over several lines
+    value: Vec<Span>,
+}
+
+impl SourceFile {
+    pub fn parse(path: PathBuf, text: String) -> Self {
+        let mut entries = IndexMap::<Vec<String>, Entry>::new();
+        let mut keys: Vec<(usize, String)> = vec![];
+        let mut last_entry: Option<Vec<String>> = None;
+        // Indentation of the key whose block scalar is being read
+        let mut block_indent: Option<usize> = None;
+
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let indent = line.chars().take_while(|c| *c == ' ').count();
+            let content = line[indent..].trim_end();
+
+            let value_line = Span {
+                line: number,
+                column: indent + 1,
+                length: content.chars().count(),
+            };
+
+            if let Some(parent_indent) = block_indent {
+                if content.is_empty() {
+                    continue;
+                }
+
+                if indent > parent_indent {
+                    if let Some(entry) = last_entry.as_ref().and_then(|x| entries.get_mut(x)) {
+                        entry.value.push(value_line);
+                    }
+                    continue;
+                }
+
+                block_indent = None;
+            }
+
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+
+            let key = match content.starts_with('-') {
+                true => None,
+                false => parse_key(content),
+            };
+
+            let Some((key, key_length, value_start)) = key else {
+                // Items of a block sequence leave out their `- ` marker
+                let value_line = match content.strip_prefix("- ") {
+                    Some(item) => Span {
+                        column: value_line.column + 2,
+                        length: item.chars().count(),
+                        ..value_line
+                    },
+                    None => value_line,
+                };
+
+                if let Some(entry) = last_entry.as_ref().and_then(|x| entries.get_mut(x)) {
+                    entry.value.push(value_line);
+                }
+                continue;
+            };
+
+            while keys
+                .last()
+                .is_some_and(|(key_indent, _)| *key_indent >= indent)
+            {
+                keys.pop();
+            }
+            keys.push((indent, key));
+
+            let mut entry = Entry {
+                key: Span {
+                    line: number,
+                    column: indent + 1,
+                    length: key_length,
+                },
+                value: vec![],
+            };
+
+            let value = content[value_start..].trim_start();
+            if value.starts_with('|') || value.starts_with('>') {
+                block_indent = Some(indent);
+            } else if !value.is_empty() && !value.starts_with(['#', '[', '{']) {
+                let skipped = content[..content.len() - value.len()].chars().count();
+                entry.value.push(Span {
+                    line: number,
+                    column: indent + skipped + 1,
+                    length: value.chars().count(),
+                });
+            }
+
+            let path = keys.iter().map(|(_, key)| key.clone()).collect::<Vec<_>>();
+            entries.insert(path.clone(), entry);
+            last_entry = Some(path);
+        }
+
+        SourceFile {
+            path,
+            text,
+            entries,
+        }
+    }
+
+    /// Finds the key of the entry at `path`, or of its closest ancestor that
+    /// is in the file when the entry itself is missing
+    pub fn span(&self, path: &[&str]) -> Option<Span> {
+        (0..=path.len())
+            .rev()
+            .find_map(|length| self.entry(&path[..length]))
+            .map(|entry| entry.key)
+    }
+
+    /// Finds the key of the entry at `path` only if it is in the file
+    pub fn exact_span(&self, path: &[&str]) -> Option<Span> {
+        self.entry(path).map(|entry| entry.key)
+    }
+
+    /// Finds the `index`th whitespace separated key in the value at `path`,
+    /// such as a key of a layer. An index past the last key points just after
+    /// it, where the missing key would go.
+    pub fn key_span(&self, path: &[&str], index: usize) -> Option<Span> {
+        let entry = self.entry(path)?;
+        let mut last = None;
+        let mut count = 0;
+
+        for value in &entry.value {
+            let line = self.text.lines().nth(value.line - 1)?;
+            let text = line
+                .chars()
+                .skip(value.column - 1)
+                .take(value.length)
+                .collect::<String>();
+
+            for (column, key) in split_keys_with_columns(&text) {
+                let span = Span {
+                    line: value.line,
+                    column: value.column + column,
+                    length: key.chars().count(),
+                };
+
+                if count == index {
+                    return Some(span);
+                }
+
+                count += 1;
+                last = Some(span);
+            }
+        }
+
+        last.map(|span| Span {
+            line: span.line,
+            column: span.column + span.length + 1,
+            length: 1,
+        })
+    }
+
+    /// Renders the line of a span with a caret under it and a label, like a
+    /// compiler would. The snippet starts on a new line so it can follow a
+    /// message directly.
+    pub fn snippet(&self, span: Span, label: &str) -> String {
+        let line = self.text.lines().nth(span.line - 1).unwrap_or_default();
+        let number = span.line.to_string();
+        let gutter = " ".repeat(number.len());
+
+        format!(
+            "\n{gutter}--> {}:{}:{}\n{gutter} |\n{number} | {line}\n{gutter} | {}{} {label}",
+            self.path.display(),
+            span.line,
+            span.column,
+            " ".repeat(span.column - 1),
+            "^".repeat(span.length.max(1)),
+        )
+        .trim_end()
+        .to_string()
+    }
+
+    fn entry(&self, path: &[&str]) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key.iter().map(String::as_str).eq(path.iter().copied()))
+            .map(|(_, entry)| entry)
+    }
+}
+
+/// Names the ISO key at a position of a layer string
+pub fn key_name(index: usize) -> String {
+    match IsoKey::from_index(index) {
+        Some(key) => key.to_string(),
+        None => format!("key {}", index + 1),
+    }
+}
+
+/// The name a value such as a layer has as a YAML key
+pub fn yaml_key<T: Serialize>(value: &T) -> String {
+    match serde_yaml::to_value(value) {
+        Ok(serde_yaml::Value::String(key)) => key,
+        _ => "?".to_string(),
+    }
+}
+
+/// Splits a layer line into keys, with the character offset of each
+fn split_keys_with_columns(text: &str) -> Vec<(usize, &str)> {
+    let mut keys = vec![];
+    let mut start = None;
+
+    for (column, (offset, c)) in text.char_indices().enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some((column, offset)),
+            (true, Some((start_column, start_offset))) => {
+                keys.push((start_column, &text[start_offset..offset]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((start_column, start_offset)) = start {
+        keys.push((start_column, &text[start_offset..]));
+    }
+
+    keys
+}
+
+/// Reads the key of a `key: value` line, returning the unquoted key, its
+/// length as written and the byte offset of the value
+fn parse_key(content: &str) -> Option<(String, usize, usize)> {
+    let (key, key_end) = match content.chars().next()? {
+        '\'' => {
+            let mut key = String::new();
+            let mut chars = content.char_indices().skip(1).peekable();
+            let mut end = None;
+
+            while let Some((offset, c)) = chars.next() {
+                if c == '\'' {
+                    if chars.peek().is_some_and(|(_, next)| *next == '\'') {
+                        chars.next();
+                        key.push('\'');
+                        continue;
+                    }
+                    end = Some(offset + 1);
+                    break;
+                }
+                key.push(c);
+            }
+
+            (key, end?)
+        }
+        '"' => {
+            let mut key = String::new();
+            let mut chars = content.char_indices().skip(1);
+            let mut end = None;
+
+            while let Some((offset, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        end = Some(offset + 1);
+                        break;
+                    }
+                    '\\' => {
+                        let (_, escape) = chars.next()?;
+                        let hex_length = match escape {
+                            'x' => 2,
+                            'u' => 4,
+                            'U' => 8,
+                            _ => 0,
+                        };
+
+                        if hex_length > 0 {
+                            let hex = (0..hex_length)
+                                .filter_map(|_| chars.next().map(|(_, c)| c))
+                                .collect::<String>();
+                            key.push(
+                                u32::from_str_radix(&hex, 16)
+                                    .ok()
+                                    .and_then(char::from_u32)
+                                    .unwrap_or(char::REPLACEMENT_CHARACTER),
+                            );
+                        } else {
+                            key.push(match escape {
+                                '0' => '\0',
+                                't' => '\t',
+                                'n' => '\n',
+                                '_' => '\u{a0}',
+                                other => other,
+                            });
+                        }
+                    }
+                    c => key.push(c),
+                }
+            }
+
+            (key, end?)
+        }
+        '[' | '{' | '?' => return None,
+        _ => {
+            let key_end = content
+                .find(": ")
+                .or_else(|| content.strip_suffix(':').map(str::len))?;
+            (content[..key_end].trim_end().to_string(), key_end)
+        }
+    };
+
+    let rest = &content[key_end..];
+    let value_start = key_end + rest.len() - rest.trim_start().len();
+    if !content[value_start..].starts_with(':') {
+        return None;
+    }
+
+    Some((key, content[..key_end].chars().count(), value_start + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_layer_keys() {
+        let text = r#"displayNames:
+  se: Davvisámegiella
+windows:
+  primary:
+    layers:
+      default: |
+        § 1 2 3
+        á š e r
+  deadKeys:
+    default:
+    - ´
+transforms:
+  ' ':
+    "a": á
+"#;
+        let source = SourceFile::parse(PathBuf::from("se.yaml"), text.to_string());
+
+        let default = ["windows", "primary", "layers", "default"];
+        assert_eq!(
+            source.key_span(&default, 5),
+            Some(Span {
+                line: 8,
+                column: 11,
+                length: 1
+            })
+        );
+        assert_eq!(
+            source.key_span(&default, 8),
+            Some(Span {
+                line: 8,
+                column: 17,
+                length: 1
+            })
+        );
+        assert_eq!(
+            source.key_span(&["windows", "deadKeys", "default"], 0),
+            Some(Span {
+                line: 11,
+                column: 7,
+                length: 1
+            })
+        );
+        assert_eq!(
+            source.span(&["transforms", " ", "a"]).map(|x| x.line),
+            Some(14)
+        );
+        assert_eq!(
+            source
+                .span(&["windows", "primary", "shift"])
+                .map(|x| x.line),
+            Some(4)
+        );
+
+        let snippet = source.snippet(source.key_span(&default, 8).unwrap(), "C01 is missing");
+        assert_eq!(
+            snippet,
+            "\n --> se.yaml:8:17\n  |\n8 |         á š e r\n  |                 ^ C01 is missing"
+        );
+    }
+}
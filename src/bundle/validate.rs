@@ -0,0 +1,476 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use super::KbdgenBundle;
+use super::layout::{Layout, Transform};
+use super::source::{SourceFile, key_name, yaml_key};
+use super::{LAYOUTS_FOLDER, YAML_EXT};
+use crate::build::chromeos::keymap::CHROMEOS_KEYS;
+use crate::build::linux::keymap::XKB_KEYS;
+use crate::build::macos::keymap::MACOS_KEYS;
+use crate::build::windows::klc::keymap::MSKLC_KEYS;
+use crate::util::{TRANSFORM_ESCAPE, decode_unicode_escapes, split_keys};
+
+const MAX_BMP: char = '\u{FFFF}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Code {
+    /// A layer has fewer keys than the platform's keymap
+    MissingKeys,
+    /// A layer has more keys than the platform's keymap, the rest are ignored
+    ExtraKeys,
+    /// A dead key has no transforms to apply
+    DeadKeyWithoutTransform,
+    /// A transform has no entry for the key typed after the dead key
+    MissingEscape,
+    /// A dead key's transform is a string rather than a map of keys
+    TransformEndsEarly,
+    /// A Windows dead key or transform uses a character outside the Basic
+    /// Multilingual Plane, which KLC can't express
+    NonBmpOnWindows,
+    /// Two layouts share a display name in the same language
+    DuplicateDisplayName,
+}
+
+impl Display for Code {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Code::MissingKeys => "missing-keys",
+            Code::ExtraKeys => "extra-keys",
+            Code::DeadKeyWithoutTransform => "dead-key-without-transform",
+            Code::MissingEscape => "missing-escape",
+            Code::TransformEndsEarly => "transform-ends-early",
+            Code::NonBmpOnWindows => "non-bmp-on-windows",
+            Code::DuplicateDisplayName => "duplicate-display-name",
+        })
+    }
+}
+
+/// A problem found in a bundle file, located by the YAML path of the value
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    pub severity: Severity,
+    pub code: Code,
+    pub message: String,
+    /// The line of the file the problem is on, with the value marked
+    #[serde(skip)]
+    pub snippet: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]: ", self.severity, self.code)?;
+
+        if self.snippet.is_empty() {
+            write!(f, "{}: ", self.file.display())?;
+        }
+
+        write!(f, "{}: {}{}", self.path, self.message, self.snippet)
+    }
+}
+
+struct Diagnostics<'a> {
+    file: PathBuf,
+    source: Option<&'a SourceFile>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics<'_> {
+    /// Adds a diagnostic for the value at `path`, or for one of the keys in
+    /// it along with a label for that key
+    fn push(
+        &mut self,
+        path: &[String],
+        key: Option<(usize, String)>,
+        severity: Severity,
+        code: Code,
+        message: String,
+    ) {
+        let segments = path.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let located = self.source.and_then(|source| {
+            let (span, label) = match key
+                .and_then(|(index, label)| Some((source.key_span(&segments, index)?, label)))
+            {
+                Some(located) => located,
+                None => (source.span(&segments)?, String::new()),
+            };
+            Some((span, source.snippet(span, &label)))
+        });
+
+        self.diagnostics.push(Diagnostic {
+            file: self.file.clone(),
+            path: path.join("."),
+            line: located.as_ref().map(|(span, _)| span.line),
+            column: located.as_ref().map(|(span, _)| span.column),
+            severity,
+            code,
+            message,
+            snippet: located.map(|(_, snippet)| snippet).unwrap_or_default(),
+        });
+    }
+}
+
+fn path(segments: &[&str]) -> Vec<String> {
+    segments.iter().map(|x| x.to_string()).collect()
+}
+
+/// Checks every layout in the bundle for problems that the generators would
+/// otherwise only report, or panic on, partway through a build
+pub fn validate(bundle: &KbdgenBundle) -> Vec<Diagnostic> {
+    let mut layouts = bundle.layouts.iter().collect::<Vec<_>>();
+    layouts.sort_by_key(|(tag, _)| tag.to_string());
+
+    let mut diagnostics = vec![];
+    let mut display_names = IndexMap::<(String, &str), String>::new();
+
+    for (language_tag, layout) in layouts {
+        let mut layout_diagnostics = Diagnostics {
+            file: bundle
+                .path
+                .join(LAYOUTS_FOLDER)
+                .join(format!("{}.{}", language_tag, YAML_EXT)),
+            source: bundle.sources.get(language_tag),
+            diagnostics: vec![],
+        };
+
+        validate_layout(&mut layout_diagnostics, layout);
+
+        for (display_tag, name) in &layout.display_names {
+            let key = (display_tag.to_string(), name.as_str());
+            match display_names.get(&key) {
+                Some(other) => layout_diagnostics.push(
+                    &path(&["displayNames", &display_tag.to_string()]),
+                    None,
+                    Severity::Warning,
+                    Code::DuplicateDisplayName,
+                    format!("'{}' is also the display name of {}", name, other),
+                ),
+                None => {
+                    display_names.insert(key, language_tag.to_string());
+                }
+            }
+        }
+
+        diagnostics.extend(layout_diagnostics.diagnostics);
+    }
+
+    diagnostics
+}
+
+fn validate_layout(diagnostics: &mut Diagnostics, layout: &Layout) {
+    if let Some(target) = &layout.windows {
+        check_layers(
+            diagnostics,
+            "windows",
+            &target.primary.layers,
+            MSKLC_KEYS.len(),
+        );
+        check_dead_keys(diagnostics, "windows", target.dead_keys.as_ref(), layout);
+
+        // Other keys outside the BMP are typed as ligatures of their
+        // surrogate pairs, but dead keys and transforms can't use them
+        for (layer, keys) in target.dead_keys.iter().flatten() {
+            let path = path(&["windows", "deadKeys", &yaml_key(layer)]);
+            for (index, key) in keys.iter().enumerate() {
+                if exceeds_bmp(key) {
+                    diagnostics.push(
+                        &path,
+                        Some((index, String::new())),
+                        Severity::Warning,
+                        Code::NonBmpOnWindows,
+                        format!(
+                            "Dead key `{}` is outside the Basic Multilingual Plane, so on Windows it only types itself",
+                            key
+                        ),
+                    );
+                }
+            }
+        }
+
+        let dead_keys = target.dead_keys.iter().flat_map(|x| x.values()).flatten();
+        for dead_key in dead_keys.filter(|x| !exceeds_bmp(x)) {
+            if let Some(transform) = layout.transforms.as_ref().and_then(|x| x.get(dead_key)) {
+                check_transform_bmp(diagnostics, path(&["transforms", dead_key]), transform);
+            }
+        }
+    }
+
+    if let Some(target) = &layout.mac_os {
+        check_layers(
+            diagnostics,
+            "macOS",
+            &target.primary.layers,
+            MACOS_KEYS.len(),
+        );
+        check_dead_keys(diagnostics, "macOS", target.dead_keys.as_ref(), layout);
+    }
+
+    if let Some(target) = &layout.chrome_os {
+        check_layers(
+            diagnostics,
+            "chromeOS",
+            &target.primary.layers,
+            CHROMEOS_KEYS.len(),
+        );
+        check_dead_keys(diagnostics, "chromeOS", target.dead_keys.as_ref(), layout);
+    }
+
+    if let Some(target) = &layout.linux {
+        check_layers(diagnostics, "linux", &target.primary.layers, XKB_KEYS.len());
+        check_dead_keys(diagnostics, "linux", target.dead_keys.as_ref(), layout);
+    }
+
+    if let Some(target) = &layout.i_os {
+        check_dead_keys(diagnostics, "iOS", target.dead_keys.as_ref(), layout);
+    }
+
+    for (key, transform) in layout.transforms.iter().flatten() {
+        let path = path(&["transforms", key]);
+        match transform {
+            Transform::End(_) => diagnostics.push(
+                &path,
+                None,
+                Severity::Warning,
+                Code::TransformEndsEarly,
+                format!("The transform of `{}` has no keys to combine with", key),
+            ),
+            Transform::More(map) => check_escapes(diagnostics, path, map),
+        }
+    }
+}
+
+fn check_layers<K: Serialize>(
+    diagnostics: &mut Diagnostics,
+    target: &str,
+    layers: &IndexMap<K, String>,
+    expected: usize,
+) {
+    for (layer, keys) in layers {
+        let found = split_keys(keys).len();
+        let path = path(&[target, "primary", "layers", &yaml_key(layer)]);
+
+        if found < expected {
+            diagnostics.push(
+                &path,
+                Some((found, format!("expected {} here", key_name(found)))),
+                Severity::Error,
+                Code::MissingKeys,
+                format!("Expected {} keys but found {}", expected, found),
+            );
+        } else if found > expected {
+            diagnostics.push(
+                &path,
+                Some((
+                    expected,
+                    format!(
+                        "ignored from here, {} is the last key",
+                        key_name(expected - 1)
+                    ),
+                )),
+                Severity::Warning,
+                Code::ExtraKeys,
+                format!(
+                    "Expected {} keys but found {}, the last {} are ignored",
+                    expected,
+                    found,
+                    found - expected
+                ),
+            );
+        }
+    }
+}
+
+fn check_dead_keys<K: Serialize>(
+    diagnostics: &mut Diagnostics,
+    target: &str,
+    dead_keys: Option<&IndexMap<K, Vec<String>>>,
+    layout: &Layout,
+) {
+    for (layer, keys) in dead_keys.into_iter().flatten() {
+        let path = path(&[target, "deadKeys", &yaml_key(layer)]);
+        for (index, key) in keys.iter().enumerate() {
+            let has_transform = layout
+                .transforms
+                .as_ref()
+                .is_some_and(|transforms| transforms.contains_key(key));
+
+            if !has_transform {
+                diagnostics.push(
+                    &path,
+                    Some((index, String::new())),
+                    Severity::Warning,
+                    Code::DeadKeyWithoutTransform,
+                    format!("Dead key `{}` has no transforms", key),
+                );
+            }
+        }
+    }
+}
+
+fn check_escapes(
+    diagnostics: &mut Diagnostics,
+    path: Vec<String>,
+    map: &IndexMap<String, Transform>,
+) {
+    if !map.contains_key(TRANSFORM_ESCAPE) {
+        diagnostics.push(
+            &path,
+            None,
+            Severity::Error,
+            Code::MissingEscape,
+            format!(
+                "Missing the `'{}'` entry giving the output when no transform matches",
+                TRANSFORM_ESCAPE
+            ),
+        );
+    }
+
+    for (key, transform) in map {
+        if let Transform::More(map) = transform {
+            check_escapes(
+                diagnostics,
+                [path.as_slice(), std::slice::from_ref(key)].concat(),
+                map,
+            );
+        }
+    }
+}
+
+fn check_transform_bmp(diagnostics: &mut Diagnostics, path: Vec<String>, transform: &Transform) {
+    match transform {
+        Transform::End(output) => {
+            if exceeds_bmp(output) {
+                diagnostics.push(
+                    &path,
+                    None,
+                    Severity::Warning,
+                    Code::NonBmpOnWindows,
+                    format!(
+                        "`{}` is outside the Basic Multilingual Plane, so this transform is left out on Windows",
+                        output
+                    ),
+                );
+            }
+        }
+        Transform::More(map) => {
+            for (key, transform) in map {
+                let path = [path.as_slice(), std::slice::from_ref(key)].concat();
+                if exceeds_bmp(key) {
+                    diagnostics.push(
+                        &path,
+                        None,
+                        Severity::Warning,
+                        Code::NonBmpOnWindows,
+                        format!(
+                            "`{}` is outside the Basic Multilingual Plane, so its transforms are left out on Windows",
+                            key
+                        ),
+                    );
+                }
+                check_transform_bmp(diagnostics, path, transform);
+            }
+        }
+    }
+}
+
+fn exceeds_bmp(key: &str) -> bool {
+    decode_unicode_escapes(key).chars().any(|c| c > MAX_BMP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::test_layout;
+
+    #[test]
+    fn test_layout_diagnostics() {
+        let text = r#"
+displayNames:
+  se: Davvisámegiella
+windows:
+  primary:
+    layers:
+      default: |
+        § 1 2 3 4 5 6 7 8 9 0 + ´
+        á š e r t y u i o p å ŋ
+        a s d f g h j k l ö ä đ
+        ž z č c v b n m , .
+      shift: |
+        ° ! " # ¤ % & / ( ) = ? `
+        Á Š E R T Y U I O P Å Ŋ
+        A S D F G H J K L Ö Ä Đ
+        Ž Z Č C V B N M ; : 𝔸
+  deadKeys:
+    default: ["´", "^"]
+transforms:
+  ´:
+    a: á
+    e:
+      " ": é
+    𝔸: x
+"#;
+
+        let layout = test_layout("se", text);
+
+        let source = SourceFile::parse(PathBuf::from("se.yaml"), text.to_string());
+        let mut diagnostics = Diagnostics {
+            file: source.path.clone(),
+            source: Some(&source),
+            diagnostics: vec![],
+        };
+        validate_layout(&mut diagnostics, &layout);
+
+        let found = diagnostics
+            .diagnostics
+            .iter()
+            .map(|x| (x.code, x.path.as_str(), x.line, x.column))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            found,
+            vec![
+                (
+                    Code::MissingKeys,
+                    "windows.primary.layers.default",
+                    Some(11),
+                    Some(29)
+                ),
+                (
+                    Code::DeadKeyWithoutTransform,
+                    "windows.deadKeys.default",
+                    Some(18),
+                    Some(5)
+                ),
+                (Code::NonBmpOnWindows, "transforms.´.𝔸", Some(24), Some(5)),
+                (Code::MissingEscape, "transforms.´", Some(20), Some(3)),
+            ]
+        );
+    }
+}
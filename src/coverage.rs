@@ -0,0 +1,406 @@
+//! Checks that the characters a language needs can be typed on each target
+//! of its layout
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+use indexmap::IndexSet;
+use language_tags::LanguageTag;
+
+use crate::bundle::{Error, KbdgenBundle, layout::Layout};
+use crate::model::{DeadKeys, KeyAction, Keyboard, Next, Platform, StateId};
+use crate::util::{decode_unicode_escapes, split_keys};
+
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    pub language_tag: LanguageTag,
+    pub target: &'static str,
+    /// Exemplar characters that can't be typed on the target
+    pub missing: Vec<String>,
+    /// Transform results and longpress keys of the layout that the target
+    /// has no way of reaching
+    pub unreachable: Vec<String>,
+}
+
+/// The keys of a target before any transforms are applied
+#[derive(Debug, Default)]
+struct TargetKeys {
+    /// Text typed by pressing a key, or picking it from a longpress menu
+    keys: IndexSet<String>,
+    /// Keys that start a transform
+    starts: IndexSet<String>,
+    /// Longpress keys whose base key isn't on the target
+    unreachable: Vec<String>,
+}
+
+/// What can be typed on a target, directly or through transforms
+#[derive(Debug, Default)]
+pub struct Reachable {
+    pub typed: IndexSet<String>,
+    pub unreachable: Vec<String>,
+}
+
+impl Reachable {
+    /// Whether `text` can be typed, at once or a character at a time
+    pub fn contains(&self, text: &str) -> bool {
+        self.typed.contains(text) || text.chars().all(|c| self.typed.contains(&c.to_string()))
+    }
+}
+
+fn desktop_keys(keyboard: &Keyboard) -> TargetKeys {
+    let mut target = TargetKeys::default();
+
+    for key in keyboard.layers.iter().flat_map(|x| x.keys.values()) {
+        match key {
+            KeyAction::DeadKey(text) => {
+                target.starts.insert(text.clone());
+            }
+            key => {
+                if let Some(text) = key.output() {
+                    target.keys.insert(text);
+                }
+            }
+        }
+    }
+
+    target
+}
+
+/// Mobile keyboards apply transforms to whatever starts them, and have
+/// longpress menus on their keys
+fn mobile_keys<'a>(
+    layout: &Layout,
+    layers: impl Iterator<Item = &'a String>,
+    automaton: &DeadKeys,
+) -> TargetKeys {
+    let mut target = TargetKeys::default();
+
+    for key in layers.flat_map(|x| split_keys(x)) {
+        if let Some(text) = KeyAction::parse(&key, &[]).output() {
+            target.keys.insert(text);
+        }
+    }
+
+    for (key, alternatives) in layout.longpress.iter().flatten() {
+        let alternatives = alternatives.iter().map(|x| decode_unicode_escapes(x));
+        if target.keys.contains(&decode_unicode_escapes(key)) {
+            target.keys.extend(alternatives);
+        } else {
+            target.unreachable.extend(alternatives);
+        }
+    }
+
+    target.starts = automaton
+        .starts
+        .keys()
+        .filter(|x| target.keys.contains(*x))
+        .cloned()
+        .collect();
+
+    target
+}
+
+fn walk(automaton: &DeadKeys, id: StateId, typeable: &IndexSet<String>, output: &mut Reachable) {
+    let state = &automaton.states[id];
+    output.typed.insert(
+        state
+            .escape
+            .clone()
+            .unwrap_or_else(|| state.sequence.clone()),
+    );
+
+    for (next_char, next) in &state.transitions {
+        if !typeable.contains(next_char) {
+            continue;
+        }
+
+        match next {
+            Next::Output(text) => {
+                output.typed.insert(text.clone());
+            }
+            Next::State(next_id) => walk(automaton, *next_id, typeable, output),
+        }
+    }
+}
+
+fn reachable(automaton: &DeadKeys, target: TargetKeys) -> Reachable {
+    let typeable: IndexSet<String> = target.keys.union(&target.starts).cloned().collect();
+    let mut output = Reachable {
+        typed: target.keys.clone(),
+        unreachable: vec![],
+    };
+
+    for (dead_key, id) in &automaton.starts {
+        if target.starts.contains(dead_key) {
+            walk(automaton, *id, &typeable, &mut output);
+        }
+    }
+    // Dead keys without transforms type themselves
+    for dead_key in &target.starts {
+        if !automaton.starts.contains_key(dead_key) {
+            output.typed.insert(dead_key.clone());
+        }
+    }
+
+    let results = automaton.states.iter().flat_map(|state| {
+        state
+            .escape
+            .iter()
+            .chain(state.transitions.values().filter_map(|next| match next {
+                Next::Output(text) => Some(text),
+                Next::State(_) => None,
+            }))
+    });
+
+    let mut unreachable = IndexSet::new();
+    for text in results.chain(&target.unreachable) {
+        if !output.typed.contains(text) {
+            unreachable.insert(text.clone());
+        }
+    }
+    output.unreachable = unreachable.into_iter().collect();
+
+    output
+}
+
+/// What can be typed on each target of a layout, in the order the targets
+/// appear in a layout file
+pub fn reachable_by_target(layout: &Layout) -> Vec<(&'static str, Reachable)> {
+    let automaton = layout
+        .transforms
+        .as_ref()
+        .map(DeadKeys::from_transforms)
+        .unwrap_or_default();
+    let mut targets = vec![];
+
+    for platform in Platform::ALL.iter().copied() {
+        if let Some(keyboard) = Keyboard::from_layout(layout, platform) {
+            let keys = desktop_keys(&keyboard);
+            targets.push((platform.target_name(), reachable(&keyboard.dead_keys, keys)));
+        }
+    }
+
+    if let Some(target) = &layout.i_os {
+        let layers = [&target.primary, &target.i_pad_9in, &target.i_pad_12in]
+            .into_iter()
+            .flatten()
+            .flat_map(|x| x.layers.values());
+        let keys = mobile_keys(layout, layers, &automaton);
+        targets.push(("iOS", reachable(&automaton, keys)));
+    }
+
+    if let Some(target) = &layout.android {
+        let layers = [&target.primary, &target.tablet_600]
+            .into_iter()
+            .flat_map(|x| x.layers.values());
+        let keys = mobile_keys(layout, layers, &automaton);
+        targets.push(("android", reachable(&automaton, keys)));
+    }
+
+    targets
+}
+
+/// Compares what each target of a layout can type with the exemplar
+/// characters of its language. Both cases of each exemplar are required.
+pub fn coverage(layout: &Layout, exemplars: &[String]) -> Vec<Coverage> {
+    let mut required = IndexSet::new();
+    for exemplar in exemplars {
+        required.insert(exemplar.clone());
+        required.insert(exemplar.to_uppercase());
+    }
+
+    reachable_by_target(layout)
+        .into_iter()
+        .map(|(target, reachable)| Coverage {
+            language_tag: layout.language_tag.clone(),
+            target,
+            missing: required
+                .iter()
+                .filter(|x| !reachable.contains(x))
+                .cloned()
+                .collect(),
+            unreachable: reachable.unreachable,
+        })
+        .collect()
+}
+
+/// Reads the exemplar characters of a layout from the bundle's
+/// `resources/exemplars/<tag>.txt`, if there is one
+pub fn read_exemplars(
+    bundle: &KbdgenBundle,
+    language_tag: &LanguageTag,
+) -> Result<Option<Vec<String>>, Error> {
+    let Some(path) = bundle
+        .resources
+        .exemplars
+        .as_ref()
+        .and_then(|x| x.files.get(language_tag))
+    else {
+        return Ok(None);
+    };
+
+    let text = std::fs::read_to_string(path).map_err(|e| Error::Io(path.clone(), e))?;
+    Ok(Some(parse_exemplars(&text)))
+}
+
+fn read_char(chars: &mut Peekable<Chars>) -> Option<char> {
+    let c = chars.next()?;
+    if c != '\\' {
+        return Some(c);
+    }
+
+    let c = chars.next()?;
+    if c != 'u' {
+        return Some(c);
+    }
+
+    let hex: String = if chars.peek() == Some(&'{') {
+        chars.next();
+        chars.by_ref().take_while(|x| *x != '}').collect()
+    } else {
+        chars.by_ref().take(4).collect()
+    };
+    u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+}
+
+/// Reads an exemplar character list written like CLDR's exemplar sets,
+/// e.g. `[a á b c č {dz} ́ e-g]`. Lines starting with `#` are comments.
+pub fn parse_exemplars(text: &str) -> Vec<String> {
+    let mut exemplars = IndexSet::new();
+
+    for line in text.lines().filter(|x| !x.trim_start().starts_with('#')) {
+        let mut chars = line.chars().peekable();
+        let mut previous = None;
+
+        while let Some(&c) = chars.peek() {
+            match c {
+                '[' | ']' => {
+                    chars.next();
+                }
+                c if c.is_whitespace() => {
+                    chars.next();
+                }
+                '{' => {
+                    chars.next();
+                    let mut sequence = String::new();
+                    while chars.peek().is_some_and(|x| *x != '}') {
+                        sequence.extend(read_char(&mut chars));
+                    }
+                    chars.next();
+                    exemplars.insert(sequence);
+                    previous = None;
+                }
+                '-' if previous.is_some() => {
+                    chars.next();
+                    let (Some(start), Some(end)) = (previous.take(), read_char(&mut chars)) else {
+                        continue;
+                    };
+                    for c in (start..=end).skip(1) {
+                        exemplars.insert(c.to_string());
+                    }
+                }
+                _ => {
+                    let Some(c) = read_char(&mut chars) else {
+                        continue;
+                    };
+                    exemplars.insert(c.to_string());
+                    previous = Some(c);
+                }
+            }
+        }
+    }
+
+    exemplars.into_iter().filter(|x| !x.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::test_layout;
+
+    #[test]
+    fn test_parse_exemplars() {
+        assert_eq!(
+            parse_exemplars("# Northern Sámi\n[a á b-d {dz} \\u0301 \\-]"),
+            vec!["a", "á", "b", "c", "d", "dz", "\u{301}", "-"]
+        );
+    }
+
+    #[test]
+    fn test_coverage() {
+        let text = r#"
+displayNames:
+  sje: Bidumsámegiella
+windows:
+  primary:
+    layers:
+      default: |
+        § 1 2 3 4 5 6 7 8 9 0 + ´
+        á š e r t y u i o p å ŋ
+        a s d f g h j k l ö ä đ
+        ž z č c v b n m , . -
+      shift: |
+        ½ ! " # ¤ % & / ( ) = ? `
+        Á Š E R T Y U I O P Å Ŋ
+        A S D F G H J K L Ö Ä Đ
+        Ž Z Č C V B N M ; : _
+  deadKeys:
+    default: ['´']
+android:
+  primary:
+    layers:
+      default: |
+        á š e r t y u i o p
+        a s d f g h j k l
+        \s{shift} z č c v b n m \s{backspace}
+      shift: |
+        Á Š E R T Y U I O P
+        A S D F G H J K L
+        \s{shift} Z Č C V B N M \s{backspace}
+  tablet-600:
+    layers:
+      default: |
+        á š e r t y u i o p
+      shift: |
+        Á Š E R T Y U I O P
+longpress:
+  a: ä æ
+  q: ʼ
+transforms:
+  ´:
+    ' ': ´
+    e: é
+    ¨:
+      u: ǘ
+"#;
+
+        let layout = test_layout("sje", text);
+
+        let exemplars = parse_exemplars("[a ä é ŋ æ]");
+        let found = coverage(&layout, &exemplars)
+            .into_iter()
+            .map(|x| (x.target, x.missing, x.unreachable))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            found,
+            vec![
+                ("windows", vec!["É", "æ", "Æ"], vec!["ǘ"]),
+                (
+                    "android",
+                    vec!["Ä", "é", "É", "ŋ", "Ŋ", "Æ"],
+                    vec!["´", "é", "ǘ", "ʼ"]
+                ),
+            ]
+            .into_iter()
+            .map(|(target, missing, unreachable)| {
+                (
+                    target,
+                    missing.into_iter().map(str::to_string).collect(),
+                    unreachable.into_iter().map(str::to_string).collect(),
+                )
+            })
+            .collect::<Vec<(&str, Vec<String>, Vec<String>)>>()
+        );
+    }
+}
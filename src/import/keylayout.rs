@@ -0,0 +1,618 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result, bail};
+use indexmap::IndexMap;
+use language_tags::LanguageTag;
+use xmlem::{Document, Element, Selector};
+
+use crate::{
+    build::macos::keymap::MACOS_KEYS,
+    bundle::layout::{Layout, MacOsPrimaryPlatform, MacOsTarget, Transform, macos::MacOsKbdLayer},
+    util::TRANSFORM_ESCAPE,
+};
+
+use super::{layer_string, read_text, resolve_language_tag, write_layout};
+
+const NONE_STATE: &str = "none";
+const DECIMAL_KEY_CODE: usize = 65;
+const MAX_DEAD_KEY_DEPTH: usize = 8;
+
+/// What a key does in a `keyMap`, either directly or through an action
+#[derive(Debug, Clone)]
+pub enum KeylayoutKey {
+    Output(String),
+    Action(Vec<KeylayoutWhen>),
+}
+
+#[derive(Debug, Clone)]
+pub struct KeylayoutWhen {
+    pub state: String,
+    pub output: Option<String>,
+    pub next: Option<String>,
+}
+
+/// The contents of a `.keylayout` file, as far as kbdgen can represent them
+pub struct KeylayoutSource {
+    pub name: String,
+    /// Key maps of the default key map set by layer, keyed by key code
+    pub layers: IndexMap<MacOsKbdLayer, IndexMap<usize, KeylayoutKey>>,
+    /// The output of each dead key state when followed by an unrelated key
+    pub terminators: IndexMap<String, String>,
+}
+
+/// Matches a `modifier` `keys` attribute to a layer, going by the modifiers
+/// that are required. Optional modifiers (`caps?`) are ignored.
+fn modifier_layer(keys: &str) -> Option<MacOsKbdLayer> {
+    let mut shift = false;
+    let mut caps = false;
+    let mut option = false;
+    let mut command = false;
+    let mut control = false;
+
+    for key in keys.split_whitespace() {
+        if key.ends_with('?') {
+            continue;
+        }
+
+        match key {
+            "shift" | "anyShift" | "rightShift" => shift = true,
+            "caps" => caps = true,
+            "option" | "anyOption" | "rightOption" => option = true,
+            "command" => command = true,
+            "control" | "anyControl" | "rightControl" => control = true,
+            _ => tracing::warn!("Unknown modifier `{}`", key),
+        }
+    }
+
+    Some(match (shift, caps, option, command, control) {
+        (_, _, _, false, true) => MacOsKbdLayer::Ctrl,
+        (false, false, false, false, false) => MacOsKbdLayer::Default,
+        (true, false, false, false, false) => MacOsKbdLayer::Shift,
+        (false, true, false, false, false) => MacOsKbdLayer::Caps,
+        (true, true, false, false, false) => MacOsKbdLayer::CapsAndShift,
+        (false, false, true, false, false) => MacOsKbdLayer::Alt,
+        (true, false, true, false, false) => MacOsKbdLayer::AltAndShift,
+        (false, true, true, false, false) => MacOsKbdLayer::AltAndCaps,
+        (false, false, false, true, false) => MacOsKbdLayer::Cmd,
+        (true, false, false, true, false) => MacOsKbdLayer::CmdAndShift,
+        (false, false, true, true, false) => MacOsKbdLayer::CmdAndAlt,
+        (true, false, true, true, false) => MacOsKbdLayer::CmdAndAltAndShift,
+        _ => return None,
+    })
+}
+
+fn selector(name: &str) -> Selector {
+    Selector::new(name).expect("element names are valid selectors")
+}
+
+fn parse_when(document: &Document, element: &Element) -> Option<KeylayoutWhen> {
+    let state = element.attribute(document, "state")?.to_string();
+
+    if element.attribute(document, "through").is_some() {
+        tracing::warn!("Ignoring state range starting at `{}`", state);
+        return None;
+    }
+
+    Some(KeylayoutWhen {
+        state,
+        output: element.attribute(document, "output").map(str::to_string),
+        next: element.attribute(document, "next").map(str::to_string),
+    })
+}
+
+fn parse_whens(document: &Document, element: &Element) -> Vec<KeylayoutWhen> {
+    element
+        .children(document)
+        .into_iter()
+        .filter(|x| x.name(document) == "when")
+        .filter_map(|x| parse_when(document, &x))
+        .collect()
+}
+
+fn parse_key(
+    document: &Document,
+    element: &Element,
+    actions: &IndexMap<String, Vec<KeylayoutWhen>>,
+) -> Option<KeylayoutKey> {
+    if let Some(output) = element.attribute(document, "output") {
+        return Some(KeylayoutKey::Output(output.to_string()));
+    }
+
+    if let Some(id) = element.attribute(document, "action") {
+        return match actions.get(id) {
+            Some(whens) => Some(KeylayoutKey::Action(whens.clone())),
+            None => {
+                tracing::warn!("Action `{}` not found", id);
+                None
+            }
+        };
+    }
+
+    // Actions may also be written inline
+    element
+        .children(document)
+        .into_iter()
+        .find(|x| x.name(document) == "action")
+        .map(|x| KeylayoutKey::Action(parse_whens(document, &x)))
+}
+
+/// Reads the keys of a `keyMap`, including those it inherits through
+/// `baseMapSet` and `baseIndex`
+fn parse_key_map(
+    document: &Document,
+    key_map_sets: &IndexMap<String, Element>,
+    key_map_set: &Element,
+    index: &str,
+    actions: &IndexMap<String, Vec<KeylayoutWhen>>,
+    depth: usize,
+) -> IndexMap<usize, KeylayoutKey> {
+    let mut keys = IndexMap::new();
+
+    let Some(key_map) = key_map_set
+        .children(document)
+        .into_iter()
+        .find(|x| x.name(document) == "keyMap" && x.attribute(document, "index") == Some(index))
+    else {
+        return keys;
+    };
+
+    if let (Some(base_map_set), Some(base_index)) = (
+        key_map.attribute(document, "baseMapSet"),
+        key_map.attribute(document, "baseIndex"),
+    ) && let Some(base) = key_map_sets.get(base_map_set)
+        && depth < MAX_DEAD_KEY_DEPTH
+    {
+        keys = parse_key_map(document, key_map_sets, base, base_index, actions, depth + 1);
+    }
+
+    for key in key_map.children(document) {
+        if key.name(document) != "key" {
+            continue;
+        }
+
+        let Some(code) = key
+            .attribute(document, "code")
+            .and_then(|x| x.parse::<usize>().ok())
+        else {
+            continue;
+        };
+
+        if let Some(value) = parse_key(document, &key, actions) {
+            keys.insert(code, value);
+        }
+    }
+
+    keys
+}
+
+pub fn parse_keylayout(text: &str) -> Result<KeylayoutSource> {
+    let document = Document::from_str(text).context("Invalid keylayout XML")?;
+    let root = document.root();
+
+    if root.name(&document) != "keyboard" {
+        bail!(
+            "Expected a `keyboard` element, found `{}`",
+            root.name(&document)
+        );
+    }
+
+    let name = root
+        .attribute(&document, "name")
+        .unwrap_or_default()
+        .to_string();
+
+    let actions = root
+        .query_selector_all(&document, &selector("action"))
+        .into_iter()
+        .filter_map(|x| {
+            let id = x.attribute(&document, "id")?.to_string();
+            Some((id, parse_whens(&document, &x)))
+        })
+        .collect::<IndexMap<_, _>>();
+
+    let terminators = root
+        .query_selector(&document, &selector("terminators"))
+        .map(|x| parse_whens(&document, &x))
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|x| Some((x.state, x.output?)))
+        .collect::<IndexMap<_, _>>();
+
+    let key_map_sets = root
+        .query_selector_all(&document, &selector("keyMapSet"))
+        .into_iter()
+        .filter_map(|x| Some((x.attribute(&document, "id")?.to_string(), x)))
+        .collect::<IndexMap<_, _>>();
+
+    // The first `layout` covers the keyboard types kbdgen generates for
+    let layout = root
+        .query_selector(&document, &selector("layout"))
+        .context("No `layout` element found")?;
+    let map_set_id = layout.attribute(&document, "mapSet").unwrap_or_default();
+    let modifiers_id = layout.attribute(&document, "modifiers").unwrap_or_default();
+
+    let key_map_set = key_map_sets
+        .get(map_set_id)
+        .with_context(|| format!("No `keyMapSet` with id `{}` found", map_set_id))?;
+    let modifier_map = root
+        .query_selector_all(&document, &selector("modifierMap"))
+        .into_iter()
+        .find(|x| x.attribute(&document, "id") == Some(modifiers_id))
+        .with_context(|| format!("No `modifierMap` with id `{}` found", modifiers_id))?;
+
+    let mut layers = IndexMap::new();
+
+    for key_map_select in modifier_map.children(&document) {
+        if key_map_select.name(&document) != "keyMapSelect" {
+            continue;
+        }
+
+        let Some(index) = key_map_select.attribute(&document, "mapIndex") else {
+            continue;
+        };
+
+        let layer = key_map_select
+            .children(&document)
+            .into_iter()
+            .filter(|x| x.name(&document) == "modifier")
+            .filter_map(|x| modifier_layer(x.attribute(&document, "keys").unwrap_or_default()))
+            .find(|x| !layers.contains_key(x));
+
+        let Some(layer) = layer else {
+            tracing::warn!("Ignoring key map {}, it matches no layer", index);
+            continue;
+        };
+
+        layers.insert(
+            layer,
+            parse_key_map(&document, &key_map_sets, key_map_set, index, &actions, 0),
+        );
+    }
+
+    Ok(KeylayoutSource {
+        name,
+        layers,
+        terminators,
+    })
+}
+
+impl KeylayoutSource {
+    /// The character a key types when no dead key is active
+    fn typed(key: &KeylayoutKey) -> Option<&str> {
+        match key {
+            KeylayoutKey::Output(output) => Some(output),
+            KeylayoutKey::Action(whens) => whens
+                .iter()
+                .find(|x| x.state == NONE_STATE)
+                .and_then(|x| x.output.as_deref()),
+        }
+    }
+
+    /// The dead key state a key enters when no dead key is active
+    fn next_state(key: &KeylayoutKey) -> Option<&str> {
+        match key {
+            KeylayoutKey::Output(_) => None,
+            KeylayoutKey::Action(whens) => whens
+                .iter()
+                .find(|x| x.state == NONE_STATE)
+                .and_then(|x| x.next.as_deref()),
+        }
+    }
+
+    /// The character standing in for the dead key of each state, keyed by
+    /// state name. It is what the action entering the state types when the
+    /// dead key is pressed twice, or else the terminator of the state.
+    fn dead_key_characters(&self) -> IndexMap<String, String> {
+        let mut characters = IndexMap::new();
+        let mut unknown = HashSet::new();
+
+        for keys in self.layers.values() {
+            for key in keys.values() {
+                let (KeylayoutKey::Action(whens), Some(state)) = (key, Self::next_state(key))
+                else {
+                    continue;
+                };
+
+                if characters.contains_key(state) {
+                    continue;
+                }
+
+                let character = whens
+                    .iter()
+                    .find(|x| x.state == state)
+                    .and_then(|x| x.output.as_ref())
+                    .or_else(|| self.terminators.get(state))
+                    .filter(|x| !x.is_empty());
+
+                match character {
+                    Some(character) => {
+                        unknown.remove(state);
+                        characters.insert(state.to_string(), character.clone());
+                    }
+                    None => {
+                        unknown.insert(state.to_string());
+                    }
+                }
+            }
+        }
+
+        for state in unknown {
+            tracing::warn!(
+                "The character of the dead key entering state `{}` can't be determined, \
+                 leaving the dead key out",
+                state
+            );
+        }
+
+        characters
+    }
+
+    /// The character standing in for a key in layers and transforms, which
+    /// for dead keys is the character of the state they enter
+    fn key_character(
+        key: &KeylayoutKey,
+        dead_key_characters: &IndexMap<String, String>,
+    ) -> Option<String> {
+        match Self::next_state(key) {
+            Some(state) => dead_key_characters.get(state).cloned(),
+            None => Self::typed(key).map(str::to_string),
+        }
+    }
+
+    fn transform(
+        &self,
+        state: &str,
+        dead_key_characters: &IndexMap<String, String>,
+        visited: &mut HashSet<String>,
+    ) -> Transform {
+        let mut map = IndexMap::new();
+        visited.insert(state.to_string());
+
+        for keys in self.layers.values() {
+            for key in keys.values() {
+                let KeylayoutKey::Action(whens) = key else {
+                    continue;
+                };
+
+                let Some(when) = whens.iter().find(|x| x.state == state) else {
+                    continue;
+                };
+
+                let Some(character) = Self::key_character(key, dead_key_characters) else {
+                    continue;
+                };
+
+                if character.is_empty() || map.contains_key(&character) {
+                    continue;
+                }
+
+                let transform = match (&when.next, &when.output) {
+                    (Some(next), _) if !visited.contains(next) => {
+                        self.transform(next, dead_key_characters, &mut visited.clone())
+                    }
+                    (_, Some(output)) => Transform::End(output.clone()),
+                    _ => continue,
+                };
+
+                map.insert(character, transform);
+            }
+        }
+
+        // Without a terminator the escape is left to the usual fallback
+        if let Some(terminator) = self.terminators.get(state) {
+            map.entry(TRANSFORM_ESCAPE.to_string())
+                .or_insert_with(|| Transform::End(terminator.clone()));
+        }
+
+        Transform::More(map)
+    }
+
+    pub fn to_layout(&self, language_tag: LanguageTag) -> Result<Layout> {
+        let primary_language: LanguageTag = language_tag.primary_language().parse()?;
+
+        let mut display_names = IndexMap::new();
+        display_names.insert(primary_language, self.name.clone());
+
+        let mut layers = IndexMap::new();
+        let mut dead_keys: IndexMap<MacOsKbdLayer, Vec<String>> = IndexMap::new();
+        let dead_key_characters = self.dead_key_characters();
+
+        for (layer, keys) in &self.layers {
+            let mut layer_keys = vec![];
+
+            for key_code in MACOS_KEYS.values() {
+                let Some(key) = keys.get(key_code) else {
+                    layer_keys.push(None);
+                    continue;
+                };
+
+                let character = Self::key_character(key, &dead_key_characters);
+
+                if let (Some(_), Some(character)) = (Self::next_state(key), &character) {
+                    let layer_dead_keys = dead_keys.entry(*layer).or_default();
+                    if !layer_dead_keys.contains(character) {
+                        layer_dead_keys.push(character.clone());
+                    }
+                }
+
+                layer_keys.push(character);
+            }
+
+            layers.insert(*layer, layer_string(&layer_keys));
+        }
+
+        let mut transforms = IndexMap::new();
+        for (state, character) in &dead_key_characters {
+            if transforms.contains_key(character) {
+                tracing::warn!(
+                    "The dead key of state `{}` types `{}` like another dead key, leaving it out",
+                    state,
+                    character
+                );
+                continue;
+            }
+
+            let transform = self.transform(state, &dead_key_characters, &mut HashSet::new());
+            transforms.insert(character.clone(), transform);
+        }
+
+        let decimal = self
+            .layers
+            .get(&MacOsKbdLayer::Default)
+            .and_then(|x| x.get(&DECIMAL_KEY_CODE))
+            .and_then(Self::typed)
+            .filter(|x| *x != ".")
+            .map(str::to_string);
+
+        Ok(Layout {
+            language_tag,
+            display_names,
+            decimal,
+            windows: None,
+            chrome_os: None,
+            mac_os: Some(MacOsTarget {
+                primary: MacOsPrimaryPlatform { layers },
+                dead_keys: (!dead_keys.is_empty()).then_some(dead_keys),
+                space: IndexMap::new(),
+            }),
+            i_os: None,
+            android: None,
+            linux: None,
+            longpress: None,
+            transforms: (!transforms.is_empty()).then_some(transforms),
+            key_names: None,
+        })
+    }
+}
+
+/// Imports a `.keylayout` file into the bundle, returning the path of the
+/// new layout
+pub fn import_keylayout(
+    keylayout_path: &Path,
+    bundle_path: &Path,
+    language_tag: Option<LanguageTag>,
+) -> Result<PathBuf> {
+    let source = parse_keylayout(&read_text(keylayout_path)?)
+        .with_context(|| format!("Parsing '{}'", keylayout_path.display()))?;
+
+    let language_tag = resolve_language_tag(language_tag, Some(&source.name))?;
+    let layout = source.to_layout(language_tag)?;
+
+    write_layout(bundle_path, &layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYLAYOUT: &str = r#"<?xml version="1.1" encoding="UTF-8"?>
+<keyboard group="126" id="-1" name="Bidumsámegiella">
+  <layouts>
+    <layout first="0" last="17" mapSet="default" modifiers="modifiers" />
+  </layouts>
+  <modifierMap defaultIndex="0" id="modifiers">
+    <keyMapSelect mapIndex="0">
+      <modifier keys="command?" />
+    </keyMapSelect>
+    <keyMapSelect mapIndex="1">
+      <modifier keys="anyShift caps? command?" />
+    </keyMapSelect>
+  </modifierMap>
+  <keyMapSet id="default">
+    <keyMap index="0">
+      <key code="0" action="a" />
+      <key code="24" action="acute" />
+      <key code="30" action="hook" />
+      <key code="33" action="grave" />
+      <key code="32">
+        <action id="u">
+          <when state="none" output="u" />
+          <when state="diaeresis" output="ü" />
+          <when state="acute_diaeresis" output="ǘ" />
+        </action>
+      </key>
+    </keyMap>
+    <keyMap index="1">
+      <key code="0" output="A" />
+      <key code="24" action="diaeresis" />
+    </keyMap>
+  </keyMapSet>
+  <actions>
+    <action id="a">
+      <when state="none" output="a" />
+      <when state="acute" output="á" />
+    </action>
+    <action id="acute">
+      <when state="none" next="acute" />
+    </action>
+    <action id="grave">
+      <when state="none" next="grave" />
+      <when state="grave" output="`" />
+    </action>
+    <action id="hook">
+      <when state="none" next="hook" />
+    </action>
+    <action id="diaeresis">
+      <when state="none" next="diaeresis" />
+      <when state="acute" next="acute_diaeresis" />
+    </action>
+  </actions>
+  <terminators>
+    <when state="acute" output="´" />
+    <when state="diaeresis" output="¨" />
+    <when state="acute_diaeresis" output="´¨" />
+  </terminators>
+</keyboard>
+"#;
+
+    #[test]
+    fn test_keylayout_to_layout() {
+        let source = parse_keylayout(KEYLAYOUT).unwrap();
+        let layout = source.to_layout("sje".parse().unwrap()).unwrap();
+        let mac_os = layout.mac_os.as_ref().unwrap();
+
+        let default = crate::util::split_keys(&mac_os.primary.layers[&MacOsKbdLayer::Default]);
+        assert_eq!(default[12], "´");
+        assert_eq!(default[19], "u");
+        assert_eq!(default[25], "a");
+        assert_eq!(default[0], r"\u{0}");
+
+        let shift = crate::util::split_keys(&mac_os.primary.layers[&MacOsKbdLayer::Shift]);
+        assert_eq!(shift[12], "¨");
+        assert_eq!(shift[25], "A");
+
+        assert_eq!(
+            mac_os.dead_keys.as_ref().unwrap()[&MacOsKbdLayer::Shift],
+            vec!["¨".to_string()]
+        );
+
+        // A dead key without a terminator is what it types when pressed
+        // twice, and one without either is left out
+        assert_eq!(
+            mac_os.dead_keys.as_ref().unwrap()[&MacOsKbdLayer::Default],
+            vec!["´".to_string(), "`".to_string()]
+        );
+
+        let transforms = layout.transforms.as_ref().unwrap();
+        assert_eq!(transforms.keys().collect::<Vec<_>>(), ["´", "`", "¨"]);
+        let Transform::More(grave) = &transforms["`"] else {
+            panic!("expected more transforms");
+        };
+        assert!(!grave.contains_key(" "));
+
+        let Transform::More(acute) = &transforms["´"] else {
+            panic!("expected more transforms");
+        };
+        assert!(matches!(&acute["a"], Transform::End(x) if x == "á"));
+        assert!(matches!(&acute[" "], Transform::End(x) if x == "´"));
+        let Transform::More(diaeresis) = &acute["¨"] else {
+            panic!("expected a chained dead key");
+        };
+        assert!(matches!(&diaeresis["u"], Transform::End(x) if x == "ǘ"));
+        assert!(matches!(&diaeresis[" "], Transform::End(x) if x == "´¨"));
+    }
+}
@@ -0,0 +1,368 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use language_tags::LanguageTag;
+
+use crate::{
+    build::windows::klc::{
+        file::KlcFile,
+        key::KlcKey,
+        keymap::MSKLC_KEYS,
+        layout::KlcLayoutRow,
+        parse::{layout_keys, parse_lenient},
+    },
+    bundle::layout::{
+        Layout, Transform, WindowsConfig, WindowsPrimaryPlatform, WindowsTarget,
+        windows::WindowsKbdLayer,
+    },
+    util::TRANSFORM_ESCAPE,
+};
+
+use super::{layer_string, read_text, resolve_language_tag, write_layout};
+
+const SG_CAP: &str = "SGCap";
+/// The scancode of the rows giving the caps lock outputs of an `SGCap` row
+const SG_CAP_SCANCODE: &str = "-1";
+const DECIMAL_VIRTUAL_KEY: &str = "DECIMAL";
+const MAX_DEAD_KEY_DEPTH: usize = 8;
+
+/// The layers of the columns of a `KlcLayoutRow`
+const COLUMN_LAYERS: [WindowsKbdLayer; 5] = [
+    WindowsKbdLayer::Default,
+    WindowsKbdLayer::Shift,
+    WindowsKbdLayer::Ctrl,
+    WindowsKbdLayer::Alt,
+    WindowsKbdLayer::AltAndShift,
+];
+
+/// A key of a positional layer, and whether it is a dead key
+type LayerKey = Option<(String, bool)>;
+
+fn resolve_key(file: &KlcFile, row: &KlcLayoutRow, column: usize, key: &KlcKey) -> LayerKey {
+    match key {
+        KlcKey::Character(character) => Some((character.to_string(), false)),
+        KlcKey::DeadKey(character) => Some((character.to_string(), true)),
+        KlcKey::Ligature => {
+            let ligature = file
+                .ligature
+                .rows
+                .iter()
+                .find(|x| x.virtual_key == row.virtual_key && x.shift_state == column.to_string())
+                .and_then(|x| String::from_utf16(&x.utf16s).ok());
+
+            if ligature.is_none() {
+                tracing::warn!(
+                    "No ligature found for {} in column {}",
+                    row.virtual_key,
+                    column
+                );
+            }
+
+            ligature.map(|x| (x, false))
+        }
+        KlcKey::None | KlcKey::Skip => None,
+    }
+}
+
+fn dead_key_transform(file: &KlcFile, dead_key: char, depth: usize) -> Transform {
+    let mut map = IndexMap::new();
+
+    let table = file
+        .dead_keys
+        .tables
+        .iter()
+        .find(|x| x.dead_key == dead_key);
+    if table.is_none() {
+        tracing::warn!("No DEADKEY table found for {}", dead_key);
+    }
+
+    for row in table.iter().flat_map(|x| &x.transforms) {
+        let (Some(from), Some(to)) = (
+            char::from_u32(row.from as u32),
+            char::from_u32(row.to as u32),
+        ) else {
+            tracing::warn!(
+                "Ignoring the transform {:04x} to {:04x} of {}, as it is half of a surrogate pair",
+                row.from,
+                row.to,
+                dead_key
+            );
+            continue;
+        };
+
+        let transform = if row.chained && depth < MAX_DEAD_KEY_DEPTH {
+            dead_key_transform(file, to, depth + 1)
+        } else {
+            Transform::End(to.to_string())
+        };
+
+        map.insert(from.to_string(), transform);
+    }
+
+    map.entry(TRANSFORM_ESCAPE.to_string())
+        .or_insert_with(|| Transform::End(dead_key.to_string()));
+
+    Transform::More(map)
+}
+
+fn klc_layers(file: &KlcFile) -> IndexMap<WindowsKbdLayer, Vec<LayerKey>> {
+    let rows = &file.layout.rows;
+    let mut columns: IndexMap<WindowsKbdLayer, Vec<LayerKey>> = IndexMap::new();
+    let mut caps_modes = vec![];
+    let mut sg_caps = vec![];
+
+    for (_iso_key, klc_key) in MSKLC_KEYS.iter() {
+        let index = rows
+            .iter()
+            .position(|x| x.scancode.eq_ignore_ascii_case(&klc_key.scancode));
+        let row = index.map(|i| &rows[i]);
+
+        for (column, layer) in COLUMN_LAYERS.iter().enumerate() {
+            let key = row.and_then(|row| resolve_key(file, row, column, layout_keys(row)[column]));
+            columns.entry(*layer).or_default().push(key);
+        }
+
+        caps_modes.push(row.map(|x| x.caps_mode.clone()));
+        sg_caps.push(index.and_then(|i| {
+            let row = &rows[i];
+            let caps_row = rows
+                .get(i + 1)
+                .filter(|x| row.caps_mode == SG_CAP && x.scancode == SG_CAP_SCANCODE)?;
+
+            Some(
+                [&caps_row.default_key, &caps_row.shift_key]
+                    .iter()
+                    .enumerate()
+                    .map(|(column, key)| resolve_key(file, row, column, key))
+                    .collect::<Vec<_>>(),
+            )
+        }));
+    }
+
+    let default = columns[&WindowsKbdLayer::Default].clone();
+    let shift = columns[&WindowsKbdLayer::Shift].clone();
+    let alt = columns[&WindowsKbdLayer::Alt].clone();
+    let alt_and_shift = columns[&WindowsKbdLayer::AltAndShift].clone();
+
+    // kbdgen infers caps lock from the default and shift layers, so caps
+    // layers are only written when the file says otherwise
+    let caps_bits = caps_modes
+        .iter()
+        .map(|x| x.as_deref().and_then(|x| x.parse::<u8>().ok()).unwrap_or(0))
+        .collect::<Vec<_>>();
+    let needs_caps = sg_caps.iter().any(Option::is_some)
+        || (0..MSKLC_KEYS.len()).any(|i| {
+            caps_modes[i].is_some() && (caps_bits[i] & 1 == 1) != (default[i] != shift[i])
+        });
+
+    let mut layers: IndexMap<WindowsKbdLayer, Vec<LayerKey>> = IndexMap::new();
+    layers.insert(WindowsKbdLayer::Default, default.clone());
+    layers.insert(WindowsKbdLayer::Shift, shift.clone());
+
+    if needs_caps {
+        let mut caps = vec![];
+        let mut caps_and_shift = vec![];
+
+        for i in 0..MSKLC_KEYS.len() {
+            if let Some(sg_caps) = &sg_caps[i] {
+                caps.push(sg_caps[0].clone());
+                caps_and_shift.push(sg_caps[1].clone());
+            } else if caps_bits[i] & 1 == 1 {
+                caps.push(shift[i].clone());
+                caps_and_shift.push(default[i].clone());
+            } else {
+                caps.push(default[i].clone());
+                caps_and_shift.push(shift[i].clone());
+            }
+        }
+
+        layers.insert(WindowsKbdLayer::Caps, caps);
+        layers.insert(WindowsKbdLayer::CapsAndShift, caps_and_shift);
+    }
+
+    layers.insert(WindowsKbdLayer::Alt, alt.clone());
+    layers.insert(WindowsKbdLayer::AltAndShift, alt_and_shift.clone());
+
+    if needs_caps && caps_bits.iter().any(|x| x & 4 == 4) {
+        let alt_and_caps = (0..MSKLC_KEYS.len())
+            .map(|i| {
+                if caps_bits[i] & 4 == 4 {
+                    alt_and_shift[i].clone()
+                } else {
+                    alt[i].clone()
+                }
+            })
+            .collect();
+
+        layers.insert(WindowsKbdLayer::AltAndCaps, alt_and_caps);
+    }
+
+    layers.insert(
+        WindowsKbdLayer::Ctrl,
+        columns[&WindowsKbdLayer::Ctrl].clone(),
+    );
+
+    // Modifier layers without a single key are left out altogether
+    layers.retain(|layer, keys| {
+        matches!(layer, WindowsKbdLayer::Default | WindowsKbdLayer::Shift)
+            || keys.iter().any(Option::is_some)
+    });
+
+    layers
+}
+
+fn decimal(file: &KlcFile) -> Option<String> {
+    let row = file
+        .layout
+        .rows
+        .iter()
+        .find(|x| x.virtual_key == DECIMAL_VIRTUAL_KEY)?;
+
+    match row.default_key {
+        KlcKey::Character(character) if character != '.' => Some(character.to_string()),
+        _ => None,
+    }
+}
+
+pub fn klc_layout(file: &KlcFile, language_tag: LanguageTag) -> Result<Layout> {
+    let primary_language: LanguageTag = language_tag.primary_language().parse()?;
+
+    let mut display_names = IndexMap::new();
+    display_names.insert(primary_language, file.metadata.description.clone());
+
+    let mut layers = IndexMap::new();
+    let mut dead_keys: IndexMap<WindowsKbdLayer, Vec<String>> = IndexMap::new();
+
+    for (layer, keys) in klc_layers(file) {
+        for (key, _) in keys.iter().flatten().filter(|(_, dead)| *dead) {
+            let layer_dead_keys = dead_keys.entry(layer).or_default();
+            if !layer_dead_keys.contains(key) {
+                layer_dead_keys.push(key.clone());
+            }
+        }
+
+        let keys = keys
+            .into_iter()
+            .map(|x| x.map(|(key, _)| key))
+            .collect::<Vec<_>>();
+        layers.insert(layer, layer_string(&keys));
+    }
+
+    // Tables only reached by chaining end up nested under the dead keys
+    // chaining to them, rather than as dead keys of their own
+    let mut transforms = IndexMap::new();
+    for dead_key in dead_keys.values().flatten() {
+        let Some(character) = dead_key.chars().next() else {
+            continue;
+        };
+
+        if !transforms.contains_key(dead_key) {
+            transforms.insert(dead_key.clone(), dead_key_transform(file, character, 0));
+        }
+    }
+
+    let keyboard_name = &file.metadata.keyboard_name;
+    let id = keyboard_name
+        .strip_prefix("kbd")
+        .unwrap_or(keyboard_name)
+        .to_string();
+
+    Ok(Layout {
+        language_tag,
+        display_names,
+        decimal: decimal(file),
+        windows: Some(WindowsTarget {
+            config: Some(WindowsConfig {
+                locale: file.metadata.locale_name.parse().ok(),
+                id: Some(id),
+            }),
+            primary: WindowsPrimaryPlatform { layers },
+            dead_keys: (!dead_keys.is_empty()).then_some(dead_keys),
+        }),
+        chrome_os: None,
+        mac_os: None,
+        i_os: None,
+        android: None,
+        linux: None,
+        longpress: None,
+        transforms: (!transforms.is_empty()).then_some(transforms),
+        key_names: None,
+    })
+}
+
+/// Imports a `.klc` file into the bundle, returning the path of the new layout
+pub fn import_klc(
+    klc_path: &Path,
+    bundle_path: &Path,
+    language_tag: Option<LanguageTag>,
+) -> Result<PathBuf> {
+    let file = parse_lenient(&read_text(klc_path)?)
+        .with_context(|| format!("Parsing '{}'", klc_path.display()))?;
+
+    let locale_name = &file.metadata.locale_name;
+    let language_tag = resolve_language_tag(
+        language_tag,
+        (!locale_name.is_empty()).then_some(locale_name),
+    )?;
+    let layout = klc_layout(&file, language_tag)?;
+
+    write_layout(bundle_path, &layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KLC: &str = "KBD\tkbdsje\t\"Bidumsámegiella\"\n\
+        LOCALENAME\t\"sje-Latn-SE\"\n\
+        SHIFTSTATE\n\
+        0\n1\n2\n6\n7\n\
+        LAYOUT\n\
+        02\t1\t0\t1\t0021\t-1\t-1\t-1\n\
+        1e\tA\t1\ta\tA\t-1\t00e2\t00c2\n\
+        0d\tOEM_PLUS\t0\t00b4@\t0060@\t-1\t%%\t-1\n\
+        LIGATURE\n\
+        OEM_PLUS\t3\t0069\t0308\n\
+        DEADKEY\t00b4\n\
+        0061\t00e1\n\
+        00a8\t00a8@\n\
+        DEADKEY\t00a8\n\
+        0075\t00fc\n\
+        0020\t00a8\n\
+        ENDKBD\n";
+
+    #[test]
+    fn test_klc_to_layout() {
+        let file = parse_lenient(KLC).unwrap();
+        let layout = klc_layout(&file, "sje".parse().unwrap()).unwrap();
+        let windows = layout.windows.as_ref().unwrap();
+
+        let default = crate::util::split_keys(&windows.primary.layers[&WindowsKbdLayer::Default]);
+        assert_eq!(default[1], "1");
+        assert_eq!(default[12], "´");
+        assert_eq!(default[25], "a");
+        assert_eq!(default[0], r"\u{0}");
+
+        let alt = crate::util::split_keys(&windows.primary.layers[&WindowsKbdLayer::Alt]);
+        assert_eq!(alt[12], "i\u{308}");
+
+        assert_eq!(
+            windows.dead_keys.as_ref().unwrap()[&WindowsKbdLayer::Default],
+            vec!["´".to_string()]
+        );
+        assert!(!windows.primary.layers.contains_key(&WindowsKbdLayer::Ctrl));
+
+        let transforms = layout.transforms.as_ref().unwrap();
+        assert_eq!(transforms.keys().collect::<Vec<_>>(), ["´", "`"]);
+        let Transform::More(acute) = &transforms["´"] else {
+            panic!("expected more transforms");
+        };
+        assert!(matches!(&acute["a"], Transform::End(x) if x == "á"));
+        assert!(matches!(&acute[" "], Transform::End(x) if x == "´"));
+        let Transform::More(diaeresis) = &acute["¨"] else {
+            panic!("expected a chained dead key");
+        };
+        assert!(matches!(&diaeresis["u"], Transform::End(x) if x == "ü"));
+    }
+}
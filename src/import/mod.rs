@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use language_tags::LanguageTag;
+
+use crate::{
+    bundle::layout::Layout,
+    util::{encode_unicode_escapes, iso_key::ISO_ROW_LENGTHS},
+};
+
+pub mod keylayout;
+pub mod klc;
+pub mod xkb;
+
+const LAYOUTS_FOLDER: &str = "layouts";
+const YAML_EXT: &str = "yaml";
+pub(crate) const NULL_KEY: &str = r"\u{0}";
+
+/// Formats positional keys into a layer string with one keyboard row per
+/// line, writing absent keys as `\u{0}`
+pub fn layer_string(keys: &[Option<String>]) -> String {
+    let mut keys = keys.iter();
+    let mut output = String::new();
+
+    for row_length in ISO_ROW_LENGTHS {
+        let row = keys
+            .by_ref()
+            .take(row_length)
+            .map(|key| match key {
+                Some(key) if !key.is_empty() => encode_unicode_escapes(key),
+                _ => NULL_KEY.to_string(),
+            })
+            .collect::<Vec<_>>();
+
+        output.push_str(&row.join(" "));
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Reads a text file that may be UTF-8 or, as is common for Windows
+/// keyboard sources, UTF-16 with a byte order mark
+pub fn read_text(path: &Path) -> Result<String> {
+    let bytes = std::fs::read(path).with_context(|| format!("Reading '{}'", path.display()))?;
+
+    let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        let units = bytes
+            .chunks_exact(2)
+            .map(|x| from_bytes([x[0], x[1]]))
+            .collect::<Vec<_>>();
+        String::from_utf16(&units)
+            .with_context(|| format!("'{}' is not valid UTF-16", path.display()))
+    };
+
+    match bytes.as_slice() {
+        [0xff, 0xfe, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xfe, 0xff, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [0xef, 0xbb, 0xbf, rest @ ..] => String::from_utf8(rest.to_vec())
+            .with_context(|| format!("'{}' is not valid UTF-8", path.display())),
+        _ => String::from_utf8(bytes)
+            .with_context(|| format!("'{}' is not valid UTF-8", path.display())),
+    }
+}
+
+/// Writes an imported or new layout to `layouts/<tag>.yaml` in the bundle,
+/// refusing to replace an existing layout
+pub fn write_layout(bundle_path: &Path, layout: &Layout) -> Result<PathBuf> {
+    let layouts_path = bundle_path.join(LAYOUTS_FOLDER);
+    let layout_path = layouts_path.join(format!("{}.{}", layout.language_tag, YAML_EXT));
+
+    if layout_path.exists() {
+        bail!(
+            "'{}' already exists, remove it first to import over it",
+            layout_path.display()
+        );
+    }
+
+    std::fs::create_dir_all(&layouts_path)?;
+    std::fs::write(&layout_path, serde_yaml::to_string(layout)?)
+        .with_context(|| format!("Writing to '{}'", layout_path.display()))?;
+
+    Ok(layout_path)
+}
+
+/// Picks the language tag for an imported layout, preferring the one given
+/// on the command line over the one found in the source file
+pub fn resolve_language_tag(
+    language_tag: Option<LanguageTag>,
+    found: Option<&str>,
+) -> Result<LanguageTag> {
+    if let Some(language_tag) = language_tag {
+        return Ok(language_tag);
+    }
+
+    match found {
+        Some(found) => found
+            .parse()
+            .with_context(|| format!("'{}' is not a valid language tag", found)),
+        None => bail!("No language tag found in the source file, provide one with --language-tag"),
+    }
+}
@@ -0,0 +1,506 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use indexmap::IndexMap;
+use language_tags::LanguageTag;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::{
+    build::linux::{
+        keymap::XKB_KEYS,
+        keysym::{NO_SYMBOL, char_for_dead_keysym, char_for_keysym},
+    },
+    bundle::layout::{
+        Layout, LinuxConfig, LinuxPrimaryPlatform, LinuxTarget, Transform, linux::LinuxKbdLayer,
+    },
+    util::TRANSFORM_ESCAPE,
+};
+
+use super::{layer_string, read_text, resolve_language_tag, write_layout};
+
+const VOID_SYMBOL: &str = "VoidSymbol";
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Layers in the order of the xkb levels they are read from
+const XKB_LEVELS: [LinuxKbdLayer; 4] = [
+    LinuxKbdLayer::Default,
+    LinuxKbdLayer::Shift,
+    LinuxKbdLayer::Alt,
+    LinuxKbdLayer::AltAndShift,
+];
+
+static SECTION_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?m)^([\w\s]*?)xkb_symbols\s+"([^"]+)"\s*\{"#).expect("valid regex")
+});
+static INCLUDE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"^include\s+"([^"]+)""#).expect("valid regex"));
+static NAME_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"^name\[\s*Group1\s*\]\s*=\s*"([^"]*)""#).expect("valid regex"));
+static KEY_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^(?:(?:replace|override|augment)\s+)?key\s*<(\w+)>\s*\{(.*)\}$"#)
+        .expect("valid regex")
+});
+static TYPE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"type(\[\s*\w+\s*\])?\s*=\s*"[^"]*""#).expect("valid regex"));
+static LEVELS_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?:(\w+)\[\s*Group(\d+)\s*\]\s*=\s*)?\[([^\]]*)\]"#).expect("valid regex")
+});
+static COMPOSE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"^((?:\s*<\w+>)+)\s*:\s*"((?:[^"\\]|\\.)*)""#).expect("valid regex"));
+static COMPOSE_KEY_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"<(\w+)>").expect("valid regex"));
+
+/// A key as read from a symbols file, by xkb level
+pub type XkbLevels = Vec<String>;
+
+/// A resolved `xkb_symbols` section, with its includes merged in
+pub struct XkbSymbols {
+    pub layout: String,
+    pub variant: String,
+    pub name: Option<String>,
+    pub keys: IndexMap<String, XkbLevels>,
+}
+
+fn strip_comments(text: &str) -> String {
+    text.lines()
+        .map(|x| x.split("//").next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns the body of each `xkb_symbols` section in a file, and whether it
+/// is marked as the default
+fn sections(text: &str) -> IndexMap<String, (bool, String)> {
+    let mut sections = IndexMap::new();
+
+    for captures in SECTION_RE.captures_iter(text) {
+        let flags = captures.get(1).map(|x| x.as_str()).unwrap_or_default();
+        let name = captures[2].to_string();
+        let start = captures.get(0).expect("whole match").end();
+
+        let mut depth = 1;
+        let mut end = text.len();
+        for (index, character) in text[start..].char_indices() {
+            match character {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => continue,
+            }
+
+            if depth == 0 {
+                end = start + index;
+                break;
+            }
+        }
+
+        let is_default = flags.split_whitespace().any(|x| x == "default");
+        sections.insert(name, (is_default, text[start..end].to_string()));
+    }
+
+    sections
+}
+
+/// Splits a section body into statements. `include` statements are not
+/// terminated by a semicolon, so they end at the line instead.
+fn statements(body: &str) -> Vec<String> {
+    let mut statements = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+
+    for line in body.lines() {
+        let line = line.trim();
+
+        if depth == 0 && current.trim().is_empty() && line.starts_with("include") {
+            statements.push(line.trim_end_matches(';').to_string());
+            continue;
+        }
+
+        for character in line.chars() {
+            match character {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                ';' if depth == 0 => {
+                    statements.push(current.trim().to_string());
+                    current.clear();
+                    continue;
+                }
+                _ => {}
+            }
+            current.push(character);
+        }
+        current.push(' ');
+    }
+
+    statements
+}
+
+fn parse_levels(body: &str) -> Option<XkbLevels> {
+    let body = TYPE_RE.replace_all(body, "");
+
+    LEVELS_RE
+        .captures_iter(&body)
+        .find(|x| {
+            let label = x.get(1).map(|x| x.as_str()).unwrap_or("symbols");
+            let group = x.get(2).map(|x| x.as_str()).unwrap_or("1");
+            label == "symbols" && group == "1"
+        })
+        .map(|x| {
+            x[3].split(',')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect()
+        })
+}
+
+struct SymbolsReader<'a> {
+    symbols_path: &'a Path,
+}
+
+impl SymbolsReader<'_> {
+    fn read_section(
+        &self,
+        layout: &str,
+        variant: Option<&str>,
+        depth: usize,
+    ) -> Result<(String, Option<String>, IndexMap<String, XkbLevels>)> {
+        let path = self.symbols_path.join(layout);
+        let text = strip_comments(&read_text(&path)?);
+        let sections = sections(&text);
+
+        let (variant, (_, body)) = match variant {
+            Some(variant) => sections
+                .get_key_value(variant)
+                .with_context(|| format!("No section '{}' in '{}'", variant, path.display()))?,
+            None => sections
+                .iter()
+                .find(|(_, (is_default, _))| *is_default)
+                .or_else(|| sections.first())
+                .with_context(|| format!("No xkb_symbols in '{}'", path.display()))?,
+        };
+
+        let mut name = None;
+        let mut keys = IndexMap::new();
+
+        for statement in statements(body) {
+            if let Some(captures) = INCLUDE_RE.captures(&statement) {
+                if depth >= MAX_INCLUDE_DEPTH {
+                    tracing::warn!("Not following includes past '{}'", &captures[1]);
+                    continue;
+                }
+
+                // Includes may merge several sections, as in `latin(type2)+level3`
+                for include in captures[1].split(['+', '|']) {
+                    let (include_layout, include_variant) = match include.split_once('(') {
+                        Some((layout, variant)) => (layout, Some(variant.trim_end_matches(')'))),
+                        None => (include, None),
+                    };
+
+                    match self.read_section(include_layout, include_variant, depth + 1) {
+                        Ok((_, _, include_keys)) => keys.extend(include_keys),
+                        Err(e) => tracing::warn!("Skipping include '{}': {:#}", include, e),
+                    }
+                }
+            } else if let Some(captures) = NAME_RE.captures(&statement) {
+                name = Some(captures[1].to_string());
+            } else if let Some(captures) = KEY_RE.captures(&statement)
+                && let Some(levels) = parse_levels(&captures[2])
+            {
+                keys.insert(captures[1].to_string(), levels);
+            }
+        }
+
+        Ok((variant.clone(), name, keys))
+    }
+}
+
+/// Reads the `xkb_symbols` section `variant` of a symbols file, or its
+/// default section, following includes relative to the file's folder
+pub fn read_symbols(path: &Path, variant: Option<&str>) -> Result<XkbSymbols> {
+    let layout = path
+        .file_name()
+        .and_then(|x| x.to_str())
+        .with_context(|| format!("Invalid symbols file '{}'", path.display()))?;
+    let reader = SymbolsReader {
+        symbols_path: path.parent().unwrap_or(Path::new(".")),
+    };
+
+    let (variant, name, keys) = reader.read_section(layout, variant, 0)?;
+
+    Ok(XkbSymbols {
+        layout: layout.to_string(),
+        variant,
+        name,
+        keys,
+    })
+}
+
+/// A keysym as it appears on a layer, and whether it is a dead key
+fn keysym_character(keysym: &str) -> Option<(String, bool)> {
+    if keysym.starts_with("dead_") {
+        return char_for_dead_keysym(keysym).map(|x| (x.to_string(), true));
+    }
+
+    char_for_keysym(keysym).map(|x| (x.to_string(), false))
+}
+
+fn resolve_keysym(keysym: &str) -> Option<(String, bool)> {
+    if keysym == NO_SYMBOL || keysym == VOID_SYMBOL {
+        return None;
+    }
+
+    let character = keysym_character(keysym);
+    if character.is_none() {
+        tracing::warn!("Keysym '{}' has no character, skipping", keysym);
+    }
+
+    character
+}
+
+fn unescape_compose_string(input: &str) -> String {
+    let mut output = String::new();
+    let mut chars = input.chars();
+
+    while let Some(character) = chars.next() {
+        if character != '\\' {
+            output.push(character);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some(escaped) => output.push(escaped),
+            None => output.push('\\'),
+        }
+    }
+
+    output
+}
+
+/// Inserts a compose sequence into a transform tree, keeping the first
+/// result if sequences overlap
+fn insert_sequence(map: &mut IndexMap<String, Transform>, sequence: &[String], result: String) {
+    let Some((first, rest)) = sequence.split_first() else {
+        return;
+    };
+
+    if rest.is_empty() {
+        map.entry(first.clone()).or_insert(Transform::End(result));
+        return;
+    }
+
+    let entry = map
+        .entry(first.clone())
+        .or_insert_with(|| Transform::More(IndexMap::new()));
+
+    match entry {
+        Transform::More(next) => insert_sequence(next, rest, result),
+        Transform::End(_) => {
+            tracing::warn!(
+                "Sequence after '{}' overlaps a shorter one, skipping",
+                first
+            )
+        }
+    }
+}
+
+/// Reads the sequences of a Compose file that start with one of the given
+/// dead keys into transforms. Sequences using `Multi_key` have no place in
+/// kbdgen and are left out.
+pub fn read_compose(text: &str, dead_keys: &[String]) -> IndexMap<String, Transform> {
+    let mut transforms = IndexMap::new();
+
+    for line in text.lines() {
+        let Some(captures) = COMPOSE_RE.captures(line.trim()) else {
+            continue;
+        };
+
+        let keysyms = COMPOSE_KEY_RE
+            .captures_iter(&captures[1])
+            .map(|x| x[1].to_string())
+            .collect::<Vec<_>>();
+
+        let Some(sequence) = keysyms
+            .iter()
+            .map(|x| keysym_character(x).map(|(character, _)| character))
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+
+        if !keysyms[0].starts_with("dead_") || !dead_keys.contains(&sequence[0]) {
+            continue;
+        }
+
+        insert_sequence(
+            &mut transforms,
+            &sequence,
+            unescape_compose_string(&captures[2]),
+        );
+    }
+
+    transforms
+}
+
+/// Adds the escape transform wherever a dead key state lacks one, so the
+/// dead key can still be typed on its own
+fn add_escapes(typed: &str, transform: &mut Transform) {
+    let Transform::More(map) = transform else {
+        return;
+    };
+
+    for (next, transform) in map.iter_mut() {
+        add_escapes(&format!("{}{}", typed, next), transform);
+    }
+
+    map.entry(TRANSFORM_ESCAPE.to_string())
+        .or_insert_with(|| Transform::End(typed.to_string()));
+}
+
+impl XkbSymbols {
+    pub fn to_layout(&self, language_tag: LanguageTag, compose: Option<&str>) -> Result<Layout> {
+        let primary_language: LanguageTag = language_tag.primary_language().parse()?;
+
+        let mut display_names = IndexMap::new();
+        display_names.insert(
+            primary_language,
+            self.name
+                .clone()
+                .unwrap_or_else(|| format!("{}({})", self.layout, self.variant)),
+        );
+
+        let mut layers = IndexMap::new();
+        let mut dead_keys: IndexMap<LinuxKbdLayer, Vec<String>> = IndexMap::new();
+        let mut all_dead_keys = vec![];
+
+        for (level, layer) in XKB_LEVELS.iter().enumerate() {
+            let mut keys = vec![];
+
+            for xkb_key in XKB_KEYS.values() {
+                let key = self
+                    .keys
+                    .get(xkb_key)
+                    .and_then(|x| x.get(level))
+                    .and_then(|x| resolve_keysym(x));
+
+                if let Some((character, true)) = &key {
+                    let layer_dead_keys = dead_keys.entry(*layer).or_default();
+                    if !layer_dead_keys.contains(character) {
+                        layer_dead_keys.push(character.clone());
+                    }
+                    if !all_dead_keys.contains(character) {
+                        all_dead_keys.push(character.clone());
+                    }
+                }
+
+                keys.push(key.map(|(character, _)| character));
+            }
+
+            if matches!(layer, LinuxKbdLayer::Default | LinuxKbdLayer::Shift)
+                || keys.iter().any(Option::is_some)
+            {
+                layers.insert(*layer, layer_string(&keys));
+            }
+        }
+
+        let mut transforms = match compose {
+            Some(compose) => read_compose(compose, &all_dead_keys),
+            None => {
+                if !all_dead_keys.is_empty() {
+                    tracing::warn!("No Compose file given, dead keys will only type themselves");
+                }
+                IndexMap::new()
+            }
+        };
+
+        for dead_key in &all_dead_keys {
+            let transform = transforms
+                .entry(dead_key.clone())
+                .or_insert_with(|| Transform::More(IndexMap::new()));
+            add_escapes(dead_key, transform);
+        }
+
+        Ok(Layout {
+            language_tag,
+            display_names,
+            decimal: None,
+            windows: None,
+            chrome_os: None,
+            mac_os: None,
+            i_os: None,
+            android: None,
+            linux: Some(LinuxTarget {
+                config: Some(LinuxConfig {
+                    xkb_layout: Some(self.layout.clone()),
+                    xkb_variant: Some(self.variant.clone()),
+                }),
+                primary: LinuxPrimaryPlatform { layers },
+                dead_keys: (!dead_keys.is_empty()).then_some(dead_keys),
+            }),
+            longpress: None,
+            transforms: (!transforms.is_empty()).then_some(transforms),
+            key_names: None,
+        })
+    }
+}
+
+/// Imports a section of an xkb symbols file into the bundle, returning the
+/// path of the new layout
+pub fn import_xkb(
+    symbols_path: &Path,
+    variant: Option<&str>,
+    compose_path: Option<&Path>,
+    bundle_path: &Path,
+    language_tag: Option<LanguageTag>,
+) -> Result<PathBuf> {
+    let symbols = read_symbols(symbols_path, variant)
+        .with_context(|| format!("Parsing '{}'", symbols_path.display()))?;
+
+    if symbols.keys.is_empty() {
+        bail!("No keys found in {}({})", symbols.layout, symbols.variant);
+    }
+
+    let compose = compose_path.map(read_text).transpose()?;
+
+    let language_tag = resolve_language_tag(language_tag, Some(&symbols.layout))?;
+    let layout = symbols.to_layout(language_tag, compose.as_deref())?;
+
+    write_layout(bundle_path, &layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_to_transforms() {
+        let compose = r#"
+include "%L"
+<dead_acute> <a> : "á" aacute
+<dead_acute> <dead_diaeresis> <u> : "ǘ"
+<dead_acute> <space> : "'"
+<Multi_key> <a> <e> : "æ"
+<dead_grave> <a> : "à"
+"#;
+        let transforms = read_compose(compose, &["´".to_string()]);
+
+        assert_eq!(transforms.len(), 1);
+        let Transform::More(acute) = &transforms["´"] else {
+            panic!("expected more transforms");
+        };
+        assert!(matches!(&acute["a"], Transform::End(x) if x == "á"));
+        assert!(matches!(&acute[" "], Transform::End(x) if x == "'"));
+        let Transform::More(diaeresis) = &acute["¨"] else {
+            panic!("expected a chained dead key");
+        };
+        assert!(matches!(&diaeresis["u"], Transform::End(x) if x == "ǘ"));
+    }
+
+    #[test]
+    fn test_key_levels() {
+        let levels = parse_levels(r#" type[Group1]="FOUR_LEVEL", [ a, A, aacute, Aacute ] "#);
+        assert_eq!(levels.unwrap(), vec!["a", "A", "aacute", "Aacute"]);
+
+        let levels = parse_levels(r#" symbols[Group2] = [ x ], symbols[Group1] = [ 1, exclam ] "#);
+        assert_eq!(levels.unwrap(), vec!["1", "exclam"]);
+    }
+}
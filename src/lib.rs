@@ -1,3 +1,8 @@
 pub mod build;
 pub mod bundle;
+pub mod coverage;
+pub mod import;
+pub mod model;
+pub mod parity;
+pub mod simulate;
 pub mod util;
@@ -1,7 +1,8 @@
 use std::path::PathBuf;
 
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use kbdgen::build::BuildStep;
+use kbdgen::build::all::{BuildTarget, build_all, configured_targets, summary_table};
 use kbdgen::build::android::clone_giellakbd::CloneGiellaKbd;
 use kbdgen::build::android::generate_android::GenerateAndroid;
 use kbdgen::build::macos::{GenerateInstaller, GenerateMacOs};
@@ -10,10 +11,26 @@ use kbdgen::bundle::KbdgenBundle;
 use kbdgen::build::BuildSteps;
 use kbdgen::build::android::AndroidBuild;
 use kbdgen::build::chromeos::ChromeOsBuild;
+use kbdgen::build::cldr::CldrBuild;
+use kbdgen::build::docs::DocsBuild;
 use kbdgen::build::ios::{self, IosBuild, IosProjectExt};
+use kbdgen::build::keyman::KeymanBuild;
+use kbdgen::build::linux::LinuxBuild;
 use kbdgen::build::svg::SvgBuild;
 use kbdgen::build::windows::WindowsBuild;
+use kbdgen::bundle::init::{init_bundle, new_layout};
+use kbdgen::bundle::migrate::{apply_migration, migrate_bundle};
 use kbdgen::bundle::read_kbdgen_bundle;
+use kbdgen::bundle::validate::{Diagnostic, Severity, validate};
+use kbdgen::coverage::{coverage, read_exemplars};
+use kbdgen::import::keylayout::import_keylayout;
+use kbdgen::import::klc::import_klc;
+use kbdgen::import::write_layout;
+use kbdgen::import::xkb::import_xkb;
+use kbdgen::model::Platform;
+use kbdgen::parity::{parity, parity_table};
+use kbdgen::simulate::{KeyEvent, simulate};
+use language_tags::LanguageTag;
 
 async fn android_target(
     bundle: KbdgenBundle,
@@ -76,10 +93,193 @@ async fn main() -> anyhow::Result<()> {
 
             kbdgen::bundle::fetch(&bundle.path, &bundle.project).await?;
         }
+        Command::Import(import_command) => {
+            let layout_path = match &import_command.format {
+                ImportFormat::Klc(options) => import_klc(
+                    &options.file,
+                    &options.bundle_path,
+                    options.language_tag.clone(),
+                )?,
+                ImportFormat::Keylayout(options) => import_keylayout(
+                    &options.file,
+                    &options.bundle_path,
+                    options.language_tag.clone(),
+                )?,
+                ImportFormat::Xkb(options) => import_xkb(
+                    &options.file,
+                    options.variant.as_deref(),
+                    options.compose.as_deref(),
+                    &options.bundle_path,
+                    options.language_tag.clone(),
+                )?,
+            };
+
+            tracing::info!("Imported layout to {}", layout_path.display());
+        }
+        Command::Migrate(options) => {
+            let changes = migrate_bundle(&options.bundle_path)?;
+
+            if changes.is_empty() {
+                tracing::info!("Nothing to migrate, the bundle is already up to date");
+            } else if options.dry_run {
+                for change in &changes {
+                    print!("{}", change.diff());
+                }
+            } else {
+                let backup_path = apply_migration(&options.bundle_path, &changes)?;
+                tracing::info!(
+                    "Migrated {} files, the original bundle is backed up at {}",
+                    changes.len(),
+                    backup_path.display()
+                );
+            }
+        }
+        Command::Validate(options) => {
+            let bundle = read_kbdgen_bundle(&options.bundle_path)?;
+            let diagnostics = validate(&bundle);
+
+            match options.format {
+                OutputFormat::Text => {
+                    for diagnostic in &diagnostics {
+                        println!("{}", diagnostic);
+                    }
+                }
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&diagnostics)?);
+                }
+            }
+
+            check_diagnostics(&diagnostics)?;
+        }
+        Command::Simulate(options) => {
+            let bundle = read_kbdgen_bundle(&options.bundle_path)?;
+
+            let layout = match &options.language_tag {
+                Some(language_tag) => bundle.layouts.get(language_tag),
+                None if bundle.layouts.len() == 1 => bundle.layouts.values().next(),
+                None => anyhow::bail!(
+                    "The bundle has {} layouts, choose one with --language-tag",
+                    bundle.layouts.len()
+                ),
+            };
+            let Some(layout) = layout else {
+                anyhow::bail!("The bundle has no such layout");
+            };
+
+            println!("{}", simulate(layout, options.platform, &options.events));
+        }
+        Command::Parity(options) => {
+            let bundle = read_kbdgen_bundle(&options.bundle_path)?;
+
+            let mismatches = bundle
+                .layouts
+                .iter()
+                .filter(|(language_tag, _)| {
+                    options
+                        .language_tag
+                        .as_ref()
+                        .is_none_or(|x| x == *language_tag)
+                })
+                .flat_map(|(_, layout)| parity(layout))
+                .collect::<Vec<_>>();
+
+            if mismatches.is_empty() {
+                tracing::info!("No differences between platforms");
+            } else {
+                print!("{}", parity_table(&mismatches));
+            }
+
+            if options.strict && !mismatches.is_empty() {
+                anyhow::bail!("Found {} differences between platforms", mismatches.len());
+            }
+        }
+        Command::Coverage(options) => {
+            let bundle = read_kbdgen_bundle(&options.bundle_path)?;
+
+            let mut layouts = bundle
+                .layouts
+                .iter()
+                .filter(|(language_tag, _)| {
+                    options
+                        .language_tag
+                        .as_ref()
+                        .is_none_or(|x| x == *language_tag)
+                })
+                .collect::<Vec<_>>();
+            layouts.sort_by_key(|(language_tag, _)| language_tag.as_str());
+
+            for (language_tag, layout) in layouts {
+                let exemplars = read_exemplars(&bundle, language_tag)?;
+                if exemplars.is_none() {
+                    tracing::warn!(
+                        "No exemplar characters for {}, add them to resources/exemplars/{}.txt",
+                        language_tag,
+                        language_tag
+                    );
+                }
+
+                for report in coverage(layout, exemplars.as_deref().unwrap_or_default()) {
+                    let heading = format!("{} {}", report.language_tag, report.target);
+                    if report.missing.is_empty() && report.unreachable.is_empty() {
+                        println!("{}: complete", heading);
+                        continue;
+                    }
+
+                    if !report.missing.is_empty() {
+                        println!("{}: missing {}", heading, report.missing.join(" "));
+                    }
+                    if !report.unreachable.is_empty() {
+                        println!("{}: unreachable {}", heading, report.unreachable.join(" "));
+                    }
+                }
+            }
+        }
+        Command::Init(options) => {
+            init_bundle(&options.bundle_path)?;
+            tracing::info!(
+                "Created {}, add a layout with `kbdgen layout new <tag> --bundle-path {}`",
+                options.bundle_path.display(),
+                options.bundle_path.display()
+            );
+        }
+        Command::Layout(layout_command) => match &layout_command.command {
+            LayoutSubcommand::New(options) => {
+                let bundle = read_kbdgen_bundle(&options.bundle_path)?;
+                let layout = new_layout(&bundle, &options.language_tag, &options.from)?;
+                let layout_path = write_layout(&bundle.path, &layout)?;
+                tracing::info!("Created layout {}", layout_path.display());
+            }
+        },
+        Command::Build(options) => {
+            let bundle = read_kbdgen_bundle(&options.bundle_path)?;
+            log_diagnostics(&bundle)?;
+
+            let targets = if options.all {
+                configured_targets(&bundle)
+            } else {
+                options.targets.clone()
+            };
+            if targets.is_empty() {
+                anyhow::bail!("The bundle has no targets configured");
+            }
+
+            std::fs::create_dir_all(&options.output_path)?;
+            let output_path = dunce::canonicalize(&options.output_path)?;
+
+            let results = build_all(&bundle, &output_path, &targets).await;
+            print!("{}", summary_table(&results));
+
+            let failures = results.iter().filter(|x| x.result.is_err()).count();
+            if failures > 0 {
+                anyhow::bail!("{} of {} targets failed to build", failures, results.len());
+            }
+        }
         Command::Target(target_command_struct) => {
             let bundle_path = &target_command_struct.bundle_path;
             let bundle = read_kbdgen_bundle(&bundle_path)?;
 
+            log_diagnostics(&bundle)?;
+
             let output_path = &target_command_struct.output_path;
             std::fs::create_dir_all(&output_path)?;
             let output_path = dunce::canonicalize(output_path).unwrap();
@@ -99,11 +299,31 @@ async fn main() -> anyhow::Result<()> {
                 TargetCommand::MacOs(target) => {
                     macos_target(bundle, output_path, target).await?;
                 }
+                TargetCommand::Linux(_linux_command) => {
+                    let build = LinuxBuild::new(bundle, output_path.clone());
+
+                    build.build_full().await?;
+                }
+                TargetCommand::Keyman(_keyman_command) => {
+                    let build = KeymanBuild::new(bundle, output_path.clone());
+
+                    build.build_full().await?;
+                }
+                TargetCommand::Cldr(_cldr_command) => {
+                    let build = CldrBuild::new(bundle, output_path.clone());
+
+                    build.build_full().await?;
+                }
                 TargetCommand::Svg(_svg_command) => {
                     let build = SvgBuild::new(bundle, output_path.clone());
 
                     build.build_full().await?;
                 }
+                TargetCommand::Docs(_docs_command) => {
+                    let build = DocsBuild::new(bundle, output_path.clone());
+
+                    build.build_full().await?;
+                }
                 TargetCommand::Android(target) => {
                     android_target(bundle, output_path, target).await?;
                 }
@@ -131,6 +351,32 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Logs the problems of a bundle before building it, failing if any are errors
+fn log_diagnostics(bundle: &KbdgenBundle) -> anyhow::Result<()> {
+    let diagnostics = validate(bundle);
+    for diagnostic in &diagnostics {
+        match diagnostic.severity {
+            Severity::Warning => tracing::warn!("{}", diagnostic),
+            Severity::Error => tracing::error!("{}", diagnostic),
+        }
+    }
+
+    check_diagnostics(&diagnostics)
+}
+
+fn check_diagnostics(diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+    let errors = diagnostics
+        .iter()
+        .filter(|x| x.severity == Severity::Error)
+        .count();
+
+    if errors > 0 {
+        anyhow::bail!("The bundle has {} errors", errors);
+    }
+
+    Ok(())
+}
+
 #[derive(Parser)]
 #[clap(version)]
 struct Cli {
@@ -143,8 +389,35 @@ enum Command {
     #[clap(about = "Functionality relating to specific targets")]
     Target(TargetCommandStruct),
 
+    #[clap(about = "Build several targets of a bundle at once")]
+    Build(BuildCommand),
+
     #[clap(about = "Fetch dependencies for provided project")]
     Fetch(FetchCommand),
+
+    #[clap(about = "Import layouts from other formats into a bundle")]
+    Import(ImportCommand),
+
+    #[clap(about = "Upgrade a kbdgen 2 bundle to the current format")]
+    Migrate(MigrateCommand),
+
+    #[clap(about = "Check a bundle for problems before building it")]
+    Validate(ValidateCommand),
+
+    #[clap(about = "Print what typing a sequence of keys on a layout produces")]
+    Simulate(SimulateCommand),
+
+    #[clap(about = "Compare the desktop targets of layouts with each other")]
+    Parity(ParityCommand),
+
+    #[clap(about = "Report characters of a language that its layout can't type")]
+    Coverage(CoverageCommand),
+
+    #[clap(about = "Create a new bundle from templates")]
+    Init(InitCommand),
+
+    #[clap(about = "Manage the layouts of a bundle")]
+    Layout(LayoutCommand),
 }
 
 #[derive(Args)]
@@ -154,6 +427,177 @@ struct FetchCommand {
     bundle_path: PathBuf,
 }
 
+#[derive(Args)]
+struct ValidateCommand {
+    #[clap(short, long)]
+    /// Path to a .kbdgen bundle to check
+    bundle_path: PathBuf,
+
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    /// How to print the diagnostics
+    format: OutputFormat,
+}
+
+#[derive(Args)]
+struct SimulateCommand {
+    #[clap(short, long)]
+    /// Path to the .kbdgen bundle with the layout
+    bundle_path: PathBuf,
+
+    #[clap(short, long)]
+    /// Language tag of the layout, if the bundle has more than one
+    language_tag: Option<LanguageTag>,
+
+    #[clap(short, long)]
+    /// Platform whose layers are typed on: windows, macOS, chromeOS or linux
+    platform: Platform,
+
+    #[clap(required = true)]
+    /// Keys to press, as ISO positions with any modifiers, e.g. E12 alt+shift+D01 space
+    events: Vec<KeyEvent>,
+}
+
+#[derive(Args)]
+struct ParityCommand {
+    #[clap(short, long)]
+    /// Path to the .kbdgen bundle to compare
+    bundle_path: PathBuf,
+
+    #[clap(short, long)]
+    /// Only compare the layout with this language tag
+    language_tag: Option<LanguageTag>,
+
+    #[clap(long)]
+    /// Fail if any differences are found
+    strict: bool,
+}
+
+#[derive(Args)]
+struct CoverageCommand {
+    #[clap(short, long)]
+    /// Path to the .kbdgen bundle to check
+    bundle_path: PathBuf,
+
+    #[clap(short, long)]
+    /// Only check the layout with this language tag
+    language_tag: Option<LanguageTag>,
+}
+
+#[derive(Args)]
+struct InitCommand {
+    /// Path of the .kbdgen bundle to create
+    bundle_path: PathBuf,
+}
+
+#[derive(Args)]
+struct LayoutCommand {
+    #[clap(subcommand)]
+    command: LayoutSubcommand,
+}
+
+#[derive(Subcommand)]
+enum LayoutSubcommand {
+    #[clap(about = "Add a layout with every target filled in from a base layout")]
+    New(NewLayoutCommand),
+}
+
+#[derive(Args)]
+struct NewLayoutCommand {
+    /// Language tag of the new layout
+    language_tag: LanguageTag,
+
+    #[clap(short, long)]
+    /// Path to the .kbdgen bundle to add the layout to
+    bundle_path: PathBuf,
+
+    #[clap(long, default_value = "iso-qwerty")]
+    /// A layout of the bundle to copy, or one of the built-in bases us and iso-qwerty
+    from: String,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Args)]
+struct MigrateCommand {
+    #[clap(short, long)]
+    /// Path to a .kbdgen bundle to upgrade in place
+    bundle_path: PathBuf,
+
+    #[clap(long)]
+    /// Print the changes as a diff instead of writing them
+    dry_run: bool,
+}
+
+#[derive(Args)]
+struct ImportCommand {
+    #[clap(subcommand)]
+    format: ImportFormat,
+}
+
+#[derive(Subcommand)]
+enum ImportFormat {
+    #[clap(about = "Import a Windows .klc file")]
+    Klc(ImportKlcCommand),
+    #[clap(about = "Import a macOS .keylayout file")]
+    Keylayout(ImportKeylayoutCommand),
+    #[clap(about = "Import a section of an xkb symbols file")]
+    Xkb(ImportXkbCommand),
+}
+
+#[derive(Args)]
+struct ImportKlcCommand {
+    /// Path to the .klc file to import
+    file: PathBuf,
+
+    #[clap(short, long)]
+    /// Path to the .kbdgen bundle to add the layout to
+    bundle_path: PathBuf,
+
+    #[clap(long)]
+    /// Language tag of the layout, if not the one in the file's LOCALENAME
+    language_tag: Option<LanguageTag>,
+}
+
+#[derive(Args)]
+struct ImportKeylayoutCommand {
+    /// Path to the .keylayout file to import
+    file: PathBuf,
+
+    #[clap(short, long)]
+    /// Path to the .kbdgen bundle to add the layout to
+    bundle_path: PathBuf,
+
+    #[clap(long)]
+    /// Language tag of the layout, if not the keyboard's name
+    language_tag: Option<LanguageTag>,
+}
+
+#[derive(Args)]
+struct ImportXkbCommand {
+    /// Path to the xkb symbols file to import, with any included files next to it
+    file: PathBuf,
+
+    #[clap(short, long)]
+    /// Path to the .kbdgen bundle to add the layout to
+    bundle_path: PathBuf,
+
+    #[clap(long)]
+    /// The xkb_symbols section to import, if not the default one
+    variant: Option<String>,
+
+    #[clap(long)]
+    /// Compose file providing the sequences of the layout's dead keys
+    compose: Option<PathBuf>,
+
+    #[clap(long)]
+    /// Language tag of the layout, if not the symbols file's name
+    language_tag: Option<LanguageTag>,
+}
+
 #[derive(Subcommand)]
 enum TargetCommand {
     #[clap(about = "Windows functionality")]
@@ -162,14 +606,41 @@ enum TargetCommand {
     ChromeOs(TargetChromeOsCommand),
     #[clap(name = "macos", about = "macOS functionality")]
     MacOs(TargetMacOs),
+    #[clap(name = "linux", about = "Linux (xkb) functionality")]
+    Linux(TargetLinuxCommand),
+    #[clap(name = "keyman", about = "Keyman functionality")]
+    Keyman(TargetKeymanCommand),
+    #[clap(name = "cldr", about = "CLDR keyboard3 (LDML) functionality")]
+    Cldr(TargetCldrCommand),
     #[clap(name = "svg", about = "SVG functionality")]
     Svg(TargetSvgCommand),
+    #[clap(name = "docs", about = "Static HTML documentation site")]
+    Docs(TargetDocsCommand),
     #[clap(about = "Android functionality")]
     Android(TargetAndroid),
     #[clap(about = "iOS functionality")]
     Ios(TargetIos),
 }
 
+#[derive(Args)]
+struct BuildCommand {
+    #[clap(short, long)]
+    /// Path to a .kbdgen bundle to process
+    bundle_path: PathBuf,
+
+    #[clap(short, long)]
+    /// The directory to place generated output, with a folder for each target
+    output_path: PathBuf,
+
+    #[clap(long, required_unless_present = "targets")]
+    /// Build every target the bundle is configured for
+    all: bool,
+
+    #[clap(long, value_delimiter = ',', conflicts_with = "all")]
+    /// The targets to build, e.g. windows,macos,chromeos
+    targets: Vec<BuildTarget>,
+}
+
 #[derive(Args)]
 struct TargetCommandStruct {
     #[clap(subcommand)]
@@ -283,7 +754,27 @@ struct TargetMacOsGenerateCommand {}
 #[derive(Parser)]
 struct TargetMacOsInstallerCommand {}
 
+// Linux
+
+#[derive(Parser)]
+struct TargetLinuxCommand {}
+
+// Keyman
+
+#[derive(Parser)]
+struct TargetKeymanCommand {}
+
+// CLDR
+
+#[derive(Parser)]
+struct TargetCldrCommand {}
+
 // SVG
 
 #[derive(Parser)]
 struct TargetSvgCommand {}
+
+// Docs
+
+#[derive(Parser)]
+struct TargetDocsCommand {}
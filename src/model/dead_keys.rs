@@ -0,0 +1,146 @@
+use indexmap::IndexMap;
+
+use crate::bundle::layout::Transform;
+use crate::util::{TRANSFORM_ESCAPE, decode_unicode_escapes};
+
+pub type StateId = usize;
+
+/// The transforms of a layout as a state machine. Typing a dead key enters
+/// its start state, and each key typed after it either outputs text or moves
+/// on to the state of a chained dead key.
+#[derive(Debug, Clone, Default)]
+pub struct DeadKeys {
+    /// Every state, numbered in the order they appear in the transforms
+    pub states: Vec<DeadKeyState>,
+    /// The state entered by each dead key
+    pub starts: IndexMap<String, StateId>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeadKeyState {
+    /// What was typed to reach this state, starting with the dead key
+    pub sequence: String,
+    /// The output when a key without a transition is typed
+    pub escape: Option<String>,
+    pub transitions: IndexMap<String, Next>,
+}
+
+impl DeadKeyState {
+    /// The output when a key without a transition is typed, which is what was
+    /// typed to reach this state when it has no escape. The missing escape is
+    /// warned about once, when the states are built.
+    pub fn escape_or_fallback(&self) -> &str {
+        self.escape.as_deref().unwrap_or(&self.sequence)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Next {
+    Output(String),
+    State(StateId),
+}
+
+impl DeadKeys {
+    pub fn from_transforms(transforms: &IndexMap<String, Transform>) -> Self {
+        let mut dead_keys = DeadKeys::default();
+
+        for (dead_key, transform) in transforms {
+            match transform {
+                Transform::End(character) => {
+                    tracing::error!(
+                        "Transform ended too soon for dead key {} - character {}",
+                        dead_key,
+                        character
+                    );
+                }
+                Transform::More(map) => {
+                    let dead_key = decode_unicode_escapes(dead_key);
+                    let id = dead_keys.add_state(dead_key.clone(), map);
+                    dead_keys.starts.insert(dead_key, id);
+                }
+            }
+        }
+
+        dead_keys
+    }
+
+    fn add_state(&mut self, sequence: String, map: &IndexMap<String, Transform>) -> StateId {
+        let id = self.states.len();
+        self.states.push(DeadKeyState {
+            sequence: sequence.clone(),
+            escape: None,
+            transitions: IndexMap::new(),
+        });
+
+        for (next_char, transform) in map {
+            let next_char = decode_unicode_escapes(next_char);
+
+            let next = match (next_char == TRANSFORM_ESCAPE, transform) {
+                (true, Transform::End(output)) => {
+                    self.states[id].escape = Some(decode_unicode_escapes(output));
+                    continue;
+                }
+                (true, Transform::More(_transform)) => {
+                    tracing::error!(
+                        "The escape transform after `{}` should be a string, not another transform",
+                        sequence
+                    );
+                    continue;
+                }
+                (false, Transform::End(output)) => Next::Output(decode_unicode_escapes(output)),
+                (false, Transform::More(map)) => {
+                    Next::State(self.add_state(format!("{}{}", sequence, next_char), map))
+                }
+            };
+
+            self.states[id].transitions.insert(next_char, next);
+        }
+
+        if self.states[id].escape.is_none() {
+            tracing::warn!(
+                "The escape transform `{}` not found after `{}`, falling back to `{}`",
+                TRANSFORM_ESCAPE,
+                sequence,
+                sequence
+            );
+        }
+
+        id
+    }
+
+    /// The start state of a dead key
+    pub fn start(&self, dead_key: &str) -> Option<&DeadKeyState> {
+        self.starts.get(dead_key).map(|id| &self.states[*id])
+    }
+
+    /// The transforms of each dead key flattened into what is typed after
+    /// it, so chained dead keys become multi-character sequences such as `¨u`
+    pub fn sequences(&self) -> IndexMap<String, IndexMap<String, String>> {
+        self.starts
+            .iter()
+            .map(|(dead_key, id)| {
+                let mut sequences = IndexMap::new();
+                self.collect_sequences(&mut sequences, "", *id);
+                (dead_key.clone(), sequences)
+            })
+            .collect()
+    }
+
+    fn collect_sequences(&self, output: &mut IndexMap<String, String>, typed: &str, id: StateId) {
+        let state = &self.states[id];
+
+        if let Some(escape) = &state.escape {
+            output.insert(format!("{}{}", typed, TRANSFORM_ESCAPE), escape.clone());
+        }
+
+        for (next_char, next) in &state.transitions {
+            let typed = format!("{}{}", typed, next_char);
+            match next {
+                Next::Output(text) => {
+                    output.insert(typed, text.clone());
+                }
+                Next::State(id) => self.collect_sequences(output, &typed, *id),
+            }
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! A platform neutral form of a layout, lowered once from the bundle so that
+//! every generator reads the same keys and dead keys
+
+use indexmap::IndexMap;
+use language_tags::LanguageTag;
+
+use crate::bundle::{
+    Error, KbdgenBundle,
+    layout::{
+        Layout, Transform, chrome::ChromeOsKbdLayer, linux::LinuxKbdLayer, macos::MacOsKbdLayer,
+        windows::WindowsKbdLayer,
+    },
+    source::yaml_key,
+};
+use crate::util::{
+    decode_unicode_escapes,
+    iso_key::{IsoKey, layer_keys},
+    split_keys,
+};
+
+mod dead_keys;
+mod modifiers;
+
+pub use dead_keys::{DeadKeyState, DeadKeys, Next, StateId};
+pub use modifiers::{Modifiers, PlatformLayer};
+
+/// What pressing a key does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyAction {
+    Char(char),
+    DeadKey(String),
+    /// Several characters typed by a single key
+    Ligature(String),
+    /// A key written as `\s{name}`
+    Special(String),
+    None,
+}
+
+static NO_KEY: KeyAction = KeyAction::None;
+
+impl KeyAction {
+    /// Reads a key of a layer string, which is a dead key if it is one of
+    /// the `dead_keys` of its layer
+    pub fn parse(key: &str, dead_keys: &[String]) -> KeyAction {
+        if let Some(name) = key.strip_prefix(r"\s{").and_then(|x| x.strip_suffix('}')) {
+            return KeyAction::Special(name.to_string());
+        }
+
+        let key = decode_unicode_escapes(key);
+        if dead_keys.contains(&key) {
+            return KeyAction::DeadKey(key);
+        }
+
+        let mut chars = key.chars();
+        match (chars.next(), chars.next()) {
+            (None, _) | (Some('\0'), None) => KeyAction::None,
+            (Some(character), None) => KeyAction::Char(character),
+            _ => KeyAction::Ligature(key),
+        }
+    }
+
+    /// The text typed by the key, or by a dead key when it is not followed
+    /// by anything it transforms
+    pub fn output(&self) -> Option<String> {
+        match self {
+            KeyAction::Char(character) => Some(character.to_string()),
+            KeyAction::DeadKey(text) | KeyAction::Ligature(text) => Some(text.clone()),
+            KeyAction::Special(_) | KeyAction::None => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub modifiers: Modifiers,
+    pub keys: IndexMap<IsoKey, KeyAction>,
+}
+
+impl Layer {
+    pub fn key(&self, iso_key: IsoKey) -> &KeyAction {
+        self.keys.get(&iso_key).unwrap_or(&NO_KEY)
+    }
+}
+
+/// A desktop target whose layers are positional
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+    Windows,
+    MacOs,
+    ChromeOs,
+    Linux,
+}
+
+impl Platform {
+    pub const ALL: &'static [Platform] = &[
+        Platform::Windows,
+        Platform::MacOs,
+        Platform::ChromeOs,
+        Platform::Linux,
+    ];
+
+    /// The name of the target in a layout file
+    pub fn target_name(&self) -> &'static str {
+        match self {
+            Platform::Windows => "windows",
+            Platform::MacOs => "macOS",
+            Platform::ChromeOs => "chromeOS",
+            Platform::Linux => "linux",
+        }
+    }
+
+    /// Whether the target of the platform can have a layer for `modifiers`
+    pub fn has_layer(&self, modifiers: Modifiers) -> bool {
+        match self {
+            Platform::Windows => WindowsKbdLayer::from_modifiers(modifiers).is_some(),
+            Platform::MacOs => MacOsKbdLayer::from_modifiers(modifiers).is_some(),
+            Platform::ChromeOs => ChromeOsKbdLayer::from_modifiers(modifiers).is_some(),
+            Platform::Linux => LinuxKbdLayer::from_modifiers(modifiers).is_some(),
+        }
+    }
+}
+
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.target_name())
+    }
+}
+
+impl std::str::FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Platform::ALL
+            .iter()
+            .find(|platform| platform.target_name().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "`{}` is not one of {}",
+                    s,
+                    Platform::ALL
+                        .iter()
+                        .map(|x| x.target_name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+}
+
+/// The desktop layers of one target of a layout along with its dead keys
+#[derive(Debug, Clone, Default)]
+pub struct Keyboard {
+    pub layers: Vec<Layer>,
+    pub dead_keys: DeadKeys,
+}
+
+impl Keyboard {
+    /// Lowers the layers of a target, `target` being its name in the layout
+    /// file. Layers with too few keys are an error pointing at the layout.
+    pub fn lower<L: PlatformLayer>(
+        bundle: &KbdgenBundle,
+        language_tag: &LanguageTag,
+        target: &str,
+        layers: &IndexMap<L, String>,
+        dead_keys: Option<&IndexMap<L, Vec<String>>>,
+    ) -> Result<Keyboard, Error> {
+        let expected = layer_keys().len();
+
+        for (layer, key_map) in layers {
+            let found = split_keys(key_map).len();
+
+            if found < expected {
+                return Err(bundle.key_error(
+                    language_tag,
+                    &[target, "primary", "layers", &yaml_key(layer)],
+                    found,
+                    format!(
+                        "Provided layer does not have enough keys, expected {} keys but got {}",
+                        expected, found
+                    ),
+                ));
+            }
+        }
+
+        let transforms = bundle
+            .layouts
+            .get(language_tag)
+            .and_then(|layout| layout.transforms.as_ref());
+
+        Ok(Keyboard::from_layers(layers, dead_keys, transforms))
+    }
+
+    /// Lowers layers without checking them, keys missing from the end of a
+    /// layer do nothing
+    pub fn from_layers<L: PlatformLayer>(
+        layers: &IndexMap<L, String>,
+        dead_keys: Option<&IndexMap<L, Vec<String>>>,
+        transforms: Option<&IndexMap<String, Transform>>,
+    ) -> Keyboard {
+        let layers = layers
+            .iter()
+            .map(|(layer, key_map)| {
+                let layer_dead_keys: Vec<String> = dead_keys
+                    .and_then(|x| x.get(layer))
+                    .map(|x| x.iter().map(|x| decode_unicode_escapes(x)).collect())
+                    .unwrap_or_default();
+
+                Layer {
+                    modifiers: layer.modifiers(),
+                    keys: layer_keys()
+                        .iter()
+                        .zip(split_keys(key_map).iter())
+                        .map(|(iso_key, key)| (*iso_key, KeyAction::parse(key, &layer_dead_keys)))
+                        .collect(),
+                }
+            })
+            .collect();
+
+        Keyboard {
+            layers,
+            dead_keys: transforms
+                .map(DeadKeys::from_transforms)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Lowers the target of a layout for a platform, if the layout has one
+    pub fn from_layout(layout: &Layout, platform: Platform) -> Option<Keyboard> {
+        let transforms = layout.transforms.as_ref();
+
+        Some(match platform {
+            Platform::Windows => {
+                let target = layout.windows.as_ref()?;
+                Keyboard::from_layers(
+                    &target.primary.layers,
+                    target.dead_keys.as_ref(),
+                    transforms,
+                )
+            }
+            Platform::MacOs => {
+                let target = layout.mac_os.as_ref()?;
+                Keyboard::from_layers(
+                    &target.primary.layers,
+                    target.dead_keys.as_ref(),
+                    transforms,
+                )
+            }
+            Platform::ChromeOs => {
+                let target = layout.chrome_os.as_ref()?;
+                Keyboard::from_layers(
+                    &target.primary.layers,
+                    target.dead_keys.as_ref(),
+                    transforms,
+                )
+            }
+            Platform::Linux => {
+                let target = layout.linux.as_ref()?;
+                Keyboard::from_layers(
+                    &target.primary.layers,
+                    target.dead_keys.as_ref(),
+                    transforms,
+                )
+            }
+        })
+    }
+
+    pub fn layer(&self, modifiers: Modifiers) -> Option<&Layer> {
+        self.layers
+            .iter()
+            .find(|layer| layer.modifiers == modifiers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_actions() {
+        let dead_keys = vec!["´".to_string()];
+
+        assert_eq!(KeyAction::parse("a", &dead_keys), KeyAction::Char('a'));
+        assert_eq!(
+            KeyAction::parse(r"\u{b4}", &dead_keys),
+            KeyAction::DeadKey("´".to_string())
+        );
+        assert_eq!(
+            KeyAction::parse("ij", &dead_keys),
+            KeyAction::Ligature("ij".to_string())
+        );
+        assert_eq!(
+            KeyAction::parse(r"\s{shift}", &dead_keys),
+            KeyAction::Special("shift".to_string())
+        );
+        assert_eq!(KeyAction::parse(r"\u{0}", &dead_keys), KeyAction::None);
+    }
+
+    #[test]
+    fn test_chained_dead_keys() {
+        let end = |x: &str| Transform::End(x.to_string());
+
+        let chained = IndexMap::from([(" ".to_string(), end("´¨")), ("u".to_string(), end("ǘ"))]);
+        let acute = IndexMap::from([
+            (" ".to_string(), end("´")),
+            ("a".to_string(), end("á")),
+            ("¨".to_string(), Transform::More(chained)),
+        ]);
+        let transforms = IndexMap::from([("´".to_string(), Transform::More(acute))]);
+
+        let dead_keys = DeadKeys::from_transforms(&transforms);
+        let start = dead_keys.start("´").unwrap();
+        assert_eq!(start.escape.as_deref(), Some("´"));
+        assert_eq!(start.transitions["a"], Next::Output("á".to_string()));
+
+        let Next::State(id) = start.transitions["¨"] else {
+            panic!("Expected ¨ to lead on to another state");
+        };
+        assert_eq!(dead_keys.states[id].sequence, "´¨");
+
+        let sequences = &dead_keys.sequences()["´"];
+        assert_eq!(sequences["¨u"], "ǘ");
+        assert_eq!(sequences["¨ "], "´¨");
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt::Display;
+use std::hash::Hash;
+
+use serde::Serialize;
+
+use crate::bundle::layout::{
+    chrome::ChromeOsKbdLayer, linux::LinuxKbdLayer, macos::MacOsKbdLayer, windows::WindowsKbdLayer,
+};
+
+/// The modifier keys that select a layer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub caps: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+    pub cmd: bool,
+}
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers {
+        shift: false,
+        caps: false,
+        alt: false,
+        ctrl: false,
+        cmd: false,
+    };
+
+    const fn new(shift: bool, caps: bool, alt: bool, ctrl: bool, cmd: bool) -> Self {
+        Modifiers {
+            shift,
+            caps,
+            alt,
+            ctrl,
+            cmd,
+        }
+    }
+}
+
+/// Written the way layers are named in a layout, e.g. `alt+shift`
+impl Display for Modifiers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = [
+            (self.cmd, "cmd"),
+            (self.ctrl, "ctrl"),
+            (self.alt, "alt"),
+            (self.caps, "caps"),
+            (self.shift, "shift"),
+        ]
+        .into_iter()
+        .filter_map(|(pressed, name)| pressed.then_some(name))
+        .collect::<Vec<_>>();
+
+        if names.is_empty() {
+            f.write_str("default")
+        } else {
+            f.write_str(&names.join("+"))
+        }
+    }
+}
+
+/// Reads modifiers written the way [`Display`] writes them
+impl std::str::FromStr for Modifiers {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = Modifiers::NONE;
+
+        if s == "default" {
+            return Ok(modifiers);
+        }
+
+        for name in s.split('+') {
+            let pressed = match name {
+                "shift" => &mut modifiers.shift,
+                "caps" => &mut modifiers.caps,
+                "alt" => &mut modifiers.alt,
+                "ctrl" => &mut modifiers.ctrl,
+                "cmd" => &mut modifiers.cmd,
+                _ => return Err(format!("`{}` is not a modifier", name)),
+            };
+            *pressed = true;
+        }
+
+        Ok(modifiers)
+    }
+}
+
+/// A layer of a desktop target in a layout file
+pub trait PlatformLayer: Copy + Eq + Hash + Serialize + 'static {
+    const ALL: &'static [Self];
+
+    fn modifiers(&self) -> Modifiers;
+
+    fn from_modifiers(modifiers: Modifiers) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|layer| layer.modifiers() == modifiers)
+            .copied()
+    }
+}
+
+impl PlatformLayer for WindowsKbdLayer {
+    const ALL: &'static [Self] = &[
+        WindowsKbdLayer::Default,
+        WindowsKbdLayer::Shift,
+        WindowsKbdLayer::Caps,
+        WindowsKbdLayer::CapsAndShift,
+        WindowsKbdLayer::Alt,
+        WindowsKbdLayer::AltAndShift,
+        WindowsKbdLayer::AltAndCaps,
+        WindowsKbdLayer::Ctrl,
+    ];
+
+    fn modifiers(&self) -> Modifiers {
+        match self {
+            WindowsKbdLayer::Default => Modifiers::NONE,
+            WindowsKbdLayer::Shift => Modifiers::new(true, false, false, false, false),
+            WindowsKbdLayer::Caps => Modifiers::new(false, true, false, false, false),
+            WindowsKbdLayer::CapsAndShift => Modifiers::new(true, true, false, false, false),
+            WindowsKbdLayer::Alt => Modifiers::new(false, false, true, false, false),
+            WindowsKbdLayer::AltAndShift => Modifiers::new(true, false, true, false, false),
+            WindowsKbdLayer::AltAndCaps => Modifiers::new(false, true, true, false, false),
+            WindowsKbdLayer::Ctrl => Modifiers::new(false, false, false, true, false),
+        }
+    }
+}
+
+impl PlatformLayer for MacOsKbdLayer {
+    const ALL: &'static [Self] = &[
+        MacOsKbdLayer::Default,
+        MacOsKbdLayer::Shift,
+        MacOsKbdLayer::Caps,
+        MacOsKbdLayer::CapsAndShift,
+        MacOsKbdLayer::Alt,
+        MacOsKbdLayer::AltAndShift,
+        MacOsKbdLayer::AltAndCaps,
+        MacOsKbdLayer::Ctrl,
+        MacOsKbdLayer::Cmd,
+        MacOsKbdLayer::CmdAndShift,
+        MacOsKbdLayer::CmdAndAlt,
+        MacOsKbdLayer::CmdAndAltAndShift,
+    ];
+
+    fn modifiers(&self) -> Modifiers {
+        match self {
+            MacOsKbdLayer::Default => Modifiers::NONE,
+            MacOsKbdLayer::Shift => Modifiers::new(true, false, false, false, false),
+            MacOsKbdLayer::Caps => Modifiers::new(false, true, false, false, false),
+            MacOsKbdLayer::CapsAndShift => Modifiers::new(true, true, false, false, false),
+            MacOsKbdLayer::Alt => Modifiers::new(false, false, true, false, false),
+            MacOsKbdLayer::AltAndShift => Modifiers::new(true, false, true, false, false),
+            MacOsKbdLayer::AltAndCaps => Modifiers::new(false, true, true, false, false),
+            MacOsKbdLayer::Ctrl => Modifiers::new(false, false, false, true, false),
+            MacOsKbdLayer::Cmd => Modifiers::new(false, false, false, false, true),
+            MacOsKbdLayer::CmdAndShift => Modifiers::new(true, false, false, false, true),
+            MacOsKbdLayer::CmdAndAlt => Modifiers::new(false, false, true, false, true),
+            MacOsKbdLayer::CmdAndAltAndShift => Modifiers::new(true, false, true, false, true),
+        }
+    }
+}
+
+impl PlatformLayer for ChromeOsKbdLayer {
+    const ALL: &'static [Self] = &[
+        ChromeOsKbdLayer::Default,
+        ChromeOsKbdLayer::Shift,
+        ChromeOsKbdLayer::Caps,
+        ChromeOsKbdLayer::CapsAndShift,
+        ChromeOsKbdLayer::Alt,
+        ChromeOsKbdLayer::AltAndShift,
+        ChromeOsKbdLayer::Ctrl,
+    ];
+
+    fn modifiers(&self) -> Modifiers {
+        match self {
+            ChromeOsKbdLayer::Default => Modifiers::NONE,
+            ChromeOsKbdLayer::Shift => Modifiers::new(true, false, false, false, false),
+            ChromeOsKbdLayer::Caps => Modifiers::new(false, true, false, false, false),
+            ChromeOsKbdLayer::CapsAndShift => Modifiers::new(true, true, false, false, false),
+            ChromeOsKbdLayer::Alt => Modifiers::new(false, false, true, false, false),
+            ChromeOsKbdLayer::AltAndShift => Modifiers::new(true, false, true, false, false),
+            ChromeOsKbdLayer::Ctrl => Modifiers::new(false, false, false, true, false),
+        }
+    }
+}
+
+impl PlatformLayer for LinuxKbdLayer {
+    const ALL: &'static [Self] = &[
+        LinuxKbdLayer::Default,
+        LinuxKbdLayer::Shift,
+        LinuxKbdLayer::Alt,
+        LinuxKbdLayer::AltAndShift,
+    ];
+
+    fn modifiers(&self) -> Modifiers {
+        match self {
+            LinuxKbdLayer::Default => Modifiers::NONE,
+            LinuxKbdLayer::Shift => Modifiers::new(true, false, false, false, false),
+            LinuxKbdLayer::Alt => Modifiers::new(false, false, true, false, false),
+            LinuxKbdLayer::AltAndShift => Modifiers::new(true, false, true, false, false),
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! Compares the desktop targets of a layout, which are written separately
+//! and easily drift apart
+
+use indexmap::{IndexMap, IndexSet};
+use language_tags::LanguageTag;
+
+use crate::bundle::layout::Layout;
+use crate::model::{KeyAction, Keyboard, Modifiers, Platform, PlatformLayer};
+use crate::util::{decode_unicode_escapes, iso_key::IsoKey, iso_key::layer_keys};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difference {
+    /// A key types something different
+    Key(IsoKey),
+    /// The layer has different dead keys
+    DeadKeys,
+    /// Some targets that could have the layer don't
+    MissingLayer,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub language_tag: LanguageTag,
+    pub modifiers: Modifiers,
+    pub difference: Difference,
+    /// What each of the compared targets has
+    pub values: IndexMap<Platform, String>,
+}
+
+const NO_VALUE: &str = "(none)";
+
+fn describe(key: &KeyAction) -> String {
+    match key {
+        KeyAction::Char(character) => character.to_string(),
+        KeyAction::Ligature(text) => text.clone(),
+        KeyAction::DeadKey(text) => format!("{} (dead)", text),
+        KeyAction::Special(name) => format!(r"\s{{{}}}", name),
+        KeyAction::None => NO_VALUE.to_string(),
+    }
+}
+
+fn collect_dead_keys<L: PlatformLayer>(
+    dead_keys: Option<&IndexMap<L, Vec<String>>>,
+) -> IndexMap<Modifiers, Vec<String>> {
+    dead_keys
+        .into_iter()
+        .flatten()
+        .map(|(layer, keys)| {
+            let mut keys = keys
+                .iter()
+                .map(|x| decode_unicode_escapes(x))
+                .collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+            (layer.modifiers(), keys)
+        })
+        .collect()
+}
+
+/// The `deadKeys` of the target of a platform, by layer
+fn declared_dead_keys(layout: &Layout, platform: Platform) -> IndexMap<Modifiers, Vec<String>> {
+    match platform {
+        Platform::Windows => {
+            collect_dead_keys(layout.windows.as_ref().and_then(|x| x.dead_keys.as_ref()))
+        }
+        Platform::MacOs => {
+            collect_dead_keys(layout.mac_os.as_ref().and_then(|x| x.dead_keys.as_ref()))
+        }
+        Platform::ChromeOs => {
+            collect_dead_keys(layout.chrome_os.as_ref().and_then(|x| x.dead_keys.as_ref()))
+        }
+        Platform::Linux => {
+            collect_dead_keys(layout.linux.as_ref().and_then(|x| x.dead_keys.as_ref()))
+        }
+    }
+}
+
+/// Compares each layer that more than one desktop target of a layout has,
+/// key by key, along with the dead keys of the layer
+pub fn parity(layout: &Layout) -> Vec<Mismatch> {
+    let keyboards: IndexMap<Platform, Keyboard> = Platform::ALL
+        .iter()
+        .filter_map(|platform| Some((*platform, Keyboard::from_layout(layout, *platform)?)))
+        .collect();
+
+    let mut mismatches = vec![];
+    if keyboards.len() < 2 {
+        return mismatches;
+    }
+
+    let all_modifiers: IndexSet<Modifiers> = keyboards
+        .values()
+        .flat_map(|keyboard| keyboard.layers.iter().map(|layer| layer.modifiers))
+        .collect();
+
+    for modifiers in all_modifiers {
+        let mismatch = |difference, values| Mismatch {
+            language_tag: layout.language_tag.clone(),
+            modifiers,
+            difference,
+            values,
+        };
+
+        let layers: IndexMap<Platform, _> = keyboards
+            .iter()
+            .filter_map(|(platform, keyboard)| Some((*platform, keyboard.layer(modifiers)?)))
+            .collect();
+
+        let missing = keyboards
+            .keys()
+            .filter(|platform| !layers.contains_key(*platform) && platform.has_layer(modifiers))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            let values = keyboards
+                .keys()
+                .filter(|platform| platform.has_layer(modifiers))
+                .map(|platform| {
+                    let value = if missing.contains(&platform) {
+                        "missing"
+                    } else {
+                        "defined"
+                    };
+                    (*platform, value.to_string())
+                })
+                .collect();
+            mismatches.push(mismatch(Difference::MissingLayer, values));
+        }
+
+        if layers.len() < 2 {
+            continue;
+        }
+
+        for iso_key in layer_keys() {
+            let values: IndexMap<Platform, String> = layers
+                .iter()
+                .map(|(platform, layer)| (*platform, describe(layer.key(*iso_key))))
+                .collect();
+
+            if values.values().collect::<IndexSet<_>>().len() > 1 {
+                mismatches.push(mismatch(Difference::Key(*iso_key), values));
+            }
+        }
+
+        let dead_keys: IndexMap<Platform, Vec<String>> = layers
+            .keys()
+            .map(|platform| {
+                let dead_keys = declared_dead_keys(layout, *platform)
+                    .swap_remove(&modifiers)
+                    .unwrap_or_default();
+                (*platform, dead_keys)
+            })
+            .collect();
+
+        if dead_keys.values().collect::<IndexSet<_>>().len() > 1 {
+            let values = dead_keys
+                .into_iter()
+                .map(|(platform, keys)| {
+                    let keys = if keys.is_empty() {
+                        NO_VALUE.to_string()
+                    } else {
+                        keys.join(" ")
+                    };
+                    (platform, keys)
+                })
+                .collect();
+            mismatches.push(mismatch(Difference::DeadKeys, values));
+        }
+    }
+
+    mismatches
+}
+
+/// Lays out mismatches as a table with a column per platform
+pub fn parity_table(mismatches: &[Mismatch]) -> String {
+    let platforms: Vec<Platform> = Platform::ALL
+        .iter()
+        .filter(|platform| mismatches.iter().any(|x| x.values.contains_key(*platform)))
+        .copied()
+        .collect();
+
+    let mut rows = vec![
+        ["layout", "layer", "key"]
+            .into_iter()
+            .map(str::to_string)
+            .chain(platforms.iter().map(|x| x.to_string()))
+            .collect::<Vec<_>>(),
+    ];
+
+    for mismatch in mismatches {
+        let key = match mismatch.difference {
+            Difference::Key(iso_key) => iso_key.to_string(),
+            Difference::DeadKeys => "dead keys".to_string(),
+            Difference::MissingLayer => "layer".to_string(),
+        };
+
+        rows.push(
+            [
+                mismatch.language_tag.to_string(),
+                mismatch.modifiers.to_string(),
+                key,
+            ]
+            .into_iter()
+            .chain(platforms.iter().map(|platform| {
+                mismatch
+                    .values
+                    .get(platform)
+                    .cloned()
+                    .unwrap_or_else(|| "-".to_string())
+            }))
+            .collect(),
+        );
+    }
+
+    let widths = (0..rows[0].len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+
+    let mut output = String::new();
+    for row in rows {
+        let cells = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| {
+                let padding = width - cell.chars().count();
+                format!("{}{}", cell, " ".repeat(padding))
+            })
+            .collect::<Vec<_>>();
+
+        output.push_str(cells.join("  ").trim_end());
+        output.push('\n');
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::test_layout;
+
+    #[test]
+    fn test_parity_mismatches() {
+        let text = r#"
+displayNames:
+  sje: Bidumsámegiella
+windows:
+  primary:
+    layers:
+      default: |
+        § 1 2 3 4 5 6 7 8 9 0 + ´
+        á š e r t y u i o p å ŋ
+        a s d f g h j k l ö ä đ
+        ž z č c v b n m , . -
+      alt: |
+        \u{0} \u{0} @ £ $ € \u{0} { [ ] } \ \u{0}
+        \u{0} \u{0} € \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0}
+        \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} ø æ \u{0}
+        \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0} \u{0}
+  deadKeys:
+    default: ['´']
+macOS:
+  primary:
+    layers:
+      default: |
+        § 1 2 3 4 5 6 7 8 9 0 + ´
+        á š e r t y u i o p å ŋ
+        a s d f g h j k l ö ä đ
+        z ž č c v b n m , . -
+"#;
+
+        let layout = test_layout("sje", text);
+
+        let found = parity(&layout)
+            .into_iter()
+            .map(|x| (x.modifiers.to_string(), x.difference, x.values))
+            .collect::<Vec<_>>();
+
+        let values = |windows: &str, mac_os: &str| {
+            IndexMap::from([
+                (Platform::Windows, windows.to_string()),
+                (Platform::MacOs, mac_os.to_string()),
+            ])
+        };
+
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "default".to_string(),
+                    Difference::Key(IsoKey::E12),
+                    values("´ (dead)", "´")
+                ),
+                (
+                    "default".to_string(),
+                    Difference::Key(IsoKey::B00),
+                    values("ž", "z")
+                ),
+                (
+                    "default".to_string(),
+                    Difference::Key(IsoKey::B01),
+                    values("z", "ž")
+                ),
+                (
+                    "default".to_string(),
+                    Difference::DeadKeys,
+                    values("´", "(none)")
+                ),
+                (
+                    "alt".to_string(),
+                    Difference::MissingLayer,
+                    values("defined", "missing")
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Replays key presses on a layout, to see what a keyboard types on a
+//! platform without installing it
+
+use std::str::FromStr;
+
+use crate::bundle::layout::Layout;
+use crate::model::{DeadKeyState, KeyAction, Keyboard, Modifiers, Next, Platform, StateId};
+use crate::util::{TRANSFORM_ESCAPE, iso_key::IsoKey};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Iso(IsoKey),
+    Space,
+}
+
+/// A key pressed along with the modifiers held down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+impl KeyEvent {
+    pub fn new(key: IsoKey, modifiers: Modifiers) -> Self {
+        KeyEvent {
+            key: Key::Iso(key),
+            modifiers,
+        }
+    }
+}
+
+/// Reads events such as `D01`, `alt+shift+C03` or `space`
+impl FromStr for KeyEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (modifiers, key) = match s.rsplit_once('+') {
+            Some((modifiers, key)) => (modifiers.parse()?, key),
+            None => (Modifiers::NONE, s),
+        };
+
+        let key = if key.eq_ignore_ascii_case("space") {
+            Key::Space
+        } else {
+            Key::Iso(key.parse()?)
+        };
+
+        Ok(KeyEvent { key, modifiers })
+    }
+}
+
+/// Types `events` on the target of `layout` for `platform`, returning the
+/// committed text. A dead key still waiting for its next key at the end is
+/// not part of the text.
+pub fn simulate(layout: &Layout, platform: Platform, events: &[KeyEvent]) -> String {
+    let Some(keyboard) = Keyboard::from_layout(layout, platform) else {
+        tracing::warn!(
+            "The layout {} has no {} target",
+            layout.language_tag,
+            platform
+        );
+        return String::new();
+    };
+
+    let mut simulator = Simulator::new(&keyboard);
+    for event in events {
+        simulator.press(event);
+    }
+
+    simulator.output
+}
+
+/// Types on a keyboard one key at a time
+pub struct Simulator<'a> {
+    keyboard: &'a Keyboard,
+    state: Option<StateId>,
+    output: String,
+}
+
+impl<'a> Simulator<'a> {
+    pub fn new(keyboard: &'a Keyboard) -> Self {
+        Simulator {
+            keyboard,
+            state: None,
+            output: String::new(),
+        }
+    }
+
+    /// The text committed so far
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// The dead key state waiting for the next key, if any
+    pub fn pending(&self) -> Option<&DeadKeyState> {
+        self.state.map(|id| &self.keyboard.dead_keys.states[id])
+    }
+
+    pub fn press(&mut self, event: &KeyEvent) {
+        let (text, is_dead_key) = match event.key {
+            Key::Space => (TRANSFORM_ESCAPE.to_string(), false),
+            Key::Iso(iso_key) => match self.key_action(iso_key, event.modifiers) {
+                Some(KeyAction::Char(character)) => (character.to_string(), false),
+                Some(KeyAction::Ligature(text)) => (text.clone(), false),
+                Some(KeyAction::DeadKey(text)) => (text.clone(), true),
+                Some(KeyAction::Special(_) | KeyAction::None) | None => return,
+            },
+        };
+
+        if let Some(state) = self.pending() {
+            let next = state.transitions.get(&text).cloned();
+            let escape = state.escape_or_fallback().to_string();
+            self.state = None;
+
+            match next {
+                Some(Next::Output(output)) => {
+                    self.output.push_str(&output);
+                    return;
+                }
+                Some(Next::State(id)) => {
+                    self.state = Some(id);
+                    return;
+                }
+                None => {
+                    // The escape ends the state, anything else is typed after it
+                    self.output.push_str(&escape);
+                    if text == TRANSFORM_ESCAPE {
+                        return;
+                    }
+                }
+            }
+        }
+
+        if is_dead_key && let Some(id) = self.keyboard.dead_keys.starts.get(&text) {
+            self.state = Some(*id);
+            return;
+        }
+
+        self.output.push_str(&text);
+    }
+
+    /// The action of a key, falling back to caps lock acting as shift on
+    /// cased keys when there is no caps layer for the modifiers
+    fn key_action(&self, iso_key: IsoKey, modifiers: Modifiers) -> Option<&'a KeyAction> {
+        if let Some(layer) = self.keyboard.layer(modifiers) {
+            return Some(layer.key(iso_key));
+        }
+
+        if !modifiers.caps {
+            return None;
+        }
+
+        let key = self
+            .keyboard
+            .layer(Modifiers {
+                caps: false,
+                ..modifiers
+            })?
+            .key(iso_key);
+        let shifted = self
+            .keyboard
+            .layer(Modifiers {
+                caps: false,
+                shift: !modifiers.shift,
+                ..modifiers
+            })
+            .map(|layer| layer.key(iso_key));
+
+        let is_cased = match (key.output(), shifted.and_then(|x| x.output())) {
+            (Some(key), Some(shifted)) => {
+                key != shifted && (key.to_uppercase() == shifted || key.to_lowercase() == shifted)
+            }
+            _ => false,
+        };
+
+        if is_cased { shifted } else { Some(key) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::test_layout;
+
+    fn layout() -> Layout {
+        let text = r#"
+displayNames:
+  sje: Bidumsámegiella
+windows:
+  primary:
+    layers:
+      default: |
+        § 1 2 3 4 5 6 7 8 9 0 + ´
+        á š e r t y u i o p å ŋ
+        a s d f g h j k l ö ä đ
+        ž z č c v b n m , . -
+      shift: |
+        ½ ! " # ¨ % & / ( ) = ? `
+        Á Š E R T Y U I O P Å Ŋ
+        A S D F G H J K L Ö Ä Đ
+        Ž Z Č C V B N M ; : _
+  deadKeys:
+    default: ['´']
+    shift: ['¨']
+transforms:
+  ´:
+    ' ': ´
+    e: é
+    ¨:
+      ' ': ´¨
+      u: ǘ
+  ¨:
+    u: ü
+"#;
+
+        test_layout("sje", text)
+    }
+
+    fn events(events: &[&str]) -> Vec<KeyEvent> {
+        events.iter().map(|x| x.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_simulate_dead_keys() {
+        let layout = layout();
+        let typed = |keys: &[&str]| simulate(&layout, Platform::Windows, &events(keys));
+
+        assert_eq!(typed(&["E12", "D03"]), "é");
+        assert_eq!(typed(&["E12", "shift+E04", "D07"]), "ǘ");
+        // Keys without a transform are typed after the escape
+        assert_eq!(typed(&["E12", "D04"]), "´r");
+        assert_eq!(typed(&["E12", "space"]), "´");
+        // ¨ has no escape, so what was typed is used instead
+        assert_eq!(typed(&["shift+E04", "D04"]), "¨r");
+        // Caps lock without a caps layer acts as shift on letters only
+        assert_eq!(typed(&["caps+D04", "caps+E01"]), "R1");
+        assert_eq!(typed(&["E12"]), "");
+        assert_eq!(simulate(&layout, Platform::MacOs, &events(&["D04"])), "");
+    }
+}
@@ -0,0 +1,24 @@
+/// Returns the ISO 639-3 code of a primary language subtag, which is either
+/// that code already or an ISO 639-1 code
+pub fn iso639_3(language: &str) -> Option<String> {
+    let language = language.to_ascii_lowercase();
+
+    match language.len() {
+        2 => ::iso639::autonym::get(&language).map(|x| x.tag3.to_string()),
+        3 => Some(language),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iso639_3() {
+        assert_eq!(iso639_3("se").as_deref(), Some("sme"));
+        assert_eq!(iso639_3("NB").as_deref(), Some("nob"));
+        assert_eq!(iso639_3("sje").as_deref(), Some("sje"));
+        assert_eq!(iso639_3("xx"), None);
+    }
+}
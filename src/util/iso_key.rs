@@ -1,6 +1,6 @@
 // https://commons.wikimedia.org/wiki/File:Keyboard-sections-zones-grid-ISOIEC-9995-1.jpg
 // https://commons.wikimedia.org/wiki/File:Keyboard-alphanumeric-section-ISOIEC-9995-2-2009-with-amd1-2012.png
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum IsoKey {
     E00 = 0,
@@ -53,3 +53,97 @@ pub enum IsoKey {
     B10,
     B11, // Brazillian keyboards only
 }
+
+/// Number of keys in each row of a positional layer, E00-E12, D01-D12,
+/// C01-C12 and B00-B10
+pub const ISO_ROW_LENGTHS: [usize; 4] = [13, 12, 12, 11];
+
+/// The keys of a positional layer in the order they are written, E00 to B10
+pub fn layer_keys() -> &'static [IsoKey] {
+    &ISO_KEYS[..ISO_ROW_LENGTHS[0] + ISO_ROW_LENGTHS[1] + ISO_ROW_LENGTHS[2] + ISO_ROW_LENGTHS[3]]
+}
+
+const ISO_KEYS: [IsoKey; 49] = [
+    IsoKey::E00,
+    IsoKey::E01,
+    IsoKey::E02,
+    IsoKey::E03,
+    IsoKey::E04,
+    IsoKey::E05,
+    IsoKey::E06,
+    IsoKey::E07,
+    IsoKey::E08,
+    IsoKey::E09,
+    IsoKey::E10,
+    IsoKey::E11,
+    IsoKey::E12,
+    IsoKey::D01,
+    IsoKey::D02,
+    IsoKey::D03,
+    IsoKey::D04,
+    IsoKey::D05,
+    IsoKey::D06,
+    IsoKey::D07,
+    IsoKey::D08,
+    IsoKey::D09,
+    IsoKey::D10,
+    IsoKey::D11,
+    IsoKey::D12,
+    IsoKey::C01,
+    IsoKey::C02,
+    IsoKey::C03,
+    IsoKey::C04,
+    IsoKey::C05,
+    IsoKey::C06,
+    IsoKey::C07,
+    IsoKey::C08,
+    IsoKey::C09,
+    IsoKey::C10,
+    IsoKey::C11,
+    IsoKey::C12,
+    IsoKey::B00,
+    IsoKey::B01,
+    IsoKey::B02,
+    IsoKey::B03,
+    IsoKey::B04,
+    IsoKey::B05,
+    IsoKey::B06,
+    IsoKey::B07,
+    IsoKey::B08,
+    IsoKey::B09,
+    IsoKey::B10,
+    IsoKey::B11,
+];
+
+impl IsoKey {
+    /// The key at a position of a layer string, counting from zero
+    pub fn from_index(index: usize) -> Option<IsoKey> {
+        ISO_KEYS.get(index).copied()
+    }
+}
+
+impl std::fmt::Display for IsoKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (row, number) = match *self as u8 {
+            index @ 0..=12 => ('E', index),
+            index @ 13..=24 => ('D', index - 12),
+            index @ 25..=36 => ('C', index - 24),
+            index => ('B', index - 37),
+        };
+
+        write!(f, "{}{:02}", row, number)
+    }
+}
+
+impl std::str::FromStr for IsoKey {
+    type Err = String;
+
+    /// Reads a key position such as `D01`, ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ISO_KEYS
+            .iter()
+            .find(|key| key.to_string().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| format!("`{}` is not an ISO key position such as D01", s))
+    }
+}
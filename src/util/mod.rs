@@ -1,6 +1,7 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
 
+pub mod iso639;
 pub mod iso_key;
 
 pub static UNICODE_ESCAPES: Lazy<Regex> =
@@ -20,3 +21,61 @@ pub fn decode_unicode_escapes(input: &str) -> String {
 
     new.to_string()
 }
+
+/// Escapes characters that cannot be written as-is in a whitespace separated
+/// layer string, the inverse of `decode_unicode_escapes`
+pub fn encode_unicode_escapes(input: &str) -> String {
+    input
+        .chars()
+        .map(|c| {
+            if c.is_whitespace()
+                || c.is_control()
+                || matches!(
+                    c,
+                    '\u{ad}' | '\u{200b}'..='\u{200f}' | '\u{2060}' | '\u{feff}'
+                )
+            {
+                format!("\\u{{{:X}}}", c as u32)
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Produces a line diff of two texts, prefixing removed lines with `-`, added
+/// lines with `+` and unchanged lines with a space
+pub fn diff_lines(old: &str, new: &str) -> String {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    // Longest common subsequence lengths of every pair of suffixes
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut output = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            output.push_str(&format!(" {}\n", old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            output.push_str(&format!("+{}\n", new[j]));
+            j += 1;
+        } else {
+            output.push_str(&format!("-{}\n", old[i]));
+            i += 1;
+        }
+    }
+
+    output
+}
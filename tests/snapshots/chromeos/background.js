@@ -0,0 +1,398 @@
+class Keyboard {
+  static install(descriptor) {
+    let contextId = -1
+    const kbd = new Keyboard(descriptor)
+
+    chrome.input.ime.onFocus.addListener((context) => {
+      contextId = context.contextID
+    })
+
+    chrome.input.ime.onBlur.addListener((context) => {
+      contextId = -1
+    })
+
+    chrome.input.ime.onKeyEvent.addListener((keyboardId, keyData) => {
+      console.log(keyboardId, JSON.stringify(keyData))
+      if (keyData.type === "keydown") {
+        const result = kbd.parseInput(keyboardId, keyData)
+        console.log(result)
+        
+        if (result == null) {
+          // Pass through the default value
+          return false
+        }
+
+        chrome.input.ime.commitText({
+            contextID: contextId,
+            text: result
+        })
+
+        return true
+      } else if (keyData.type === "keyup") {
+        kbd.parseKeyUp(keyData)
+        return false
+      }
+      
+      return false
+    })
+  }
+
+  static test(listener, descriptor, callback) {
+    const kbd = new Keyboard(descriptor)
+
+    listener.addEventListener("keydown", (event) => {
+      console.log(event)
+
+      const input = {
+        capsLock: event.getModifierState("CapsLock"),
+        ctrlKey: event.ctrlKey,
+        shiftKey: event.shiftKey,
+        altKey: event.altKey,
+        code: event.code
+      }
+
+      console.log(input)
+      
+      const result = kbd.parseInput(input)
+
+      if (result != null) {
+        callback(result)
+      }
+    })
+  }
+
+  constructor(descriptor) {
+    this.descriptor = descriptor
+    this.transformRef = null
+    this.sequence = ""
+  }
+
+  *deriveFallbackLayers(layer) {
+    yield layer
+
+    if (layer === "default") {
+      return
+    }
+  
+    const hasCaps = layer.includes("caps")
+    const hasShift = layer.includes("shift")
+
+    if (hasCaps && layer !== "caps") {
+      yield "caps"
+    }
+
+    if (hasShift && layer !== "shift") {
+      yield "shift"
+    }
+  
+    yield "default"
+  }
+
+  deadKey(keyboardId, value, layer) {
+    if (this.transformRef != null) {
+      return null
+    }
+    
+    console.log("DeadKey:", value, layer)
+    const deadKeyLayer = this.descriptor[keyboardId].deadKeys[layer]
+
+    if (deadKeyLayer == null) {
+      console.log("DeadKey: nope")
+      return null
+    }
+
+    if (deadKeyLayer == null || !deadKeyLayer.includes(value)) {
+      // Not a dead key
+      console.log("DeadKey: no value")
+      return null
+    }
+
+    console.log("DeadKey: transforming")
+    this.sequence = ""
+    this.transformRef = this.descriptor[keyboardId].transforms[value]
+    return this.transformRef
+  }
+
+  transform(value) {
+    const ref = this.transformRef
+
+    console.log("transformRef:", ref)
+
+    if (ref == null) {
+      return null
+    }
+
+    // Chained dead keys are flattened into sequences of what is typed after
+    // the first dead key
+    const sequence = this.sequence + value
+    const t = ref[sequence]
+    console.log("t:", t, sequence)
+
+    if (t != null) {
+      return t
+    }
+
+    // Part of a longer sequence, keep the transform and wait for the rest
+    if (Object.keys(ref).some((x) => x.startsWith(sequence))) {
+      this.sequence = sequence
+      return ref
+    }
+
+    // If the current transform is not valid for the list, return nothing
+    // This is what other kbds do on Chrome OS.
+    return null
+  }
+
+  processInput(keyboardId, code, startingLayerName) {
+    console.log(keyboardId, code)
+
+    for (const layerName of this.deriveFallbackLayers(startingLayerName)) {
+      console.log(layerName)
+
+      let layer = this.descriptor[keyboardId].layers[layerName]
+
+      console.log(layer)
+
+      if (layer == null) {
+        continue
+      }
+
+      // Check for first dead key press
+      const deadKeyRef = this.deadKey(keyboardId, layer[code], layerName)
+      if (deadKeyRef != null) {
+        this.transformRef = deadKeyRef
+        return null
+      }
+
+      let value
+
+      // Space special case
+      if (code === "Space") {
+        value = this.descriptor[keyboardId].space[startingLayerName] || " "
+      }
+
+      // If nothing is valid, run away to next layer fallback
+      if (value == null && layer[code] != null) {
+        value = layer[code]
+      }
+
+      if (value == null) {
+        continue
+      }
+
+      // If we have a current transform stack, look into it
+      if (this.transformRef != null) {
+        console.log("Try transform:", this.transformRef)
+        
+        const t = this.transform(value)
+
+        // If it's a string, we're at the end of the line
+        if (typeof t === "string") {
+          this.transformRef = null
+          this.sequence = ""
+          return t
+        }
+
+        // Otherwise, buckle up for more transforms!
+        this.transformRef = t
+        if (t == null) {
+          this.sequence = ""
+        }
+        return null
+      }
+
+      // Otherwise we just return the ordinary value
+      return value
+    }
+  }
+
+  handleAltRightKey(code) {
+    if (code === "AltRight") {
+      this.isAltGrKeyHeld = true
+      return true
+    }
+
+    return false
+  }
+
+  parseKeyUp({ code, ctrlKey, shiftKey, capsLock }) {
+    if (code == "AltRight") {
+      this.isAltGrKeyHeld = false
+    }
+  }
+
+  parseInput(keyboardId, {
+    code,
+    ctrlKey,
+    shiftKey,
+    capsLock
+  }) {
+    let layerName
+    const o = []
+
+    if (this.handleAltRightKey(code)) {
+      return null
+    }
+    
+    if (capsLock) {
+      o.push("caps")
+    }
+
+    if (ctrlKey) {
+      o.push("ctrl")
+    }
+
+    if (this.isAltGrKeyHeld) {
+      o.push("alt")
+    }
+
+    if (shiftKey) {
+      o.push("shift")
+    }
+
+    if (o.length > 0) {
+      layerName = o.join("+")
+    } else {
+      layerName = "default"
+    }
+
+    return this.processInput(keyboardId, code, layerName)
+  }   
+}
+
+
+const descriptor = {
+  "sje": {
+    "dead_keys": {
+      "default": [
+        "´"
+      ],
+      "shift": [
+        "`"
+      ]
+    },
+    "transforms": {
+      "´": {
+        " ": "´",
+        "a": "á",
+        "e": "é",
+        "¨ ": "´¨",
+        "¨u": "ǘ"
+      },
+      "`": {
+        " ": "`",
+        "a": "à",
+        "e": "è"
+      },
+      "¨": {
+        " ": "¨",
+        "u": "ü",
+        "i": "ï"
+      }
+    },
+    "layers": {
+      "default": {
+        "Backquote": "§",
+        "Digit1": "1",
+        "Digit2": "2",
+        "Digit3": "3",
+        "Digit4": "4",
+        "Digit5": "5",
+        "Digit6": "6",
+        "Digit7": "7",
+        "Digit8": "8",
+        "Digit9": "9",
+        "Digit0": "0",
+        "Minus": "+",
+        "Equal": "´",
+        "KeyQ": "á",
+        "KeyW": "š",
+        "KeyE": "e",
+        "KeyR": "r",
+        "KeyT": "t",
+        "KeyY": "y",
+        "KeyU": "u",
+        "KeyI": "i",
+        "KeyO": "o",
+        "KeyP": "p",
+        "BracketLeft": "å",
+        "BracketRight": "ŋ",
+        "KeyA": "a",
+        "KeyS": "s",
+        "KeyD": "d",
+        "KeyF": "f",
+        "KeyG": "g",
+        "KeyH": "h",
+        "KeyJ": "j",
+        "KeyK": "k",
+        "KeyL": "l",
+        "Semicolon": "ö",
+        "Quote": "ä",
+        "Backslash": "đ",
+        "IntlBackslash": "ž",
+        "KeyZ": "z",
+        "KeyX": "č",
+        "KeyC": "c",
+        "KeyV": "v",
+        "KeyB": "b",
+        "KeyN": "n",
+        "KeyM": "m",
+        "Comma": ",",
+        "Period": ".",
+        "Slash": "-"
+      },
+      "shift": {
+        "Backquote": "½",
+        "Digit1": "!",
+        "Digit2": "\"",
+        "Digit3": "#",
+        "Digit4": "¤",
+        "Digit5": "%",
+        "Digit6": "&",
+        "Digit7": "/",
+        "Digit8": "(",
+        "Digit9": ")",
+        "Digit0": "=",
+        "Minus": "?",
+        "Equal": "`",
+        "KeyQ": "Á",
+        "KeyW": "Š",
+        "KeyE": "E",
+        "KeyR": "R",
+        "KeyT": "T",
+        "KeyY": "Y",
+        "KeyU": "U",
+        "KeyI": "I",
+        "KeyO": "O",
+        "KeyP": "P",
+        "BracketLeft": "Å",
+        "BracketRight": "Ŋ",
+        "KeyA": "A",
+        "KeyS": "S",
+        "KeyD": "D",
+        "KeyF": "F",
+        "KeyG": "G",
+        "KeyH": "H",
+        "KeyJ": "J",
+        "KeyK": "K",
+        "KeyL": "L",
+        "Semicolon": "Ö",
+        "Quote": "Ä",
+        "Backslash": "Đ",
+        "IntlBackslash": "Ž",
+        "KeyZ": "Z",
+        "KeyX": "Č",
+        "KeyC": "C",
+        "KeyV": "V",
+        "KeyB": "B",
+        "KeyN": "N",
+        "KeyM": "M",
+        "Comma": ";",
+        "Period": ":",
+        "Slash": "_"
+      }
+    }
+  }
+}
+
+Keyboard.install(descriptor)
\ No newline at end of file
@@ -0,0 +1,391 @@
+/****************************** Module Header ******************************\
+* Module Name: kbdsje.c
+*
+* Bidumsámegiella
+*
+* Divvun
+*
+* Generated by kbdgen
+\***************************************************************************/
+
+#include <windows.h>
+#include "kbd.h"
+#include "kbdsje.h"
+
+#if defined(_M_IA64)
+#pragma section(".data")
+#define ALLOC_SECTION_LDATA __declspec(allocate(".data"))
+#else
+#pragma data_seg(".data")
+#define ALLOC_SECTION_LDATA
+#endif
+
+/***************************************************************************\
+* ausVK[] - Virtual Scan Code to Virtual Key conversion table
+\***************************************************************************/
+
+static ALLOC_SECTION_LDATA USHORT ausVK[] = {
+    T00, T01, T02, T03, T04, T05, T06, T07,
+    T08, T09, T0A, T0B, T0C, T0D, T0E, T0F,
+    T10, T11, T12, T13, T14, T15, T16, T17,
+    T18, T19, T1A, T1B, T1C, T1D, T1E, T1F,
+    T20, T21, T22, T23, T24, T25, T26, T27,
+    T28, T29, T2A, T2B, T2C, T2D, T2E, T2F,
+    T30, T31, T32, T33, T34, T35, T36, T37,
+    T38, T39, T3A, T3B, T3C, T3D, T3E, T3F,
+    T40, T41, T42, T43, T44, T45, T46, T47,
+    T48, T49, T4A, T4B, T4C, T4D, T4E, T4F,
+    T50, T51, T52, T53, T54, T55, T56, T57,
+    T58, T59, T5A, T5B, T5C, T5D, T5E, T5F,
+    T60, T61, T62, T63, T64, T65, T66, T67,
+    T68, T69, T6A, T6B, T6C, T6D, T6E, T6F,
+    T70, T71, T72, T73, T74, T75, T76, T77,
+    T78, T79, T7A, T7B, T7C, T7D, T7E
+};
+
+static ALLOC_SECTION_LDATA VSC_VK aE0VscToVk[] = {
+        { 0x10, X10 | KBDEXT          },  // Speedracer: Previous Track
+        { 0x19, X19 | KBDEXT          },  // Speedracer: Next Track
+        { 0x1D, X1D | KBDEXT          },  // RControl
+        { 0x20, X20 | KBDEXT          },  // Speedracer: Volume Mute
+        { 0x21, X21 | KBDEXT          },  // Speedracer: Launch App 2
+        { 0x22, X22 | KBDEXT          },  // Speedracer: Media Play/Pause
+        { 0x24, X24 | KBDEXT          },  // Speedracer: Media Stop
+        { 0x2E, X2E | KBDEXT          },  // Speedracer: Volume Down
+        { 0x30, X30 | KBDEXT          },  // Speedracer: Volume Up
+        { 0x32, X32 | KBDEXT          },  // Speedracer: Browser Home
+        { 0x35, X35 | KBDEXT          },  // Numpad Divide
+        { 0x37, X37 | KBDEXT          },  // Snapshot
+        { 0x38, X38 | KBDEXT          },  // RMenu
+        { 0x47, X47 | KBDEXT          },  // Home
+        { 0x48, X48 | KBDEXT          },  // Up
+        { 0x49, X49 | KBDEXT          },  // Prior
+        { 0x4B, X4B | KBDEXT          },  // Left
+        { 0x4D, X4D | KBDEXT          },  // Right
+        { 0x4F, X4F | KBDEXT          },  // End
+        { 0x50, X50 | KBDEXT          },  // Down
+        { 0x51, X51 | KBDEXT          },  // Next
+        { 0x52, X52 | KBDEXT          },  // Insert
+        { 0x53, X53 | KBDEXT          },  // Delete
+        { 0x5B, X5B | KBDEXT          },  // Left Win
+        { 0x5C, X5C | KBDEXT          },  // Right Win
+        { 0x5D, X5D | KBDEXT          },  // Application
+        { 0x5F, X5F | KBDEXT          },  // Speedracer: Sleep
+        { 0x65, X65 | KBDEXT          },  // Speedracer: Browser Search
+        { 0x66, X66 | KBDEXT          },  // Speedracer: Browser Favorites
+        { 0x67, X67 | KBDEXT          },  // Speedracer: Browser Refresh
+        { 0x68, X68 | KBDEXT          },  // Speedracer: Browser Stop
+        { 0x69, X69 | KBDEXT          },  // Speedracer: Browser Forward
+        { 0x6A, X6A | KBDEXT          },  // Speedracer: Browser Back
+        { 0x6B, X6B | KBDEXT          },  // Speedracer: Launch App 1
+        { 0x6C, X6C | KBDEXT          },  // Speedracer: Launch Mail
+        { 0x6D, X6D | KBDEXT          },  // Speedracer: Launch Media Selector
+        { 0x1C, X1C                   },  // Numpad Enter
+        { 0x46, X46                   },  // Break (Ctrl + Pause)
+        { 0   , 0                     }
+};
+
+static ALLOC_SECTION_LDATA VSC_VK aE1VscToVk[] = {
+        { 0x1D, Y1D                   },  // Pause
+        { 0   ,   0                   }
+};
+
+/***************************************************************************\
+* aVkToBits[] - map Virtual Keys to Modifier Bits
+\***************************************************************************/
+
+static ALLOC_SECTION_LDATA VK_TO_BIT aVkToBits[] = {
+    { VK_SHIFT    ,   KBDSHIFT     },
+    { VK_CONTROL  ,   KBDCTRL      },
+    { VK_MENU     ,   KBDALT       },
+    { 0           ,   0            }
+};
+
+/***************************************************************************\
+* CharModifiers - map character modifier bits to modification number
+\***************************************************************************/
+
+static ALLOC_SECTION_LDATA MODIFIERS CharModifiers = {
+    &aVkToBits[0],
+    7,
+    {
+    //  Modification# //  Keys Pressed
+    //  ============= // =============
+        0,            //
+        1,            // Shift
+        2,            // Control
+        SHFT_INVALID, // Shift + Control
+        SHFT_INVALID, // Menu
+        SHFT_INVALID, // Shift + Menu
+        3,            // Control + Menu
+        4             // Shift + Control + Menu
+    }
+};
+
+/***************************************************************************\
+* aVkToWch<n>[] - Virtual Key to WCHAR translation for n shift states
+\***************************************************************************/
+
+static ALLOC_SECTION_LDATA VK_TO_WCHARS5 aVkToWch5[] = {
+//                                  |         |  Shift  |  Ctrl   | Ctl+Alt |S+Ctl+Alt|
+//                                  |=========|=========|=========|=========|=========|
+  {VK_OEM_3     ,CAPLOK              ,0x00a7   ,0x00bd   ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'1'          ,CAPLOK              ,'1'      ,'!'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'2'          ,CAPLOK | CAPLOKALTGR,'2'      ,'"'      ,WCH_NONE ,'@'      ,WCH_NONE },
+  {'3'          ,CAPLOK | CAPLOKALTGR,'3'      ,'#'      ,WCH_NONE ,0x00a3   ,WCH_NONE },
+  {'4'          ,CAPLOK | CAPLOKALTGR,'4'      ,0x00a4   ,WCH_NONE ,'$'      ,WCH_NONE },
+  {'5'          ,CAPLOK | CAPLOKALTGR,'5'      ,'%'      ,WCH_NONE ,0x20ac   ,WCH_NONE },
+  {'6'          ,CAPLOK              ,'6'      ,'&'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'7'          ,CAPLOK | CAPLOKALTGR,'7'      ,'/'      ,WCH_NONE ,'{'      ,WCH_NONE },
+  {'8'          ,CAPLOK | CAPLOKALTGR,'8'      ,'('      ,WCH_NONE ,'['      ,WCH_NONE },
+  {'9'          ,CAPLOK | CAPLOKALTGR,'9'      ,')'      ,WCH_NONE ,']'      ,WCH_NONE },
+  {'0'          ,CAPLOK | CAPLOKALTGR,'0'      ,'='      ,WCH_NONE ,'}'      ,WCH_NONE },
+  {VK_OEM_MINUS ,CAPLOK | CAPLOKALTGR,'+'      ,'?'      ,WCH_NONE ,0x005c   ,WCH_NONE },
+  {VK_OEM_PLUS  ,CAPLOK | CAPLOKALTGR,WCH_DEAD ,WCH_DEAD ,WCH_NONE ,WCH_DEAD ,WCH_NONE },
+  {0xff         ,0                   ,0x00b4   ,'`'      ,WCH_NONE ,0x00a8   ,WCH_NONE },
+  {'Q'          ,CAPLOK | CAPLOKALTGR,0x00e1   ,0x00c1   ,WCH_NONE ,'q'      ,WCH_NONE },
+  {'W'          ,CAPLOK | CAPLOKALTGR,0x0161   ,0x0160   ,WCH_NONE ,'w'      ,WCH_NONE },
+  {'E'          ,CAPLOK | CAPLOKALTGR,'e'      ,'E'      ,WCH_NONE ,0x20ac   ,WCH_NONE },
+  {'R'          ,CAPLOK              ,'r'      ,'R'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'T'          ,CAPLOK | CAPLOKALTGR,'t'      ,'T'      ,WCH_NONE ,0x0167   ,WCH_NONE },
+  {'Y'          ,CAPLOK              ,'y'      ,'Y'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'U'          ,CAPLOK              ,'u'      ,'U'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'I'          ,CAPLOK | CAPLOKALTGR,'i'      ,'I'      ,WCH_NONE ,0x00ef   ,WCH_NONE },
+  {'O'          ,CAPLOK | CAPLOKALTGR,'o'      ,'O'      ,WCH_NONE ,0x00f5   ,WCH_NONE },
+  {'P'          ,CAPLOK              ,'p'      ,'P'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {VK_OEM_4     ,CAPLOK              ,0x00e5   ,0x00c5   ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {VK_OEM_6     ,CAPLOK | CAPLOKALTGR,0x014b   ,0x014a   ,WCH_NONE ,'~'      ,WCH_NONE },
+  {'A'          ,CAPLOK | CAPLOKALTGR,'a'      ,'A'      ,WCH_NONE ,0x00e2   ,WCH_NONE },
+  {'S'          ,CAPLOK              ,'s'      ,'S'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'D'          ,CAPLOK              ,'d'      ,'D'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'F'          ,CAPLOK              ,'f'      ,'F'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'G'          ,CAPLOK | CAPLOKALTGR,'g'      ,'G'      ,WCH_NONE ,0x01e7   ,WCH_NONE },
+  {'H'          ,CAPLOK | CAPLOKALTGR,'h'      ,'H'      ,WCH_NONE ,0x01e5   ,WCH_NONE },
+  {'J'          ,CAPLOK              ,'j'      ,'J'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'K'          ,CAPLOK | CAPLOKALTGR,'k'      ,'K'      ,WCH_NONE ,0x01e9   ,WCH_NONE },
+  {'L'          ,CAPLOK              ,'l'      ,'L'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {VK_OEM_1     ,CAPLOK | CAPLOKALTGR,0x00f6   ,0x00d6   ,WCH_NONE ,0x00f8   ,WCH_NONE },
+  {VK_OEM_7     ,CAPLOK | CAPLOKALTGR,0x00e4   ,0x00c4   ,WCH_NONE ,0x00e6   ,WCH_NONE },
+  {VK_OEM_5     ,CAPLOK | CAPLOKALTGR,0x0111   ,0x0110   ,WCH_NONE ,0x0027   ,WCH_NONE },
+  {VK_OEM_102   ,CAPLOK | CAPLOKALTGR,0x017e   ,0x017d   ,WCH_NONE ,0x01ef   ,WCH_NONE },
+  {'Z'          ,CAPLOK | CAPLOKALTGR,'z'      ,'Z'      ,WCH_NONE ,0x0292   ,WCH_NONE },
+  {'X'          ,CAPLOK | CAPLOKALTGR,0x010d   ,0x010c   ,WCH_NONE ,'x'      ,WCH_NONE },
+  {'C'          ,CAPLOK              ,'c'      ,'C'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'V'          ,CAPLOK              ,'v'      ,'V'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'B'          ,CAPLOK              ,'b'      ,'B'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'N'          ,CAPLOK              ,'n'      ,'N'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {'M'          ,CAPLOK | CAPLOKALTGR,'m'      ,'M'      ,WCH_NONE ,0x00b5   ,WCH_NONE },
+  {VK_OEM_COMMA ,CAPLOK | CAPLOKALTGR,','      ,';'      ,WCH_NONE ,'<'      ,WCH_NONE },
+  {VK_OEM_PERIOD,CAPLOK | CAPLOKALTGR,'.'      ,':'      ,WCH_NONE ,'>'      ,WCH_NONE },
+  {VK_OEM_2     ,CAPLOK              ,'-'      ,'_'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {VK_SPACE     ,0                   ,' '      ,' '      ,' '      ,WCH_NONE ,WCH_NONE },
+  {VK_DECIMAL   ,0                   ,'.'      ,'.'      ,WCH_NONE ,WCH_NONE ,WCH_NONE },
+  {0            ,0                   ,0        ,0        ,0        ,0        ,0        }
+};
+
+static ALLOC_SECTION_LDATA VK_TO_WCHARS3 aVkToWch3[] = {
+  {VK_BACK      ,0                   ,'\b'     ,'\b'     ,0x007f   },
+  {VK_ESCAPE    ,0                   ,0x001b   ,0x001b   ,0x001b   },
+  {VK_RETURN    ,0                   ,'\r'     ,'\r'     ,'\n'     },
+  {VK_CANCEL    ,0                   ,0x0003   ,0x0003   ,0x0003   },
+  {0            ,0                   ,0        ,0        ,0        }
+};
+
+static ALLOC_SECTION_LDATA VK_TO_WCHARS2 aVkToWch2[] = {
+  {VK_TAB       ,0                   ,'\t'     ,'\t'     },
+  {VK_ADD       ,0                   ,'+'      ,'+'      },
+  {VK_DIVIDE    ,0                   ,'/'      ,'/'      },
+  {VK_MULTIPLY  ,0                   ,'*'      ,'*'      },
+  {VK_SUBTRACT  ,0                   ,'-'      ,'-'      },
+  {0            ,0                   ,0        ,0        }
+};
+
+static ALLOC_SECTION_LDATA VK_TO_WCHARS1 aVkToWch1[] = {
+  {VK_NUMPAD0   ,0                   ,'0'      },
+  {VK_NUMPAD1   ,0                   ,'1'      },
+  {VK_NUMPAD2   ,0                   ,'2'      },
+  {VK_NUMPAD3   ,0                   ,'3'      },
+  {VK_NUMPAD4   ,0                   ,'4'      },
+  {VK_NUMPAD5   ,0                   ,'5'      },
+  {VK_NUMPAD6   ,0                   ,'6'      },
+  {VK_NUMPAD7   ,0                   ,'7'      },
+  {VK_NUMPAD8   ,0                   ,'8'      },
+  {VK_NUMPAD9   ,0                   ,'9'      },
+  {0            ,0                   ,0        }
+};
+
+static ALLOC_SECTION_LDATA VK_TO_WCHAR_TABLE aVkToWcharTable[] = {
+    {  (PVK_TO_WCHARS1)aVkToWch5, 5, sizeof(aVkToWch5[0]) },
+    {  (PVK_TO_WCHARS1)aVkToWch3, 3, sizeof(aVkToWch3[0]) },
+    {  (PVK_TO_WCHARS1)aVkToWch2, 2, sizeof(aVkToWch2[0]) },
+    {  (PVK_TO_WCHARS1)aVkToWch1, 1, sizeof(aVkToWch1[0]) },  // Numpad must be last
+    {                       NULL, 0, 0                    },
+};
+
+/***************************************************************************\
+* aKeyNames[], aKeyNamesExt[] - Virtual Scancode to Key Name tables
+\***************************************************************************/
+
+static ALLOC_SECTION_LDATA VSC_LPWSTR aKeyNames[] = {
+    0x01,    L"Esc",
+    0x0e,    L"Backspace",
+    0x0f,    L"Tab",
+    0x1c,    L"Enter",
+    0x1d,    L"Ctrl",
+    0x2a,    L"Shift",
+    0x36,    L"Right Shift",
+    0x37,    L"Num *",
+    0x38,    L"Alt",
+    0x39,    L"Space",
+    0x3a,    L"Caps Lock",
+    0x3b,    L"F1",
+    0x3c,    L"F2",
+    0x3d,    L"F3",
+    0x3e,    L"F4",
+    0x3f,    L"F5",
+    0x40,    L"F6",
+    0x41,    L"F7",
+    0x42,    L"F8",
+    0x43,    L"F9",
+    0x44,    L"F10",
+    0x45,    L"Pause",
+    0x46,    L"Scroll Lock",
+    0x47,    L"Num 7",
+    0x48,    L"Num 8",
+    0x49,    L"Num 9",
+    0x4a,    L"Num -",
+    0x4b,    L"Num 4",
+    0x4c,    L"Num 5",
+    0x4d,    L"Num 6",
+    0x4e,    L"Num +",
+    0x4f,    L"Num 1",
+    0x50,    L"Num 2",
+    0x51,    L"Num 3",
+    0x52,    L"Num 0",
+    0x53,    L"Num Del",
+    0x54,    L"Sys Req",
+    0x57,    L"F11",
+    0x58,    L"F12",
+    0x7c,    L"F13",
+    0x7d,    L"F14",
+    0x7e,    L"F15",
+    0x7f,    L"F16",
+    0x80,    L"F17",
+    0x81,    L"F18",
+    0x82,    L"F19",
+    0x83,    L"F20",
+    0x84,    L"F21",
+    0x85,    L"F22",
+    0x86,    L"F23",
+    0x87,    L"F24",
+    0   ,    NULL
+};
+
+static ALLOC_SECTION_LDATA VSC_LPWSTR aKeyNamesExt[] = {
+    0x1c,    L"Num Enter",
+    0x1d,    L"Right Ctrl",
+    0x35,    L"Num /",
+    0x37,    L"Prnt Scrn",
+    0x38,    L"Right Alt",
+    0x45,    L"Num Lock",
+    0x46,    L"Break",
+    0x47,    L"Home",
+    0x48,    L"Up",
+    0x49,    L"Page Up",
+    0x4b,    L"Left",
+    0x4d,    L"Right",
+    0x4f,    L"End",
+    0x50,    L"Down",
+    0x51,    L"Page Down",
+    0x52,    L"Insert",
+    0x53,    L"Delete",
+    0x54,    L"<00>",
+    0x56,    L"Help",
+    0x5b,    L"Left Windows",
+    0x5c,    L"Right Windows",
+    0x5d,    L"Application",
+    0   ,    NULL
+};
+
+static ALLOC_SECTION_LDATA DEADKEY_LPWSTR aKeyNamesDead[] = {
+    L"\x00b4"	L"\x00b4",
+    L"\x0060"	L"\x0060",
+    L"\x00a8"	L"\x00a8",
+    L"\x0308"	L"\x0308",
+    NULL
+};
+
+static ALLOC_SECTION_LDATA DEADKEY aDeadKey[] = {
+    DEADTRANS( L'a'   , 0x00b4 , 0x00e1 , 0x0000),
+    DEADTRANS( L'e'   , 0x00b4 , 0x00e9 , 0x0000),
+    DEADTRANS( 0x00a8 , 0x00b4 , 0x0308 , DKF_DEAD),
+    DEADTRANS( L' '   , 0x00b4 , 0x00b4 , 0x0000),
+
+    DEADTRANS( L'a'   , 0x0060 , 0x00e0 , 0x0000),
+    DEADTRANS( L'e'   , 0x0060 , 0x00e8 , 0x0000),
+    DEADTRANS( L' '   , 0x0060 , 0x0060 , 0x0000),
+
+    DEADTRANS( L'u'   , 0x00a8 , 0x00fc , 0x0000),
+    DEADTRANS( L'i'   , 0x00a8 , 0x00ef , 0x0000),
+    DEADTRANS( L' '   , 0x00a8 , 0x00a8 , 0x0000),
+
+    DEADTRANS( L'u'   , 0x0308 , 0x01d8 , 0x0000),
+    DEADTRANS( L' '   , 0x0308 , 0x00b4 , 0x0000),
+
+    0, 0
+};
+
+static ALLOC_SECTION_LDATA KBDTABLES KbdTables = {
+    /*
+     * Modifier keys
+     */
+    &CharModifiers,
+
+    /*
+     * Characters tables
+     */
+    aVkToWcharTable,
+
+    /*
+     * Diacritics
+     */
+    aDeadKey,
+
+    /*
+     * Names of Keys
+     */
+    aKeyNames,
+    aKeyNamesExt,
+    aKeyNamesDead,
+
+    /*
+     * Scan codes to Virtual Keys
+     */
+    ausVK,
+    sizeof(ausVK) / sizeof(ausVK[0]),
+    aE0VscToVk,
+    aE1VscToVk,
+
+    /*
+     * Locale-specific special processing
+     */
+    MAKELONG(KLLF_ALTGR, KBD_VERSION),
+
+    /*
+     * Ligatures
+     */
+    0,
+    0,
+    NULL,
+
+    /*
+     * Type and subtype, unused
+     */
+    0,
+    0
+};
+
+PKBDTABLES KbdLayerDescriptor(VOID)
+{
+    return &KbdTables;
+}
@@ -0,0 +1,116 @@
+/****************************** Module Header ******************************\
+* Module Name: kbdsje.h
+*
+* Bidumsámegiella
+*
+* Divvun
+*
+* Generated by kbdgen
+\***************************************************************************/
+
+#define KBD_TYPE 4
+
+#include "kbd.h"
+
+/***************************************************************************\
+* The virtual keys of the scancodes, redefined from the US keyboard of kbd.h
+\***************************************************************************/
+
+#undef  T29
+#define T29 _EQ(OEM_3)
+#undef  T02
+#define T02 '1'
+#undef  T03
+#define T03 '2'
+#undef  T04
+#define T04 '3'
+#undef  T05
+#define T05 '4'
+#undef  T06
+#define T06 '5'
+#undef  T07
+#define T07 '6'
+#undef  T08
+#define T08 '7'
+#undef  T09
+#define T09 '8'
+#undef  T0A
+#define T0A '9'
+#undef  T0B
+#define T0B '0'
+#undef  T0C
+#define T0C _EQ(OEM_MINUS)
+#undef  T0D
+#define T0D _EQ(OEM_PLUS)
+#undef  T10
+#define T10 'Q'
+#undef  T11
+#define T11 'W'
+#undef  T12
+#define T12 'E'
+#undef  T13
+#define T13 'R'
+#undef  T14
+#define T14 'T'
+#undef  T15
+#define T15 'Y'
+#undef  T16
+#define T16 'U'
+#undef  T17
+#define T17 'I'
+#undef  T18
+#define T18 'O'
+#undef  T19
+#define T19 'P'
+#undef  T1A
+#define T1A _EQ(OEM_4)
+#undef  T1B
+#define T1B _EQ(OEM_6)
+#undef  T1E
+#define T1E 'A'
+#undef  T1F
+#define T1F 'S'
+#undef  T20
+#define T20 'D'
+#undef  T21
+#define T21 'F'
+#undef  T22
+#define T22 'G'
+#undef  T23
+#define T23 'H'
+#undef  T24
+#define T24 'J'
+#undef  T25
+#define T25 'K'
+#undef  T26
+#define T26 'L'
+#undef  T27
+#define T27 _EQ(OEM_1)
+#undef  T28
+#define T28 _EQ(OEM_7)
+#undef  T2B
+#define T2B _EQ(OEM_5)
+#undef  T56
+#define T56 _EQ(OEM_102)
+#undef  T2C
+#define T2C 'Z'
+#undef  T2D
+#define T2D 'X'
+#undef  T2E
+#define T2E 'C'
+#undef  T2F
+#define T2F 'V'
+#undef  T30
+#define T30 'B'
+#undef  T31
+#define T31 'N'
+#undef  T32
+#define T32 'M'
+#undef  T33
+#define T33 _EQ(OEM_COMMA)
+#undef  T34
+#define T34 _EQ(OEM_PERIOD)
+#undef  T35
+#define T35 _EQ(OEM_2)
+#undef  T39
+#define T39 _EQ(SPACE)